@@ -0,0 +1,98 @@
+// Bloom through a mip chain: bright parts of the frame are downsampled level by level,
+// then upsampled back up with every level added to the one above, and added to the frame.
+// Every pass draws one fullscreen triangle.
+
+struct Params {
+    threshold: f32,
+    // Width of the soft transition below the threshold
+    knee: f32,
+    intensity: f32,
+    // Spread of the upsampling filter, in texels of the level read
+    radius: f32,
+}
+@group(1) @binding(0)
+var<uniform> params: Params;
+
+// Frame in the prefilter and composite passes, the level above or below otherwise
+@group(0) @binding(0)
+var t_source: texture_2d<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+
+// Top level of the chain, in the composite pass
+@group(2) @binding(0)
+var t_bloom: texture_2d<f32>;
+@group(2) @binding(1)
+var s_bloom: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32(index & 2u), f32((index << 1u) & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+fn sample_source(uv: vec2<f32>, offset: vec2<f32>, texel: vec2<f32>) -> vec3<f32> {
+    return textureSample(t_source, s_source, uv + offset * texel).rgb;
+}
+
+// 13 bilinear taps weighted to avoid the flickering of a plain 2x2 box
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_source));
+    let a = sample_source(uv, vec2<f32>(-2.0, -2.0), texel);
+    let b = sample_source(uv, vec2<f32>(0.0, -2.0), texel);
+    let c = sample_source(uv, vec2<f32>(2.0, -2.0), texel);
+    let d = sample_source(uv, vec2<f32>(-2.0, 0.0), texel);
+    let e = sample_source(uv, vec2<f32>(0.0, 0.0), texel);
+    let f = sample_source(uv, vec2<f32>(2.0, 0.0), texel);
+    let g = sample_source(uv, vec2<f32>(-2.0, 2.0), texel);
+    let h = sample_source(uv, vec2<f32>(0.0, 2.0), texel);
+    let i = sample_source(uv, vec2<f32>(2.0, 2.0), texel);
+    let j = sample_source(uv, vec2<f32>(-1.0, -1.0), texel);
+    let k = sample_source(uv, vec2<f32>(1.0, -1.0), texel);
+    let l = sample_source(uv, vec2<f32>(-1.0, 1.0), texel);
+    let m = sample_source(uv, vec2<f32>(1.0, 1.0), texel);
+    return e * 0.125 + (a + c + g + i) * 0.03125 + (b + d + f + h) * 0.0625 + (j + k + l + m) * 0.125;
+}
+
+@fragment
+fn fs_prefilter(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Half floats overflow to infinity on very bright pixels, which would spread over the whole chain
+    let color = min(downsample(in.uv), vec3<f32>(65000.0));
+    let brightness = max(color.r, max(color.g, color.b));
+    let soft = clamp(brightness - params.threshold + params.knee, 0.0, 2.0 * params.knee);
+    let soft_contribution = soft * soft / (4.0 * params.knee + 1e-4);
+    let contribution = max(soft_contribution, brightness - params.threshold) / max(brightness, 1e-4);
+    return vec4<f32>(color * contribution, 1.0);
+}
+
+@fragment
+fn fs_downsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(downsample(in.uv), 1.0);
+}
+
+// 3x3 tent filter, blended additively onto the level above
+@fragment
+fn fs_upsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = params.radius / vec2<f32>(textureDimensions(t_source));
+    let sum = sample_source(in.uv, vec2<f32>(0.0, 0.0), texel) * 4.0
+        + (sample_source(in.uv, vec2<f32>(0.0, -1.0), texel) + sample_source(in.uv, vec2<f32>(-1.0, 0.0), texel)
+            + sample_source(in.uv, vec2<f32>(1.0, 0.0), texel) + sample_source(in.uv, vec2<f32>(0.0, 1.0), texel)) * 2.0
+        + sample_source(in.uv, vec2<f32>(-1.0, -1.0), texel) + sample_source(in.uv, vec2<f32>(1.0, -1.0), texel)
+        + sample_source(in.uv, vec2<f32>(-1.0, 1.0), texel) + sample_source(in.uv, vec2<f32>(1.0, 1.0), texel);
+    return vec4<f32>(sum / 16.0, 1.0);
+}
+
+@fragment
+fn fs_composite(in: VertexOutput) -> @location(0) vec4<f32> {
+    let frame = textureSample(t_source, s_source, in.uv);
+    let bloom = textureSample(t_bloom, s_bloom, in.uv).rgb;
+    return vec4<f32>(frame.rgb + bloom * params.intensity, frame.a);
+}
@@ -0,0 +1,170 @@
+// Precomputes the sky cubemap and the image based lighting maps of an environment.
+// Every pass draws one fullscreen triangle into a single cubemap face, or the BRDF lookup table.
+
+#include "include/constants.wgsl"
+
+struct Params {
+    face: u32,
+    roughness: f32,
+    sample_count: u32,
+    // Face size of the top level of the source cubemap
+    source_size: f32,
+}
+@group(0) @binding(0)
+var<uniform> params: Params;
+
+@group(1) @binding(0)
+var t_equirect: texture_2d<f32>;
+@group(1) @binding(1)
+var s_source: sampler;
+@group(1) @binding(2)
+var t_source: texture_cube<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // One triangle covering the whole target, uv grows downwards like texture coordinates
+    let uv = vec2<f32>(f32(index & 2u), f32((index << 1u) & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+// World direction through `uv` of a cubemap face, in the +X, -X, +Y, -Y, +Z, -Z layer order
+fn face_direction(face: u32, uv: vec2<f32>) -> vec3<f32> {
+    let st = uv * 2.0 - 1.0;
+    var dir: vec3<f32>;
+    switch face {
+        case 0u: { dir = vec3<f32>(1.0, -st.y, -st.x); }
+        case 1u: { dir = vec3<f32>(-1.0, -st.y, st.x); }
+        case 2u: { dir = vec3<f32>(st.x, 1.0, st.y); }
+        case 3u: { dir = vec3<f32>(st.x, -1.0, -st.y); }
+        case 4u: { dir = vec3<f32>(st.x, -st.y, 1.0); }
+        default: { dir = vec3<f32>(-st.x, -st.y, -1.0); }
+    }
+    return normalize(dir);
+}
+
+fn hammersley(i: u32, count: u32) -> vec2<f32> {
+    return vec2<f32>(f32(i) / f32(count), f32(reverseBits(i)) * 2.3283064365386963e-10);
+}
+
+// Columns are a tangent, a bitangent and `n`
+fn tangent_frame(n: vec3<f32>) -> mat3x3<f32> {
+    let up = select(vec3<f32>(0.0, 0.0, 1.0), vec3<f32>(1.0, 0.0, 0.0), abs(n.z) > 0.999);
+    let tangent = normalize(cross(up, n));
+    return mat3x3<f32>(tangent, cross(n, tangent), n);
+}
+
+// Half vector around +Z distributed like the GGX normal distribution
+fn importance_sample_ggx(xi: vec2<f32>, alpha: f32) -> vec3<f32> {
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (alpha * alpha - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    return vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+}
+
+// Source mip whose texels cover about the solid angle of one sample, which keeps few samples free of fireflies
+fn sample_lod(pdf: f32) -> f32 {
+    let sample_angle = 1.0 / (f32(params.sample_count) * pdf + 0.0001);
+    let texel_angle = 4.0 * PI / (6.0 * params.source_size * params.source_size);
+    return max(0.5 * log2(sample_angle / texel_angle) + 1.0, 0.0);
+}
+
+@fragment
+fn fs_equirect(in: VertexOutput) -> @location(0) vec4<f32> {
+    let dir = face_direction(params.face, in.uv);
+    let uv = vec2<f32>(atan2(dir.z, dir.x) / (2.0 * PI) + 0.5, acos(clamp(dir.y, -1.0, 1.0)) / PI);
+    return vec4<f32>(textureSampleLevel(t_equirect, s_source, uv, 0.0).rgb, 1.0);
+}
+
+// Copies the top level of the source, bilinear filtering halves it when the target is a mip level down
+@fragment
+fn fs_copy(in: VertexOutput) -> @location(0) vec4<f32> {
+    let dir = face_direction(params.face, in.uv);
+    return vec4<f32>(textureSampleLevel(t_source, s_source, dir, 0.0).rgb, 1.0);
+}
+
+// Cosine weighted average of the incoming light, the lambertian term without the albedo
+@fragment
+fn fs_irradiance(in: VertexOutput) -> @location(0) vec4<f32> {
+    let n = face_direction(params.face, in.uv);
+    let frame = tangent_frame(n);
+
+    var sum = vec3<f32>(0.0);
+    for (var i = 0u; i < params.sample_count; i += 1u) {
+        let xi = hammersley(i, params.sample_count);
+        let phi = 2.0 * PI * xi.x;
+        let cos_theta = sqrt(1.0 - xi.y);
+        let sin_theta = sqrt(xi.y);
+        let dir = frame * vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+        sum += textureSampleLevel(t_source, s_source, dir, sample_lod(cos_theta / PI)).rgb;
+    }
+    return vec4<f32>(sum / f32(params.sample_count), 1.0);
+}
+
+// Incoming light convolved with the GGX lobe of `params.roughness`, assuming the view along the normal
+@fragment
+fn fs_prefilter(in: VertexOutput) -> @location(0) vec4<f32> {
+    let n = face_direction(params.face, in.uv);
+    if params.roughness == 0.0 {
+        return vec4<f32>(textureSampleLevel(t_source, s_source, n, 0.0).rgb, 1.0);
+    }
+
+    let frame = tangent_frame(n);
+    let alpha = params.roughness * params.roughness;
+    var sum = vec3<f32>(0.0);
+    var weight = 0.0;
+    for (var i = 0u; i < params.sample_count; i += 1u) {
+        let h_local = importance_sample_ggx(hammersley(i, params.sample_count), alpha);
+        let h = frame * h_local;
+        let l = 2.0 * dot(n, h) * h - n;
+        let n_l = dot(n, l);
+        if n_l > 0.0 {
+            let cos_h = h_local.z;
+            let d = alpha * alpha / (PI * pow(cos_h * cos_h * (alpha * alpha - 1.0) + 1.0, 2.0));
+            // With the view along the normal the half vector pdf reduces to D / 4
+            sum += textureSampleLevel(t_source, s_source, l, sample_lod(d * 0.25)).rgb * n_l;
+            weight += n_l;
+        }
+    }
+    return vec4<f32>(sum / max(weight, 0.0001), 1.0);
+}
+
+fn geometry_schlick(n_v: f32, k: f32) -> f32 {
+    return n_v / (n_v * (1.0 - k) + k);
+}
+
+// Scale and bias applied to F0 by the split sum approximation, over cos(view angle) in x and roughness in y
+@fragment
+fn fs_brdf(in: VertexOutput) -> @location(0) vec4<f32> {
+    let n_v = max(in.uv.x, 0.0001);
+    let roughness = in.uv.y;
+    let alpha = roughness * roughness;
+    let v = vec3<f32>(sqrt(1.0 - n_v * n_v), 0.0, n_v);
+    // Image based lighting uses k = alpha / 2
+    let k = alpha * 0.5;
+
+    var scale = 0.0;
+    var bias = 0.0;
+    for (var i = 0u; i < params.sample_count; i += 1u) {
+        let h = importance_sample_ggx(hammersley(i, params.sample_count), alpha);
+        let l = 2.0 * dot(v, h) * h - v;
+        let n_l = l.z;
+        if n_l > 0.0 {
+            let v_h = max(dot(v, h), 0.0);
+            let g = geometry_schlick(n_v, k) * geometry_schlick(n_l, k);
+            let visibility = g * v_h / (max(h.z, 0.0001) * n_v);
+            let fresnel = pow(1.0 - v_h, 5.0);
+            scale += (1.0 - fresnel) * visibility;
+            bias += fresnel * visibility;
+        }
+    }
+    let count = f32(params.sample_count);
+    return vec4<f32>(scale / count, bias / count, 0.0, 1.0);
+}
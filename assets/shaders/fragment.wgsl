@@ -0,0 +1,318 @@
+#include "include/constants.wgsl"
+#include "include/camera.wgsl"
+#include "include/lights.wgsl"
+
+@group(1) @binding(0)
+var<uniform> camera: Camera;
+
+@group(2) @binding(0)
+var<storage, read> lights: Lights;
+
+const MAX_SHADOW_CASTERS: u32 = 4u;
+
+struct Shadows {
+    view_proj: array<mat4x4<f32>, MAX_SHADOW_CASTERS>,
+    depth_bias: f32,
+    normal_bias: f32,
+    pcf_radius: u32,
+    texel_size: f32,
+}
+@group(2) @binding(1)
+var<uniform> shadows: Shadows;
+@group(2) @binding(2)
+var t_shadow: texture_depth_2d_array;
+@group(2) @binding(3)
+var s_shadow: sampler_comparison;
+
+struct Environment {
+    intensity: f32,
+    // Mip level of the prefiltered specular map at roughness 1
+    specular_max_lod: f32,
+}
+@group(2) @binding(4)
+var t_irradiance: texture_cube<f32>;
+@group(2) @binding(5)
+var t_prefiltered: texture_cube<f32>;
+@group(2) @binding(6)
+var t_brdf_lut: texture_2d<f32>;
+@group(2) @binding(7)
+var s_environment: sampler;
+@group(2) @binding(8)
+var<uniform> environment: Environment;
+// Screen space ambient occlusion, 1x1 and white while it's disabled
+@group(2) @binding(9)
+var t_ssao: texture_2d<f32>;
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1)
+var s_diffuse: sampler;
+@group(0) @binding(2)
+var t_normal: texture_2d<f32>;
+@group(0) @binding(3)
+var s_normal: sampler;
+@group(0) @binding(4)
+var t_specular: texture_2d<f32>;
+@group(0) @binding(5)
+var s_specular: sampler;
+@group(0) @binding(6)
+var t_alpha: texture_2d<f32>;
+@group(0) @binding(7)
+var s_alpha: sampler;
+@group(0) @binding(8)
+var t_shininess: texture_2d<f32>;
+@group(0) @binding(9)
+var s_shininess: sampler;
+@group(0) @binding(10)
+var t_metallic_roughness: texture_2d<f32>;
+@group(0) @binding(11)
+var s_metallic_roughness: sampler;
+@group(0) @binding(12)
+var t_occlusion: texture_2d<f32>;
+@group(0) @binding(13)
+var s_occlusion: sampler;
+@group(0) @binding(14)
+var t_emissive: texture_2d<f32>;
+@group(0) @binding(15)
+var s_emissive: sampler;
+
+const ILLUM_COLOR: u32 = 0u;
+const ILLUM_DIFFUSE: u32 = 1u;
+
+struct Material {
+    base_color: vec4<f32>,
+    ambient: vec3<f32>,
+    metallic: f32,
+    specular: vec3<f32>,
+    shininess: f32,
+    emissive: vec3<f32>,
+    roughness: f32,
+    normal_scale: f32,
+    alpha_cutoff: f32,
+    ior: f32,
+    illum: u32,
+    occlusion_strength: f32,
+}
+@group(0) @binding(16)
+var<uniform> material: Material;
+
+struct FragmentInput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) world_position: vec3<f32>,
+    @location(2) world_normal: vec3<f32>,
+    @location(3) world_tangent: vec3<f32>,
+    @location(4) world_bitangent: vec3<f32>,
+}
+
+// Returns the direction towards the light and the light's attenuated radiance
+fn light_incidence(light: Light, position: vec3<f32>) -> vec4<f32> {
+    if light.kind == LIGHT_DIRECTIONAL {
+        return vec4<f32>(normalize(-light.direction), light.intensity);
+    }
+
+    let to_light = light.position - position;
+    let distance = length(to_light);
+    let light_dir = to_light / max(distance, 0.0001);
+
+    // Windowed inverse square falloff, reaching zero at the light's range
+    let ratio = distance / max(light.range, 0.0001);
+    let window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    var attenuation = window * window / (distance * distance + 1.0);
+
+    if light.kind == LIGHT_SPOT {
+        let cos_angle = dot(-light_dir, normalize(light.direction));
+        attenuation *= smoothstep(light.cos_outer, light.cos_inner, cos_angle);
+    }
+
+    return vec4<f32>(light_dir, light.intensity * attenuation);
+}
+
+// Fraction of the light reaching `position`, filtered over a (2r+1)^2 texel kernel
+fn shadow_factor(layer: i32, position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    if layer < 0 {
+        return 1.0;
+    }
+
+    let offset_position = position + normal * shadows.normal_bias;
+    let clip = shadows.view_proj[layer] * vec4<f32>(offset_position, 1.0);
+    let ndc = clip.xyz / clip.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+
+    // Everything outside the light's frustum is lit
+    if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || ndc.z > 1.0 || ndc.z < 0.0 {
+        return 1.0;
+    }
+
+    let depth = ndc.z - shadows.depth_bias;
+    let radius = i32(shadows.pcf_radius);
+    var lit = 0.0;
+    for (var x = -radius; x <= radius; x += 1) {
+        for (var y = -radius; y <= radius; y += 1) {
+            let offset = vec2<f32>(f32(x), f32(y)) * shadows.texel_size;
+            lit += textureSampleCompareLevel(t_shadow, s_shadow, uv + offset, layer, depth);
+        }
+    }
+    let width = f32(2 * radius + 1);
+    return lit / (width * width);
+}
+
+// Normal of the fragment with the normal map applied
+fn surface_normal(in: FragmentInput) -> vec3<f32> {
+    var object_normal = textureSample(t_normal, s_normal, in.tex_coords).xyz * 2.0 - 1.0;
+    object_normal = vec3<f32>(object_normal.xy * material.normal_scale, object_normal.z);
+    let tbn = mat3x3<f32>(
+        normalize(in.world_tangent),
+        normalize(in.world_bitangent),
+        normalize(in.world_normal),
+    );
+    return normalize(tbn * object_normal);
+}
+
+fn occlusion(tex_coords: vec2<f32>) -> f32 {
+    let ao = textureSample(t_occlusion, s_occlusion, tex_coords).r;
+    return mix(1.0, ao, material.occlusion_strength);
+}
+
+// Occlusion of the ambient light from the screen space pass, at the pixel being shaded
+fn screen_occlusion(clip_position: vec4<f32>) -> f32 {
+    let size = vec2<i32>(textureDimensions(t_ssao));
+    return textureLoad(t_ssao, min(vec2<i32>(clip_position.xy), size - 1), 0).r;
+}
+
+fn emission(tex_coords: vec2<f32>) -> vec3<f32> {
+    return textureSample(t_emissive, s_emissive, tex_coords).rgb * material.emissive;
+}
+
+@fragment
+fn fs_main(in: FragmentInput) -> @location(0) vec4<f32> {
+    let diffuse_color = textureSample(t_diffuse, s_diffuse, in.tex_coords) * material.base_color;
+    let specular_color = textureSample(t_specular, s_specular, in.tex_coords).rgb * material.specular;
+    let alpha = diffuse_color.a * textureSample(t_alpha, s_alpha, in.tex_coords).r;
+    let shininess = max(textureSample(t_shininess, s_shininess, in.tex_coords).r * material.shininess, 1.0);
+    let normal = surface_normal(in);
+    let ao = occlusion(in.tex_coords) * screen_occlusion(in.clip_position);
+    let emissive = emission(in.tex_coords);
+
+    if alpha < material.alpha_cutoff {
+        discard;
+    }
+
+    if material.illum == ILLUM_COLOR {
+        return vec4<f32>(diffuse_color.rgb + emissive, alpha);
+    }
+
+    let view_dir = normalize(camera.view_position.xyz - in.world_position);
+    let geometric_normal = normalize(in.world_normal);
+
+    // Light from the environment, the diffuse part scaled by the material's ambient reflectance.
+    // Explicit levels since the lookups follow the discard.
+    let irradiance = textureSampleLevel(t_irradiance, s_environment, normal, 0.0).rgb;
+    var diffuse_light = material.ambient * irradiance * environment.intensity * ao;
+    // Roughness whose GGX lobe roughly matches the Blinn-Phong exponent
+    let roughness = sqrt(2.0 / (shininess + 2.0));
+    let n_v = max(dot(normal, view_dir), 0.0);
+    let brdf = textureSampleLevel(t_brdf_lut, s_environment, vec2<f32>(n_v, roughness), 0.0).rg;
+    let prefiltered = textureSampleLevel(t_prefiltered, s_environment, reflect(-view_dir, normal), roughness * environment.specular_max_lod).rgb;
+    var specular_light = prefiltered * (brdf.x + brdf.y) * environment.intensity * ao;
+    for (var i = 0u; i < lights.count; i += 1u) {
+        let light = lights.lights[i];
+        let incidence = light_incidence(light, in.world_position);
+        let light_dir = incidence.xyz;
+
+        let half_dir = normalize(view_dir + light_dir);
+        let shadow = shadow_factor(light.shadow_index, in.world_position, geometric_normal);
+        let radiance = light.color * incidence.w * shadow;
+
+        diffuse_light += radiance * max(dot(normal, light_dir), 0.0);
+        specular_light += radiance * pow(max(dot(normal, half_dir), 0.0), shininess);
+    }
+
+    if material.illum == ILLUM_DIFFUSE {
+        specular_light = vec3<f32>(0.0);
+    }
+
+    return vec4<f32>(diffuse_light * diffuse_color.rgb + specular_light * specular_color + emissive, alpha);
+}
+
+fn distribution_ggx(n_h: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let d = n_h * n_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+// Smith's height correlated visibility, the geometry term divided by 4 n.l n.v
+fn visibility_smith_ggx(n_v: f32, n_l: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let ggx_v = n_l * sqrt(n_v * n_v * (1.0 - a2) + a2);
+    let ggx_l = n_v * sqrt(n_l * n_l * (1.0 - a2) + a2);
+    return 0.5 / max(ggx_v + ggx_l, 0.0001);
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
+    return f0 + (1.0 - f0) * pow(1.0 - cos_theta, 5.0);
+}
+
+// Schlick with the grazing reflectance damped by roughness, for light integrated over the hemisphere
+fn fresnel_schlick_roughness(cos_theta: f32, f0: vec3<f32>, roughness: f32) -> vec3<f32> {
+    return f0 + (max(vec3<f32>(1.0 - roughness), f0) - f0) * pow(1.0 - cos_theta, 5.0);
+}
+
+// Cook-Torrance GGX for metallic-roughness materials
+@fragment
+fn fs_pbr(in: FragmentInput) -> @location(0) vec4<f32> {
+    let base_color = textureSample(t_diffuse, s_diffuse, in.tex_coords) * material.base_color;
+    let alpha = base_color.a * textureSample(t_alpha, s_alpha, in.tex_coords).r;
+    let metallic_roughness = textureSample(t_metallic_roughness, s_metallic_roughness, in.tex_coords);
+    let normal = surface_normal(in);
+    let ao = occlusion(in.tex_coords) * screen_occlusion(in.clip_position);
+    let emissive = emission(in.tex_coords);
+
+    if alpha < material.alpha_cutoff {
+        discard;
+    }
+
+    let metallic = clamp(metallic_roughness.b * material.metallic, 0.0, 1.0);
+    // Perfectly smooth surfaces make the highlight of point lights vanish
+    let roughness = clamp(metallic_roughness.g * material.roughness, 0.045, 1.0);
+    let ggx_alpha = roughness * roughness;
+
+    // Dielectrics reflect according to their index of refraction, metals with their color
+    let dielectric = (material.ior - 1.0) / (material.ior + 1.0);
+    let f0 = mix(vec3<f32>(dielectric * dielectric), base_color.rgb, metallic);
+    let albedo = base_color.rgb * (1.0 - metallic);
+
+    let view_dir = normalize(camera.view_position.xyz - in.world_position);
+    let geometric_normal = normalize(in.world_normal);
+    let n_v = max(dot(normal, view_dir), 0.0001);
+
+    var color = vec3<f32>(0.0);
+    for (var i = 0u; i < lights.count; i += 1u) {
+        let light = lights.lights[i];
+        let incidence = light_incidence(light, in.world_position);
+        let light_dir = incidence.xyz;
+        let n_l = dot(normal, light_dir);
+        if n_l <= 0.0 {
+            continue;
+        }
+
+        let half_dir = normalize(view_dir + light_dir);
+        let n_h = max(dot(normal, half_dir), 0.0);
+        let fresnel = fresnel_schlick(max(dot(view_dir, half_dir), 0.0), f0);
+        let specular = distribution_ggx(n_h, ggx_alpha) * visibility_smith_ggx(n_v, n_l, ggx_alpha) * fresnel;
+        let diffuse = (1.0 - fresnel) * albedo / PI;
+
+        let shadow = shadow_factor(light.shadow_index, in.world_position, geometric_normal);
+        color += (diffuse + specular) * light.color * incidence.w * shadow * n_l;
+    }
+
+    // Split sum image based lighting; the irradiance map holds the cosine weighted average radiance, so no 1 / PI
+    let fresnel = fresnel_schlick_roughness(n_v, f0, roughness);
+    let irradiance = textureSampleLevel(t_irradiance, s_environment, normal, 0.0).rgb;
+    let brdf = textureSampleLevel(t_brdf_lut, s_environment, vec2<f32>(n_v, roughness), 0.0).rg;
+    let prefiltered = textureSampleLevel(t_prefiltered, s_environment, reflect(-view_dir, normal), roughness * environment.specular_max_lod).rgb;
+    let ambient = (1.0 - fresnel) * albedo * irradiance + prefiltered * (f0 * brdf.x + brdf.y);
+    color += ambient * environment.intensity * ao;
+
+    return vec4<f32>(color + emissive, alpha);
+}
@@ -0,0 +1,134 @@
+// Fast approximate anti-aliasing, after FXAA 3.11: finds edges by their contrast in luma,
+// walks along them to their ends and blends each pixel towards the neighbour across the edge.
+
+struct Params {
+    // Amount of blending of single pixel details
+    subpixel: f32,
+    // Contrast below which a pixel isn't an edge, relative to its brightest neighbour
+    edge_threshold: f32,
+    // Absolute contrast below which a pixel isn't an edge, for dark areas
+    edge_threshold_min: f32,
+}
+@group(1) @binding(0)
+var<uniform> params: Params;
+
+@group(0) @binding(0)
+var t_frame: texture_2d<f32>;
+@group(0) @binding(1)
+var s_frame: sampler;
+
+const SEARCH_STEPS: i32 = 10;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32(index & 2u), f32((index << 1u) & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+// Perceptual luma of the HDR frame, compressed so edges against bright pixels aren't all treated alike
+fn luma(uv: vec2<f32>) -> f32 {
+    let color = textureSampleLevel(t_frame, s_frame, uv, 0.0).rgb;
+    let compressed = color / (1.0 + max(color.r, max(color.g, color.b)));
+    return sqrt(dot(compressed, vec3<f32>(0.299, 0.587, 0.114)));
+}
+
+// Distance covered by each step of the search along an edge, in texels
+fn search_step(i: i32) -> f32 {
+    switch i {
+        case 0, 1, 2, 3, 4: { return 1.0; }
+        case 5: { return 1.5; }
+        case 6, 7, 8: { return 2.0; }
+        default: { return 4.0; }
+    }
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_frame));
+    let uv = in.uv;
+
+    let m = luma(uv);
+    let n = luma(uv + vec2<f32>(0.0, -texel.y));
+    let s = luma(uv + vec2<f32>(0.0, texel.y));
+    let e = luma(uv + vec2<f32>(texel.x, 0.0));
+    let w = luma(uv + vec2<f32>(-texel.x, 0.0));
+
+    let highest = max(max(max(n, s), max(e, w)), m);
+    let lowest = min(min(min(n, s), min(e, w)), m);
+    let range = highest - lowest;
+    if range < max(params.edge_threshold_min, params.edge_threshold * highest) {
+        return textureSampleLevel(t_frame, s_frame, uv, 0.0);
+    }
+
+    let ne = luma(uv + vec2<f32>(texel.x, -texel.y));
+    let nw = luma(uv + vec2<f32>(-texel.x, -texel.y));
+    let se = luma(uv + vec2<f32>(texel.x, texel.y));
+    let sw = luma(uv + vec2<f32>(-texel.x, texel.y));
+
+    // Blend of pixels standing out from all their neighbours
+    let average = (2.0 * (n + s + e + w) + ne + nw + se + sw) / 12.0;
+    let subpixel_factor = smoothstep(0.0, 1.0, clamp(abs(average - m) / range, 0.0, 1.0));
+    let subpixel_blend = subpixel_factor * subpixel_factor * params.subpixel;
+
+    let horizontal = abs(n + s - 2.0 * m) * 2.0 + abs(ne + se - 2.0 * e) + abs(nw + sw - 2.0 * w);
+    let vertical = abs(e + w - 2.0 * m) * 2.0 + abs(ne + nw - 2.0 * n) + abs(se + sw - 2.0 * s);
+    let is_horizontal = horizontal >= vertical;
+
+    // Across the edge, towards the side with the larger contrast
+    var normal = select(vec2<f32>(texel.x, 0.0), vec2<f32>(0.0, texel.y), is_horizontal);
+    let positive = select(e, s, is_horizontal);
+    let negative = select(w, n, is_horizontal);
+    var opposite = positive;
+    var gradient = abs(positive - m);
+    if abs(negative - m) > gradient {
+        normal = -normal;
+        opposite = negative;
+        gradient = abs(negative - m);
+    }
+
+    // Walk along the edge both ways until its contrast drops
+    let along = select(vec2<f32>(0.0, texel.y), vec2<f32>(texel.x, 0.0), is_horizontal);
+    let edge_uv = uv + normal * 0.5;
+    let edge_luma = (m + opposite) * 0.5;
+    let threshold = gradient * 0.25;
+
+    var positive_uv = edge_uv + along;
+    var positive_delta = luma(positive_uv) - edge_luma;
+    var positive_end = abs(positive_delta) >= threshold;
+    for (var i = 0; i < SEARCH_STEPS && !positive_end; i++) {
+        positive_uv += along * search_step(i);
+        positive_delta = luma(positive_uv) - edge_luma;
+        positive_end = abs(positive_delta) >= threshold;
+    }
+
+    var negative_uv = edge_uv - along;
+    var negative_delta = luma(negative_uv) - edge_luma;
+    var negative_end = abs(negative_delta) >= threshold;
+    for (var i = 0; i < SEARCH_STEPS && !negative_end; i++) {
+        negative_uv -= along * search_step(i);
+        negative_delta = luma(negative_uv) - edge_luma;
+        negative_end = abs(negative_delta) >= threshold;
+    }
+
+    let to_positive = select(positive_uv.y - uv.y, positive_uv.x - uv.x, is_horizontal);
+    let to_negative = select(uv.y - negative_uv.y, uv.x - negative_uv.x, is_horizontal);
+    let nearest = min(to_positive, to_negative);
+    let delta = select(negative_delta, positive_delta, to_positive <= to_negative);
+
+    // Only the side of the edge the pixel belongs to is blended, by how close it is to the nearest end
+    var edge_blend = 0.0;
+    if (delta >= 0.0) != (m - edge_luma >= 0.0) {
+        edge_blend = 0.5 - nearest / (to_positive + to_negative);
+    }
+
+    let blend = max(subpixel_blend, edge_blend);
+    return textureSampleLevel(t_frame, s_frame, uv + normal * blend, 0.0);
+}
@@ -0,0 +1,9 @@
+// Layout of `CameraUniform`, bound by every shader drawing the scene
+struct Camera {
+    view_position: vec4<f32>,
+    view_proj: mat4x4<f32>,
+    view_proj_no_translation: mat4x4<f32>,
+    // Without the jitter of temporal anti-aliasing, for reprojection
+    prev_view_proj: mat4x4<f32>,
+    inv_view_proj: mat4x4<f32>,
+}
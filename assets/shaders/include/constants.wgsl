@@ -0,0 +1 @@
+const PI: f32 = 3.14159265359;
@@ -0,0 +1,23 @@
+// Layout of the light storage buffer of `LightManager`
+
+const LIGHT_POINT: u32 = 0u;
+const LIGHT_DIRECTIONAL: u32 = 1u;
+const LIGHT_SPOT: u32 = 2u;
+
+struct Light {
+    position: vec3<f32>,
+    kind: u32,
+    direction: vec3<f32>,
+    range: f32,
+    color: vec3<f32>,
+    intensity: f32,
+    cos_inner: f32,
+    cos_outer: f32,
+    // Shadow map layer, -1 if the light casts no shadow
+    shadow_index: i32,
+}
+
+struct Lights {
+    count: u32,
+    lights: array<Light>,
+}
@@ -0,0 +1,9 @@
+struct FragmentInput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+}
+
+@fragment
+fn fs_main(in: FragmentInput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
@@ -0,0 +1,34 @@
+#include "include/camera.wgsl"
+#include "include/lights.wgsl"
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+@group(1) @binding(0)
+var<storage, read> lights: Lights;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+}
+
+const MARKER_SCALE: f32 = 0.25;
+
+// Draws a small marker at every light; directional lights have no position and are moved outside the clip volume
+@vertex
+fn vs_main(model: VertexInput, @builtin(instance_index) index: u32) -> VertexOutput {
+    let light = lights.lights[index];
+
+    var out: VertexOutput;
+    if light.kind == LIGHT_DIRECTIONAL {
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+    } else {
+        out.clip_position = camera.view_proj * vec4<f32>(model.position * MARKER_SCALE + light.position, 1.0);
+    }
+    out.color = light.color;
+    return out;
+}
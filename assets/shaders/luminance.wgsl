@@ -0,0 +1,85 @@
+// Eye adaptation: a histogram of the log2 luminance of the frame, then its average
+// blended into the luminance the tonemapping pass exposes for.
+
+// Same layout as in tonemap.wgsl, only the luminance range and adaptation rates are read here
+struct Params {
+    exposure: f32,
+    gamma: f32,
+    tonemapper: u32,
+    flags: u32,
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    adapt_up: f32,
+    adapt_down: f32,
+    lut_domain_min: vec4<f32>,
+    lut_domain_max: vec4<f32>,
+}
+@group(1) @binding(0)
+var<uniform> params: Params;
+
+@group(0) @binding(0)
+var t_frame: texture_2d<f32>;
+
+@group(2) @binding(0)
+var<storage, read_write> histogram: array<atomic<u32>, 256>;
+@group(2) @binding(1)
+var<storage, read_write> adapted: f32;
+
+const BINS: u32 = 256u;
+
+var<workgroup> local_bins: array<atomic<u32>, 256>;
+var<workgroup> weighted: array<u32, 256>;
+
+// Bin 0 holds the pixels too dark to be measured
+fn bin(color: vec3<f32>) -> u32 {
+    let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    if luminance < 0.0001 {
+        return 0u;
+    }
+    let position = clamp((log2(luminance) - params.min_log_luminance) / params.log_luminance_range, 0.0, 1.0);
+    return u32(position * 254.0 + 1.0);
+}
+
+@compute @workgroup_size(16, 16)
+fn cs_histogram(@builtin(global_invocation_id) id: vec3<u32>, @builtin(local_invocation_index) index: u32) {
+    atomicStore(&local_bins[index], 0u);
+    workgroupBarrier();
+
+    let size = textureDimensions(t_frame);
+    if id.x < size.x && id.y < size.y {
+        // Half floats overflow to infinity on very bright pixels
+        let color = min(textureLoad(t_frame, vec2<i32>(id.xy), 0).rgb, vec3<f32>(65000.0));
+        atomicAdd(&local_bins[bin(color)], 1u);
+    }
+    workgroupBarrier();
+
+    atomicAdd(&histogram[index], atomicLoad(&local_bins[index]));
+}
+
+// Dispatched as a single workgroup, which also clears the histogram for the next frame
+@compute @workgroup_size(256)
+fn cs_average(@builtin(local_invocation_index) index: u32) {
+    let count = atomicExchange(&histogram[index], 0u);
+    weighted[index] = count * index;
+    workgroupBarrier();
+
+    // Sum of the bins weighted by their index, halving the active threads every step
+    for (var stride = BINS / 2u; stride > 0u; stride >>= 1u) {
+        if index < stride {
+            weighted[index] += weighted[index + stride];
+        }
+        workgroupBarrier();
+    }
+
+    if index == 0u {
+        let size = textureDimensions(t_frame);
+        let measured = i32(size.x * size.y) - i32(count);
+        if measured <= 0 {
+            return;
+        }
+        let average_bin = f32(weighted[0]) / f32(measured) - 1.0;
+        let target_luminance = exp2(average_bin / 254.0 * params.log_luminance_range + params.min_log_luminance);
+        let rate = select(params.adapt_down, params.adapt_up, target_luminance > adapted);
+        adapted += (target_luminance - adapted) * rate;
+    }
+}
@@ -0,0 +1,174 @@
+#include "include/camera.wgsl"
+
+// Screen space ambient occlusion: a prepass stores the position and normal of the surface under every pixel,
+// a hemisphere of samples around it is compared against those positions, and the noisy result is blurred
+// along both axes without bleeding across depth discontinuities.
+
+const MAX_SAMPLES: u32 = 64u;
+
+struct Params {
+    // Sample offsets in a hemisphere around +z, denser towards the center
+    kernel: array<vec4<f32>, MAX_SAMPLES>,
+    radius: f32,
+    bias: f32,
+    samples: u32,
+    intensity: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+// xyz is the position relative to the camera, w the view depth; 0 where nothing was drawn
+@group(1) @binding(0)
+var t_position: texture_2d<f32>;
+@group(1) @binding(1)
+var t_normal: texture_2d<f32>;
+// Tiled random rotations of the kernel around the normal
+@group(1) @binding(2)
+var t_noise: texture_2d<f32>;
+@group(1) @binding(3)
+var<uniform> params: Params;
+
+@group(2) @binding(0)
+var t_occlusion: texture_2d<f32>;
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+    @location(9) normal_matrix_0: vec3<f32>,
+    @location(10) normal_matrix_1: vec3<f32>,
+    @location(11) normal_matrix_2: vec3<f32>,
+}
+
+struct PrepassOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) relative_position: vec3<f32>,
+    @location(1) world_normal: vec3<f32>,
+    // Fragment position w is 1 / w on some backends, so the view depth is passed on its own
+    @location(2) view_depth: f32,
+}
+
+@vertex
+fn vs_prepass(@location(0) position: vec3<f32>, @location(2) normal: vec3<f32>, instance: InstanceInput) -> PrepassOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    let normal_matrix = mat3x3<f32>(
+        instance.normal_matrix_0,
+        instance.normal_matrix_1,
+        instance.normal_matrix_2,
+    );
+
+    let world_position = model_matrix * vec4<f32>(position, 1.0);
+
+    var out: PrepassOutput;
+    out.clip_position = camera.view_proj * world_position;
+    out.view_depth = out.clip_position.w;
+    // Relative to the camera, so half floats keep their precision far from the origin
+    out.relative_position = world_position.xyz - camera.view_position.xyz;
+    out.world_normal = normalize(normal_matrix * normal);
+    return out;
+}
+
+struct GBuffer {
+    @location(0) position: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+}
+
+@fragment
+fn fs_prepass(in: PrepassOutput) -> GBuffer {
+    var out: GBuffer;
+    out.position = vec4<f32>(in.relative_position, in.view_depth);
+    out.normal = vec4<f32>(normalize(in.world_normal), 0.0);
+    return out;
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32(index & 2u), f32((index << 1u) & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_ssao(@builtin(position) clip_position: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(clip_position.xy);
+    let size = vec2<f32>(textureDimensions(t_position));
+    let surface = textureLoad(t_position, pixel, 0);
+    if surface.w <= 0.0 {
+        return vec4<f32>(1.0);
+    }
+
+    // Basis around the normal, rotated by the noise so neighbouring pixels sample different directions
+    let normal = textureLoad(t_normal, pixel, 0).xyz;
+    let rotation = textureLoad(t_noise, pixel % vec2<i32>(textureDimensions(t_noise)), 0).xyz;
+    let tangent = normalize(rotation - normal * dot(rotation, normal));
+    let tbn = mat3x3<f32>(tangent, cross(normal, tangent), normal);
+
+    let samples = min(params.samples, MAX_SAMPLES);
+    var occlusion = 0.0;
+    for (var i = 0u; i < samples; i++) {
+        let offset = tbn * params.kernel[i].xyz * params.radius;
+        let clip = camera.view_proj * vec4<f32>(camera.view_position.xyz + surface.xyz + offset, 1.0);
+        if clip.w <= 0.0 {
+            continue;
+        }
+        let ndc = clip.xy / clip.w;
+        let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+        if any(uv < vec2<f32>(0.0)) || any(uv >= vec2<f32>(1.0)) {
+            continue;
+        }
+
+        let scene_depth = textureLoad(t_position, vec2<i32>(uv * size), 0).w;
+        if scene_depth <= 0.0 {
+            continue;
+        }
+        // Surfaces far in front of the sample don't occlude it
+        let in_range = smoothstep(0.0, 1.0, params.radius / abs(surface.w - scene_depth));
+        occlusion += select(0.0, 1.0, scene_depth <= clip.w - params.bias) * in_range;
+    }
+
+    let ao = pow(1.0 - occlusion / f32(max(samples, 1u)), params.intensity);
+    return vec4<f32>(ao, ao, ao, 1.0);
+}
+
+const BLUR_RADIUS: i32 = 4;
+const BLUR_SIGMA: f32 = 2.0;
+
+// Gaussian over the kernel weighted down by the difference in depth, so occlusion doesn't spread over edges
+fn blur(pixel: vec2<i32>, direction: vec2<i32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(t_occlusion));
+    let depth = textureLoad(t_position, pixel, 0).w;
+    if depth <= 0.0 {
+        return vec4<f32>(1.0);
+    }
+
+    var sum = 0.0;
+    var weights = 0.0;
+    for (var i = -BLUR_RADIUS; i <= BLUR_RADIUS; i++) {
+        let sample_pixel = clamp(pixel + direction * i, vec2<i32>(0), size - 1);
+        let sample_depth = textureLoad(t_position, sample_pixel, 0).w;
+        let spatial = exp(-f32(i * i) / (2.0 * BLUR_SIGMA * BLUR_SIGMA));
+        let range = select(0.0, exp(-abs(sample_depth - depth) / (0.05 * depth)), sample_depth > 0.0);
+        let weight = spatial * range;
+        sum += textureLoad(t_occlusion, sample_pixel, 0).r * weight;
+        weights += weight;
+    }
+
+    let ao = sum / max(weights, 0.0001);
+    return vec4<f32>(ao, ao, ao, 1.0);
+}
+
+@fragment
+fn fs_blur_horizontal(@builtin(position) clip_position: vec4<f32>) -> @location(0) vec4<f32> {
+    return blur(vec2<i32>(clip_position.xy), vec2<i32>(1, 0));
+}
+
+@fragment
+fn fs_blur_vertical(@builtin(position) clip_position: vec4<f32>) -> @location(0) vec4<f32> {
+    return blur(vec2<i32>(clip_position.xy), vec2<i32>(0, 1));
+}
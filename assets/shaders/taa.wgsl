@@ -0,0 +1,100 @@
+#include "include/camera.wgsl"
+
+// Temporal anti-aliasing: the jittered frame is blended with the previous result,
+// reprojected from the depth of the frame and clamped to the colors around the pixel.
+// Writes the result to both the frame and the history read by the next frame.
+
+struct Params {
+    // Offset of the projection this frame, in normalized device coordinates
+    jitter: vec2<f32>,
+    // Weight of the history
+    feedback: f32,
+    // Set on the first frame after the history was lost
+    reset: u32,
+}
+
+@group(0) @binding(0)
+var t_frame: texture_2d<f32>;
+@group(0) @binding(1)
+var s_frame: sampler;
+
+@group(1) @binding(0)
+var t_history: texture_2d<f32>;
+@group(1) @binding(1)
+var s_history: sampler;
+
+@group(2) @binding(0)
+var t_depth: texture_depth_2d;
+@group(2) @binding(1)
+var<uniform> params: Params;
+
+@group(3) @binding(0)
+var<uniform> camera: Camera;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32(index & 2u), f32((index << 1u) & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+struct Output {
+    @location(0) frame: vec4<f32>,
+    @location(1) history: vec4<f32>,
+}
+
+// Weight compressing bright pixels, so a single one doesn't dominate the blend
+fn luma_weight(color: vec3<f32>) -> f32 {
+    return 1.0 / (1.0 + dot(color, vec3<f32>(0.2126, 0.7152, 0.0722)));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> Output {
+    let pixel = vec2<i32>(in.clip_position.xy);
+    let size = vec2<i32>(textureDimensions(t_frame));
+    // Half floats overflow to infinity on very bright pixels
+    let current = min(textureLoad(t_frame, pixel, 0), vec4<f32>(65000.0));
+
+    // Bounds of the colors around the pixel, which the history shouldn't leave
+    var low = current.rgb;
+    var high = current.rgb;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let neighbour = min(textureLoad(t_frame, clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), size - 1), 0).rgb, vec3<f32>(65000.0));
+            low = min(low, neighbour);
+            high = max(high, neighbour);
+        }
+    }
+
+    // Position of the surface seen through the pixel, in the previous frame
+    let depth = textureLoad(t_depth, pixel, 0);
+    let ndc = vec2<f32>(in.uv.x * 2.0 - 1.0, 1.0 - in.uv.y * 2.0) - params.jitter;
+    let world = camera.inv_view_proj * vec4<f32>(ndc, depth, 1.0);
+    let prev_clip = camera.prev_view_proj * vec4<f32>(world.xyz / world.w, 1.0);
+    let prev_ndc = prev_clip.xy / prev_clip.w;
+    let prev_uv = vec2<f32>(prev_ndc.x * 0.5 + 0.5, 0.5 - prev_ndc.y * 0.5);
+
+    var out: Output;
+    let offscreen = any(prev_uv < vec2<f32>(0.0)) || any(prev_uv > vec2<f32>(1.0)) || prev_clip.w <= 0.0;
+    if params.reset != 0u || offscreen {
+        out.frame = current;
+        out.history = current;
+        return out;
+    }
+
+    let history = clamp(textureSampleLevel(t_history, s_history, prev_uv, 0.0).rgb, low, high);
+    let current_weight = (1.0 - params.feedback) * luma_weight(current.rgb);
+    let history_weight = params.feedback * luma_weight(history);
+    let color = (current.rgb * current_weight + history * history_weight) / (current_weight + history_weight);
+
+    out.frame = vec4<f32>(color, current.a);
+    out.history = out.frame;
+    return out;
+}
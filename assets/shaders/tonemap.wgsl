@@ -0,0 +1,161 @@
+// Final pass from the linear HDR frame to the surface: exposure, tonemapping,
+// display encoding and LUT grading, drawn as one fullscreen triangle.
+
+struct Params {
+    // Multiplier of the frame, or of the adapted exposure with eye adaptation
+    exposure: f32,
+    // Applied on top of the sRGB encoding
+    gamma: f32,
+    // 0 - clamp, 1 - Reinhard, 2 - ACES, 3 - AgX
+    tonemapper: u32,
+    // 1 - eye adaptation, 2 - encode to sRGB in the shader, 4 - LUT grading
+    flags: u32,
+    // log2 luminance of the first bin of the histogram and the range it covers
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    // Fraction of the way to the measured luminance covered this frame, when it brightens and darkens
+    adapt_up: f32,
+    adapt_down: f32,
+    // w - entries along each axis
+    lut_domain_min: vec4<f32>,
+    // w - blend between the graded and ungraded color
+    lut_domain_max: vec4<f32>,
+}
+@group(1) @binding(0)
+var<uniform> params: Params;
+
+@group(0) @binding(0)
+var t_frame: texture_2d<f32>;
+@group(0) @binding(1)
+var s_frame: sampler;
+
+@group(2) @binding(0)
+var t_lut: texture_3d<f32>;
+@group(2) @binding(1)
+var s_lut: sampler;
+// Adapted luminance, written by the eye adaptation passes
+@group(2) @binding(2)
+var<storage, read> adapted: f32;
+
+const AUTO_EXPOSURE: u32 = 1u;
+const ENCODE_SRGB: u32 = 2u;
+const LUT: u32 = 4u;
+
+// Luminance mapped to middle grey by eye adaptation
+const KEY: f32 = 0.18;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32(index & 2u), f32((index << 1u) & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+fn reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (1.0 + color);
+}
+
+// Stephen Hill's fit of the ACES reference rendering and output transforms
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let input = mat3x3<f32>(
+        vec3<f32>(0.59719, 0.07600, 0.02840),
+        vec3<f32>(0.35458, 0.90834, 0.13383),
+        vec3<f32>(0.04823, 0.01566, 0.83777),
+    );
+    let output = mat3x3<f32>(
+        vec3<f32>(1.60475, -0.10208, -0.00327),
+        vec3<f32>(-0.53108, 1.10813, -0.07276),
+        vec3<f32>(-0.07367, -0.00605, 1.07602),
+    );
+    let v = input * color;
+    let a = v * (v + 0.0245786) - 0.000090537;
+    let b = v * (0.983729 * v + 0.4329510) + 0.238081;
+    return output * (a / b);
+}
+
+// Polynomial fit of the AgX base contrast curve, over log2 values from -12.47 to 4.03 stops
+fn agx_contrast(x: vec3<f32>) -> vec3<f32> {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    return 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+}
+
+// Troy Sobotka's AgX with its default look, after Benjamin Wrensch's approximation
+fn agx(color: vec3<f32>) -> vec3<f32> {
+    let inset = mat3x3<f32>(
+        vec3<f32>(0.842479062253094, 0.0423282422610123, 0.0423756549057051),
+        vec3<f32>(0.0784335999999992, 0.878468636469772, 0.0784336),
+        vec3<f32>(0.0792237451477643, 0.0791661274605434, 0.879142973793104),
+    );
+    let outset = mat3x3<f32>(
+        vec3<f32>(1.19687900512017, -0.0528968517574562, -0.0529716355144438),
+        vec3<f32>(-0.0980208811401368, 1.15190312990417, -0.0980434501171241),
+        vec3<f32>(-0.0990297440797205, -0.0989611768448433, 1.15107367264116),
+    );
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+    let v = clamp(log2(max(inset * color, vec3<f32>(1e-10))), vec3<f32>(min_ev), vec3<f32>(max_ev));
+    let encoded = agx_contrast((v - min_ev) / (max_ev - min_ev));
+    // The curve outputs display encoded values
+    return pow(max(outset * encoded, vec3<f32>(0.0)), vec3<f32>(2.2));
+}
+
+fn tonemap(color: vec3<f32>) -> vec3<f32> {
+    switch params.tonemapper {
+        case 1u: { return reinhard(color); }
+        case 2u: { return aces(color); }
+        case 3u: { return agx(color); }
+        default: { return color; }
+    }
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
+
+// LUTs from grading tools map display encoded colors
+fn grade(color: vec3<f32>) -> vec3<f32> {
+    let size = params.lut_domain_min.w;
+    let domain = (color - params.lut_domain_min.xyz) / (params.lut_domain_max.xyz - params.lut_domain_min.xyz);
+    // Entries sit at the centers of the texels
+    let coords = clamp(domain, vec3<f32>(0.0), vec3<f32>(1.0)) * (size - 1.0) / size + 0.5 / size;
+    let graded = textureSampleLevel(t_lut, s_lut, coords, 0.0).rgb;
+    return mix(color, graded, params.lut_domain_max.w);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let frame = textureSample(t_frame, s_frame, in.uv);
+
+    var exposure = params.exposure;
+    if (params.flags & AUTO_EXPOSURE) != 0u {
+        exposure *= KEY / max(adapted, 1e-4);
+    }
+
+    let mapped = clamp(tonemap(frame.rgb * exposure), vec3<f32>(0.0), vec3<f32>(1.0));
+    var encoded = pow(linear_to_srgb(mapped), vec3<f32>(1.0 / params.gamma));
+    if (params.flags & LUT) != 0u {
+        encoded = grade(encoded);
+    }
+
+    // sRGB surfaces encode on write
+    if (params.flags & ENCODE_SRGB) != 0u {
+        return vec4<f32>(encoded, frame.a);
+    }
+    return vec4<f32>(srgb_to_linear(encoded), frame.a);
+}
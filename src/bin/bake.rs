@@ -0,0 +1,15 @@
+use engine::bake_models;
+
+/// Pre-bakes mesh caches: `bake <model.obj>...`
+fn main() {
+    let files: Vec<String> = std::env::args().skip(1).collect();
+    if files.is_empty() {
+        eprintln!("Usage: bake <model.obj>...");
+        std::process::exit(2);
+    }
+
+    if let Err(e) = bake_models(&files) {
+        log::error!("Baking failed: {e}");
+        std::process::exit(1);
+    }
+}
@@ -17,7 +17,7 @@ pub struct GuiElement {
 impl GuiElement {
     pub fn new(depth: usize) -> Self
     {
-        return Self {
+        Self {
             origin_type: Origin::TopLeft,
             origin_pos: Pos::Rel2D(Vec2::default()),
             size: Size::Rel2D(Vec2::default()),
@@ -12,18 +12,20 @@ use element::Quad;
 
 use crate::math::Vec2;
 
-use super::{renderer::{gpu::{buffer::Buffer, shader::Shader, uniform::Uniform}, Pipeline, state::{State, RenderState}, pipeline::PipelineBuilder, RendererError, resources::model::Vertex}, PathManager, Renderer};
+use std::sync::Arc;
+
+use super::renderer::{gpu::{buffer::Buffer, uniform::Uniform}, Pipeline, state::{State, RenderState}, pipeline::{PipelineBuilder, PipelineCache}, RendererError, resources::model::Vertex};
 
 pub struct Gui{
     depth_layers: usize,
     elements: Vec<Vec<GuiElement>>,
     buffers: Vec<Buffer>,
-    pipeline: Pipeline,
+    pipeline: Arc<Pipeline>,
     uniform: Uniform<UniformRaw>,
 }
 
 impl Gui {
-    pub fn new(state: &State, path_m: &PathManager, depth_layers: usize) -> Result<Self, RendererError> {
+    pub fn new(state: &State, pipelines: &mut PipelineCache, depth_layers: usize) -> Result<Self, RendererError> {
         let mut elements = Vec::with_capacity(depth_layers);
 
         for _ in 0..depth_layers {
@@ -43,12 +45,12 @@ impl Gui {
             buffers.push(vertex_buffer)
         }
 
-        let v = Shader::import_vert(state, "vs_main", path_m.shader("gui/2d_vert.wgsl"), "GUI Vertex Shader")?;
-        let f = Shader::import_frag(state, "fs_main", path_m.shader("gui/2d_frag.wgsl"), "GUI Fragment Shader")?;
+        let v = pipelines.import_vert(state, "vs_main", "gui/2d_vert.wgsl", "GUI Vertex Shader")?;
+        let f = pipelines.import_frag(state, "fs_main", "gui/2d_frag.wgsl", "GUI Fragment Shader")?;
 
         let uniform = Uniform::new(&state.device, UniformRaw::new(state.size), "GUI Uniforms", wgpu::ShaderStages::VERTEX_FRAGMENT);
 
-        let pipeline = PipelineBuilder::new(
+        let pipeline = pipelines.get(state, PipelineBuilder::new(
             v.vs_state(&[Quad::desc()]),
             Some(f.fs_state(&[Some(wgpu::ColorTargetState {
                 format: state.config.format,
@@ -56,8 +58,7 @@ impl Gui {
                 write_mask: wgpu::ColorWrites::ALL,
             })]))
         ).with_topology(wgpu::PrimitiveTopology::TriangleStrip)
-        .with_bg_layouts(&[uniform.bg.layout()])
-        .construct(state);
+        .with_layouts(&[&uniform.bg.layout]))?;
 
         Ok(Self {
                     depth_layers,
@@ -101,6 +102,11 @@ impl Gui {
         state.queue.write_buffer(&self.buffers[depth].0, 0, bytemuck::cast_slice(&verts));
     }
 
+    /// Picks up the pipeline rebuilt by [`PipelineCache::reload`]
+    pub fn refresh_pipelines(&mut self, pipelines: &PipelineCache) {
+        pipelines.refresh(&mut self.pipeline);
+    }
+
     pub fn render(&self, render_state: &mut RenderState) {
         let mut render_pass = render_state.render_pass(
             Some("GUI Render Pass"),
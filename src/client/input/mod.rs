@@ -109,4 +109,8 @@ impl <'a> InputManager<'a> {
     pub fn update(&mut self) {
         self.mouse.update();
     }
+}
+
+impl Default for InputManager<'_> {
+    fn default() -> Self { Self::new() }
 }
\ No newline at end of file
@@ -9,15 +9,15 @@ mod player;
 mod render;
 
 use player::Player;
-use crate::err::Error;
+use crate::{err::Error, math::Vec2};
 
-use self::gui::GuiElement;
+use self::{gui::GuiElement, renderer::{light::Light, scene::Node}};
+use crate::math::{Vec3, Quat, Angle};
 
 pub use {
     window::Window,
     renderer::Renderer,
     time::Time,
-    input::InputMapping,
     input::InputManager,
     path::PathManager,
     gui::Gui,
@@ -26,7 +26,7 @@ pub use {
 pub struct Client<'a> {
     pub renderer: Renderer,
     pub player:   Player,
-    pub window:   Window,
+    pub window:   Option<Window>,
     pub time:     Time,
     pub input:    InputManager<'a>,
     pub path_m:   PathManager,
@@ -35,18 +35,44 @@ pub struct Client<'a> {
 
 impl <'a> Client<'a> {
     pub async fn new(window: winit::window::Window) -> Result<Client<'a>, Error> {
-        let path_m = PathManager::new(
+        let path_m = Self::init_paths();
+
+        let window = Window::new(window);
+
+        let renderer = Renderer::new(&window, &path_m).await?;
+
+        Self::init(renderer, Some(window), path_m)
+    }
+
+    /// Creates a client without a window; frames are rendered offscreen and can be captured with [`Renderer::capture_frame`]
+    pub async fn new_headless(size: Vec2<u32>) -> Result<Client<'a>, Error> {
+        let path_m = Self::init_paths();
+
+        let renderer = Renderer::new_headless(size, &path_m).await?;
+
+        Self::init(renderer, None, path_m)
+    }
+
+    fn init_paths() -> PathManager {
+        PathManager::new(
             "assets/shaders",
             "assets/models",
             "assets/cubemaps",
             "assets/textures"
-        );
+        )
+    }
 
+    fn init(mut renderer: Renderer, window: Option<Window>, path_m: PathManager) -> Result<Client<'a>, Error> {
         let mut input = InputManager::new();
 
-        let window = Window::new(window);
+        renderer.lights.add(Light::point((2., 2., 2.), (1., 1., 1.), 4., 20.));
+        renderer.lights.add(Light::directional((-0.3, -1., -0.2), (1., 0.95, 0.85), 0.6).with_shadows());
 
-        let renderer = Renderer::new(&window, &path_m).await?;
+        renderer.set_anti_aliasing(renderer::AntiAliasing::Msaa(4))?;
+
+        let barrel = renderer.load_model("assets/models/barrel.obj", &path_m)?;
+        renderer.light_marker = Some(barrel);
+        Self::init_scene(&mut renderer, barrel);
 
         let player = Player::new(&renderer.state.device, &renderer.state.queue, renderer.state.size.x, renderer.state.size.y, &mut input);
 
@@ -55,7 +81,7 @@ impl <'a> Client<'a> {
         input.register_mapping("cursor_hide", winit::event::VirtualKeyCode::F1);
         input.register_mapping("cursor_grab", winit::event::VirtualKeyCode::F2);
 
-        let mut gui = gui::Gui::new(&renderer.state, &path_m, 8)?;
+        let mut gui = gui::Gui::new(&renderer.state, &mut renderer.pipelines, 8)?;
 
         gui.add(
             GuiElement::new(0)
@@ -79,6 +105,27 @@ impl <'a> Client<'a> {
         Ok(Client {renderer, player, window, time, input, path_m, gui })
     }
 
+    fn init_scene(renderer: &mut Renderer, model: renderer::scene::ModelHandle) {
+        const NUM_INSTANCES_PER_ROW: u32 = 10;
+        const SPACE_BETWEEN: f32 = 3.0;
+
+        for z in 0..NUM_INSTANCES_PER_ROW {
+            for x in 0..NUM_INSTANCES_PER_ROW {
+                let x = SPACE_BETWEEN * (x as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
+                let z = SPACE_BETWEEN * (z as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
+
+                let position = Vec3::new(x, 0., z);
+                let rotation = if position == Vec3::default() {
+                    Quat::identity()
+                } else {
+                    Quat::from_axis_angle(position.normalize(), Angle::from_deg(45.))
+                };
+
+                renderer.scene.spawn(Node::with_model(model).set_position(position).set_rotation(rotation));
+            }
+        }
+    }
+
     pub fn device_input(&mut self, event: &winit::event::DeviceEvent) {
         self.input.device_input(event);
     }
@@ -100,21 +147,26 @@ impl <'a> Client<'a> {
 
     pub fn update(&mut self) {
         self.time.update(&self.renderer.state);
-        self.renderer.update(&self.time);
-        self.player.update(&self.time, &self.renderer.state.queue, &self.input);
-        self.time.every(50, || self.window.set_title(&format!("{:.2}", self.time.fps.avg_fps)));
-
-        // Rare update
-        self.time.every(3, || {
-            if self.input.get_key_once("cursor_hide") {self.window.switch_cursor_visibility()}
-            if self.input.get_key_once("cursor_grab") {self.window.switch_cursor_grab()}
-        });
+        self.renderer.update();
+        if !self.renderer.reload_shaders().is_empty() {
+            self.gui.refresh_pipelines(&self.renderer.pipelines);
+        }
+        self.player.update(&self.time, &self.renderer.state.queue, &self.input, self.renderer.jitter());
+        if let Some(window) = &mut self.window {
+            self.time.every(50, || window.set_title(&format!("{:.2}", self.time.fps.avg_fps)));
+
+            // Rare update
+            self.time.every(3, || {
+                if self.input.get_key_once("cursor_hide") {window.switch_cursor_visibility()}
+                if self.input.get_key_once("cursor_grab") {window.switch_cursor_grab()}
+            });
+        }
 
 
         self.input.update();
     }
 
-    pub fn window_id(&self) -> winit::window::WindowId {
-        self.window.id()
+    pub fn window_id(&self) -> Option<winit::window::WindowId> {
+        self.window.as_ref().map(Window::id)
     }
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use crate::{common::math::{angle::Angle, vec::Vec3, mat::Mat4}, client::Time};
+use crate::common::math::{angle::Angle, vec::Vec3, mat::Mat4};
 
 
 pub struct PhysicalCamera {
@@ -1,24 +1,29 @@
 use crate::client::renderer::gpu::uniform::Uniform;
-use crate::common::math::mat::Mat4;
+use crate::common::math::{mat::Mat4, vec::{Vec2, Vec3}};
 use crate::math::Vec4;
 
 
-pub struct CameraUniform(Uniform<UniformRaw>);
+pub struct CameraUniform {
+    uniform: Uniform<UniformRaw>,
+    /// Unjittered, kept for the reprojection of the next frame
+    view_proj: Mat4<f32>,
+}
 
 impl CameraUniform {
     pub fn new(device: &wgpu::Device) -> Self {
         let raw = UniformRaw::new();
         let uniform = Uniform::new(device, raw, "Camera", wgpu::ShaderStages::VERTEX_FRAGMENT);
 
-        Self(uniform)
+        Self { uniform, view_proj: Mat4::identity() }
     }
 
-    pub fn update(&mut self, queue: &wgpu::Queue, camera: &super::PhysicalCamera, projection: &super::Projection) {
-        self.0.data.update_view_proj(camera, projection);
-        self.0.update(queue);
+    /// `jitter` offsets the projection by a fraction of a pixel, in normalized device coordinates
+    pub fn update(&mut self, queue: &wgpu::Queue, camera: &super::PhysicalCamera, projection: &super::Projection, jitter: Vec2<f32>) {
+        self.view_proj = self.uniform.data.update_view_proj(camera, projection, jitter, self.view_proj);
+        self.uniform.update(queue);
     }
 
-    pub fn bg(&self) -> &wgpu::BindGroup {&self.0.bg.group}
+    pub fn bg(&self) -> &wgpu::BindGroup {&self.uniform.bg.group}
 }
 
 #[repr(C)]
@@ -27,6 +32,8 @@ struct UniformRaw {
     view_position: [f32; 4],
     view_proj: [[f32; 4]; 4],
     view_proj_no_translation: [[f32; 4]; 4],
+    prev_view_proj: [[f32; 4]; 4],
+    inv_view_proj: [[f32; 4]; 4],
 }
 
 impl UniformRaw {
@@ -35,14 +42,25 @@ impl UniformRaw {
             view_position: [0.; 4],
             view_proj: Mat4::identity().into(),
             view_proj_no_translation: Mat4::identity().into(),
+            prev_view_proj: Mat4::identity().into(),
+            inv_view_proj: Mat4::identity().into(),
         }
     }
 
-    pub fn update_view_proj(&mut self, camera: &super::PhysicalCamera, projection: &super::Projection) {
+    /// Returns the unjittered view projection
+    pub fn update_view_proj(&mut self, camera: &super::PhysicalCamera, projection: &super::Projection, jitter: Vec2<f32>, prev_view_proj: Mat4<f32>) -> Mat4<f32> {
+        let proj = projection.calc_matrix();
+        let jittered = Mat4::from_translation(Vec3::new(jitter.x, jitter.y, 0.)) * proj;
+
         self.view_position = camera.position.homogeneous_point().into();
         let mut cubemap = camera.calc_matrix();
         cubemap.w = Vec4::unit_w();
-        self.view_proj_no_translation = (projection.calc_matrix() * cubemap).into();
-        self.view_proj = (projection.calc_matrix() * camera.calc_matrix()).into();
+        self.view_proj_no_translation = (jittered * cubemap).into();
+        self.view_proj = (jittered * camera.calc_matrix()).into();
+
+        let view_proj = proj * camera.calc_matrix();
+        self.prev_view_proj = prev_view_proj.into();
+        self.inv_view_proj = view_proj.inverse().unwrap_or_else(Mat4::identity).into();
+        view_proj
     }
-}
\ No newline at end of file
+}
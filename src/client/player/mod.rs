@@ -2,7 +2,7 @@ pub mod camera;
 
 use camera::Camera;
 
-use crate::client::Time;
+use crate::{client::Time, math::Vec2};
 
 use super::InputManager;
 
@@ -14,7 +14,7 @@ impl Player {
     pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, width: u32, height: u32, input: &mut InputManager) -> Self {
         let mut camera = Camera::new(device, width, height);
 
-        camera.uniform.update(queue, &camera.physical, &camera.projection);
+        camera.uniform.update(queue, &camera.physical, &camera.projection, Vec2::default());
 
         {
             use winit::event::VirtualKeyCode::*;
@@ -33,10 +33,11 @@ impl Player {
         self.camera.projection.resize(width, height);
     }
 
-    pub fn update(&mut self, time: &Time, queue: &wgpu::Queue, inp: &InputManager) {
+    /// `jitter` is the subpixel offset of the projection, see [`Renderer::jitter`](crate::client::Renderer::jitter)
+    pub fn update(&mut self, time: &Time, queue: &wgpu::Queue, inp: &InputManager, jitter: Vec2<f32>) {
         self.camera.update(inp);
         self.camera.controller.update_camera(&mut self.camera.physical, time);
-        self.camera.uniform.update(queue, &self.camera.physical, &self.camera.projection);
+        self.camera.uniform.update(queue, &self.camera.physical, &self.camera.projection, jitter);
     }
 
     pub fn camera_bg(&self) -> &wgpu::BindGroup {self.camera.uniform.bg()}
@@ -0,0 +1,57 @@
+pub mod taa;
+
+pub use taa::{Taa, TaaSettings};
+
+use super::{postfx::FxaaSettings, framebuffer::FRAMEBUFFER_FORMAT, resources::image::Texture};
+
+/// Anti-aliasing applied to the scene, set with [`Renderer::set_anti_aliasing`](super::Renderer::set_anti_aliasing)
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum AntiAliasing {
+    #[default]
+    None,
+    /// Geometry drawn with this many samples per pixel, resolved into the framebuffer.
+    /// Counts the device doesn't support fall back to the nearest lower one in [`State::sample_counts`](super::state::State::sample_counts).
+    Msaa(u32),
+    /// Edges smoothed after the scene is drawn, as an effect appended to the post-processing stack
+    Fxaa(FxaaSettings),
+    /// Camera jittered every frame and blended with the reprojected previous frames
+    Taa(TaaSettings),
+}
+
+impl AntiAliasing {
+    /// Samples per pixel of the scene targets
+    pub fn sample_count(&self) -> u32 {
+        match self {
+            Self::Msaa(count) => *count,
+            _ => 1,
+        }
+    }
+}
+
+/// Multisampled color and depth targets the scene is drawn to before being resolved into the framebuffer
+pub struct MsaaTargets {
+    pub color: wgpu::TextureView,
+    pub depth: wgpu::TextureView,
+    pub sample_count: u32,
+}
+
+impl MsaaTargets {
+    pub fn new(device: &wgpu::Device, width: u32, height: u32, sample_count: u32) -> Self {
+        let target = |format, label| device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        }).create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            color: target(FRAMEBUFFER_FORMAT, "Multisampled Framebuffer Texture"),
+            depth: target(Texture::DEPTH_STENCIL_FORMAT, "Multisampled Depth Texture"),
+            sample_count,
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::sync::Arc;
+
+use crate::{
+    client::renderer::{
+        framebuffer::FRAMEBUFFER_FORMAT,
+        gpu::{bind_group::{BindGroup, Layout}, buffer::Buffer, err::GpuResourceError},
+        pipeline::{Pipeline, PipelineBuilder, PipelineCache},
+        resources::image::Texture,
+        state::{RenderState, State},
+    },
+    math::Vec2,
+};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TaaSettings {
+    /// Weight of the previous frames in the blend. Higher is smoother but trails behind moving objects.
+    pub feedback: f32,
+    /// Spread of the camera jitter, in pixels
+    pub jitter: f32,
+}
+
+impl Default for TaaSettings {
+    fn default() -> Self {
+        Self { feedback: 0.9, jitter: 1.0 }
+    }
+}
+
+/// Length of the jitter sequence before it repeats
+const JITTER_SAMPLES: u32 = 8;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct TaaRaw {
+    jitter: [f32; 2],
+    feedback: f32,
+    reset: u32,
+}
+
+/// Accumulates the jittered frames into a history blended with every new one
+pub struct Taa {
+    pipeline: Arc<Pipeline>,
+    params: Buffer,
+    /// Depth of the frame and the parameters
+    bg: BindGroup,
+    /// Read from one, written to the other, swapped every frame
+    history: [Texture; 2],
+    frame: u32,
+    size: (u32, u32),
+    /// Set while the history doesn't hold a previous frame
+    reset: bool,
+    settings: TaaSettings,
+}
+
+impl Taa {
+    /// `depth` is the depth texture the scene is drawn with, `camera_layout` the layout of the camera uniform
+    pub fn new(state: &State, pipelines: &mut PipelineCache, framebuffer_layout: &Layout, camera_layout: &Layout, depth: &Texture, settings: TaaSettings) -> Result<Self, GpuResourceError> {
+        let params = Buffer::new_uniform(&state.device, &[TaaRaw { jitter: [0.0; 2], feedback: settings.feedback, reset: 1 }], "TAA Uniform Buffer");
+        let bg = BindGroup::new(
+            &state.device,
+            &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            &Self::entries(&depth.view, &params),
+            "TAA ",
+        );
+
+        let history = [Texture::create_frame_texture(state, &state.config), Texture::create_frame_texture(state, &state.config)];
+
+        let shader = pipelines.import_combined(state, ("vs_main", "fs_main"), "taa.wgsl", "TAA shader")?;
+        let target = Some(wgpu::ColorTargetState {
+            format: FRAMEBUFFER_FORMAT,
+            blend: Some(wgpu::BlendState::REPLACE),
+            write_mask: wgpu::ColorWrites::ALL,
+        });
+        let targets = [target.clone(), target];
+        let pipeline = pipelines.get(state, PipelineBuilder::new(shader.vs_state(&[]), Some(shader.fs_state(&targets)))
+            .with_label("TAA Pipeline")
+            .with_layouts(&[framebuffer_layout, &history[0].bg.layout, &bg.layout, camera_layout]))?;
+
+        Ok(Self {
+            pipeline, params, bg, history,
+            frame: 0,
+            size: (state.config.width, state.config.height),
+            reset: true,
+            settings,
+        })
+    }
+
+    fn entries<'a>(depth: &'a wgpu::TextureView, params: &'a Buffer) -> [wgpu::BindGroupEntry<'a>; 2] {
+        [
+            wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(depth) },
+            params.entry(1),
+        ]
+    }
+
+    pub fn settings(&self) -> &TaaSettings { &self.settings }
+
+    pub fn set_settings(&mut self, settings: TaaSettings) { self.settings = settings }
+
+    /// Offset of the projection this frame, in normalized device coordinates
+    pub fn jitter(&self) -> Vec2<f32> {
+        // Halton sequence in bases 2 and 3, spreading the samples evenly over the pixel
+        let halton = |mut index: u32, base: u32| {
+            let (mut fraction, mut result) = (1.0, 0.0);
+            while index > 0 {
+                fraction /= base as f32;
+                result += fraction * (index % base) as f32;
+                index /= base;
+            }
+            result
+        };
+        let index = self.frame % JITTER_SAMPLES + 1;
+        let offset = |base, size: u32| (halton(index, base) - 0.5) * 2.0 * self.settings.jitter / size as f32;
+        Vec2::new(offset(2, self.size.0), offset(3, self.size.1))
+    }
+
+    /// Moves on to the next jitter offset and uploads it with the settings
+    pub fn update_buffer(&mut self, state: &State) {
+        self.frame = self.frame.wrapping_add(1);
+        let jitter = self.jitter();
+        let raw = TaaRaw { jitter: [jitter.x, jitter.y], feedback: self.settings.feedback.clamp(0.0, 1.0), reset: self.reset as u32 };
+        state.queue.write_buffer(&self.params.0, 0, bytemuck::cast_slice(&[raw]));
+        self.reset = false;
+    }
+
+    /// Blends `frame`, bound with the framebuffer layout, with the history into `output`
+    pub fn render(&mut self, render_state: &mut RenderState, frame: &wgpu::BindGroup, output: &wgpu::TextureView, camera_bg: &wgpu::BindGroup) {
+        let [read, write] = &self.history;
+        {
+            let attachment = |view| Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Load, store: true },
+            });
+            let mut render_pass = render_state.encoder().begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("TAA Render Pass"),
+                color_attachments: &[attachment(output), attachment(&write.view)],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(&self.pipeline.pipeline);
+            render_pass.set_bind_group(0, frame, &[]);
+            render_pass.set_bind_group(1, &read.bg.group, &[]);
+            render_pass.set_bind_group(2, &self.bg.group, &[]);
+            render_pass.set_bind_group(3, camera_bg, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+        self.history.swap(0, 1);
+    }
+
+    /// Follows the size of the frame; `depth` is the recreated depth texture
+    pub fn resize(&mut self, state: &State, depth: &Texture, width: u32, height: u32) {
+        self.size = (width, height);
+        for history in &mut self.history {
+            history.resize(&state.device, width, height, "TAA History Texture");
+        }
+        self.bg.replace_group(&state.device, &Self::entries(&depth.view, &self.params), "TAA ");
+        self.reset = true;
+    }
+
+    pub fn refresh_pipelines(&mut self, pipelines: &PipelineCache) {
+        pipelines.refresh(&mut self.pipeline);
+    }
+}
@@ -9,6 +9,7 @@ pub enum RendererError {
     File(FileError),
     Init(RendererInitError),
     Resource(ResourceError),
+    Capture(RendererCaptureError),
 }
 
 impl_error!(
@@ -16,13 +17,15 @@ impl_error!(
     GpuResource(e) => "In GPU resources: {}", e;
     File(e)        => "With file: {}", e;
     Init(e)        => "While Initializing renderer: {}", e;
-    Resource(e)    => "In resources: {}", e
+    Resource(e)    => "In resources: {}", e;
+    Capture(e)     => "While capturing frame: {}", e
 );
 impl_error_conversions!(RendererError,
     FileError => File,
     GpuResourceError => GpuResource,
     RendererInitError => Init,
     ResourceError => Resource,
+    RendererCaptureError => Capture,
     ImageError => Resource,
     ShaderError => GpuResource
 );
@@ -43,4 +46,21 @@ impl_error!(
 impl_error_conversions!(RendererInitError,
     wgpu::CreateSurfaceError => Surface,
     wgpu::RequestDeviceError => Device
+);
+
+// CAPTURE TYPE
+pub enum RendererCaptureError {
+    NotHeadless,
+    Format(wgpu::TextureFormat),
+    Map(wgpu::BufferAsyncError),
+}
+
+impl_error!(
+    RendererCaptureError,
+    NotHeadless => "Frames can only be captured from a headless renderer";
+    Format(f)   => "Unsupported target format for capture: {:?}", f;
+    Map(e)      => "While mapping readback buffer: {}", e
+);
+impl_error_conversions!(RendererCaptureError,
+    wgpu::BufferAsyncError => Map
 );
\ No newline at end of file
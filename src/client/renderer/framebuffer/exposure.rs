@@ -0,0 +1,69 @@
+use crate::client::renderer::{
+    gpu::{bind_group::{BindGroup, Layout}, buffer::Buffer, err::GpuResourceError},
+    pipeline::PipelineCache,
+    state::{RenderState, State},
+};
+
+const HISTOGRAM_BINS: u64 = 256;
+/// Side of the square of pixels binned by each workgroup of the histogram pass
+const TILE: u32 = 16;
+
+/// Measures the luminance of the frame on the GPU and blends it into the luminance exposed for,
+/// which stays in [`EyeAdaptation::luminance`] between frames
+pub struct EyeAdaptation {
+    histogram_pipeline: wgpu::ComputePipeline,
+    average_pipeline: wgpu::ComputePipeline,
+    /// Histogram and adapted luminance, read and written by both passes
+    bg: BindGroup,
+    _histogram: Buffer,
+    luminance: Buffer,
+}
+
+impl EyeAdaptation {
+    pub fn new(state: &State, pipelines: &mut PipelineCache, framebuffer_layout: &Layout, params_layout: &Layout) -> Result<Self, GpuResourceError> {
+        let storage = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let histogram = Buffer::new(&state.device, &[0u32; HISTOGRAM_BINS as usize], "Luminance Histogram Buffer", wgpu::BufferUsages::STORAGE);
+        // Middle grey, exposed as is until the first measurement
+        let luminance = Buffer::new(&state.device, &[0.18f32], "Adapted Luminance Buffer", wgpu::BufferUsages::STORAGE);
+        let bg = BindGroup::new(
+            &state.device,
+            &[storage(0), storage(1)],
+            &[histogram.entry(0), luminance.entry(1)],
+            "Eye Adaptation ",
+        );
+
+        let layouts = [framebuffer_layout, params_layout, &bg.layout];
+        let histogram_shader = pipelines.import_compute(state, "cs_histogram", "luminance.wgsl", "Luminance shader")?;
+        let histogram_pipeline = histogram_shader.compute_pipeline(state, &layouts, "Luminance Histogram Pipeline")?;
+        let average_shader = pipelines.import_compute(state, "cs_average", "luminance.wgsl", "Luminance shader")?;
+        let average_pipeline = average_shader.compute_pipeline(state, &layouts, "Luminance Average Pipeline")?;
+
+        Ok(Self { histogram_pipeline, average_pipeline, bg, _histogram: histogram, luminance })
+    }
+
+    /// Adapted luminance, bound as a read-only storage buffer by the tonemapping pass
+    pub fn luminance(&self) -> &Buffer { &self.luminance }
+
+    /// Measures `frame`, the frame bound with the framebuffer layout, of `size` pixels
+    pub fn render(&self, render_state: &mut RenderState, frame: &wgpu::BindGroup, params: &wgpu::BindGroup, size: (u32, u32)) {
+        let mut pass = render_state.compute_pass(Some("Eye Adaptation Compute Pass"));
+        pass.set_bind_group(0, frame, &[]);
+        pass.set_bind_group(1, params, &[]);
+        pass.set_bind_group(2, &self.bg.group, &[]);
+
+        pass.set_pipeline(&self.histogram_pipeline);
+        pass.dispatch_workgroups(size.0.div_ceil(TILE), size.1.div_ceil(TILE), 1);
+        pass.set_pipeline(&self.average_pipeline);
+        pass.dispatch_workgroups(1, 1, 1);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::client::renderer::resources::image::lut::Domain;
+
+/// Curve compressing the HDR frame into the displayable range
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Tonemapper {
+    /// Clips everything above 1
+    Clamp,
+    Reinhard,
+    /// Fit of the ACES filmic curve, contrasty with saturated highlights
+    Aces,
+    /// Desaturates bright colors towards white instead of skewing their hue
+    #[default]
+    AgX,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Exposure {
+    /// Fixed exposure, in stops
+    Manual(f32),
+    /// Exposes for the average luminance of the frame, adapting over time
+    Auto(AutoExposure),
+}
+
+impl Default for Exposure {
+    fn default() -> Self { Self::Auto(AutoExposure::default()) }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AutoExposure {
+    /// Range of the luminance measured, in log2 units. Darker or brighter frames are exposed as if at its ends.
+    pub min_ev: f32,
+    pub max_ev: f32,
+    /// Stops added to the measured exposure
+    pub compensation: f32,
+    /// Rate of adaptation when the frame gets brighter, per second
+    pub speed_up: f32,
+    /// Rate of adaptation when the frame gets darker, per second. Eyes adjust to the dark more slowly.
+    pub speed_down: f32,
+}
+
+impl Default for AutoExposure {
+    fn default() -> Self {
+        Self { min_ev: -8.0, max_ev: 8.0, compensation: 0.0, speed_up: 3.0, speed_down: 1.0 }
+    }
+}
+
+/// Settings of the final pass from the HDR frame to the surface
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ColorSettings {
+    pub tonemapper: Tonemapper,
+    pub exposure: Exposure,
+    /// Applied on top of the sRGB encoding of the surface, above 1 brightens the midtones
+    pub gamma: f32,
+    /// Blend between the colors without and with the LUT loaded with
+    /// [`Renderer::load_lut`](crate::client::renderer::Renderer::load_lut)
+    pub lut_strength: f32,
+}
+
+impl Default for ColorSettings {
+    fn default() -> Self {
+        Self { tonemapper: Tonemapper::default(), exposure: Exposure::default(), gamma: 1.0, lut_strength: 1.0 }
+    }
+}
+
+const AUTO_EXPOSURE: u32 = 1;
+const ENCODE_SRGB: u32 = 2;
+const LUT: u32 = 4;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(super) struct ColorRaw {
+    exposure: f32,
+    gamma: f32,
+    tonemapper: u32,
+    flags: u32,
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    adapt_up: f32,
+    adapt_down: f32,
+    lut_domain_min: [f32; 4],
+    lut_domain_max: [f32; 4],
+}
+
+/// State of the pass besides the settings
+pub(super) struct ColorTarget {
+    /// Whether the surface is left to encode the output to sRGB
+    pub srgb_surface: bool,
+    /// Size and domain of the loaded LUT
+    pub lut: Option<(u32, Domain)>,
+    /// Seconds since the last frame, for eye adaptation
+    pub dt: f32,
+}
+
+impl ColorSettings {
+    pub(super) fn raw(&self, target: &ColorTarget) -> ColorRaw {
+        let (exposure, adaptation) = match self.exposure {
+            Exposure::Manual(ev) => (ev.exp2(), None),
+            Exposure::Auto(auto) => (auto.compensation.exp2(), Some(auto)),
+        };
+        let (min_log_luminance, log_luminance_range, adapt_up, adapt_down) = match adaptation {
+            // Exponential approach, the same over a second whatever the frame rate
+            Some(a) => (a.min_ev, (a.max_ev - a.min_ev).max(0.01), 1.0 - (-target.dt * a.speed_up).exp(), 1.0 - (-target.dt * a.speed_down).exp()),
+            None => (0.0, 1.0, 0.0, 0.0),
+        };
+        let (size, (min, max)) = target.lut.unwrap_or((2, ([0.0; 3], [1.0; 3])));
+
+        let mut flags = 0;
+        if adaptation.is_some() { flags |= AUTO_EXPOSURE }
+        if !target.srgb_surface { flags |= ENCODE_SRGB }
+        if target.lut.is_some() && self.lut_strength > 0.0 { flags |= LUT }
+
+        ColorRaw {
+            exposure,
+            gamma: self.gamma.max(0.01),
+            tonemapper: self.tonemapper as u32,
+            flags,
+            min_log_luminance,
+            log_luminance_range,
+            adapt_up,
+            adapt_down,
+            lut_domain_min: [min[0], min[1], min[2], size as f32],
+            lut_domain_max: [max[0], max[1], max[2], self.lut_strength.clamp(0.0, 1.0)],
+        }
+    }
+}
@@ -1,46 +1,99 @@
 use crate::client::renderer::state::State;
 
 use {
-    super::FrameBuffer,
+    super::{FrameBuffer, exposure::EyeAdaptation, grading::{ColorSettings, ColorTarget}},
     crate::client::renderer::{
-        resources::image::Texture,
-        gpu::{
-            shader::Shader,
-            err::GpuResourceError
-        },
-        pipeline::Pipeline,
+        resources::image::{Texture, Lut},
+        gpu::{bind_group::BindGroup, uniform::Uniform, err::GpuResourceError},
+        pipeline::{PipelineBuilder, PipelineCache},
     }
 };
 
 impl FrameBuffer {
-    pub fn new(state: &State, config: &wgpu::SurfaceConfiguration) -> Result<Self, GpuResourceError> {
+    pub fn new(state: &State, pipelines: &mut PipelineCache, config: &wgpu::SurfaceConfiguration) -> Result<Self, GpuResourceError> {
 
         let target_tex = Texture::create_frame_texture(state, config);
         let sample_tex = Texture::create_frame_texture(state, config);
 
-        let vpath = std::path::Path::new("assets/shaders/framebuffer_vertex.wgsl");
-        let fpath = std::path::Path::new("assets/shaders/fbuffer_fragment.wgsl");
+        let settings = ColorSettings::default();
+        let srgb_surface = config.format.is_srgb();
+        let params = Uniform::new(
+            &state.device,
+            settings.raw(&ColorTarget { srgb_surface, lut: None, dt: 0.0 }),
+            "Color Correction",
+            wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+        );
+
+        let eye_adaptation = EyeAdaptation::new(state, pipelines, &target_tex.bg.layout, &params.bg.layout)?;
 
-        let vshader = Shader::import_vert(state, "vs_main", vpath, "Framebuffer vertex shader",)?;
-        let fshader = Shader::import_frag(state, "fs_main", fpath, "Framebuffer fragment shader")?;
+        let lut_sampler = state.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Color Grading LUT Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let lut = Lut::identity(2).create_texture(state, "Identity LUT");
+        let lut_view = lut.create_view(&wgpu::TextureViewDescriptor::default());
+        let grading_bg = BindGroup::new(
+            &state.device,
+            &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            &Self::grading_entries(&lut_view, &lut_sampler, &eye_adaptation),
+            "Color Grading ",
+        );
 
-        let pipeline = Pipeline::new(
-            state,
-            &[target_tex.bg.layout()],
-            vshader.vs_state(&[]),
-            Some(fshader.fs_state(&[Some(wgpu::ColorTargetState {
+        let shader = pipelines.import_combined(state, ("vs_main", "fs_main"), "tonemap.wgsl", "Tonemapping shader")?;
+
+        let pipeline = pipelines.get(state, PipelineBuilder::new(
+            shader.vs_state(&[]),
+            Some(shader.fs_state(&[Some(wgpu::ColorTargetState {
                 format: config.format,
                 blend: Some(wgpu::BlendState::REPLACE),
                 write_mask: wgpu::ColorWrites::ALL,
             })])),
-            false,
-        );
+        ).with_label("Tonemapping Pipeline").with_layouts(&[&target_tex.bg.layout, &params.bg.layout, &grading_bg.layout]))?;
 
 
         Ok(Self {
                     target_tex,
                     sample_tex,
                     pipeline,
+                    params,
+                    grading_bg,
+                    eye_adaptation,
+                    lut_sampler,
+                    lut: None,
+                    srgb_surface,
+                    settings,
                 })
     }
-}
\ No newline at end of file
+}
@@ -1,8 +1,19 @@
 mod init;
+pub mod grading;
+pub mod exposure;
+
+use std::sync::Arc;
 
 use crate::client::renderer::{
-    resources::image::Texture,
-    pipeline::Pipeline,
+    resources::image::{Texture, Lut, lut::Domain},
+    gpu::{bind_group::BindGroup, uniform::Uniform},
+    pipeline::{Pipeline, PipelineCache},
+    state::{RenderState, State},
+};
+
+use {
+    exposure::EyeAdaptation,
+    grading::{ColorRaw, ColorSettings, ColorTarget, Exposure},
 };
 
 pub const FRAMEBUFFER_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
@@ -10,7 +21,17 @@ pub const FRAMEBUFFER_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16F
 pub struct FrameBuffer {
     pub target_tex: Texture,
     pub sample_tex: Texture,
-    pipeline: Pipeline,
+    /// Tonemaps the frame onto the surface
+    pipeline: Arc<Pipeline>,
+    params: Uniform<ColorRaw>,
+    /// LUT and adapted luminance
+    grading_bg: BindGroup,
+    eye_adaptation: EyeAdaptation,
+    lut_sampler: wgpu::Sampler,
+    /// Size and domain of the loaded LUT, an identity one is bound otherwise
+    lut: Option<(u32, Domain)>,
+    srgb_surface: bool,
+    settings: ColorSettings,
 }
 
 impl FrameBuffer {
@@ -23,8 +44,8 @@ impl FrameBuffer {
         &self.target_tex.view
     }
 
-    pub fn pipeline(&self) -> &wgpu::RenderPipeline {
-        &self.pipeline.pipeline
+    pub fn refresh_pipelines(&mut self, pipelines: &PipelineCache) {
+        pipelines.refresh(&mut self.pipeline);
     }
 
     pub fn sample_bg(&self) -> &wgpu::BindGroup {
@@ -34,4 +55,52 @@ impl FrameBuffer {
     pub fn swap_buffers(&mut self) {
         std::mem::swap(&mut self.target_tex, &mut self.sample_tex);
     }
+
+    pub fn settings(&self) -> &ColorSettings { &self.settings }
+
+    /// Uploaded by the next [`FrameBuffer::render`]
+    pub fn set_settings(&mut self, settings: ColorSettings) {
+        self.settings = settings;
+    }
+
+    /// Grades the frame with `lut`, or stops grading it
+    pub fn set_lut(&mut self, state: &State, lut: Option<&Lut>) {
+        let texture = match lut {
+            Some(lut) => lut.create_texture(state, "Color Grading LUT"),
+            None => Lut::identity(2).create_texture(state, "Identity LUT"),
+        };
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.grading_bg.replace_group(&state.device, &Self::grading_entries(&view, &self.lut_sampler, &self.eye_adaptation), "Color Grading ");
+        self.lut = lut.map(|lut| (lut.size, lut.domain));
+    }
+
+    /// Measures the frame for eye adaptation, then draws it tonemapped and graded to the surface.
+    /// `dt` is the time since the last frame, in seconds.
+    pub fn render(&mut self, state: &State, render_state: &mut RenderState, dt: f32) {
+        self.params.data = self.settings.raw(&ColorTarget { srgb_surface: self.srgb_surface, lut: self.lut, dt });
+        self.params.update(&state.queue);
+
+        if matches!(self.settings.exposure, Exposure::Auto(_)) {
+            let size = (self.sample_tex.texture.width(), self.sample_tex.texture.height());
+            self.eye_adaptation.render(render_state, self.sample_bg(), &self.params.bg.group, size);
+        }
+
+        let mut render_pass = render_state.render_pass(
+            Some("Framebuffer and Color Correction Render Pass"),
+            None, None, None);
+
+        render_pass.set_pipeline(&self.pipeline.pipeline);
+        render_pass.set_bind_group(0, &self.sample_tex.bg.group, &[]);
+        self.params.set(1, &mut render_pass);
+        render_pass.set_bind_group(2, &self.grading_bg.group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn grading_entries<'a>(lut: &'a wgpu::TextureView, sampler: &'a wgpu::Sampler, eye_adaptation: &'a EyeAdaptation) -> [wgpu::BindGroupEntry<'a>; 3] {
+        [
+            wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(lut) },
+            wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(sampler) },
+            eye_adaptation.luminance().entry(2),
+        ]
+    }
 }
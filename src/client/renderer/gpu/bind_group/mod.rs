@@ -59,7 +59,8 @@ impl BindGroup {
     }
 }
 
-pub struct Layout(pub wgpu::BindGroupLayout);
+/// Bind group layout with its entries, which pipelines check against their shaders
+pub struct Layout(pub wgpu::BindGroupLayout, Vec<wgpu::BindGroupLayoutEntry>);
 
 impl Layout {
     pub fn new(
@@ -70,6 +71,8 @@ impl Layout {
         Self(device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries,
             label: Some(label),
-        }))
+        }), entries.to_vec())
     }
+
+    pub fn entries(&self) -> &[wgpu::BindGroupLayoutEntry] { &self.1 }
 }
\ No newline at end of file
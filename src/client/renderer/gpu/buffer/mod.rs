@@ -16,6 +16,18 @@ impl Buffer {
         ))
     }
 
+    /// Creates an uninitialized buffer of `size` bytes
+    pub fn empty(device: &wgpu::Device, size: wgpu::BufferAddress, label: &str, usage: wgpu::BufferUsages) -> Self {
+        Self(device.create_buffer(
+            &wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage,
+                mapped_at_creation: false,
+            }
+        ))
+    }
+
     pub fn new_uniform<T: bytemuck::Pod>(device: &wgpu::Device, contents: &[T], label: &str) -> Self {
         Self::new(
             device,
@@ -43,7 +55,7 @@ impl Buffer {
         )
     }
 
-    pub fn entry(&self, binding: u32) -> wgpu::BindGroupEntry {
+    pub fn entry(&self, binding: u32) -> wgpu::BindGroupEntry<'_> {
         wgpu::BindGroupEntry {
             binding,
             resource: self.0.as_entire_binding(),
@@ -64,7 +76,7 @@ impl Buffer {
     //     Self::new_index(device, &indices, label)
     // }
 
-    pub fn slice<S>(&self, bounds: S) -> wgpu::BufferSlice
+    pub fn slice<S>(&self, bounds: S) -> wgpu::BufferSlice<'_>
     where S: std::ops::RangeBounds<wgpu::BufferAddress> {
         self.0.slice(bounds)
     }
@@ -0,0 +1,64 @@
+use std::{collections::HashMap, path::{Path, PathBuf}};
+
+use crate::client::renderer::state::State;
+
+use super::{Compiled, Preprocessor, Shader, ShaderError, ShaderType};
+
+/// Compiles each file once per set of defines, and again when it changes
+#[derive(Default)]
+pub struct ShaderCache {
+    modules: HashMap<(Preprocessor, PathBuf), Entry>,
+}
+
+struct Entry {
+    compiled: Compiled,
+    label: String,
+    /// Whether the last reload failed
+    failed: bool,
+}
+
+impl ShaderCache {
+    pub fn new() -> Self { Self::default() }
+
+    /// Shader for `path`, relative to the shader directory of `preprocessor`, sharing the module of earlier imports
+    pub fn import<'a, T: AsRef<Path>>(&mut self, state: &State, preprocessor: &Preprocessor, ty: ShaderType<'a>, path: T, label: &str) -> Result<Shader<'a>, ShaderError> {
+        let key = (preprocessor.clone(), path.as_ref().to_owned());
+        if let Some(entry) = self.modules.get(&key) {
+            return Ok(Shader::with_compiled(entry.compiled.clone(), ty))
+        }
+
+        let shader = Shader::from_code(state, label, ty, &preprocessor.process(path)?)?;
+        self.modules.insert(key, Entry { compiled: shader.compiled().clone(), label: label.to_owned(), failed: false });
+        Ok(shader)
+    }
+
+    /// Latest module compiled from the same file as `compiled`, which is returned as is when it isn't cached
+    pub fn current(&self, compiled: &Compiled) -> Compiled {
+        compiled.origin.as_ref()
+            .and_then(|o| self.modules.get(&(o.preprocessor().clone(), o.path().to_owned())))
+            .map_or_else(|| compiled.clone(), |e| e.compiled.clone())
+    }
+
+    /// Recompiles the modules reading any of `changed`, keeping the previous one when that fails.
+    /// A failed module retries on every change.
+    pub fn reload(&mut self, state: &State, changed: &[PathBuf]) {
+        if changed.is_empty() { return }
+
+        for entry in self.modules.values_mut() {
+            let Some(origin) = &entry.compiled.origin else { continue };
+            if !(entry.failed || origin.depends_on(changed)) { continue }
+
+            match origin.process().and_then(|code| Shader::compile(state, &entry.label, &code)) {
+                Ok(compiled) => {
+                    log::info!("Recompiled shader '{}'", compiled.path.display());
+                    entry.compiled = compiled;
+                    entry.failed = false;
+                }
+                Err(e) => {
+                    log::error!("Keeping the previous shader '{}': {e}", entry.compiled.path.display());
+                    entry.failed = true;
+                }
+            }
+        }
+    }
+}
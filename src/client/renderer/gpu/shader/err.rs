@@ -1,27 +1,31 @@
-use crate::files::FileError;
+use std::path::PathBuf;
+
+use crate::{files::FileError, err::macros::*};
 
 pub enum ShaderError {
-    File(FileError)
+    File(FileError),
+    /// File and line of a malformed preprocessor directive
+    Directive(PathBuf, u32, String),
+    /// Files including each other, ending with the first one again
+    IncludeCycle(Vec<PathBuf>),
+    /// Main file of a shader the device refused, with its message
+    Rejected(PathBuf, String),
+    /// File, line and column of invalid WGSL, with naga's message
+    Compile(PathBuf, u32, u32, String),
+    /// Shader used with an entry point or bind group layouts it doesn't declare
+    Mismatch(PathBuf, String),
+    /// Label of a pipeline whose state the device or its depth format can't support
+    Unsupported(String, String),
 }
 
-impl std::fmt::Display for ShaderError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        use ShaderError::*;
-        match self {
-            File(e) => write!(f,
-                "\n        With file: {e}"
-            ),
-        }
-    }
-}
-impl std::fmt::Debug for ShaderError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        std::fmt::Display::fmt(self, f)
-    }
-} impl std::error::Error for ShaderError {}
+impl_error!(ShaderError,
+    File(e) => "With file: {}", e;
+    Directive(p, l, msg) => "{}:{}: {}", p.display(), l, msg;
+    IncludeCycle(files) => "Include cycle: {}", files.iter().map(|f| f.display().to_string()).collect::<Vec<_>>().join(" -> ");
+    Rejected(p, msg) => "Shader '{}' was rejected: {}", p.display(), msg;
+    Compile(p, l, c, msg) => "{}:{}:{}: {}", p.display(), l, c, msg;
+    Mismatch(p, msg) => "Shader '{}' doesn't match its pipeline: {}", p.display(), msg;
+    Unsupported(label, msg) => "Pipeline '{}' is unsupported: {}", label, msg
+);
 
-impl From<FileError> for ShaderError {
-    fn from(value: FileError) -> Self {
-        Self::File(value)
-    }
-}
\ No newline at end of file
+impl_error_conversion!(ShaderError, FileError => File);
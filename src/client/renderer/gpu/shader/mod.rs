@@ -1,57 +1,133 @@
 pub mod err;
+pub mod preprocess;
+pub mod registry;
+pub mod reflect;
+pub mod cache;
 
 pub use err::ShaderError;
+pub use preprocess::{Preprocessor, ShaderCode, ShaderOrigin};
+pub use registry::ShaderRegistry;
+pub use reflect::Reflection;
+pub use cache::ShaderCache;
 
-use crate::client::renderer::state::State;
+use std::{path::PathBuf, sync::Arc};
+
+use crate::client::{renderer::{gpu::bind_group::Layout, state::State}, PathManager};
+
+/// Module and what it declares, shared by every [`Shader`] compiled from the same file
+#[derive(Clone)]
+pub struct Compiled {
+    pub module: Arc<wgpu::ShaderModule>,
+    pub reflection: Arc<Reflection>,
+    /// Set when read from a file, which makes pipelines using it reloadable
+    pub origin: Option<ShaderOrigin>,
+    /// Main file, or the label of shaders that weren't read from one
+    pub path: PathBuf,
+}
+
+impl Compiled {
+    /// See [`Reflection::check`]
+    pub fn check(&self, entry_point: &str, stage: wgpu::ShaderStages, layouts: Option<&[&[wgpu::BindGroupLayoutEntry]]>) -> Result<(), ShaderError> {
+        self.reflection.check(entry_point, stage, layouts).map_err(|msg| ShaderError::Mismatch(self.path.clone(), msg))
+    }
+}
 
 pub struct Shader<'a> {
-    module: wgpu::ShaderModule,
+    compiled: Compiled,
     ty: ShaderType<'a>,
 }
 
 impl <'a> Shader<'a> {
-    pub fn import_vert<T: AsRef<std::path::Path>>(state: &State, entry: &'a str, path: T, label: &'a str) -> Result<Self, ShaderError> {
-        let ty = ShaderType::Vertex(entry);
-        Ok(Self::new(
-            state,
-            label,
-            ty,
-            wgpu::ShaderSource::Wgsl(crate::files::read_file(std::path::Path::new(path.as_ref()))?.0.into()),
-        ))
+    /// Paths are relative to the shader directory of `path_m`, see [`Preprocessor`].
+    /// Prefer [`PipelineCache`](crate::client::renderer::pipeline::PipelineCache), which compiles each file once.
+    pub fn import_vert<T: AsRef<std::path::Path>>(state: &State, path_m: &PathManager, entry: &'a str, path: T, label: &'a str) -> Result<Self, ShaderError> {
+        Self::import(state, path_m, ShaderType::Vertex(entry), path, label)
     }
 
-    pub fn import_frag<T: AsRef<std::path::Path>>(state: &State, entry: &'a str, path: T, label: &'a str) -> Result<Self, ShaderError> {
-        let ty = ShaderType::Fragment(entry);
-        Ok(Self::new(
-            state,
-            label,
-            ty,
-            wgpu::ShaderSource::Wgsl(crate::files::read_file(std::path::Path::new(path.as_ref()))?.0.into()),
-        ))
+    pub fn import_frag<T: AsRef<std::path::Path>>(state: &State, path_m: &PathManager, entry: &'a str, path: T, label: &'a str) -> Result<Self, ShaderError> {
+        Self::import(state, path_m, ShaderType::Fragment(entry), path, label)
     }
 
-    pub fn import_combined<T: AsRef<std::path::Path>>(state: &State, entry: (&'a str, &'a str), path: T, label: &'a str) -> Result<Self, ShaderError> {
-        let ty = ShaderType::VertexFragment(entry.0, entry.1);
-        Ok(Self::new(
-            state,
-            label,
-            ty,
-            wgpu::ShaderSource::Wgsl(crate::files::read_file(std::path::Path::new(path.as_ref()))?.0.into()),
-        ))
+    pub fn import_combined<T: AsRef<std::path::Path>>(state: &State, path_m: &PathManager, entry: (&'a str, &'a str), path: T, label: &'a str) -> Result<Self, ShaderError> {
+        Self::import(state, path_m, ShaderType::VertexFragment(entry.0, entry.1), path, label)
     }
 
+    fn import<T: AsRef<std::path::Path>>(state: &State, path_m: &PathManager, ty: ShaderType<'a>, path: T, label: &'a str) -> Result<Self, ShaderError> {
+        let code = Preprocessor::new(path_m).process(path)?;
+        Self::from_code(state, label, ty, &code)
+    }
 
-    pub fn new(state: &State, label: &'a str, ty: ShaderType<'a>, source: wgpu::ShaderSource) -> Self {
+    /// Compiles preprocessed code, for permutations built with [`Preprocessor::with_define`].
+    /// Errors point at the file and line the code came from.
+    pub fn from_code(state: &State, label: &str, ty: ShaderType<'a>, code: &ShaderCode) -> Result<Self, ShaderError> {
+        let reflection = Self::reflect(code)?;
+        let module = state.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(code.code.as_str().into()),
+        });
+        let origin = code.origin().clone();
+        let compiled = Compiled { module: Arc::new(module), reflection: Arc::new(reflection), path: origin.path().to_owned(), origin: Some(origin) };
+        Ok(Self { compiled, ty })
+    }
 
+    /// Validates and creates the module, returning the device's error instead of raising it
+    pub fn compile(state: &State, label: &str, code: &ShaderCode) -> Result<Compiled, ShaderError> {
+        let reflection = Self::reflect(code)?;
+        state.device.push_error_scope(wgpu::ErrorFilter::Validation);
         let module = state.device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some(label),
-            source,
+            source: wgpu::ShaderSource::Wgsl(code.code.as_str().into()),
         });
+        let origin = code.origin().clone();
+        match pollster::block_on(state.device.pop_error_scope()) {
+            Some(e) => Err(ShaderError::Rejected(origin.path().to_owned(), e.to_string())),
+            None => Ok(Compiled { module: Arc::new(module), reflection: Arc::new(reflection), path: origin.path().to_owned(), origin: Some(origin) }),
+        }
+    }
 
-        Self {module, ty}
+    /// Compiles WGSL that wasn't read from a file; errors name `label` instead
+    pub fn new(state: &State, label: &str, ty: ShaderType<'a>, code: &str) -> Result<Self, ShaderError> {
+        let reflection = reflect::validate(code).map_err(|e| {
+            let (line, column) = e.location.unwrap_or((0, 0));
+            ShaderError::Compile(PathBuf::from(label), line, column, e.message)
+        })?;
+
+        let module = state.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(code.into()),
+        });
+
+        let compiled = Compiled { module: Arc::new(module), reflection: Arc::new(reflection), origin: None, path: PathBuf::from(label) };
+        Ok(Self { compiled, ty })
+    }
+
+    /// Shader sharing an already compiled module
+    pub fn with_compiled(compiled: Compiled, ty: ShaderType<'a>) -> Self {
+        Self { compiled, ty }
+    }
+
+    fn reflect(code: &ShaderCode) -> Result<Reflection, ShaderError> {
+        reflect::validate(&code.code).map_err(|e| {
+            let location = e.location.and_then(|(line, column)| code.locate(line).map(|(file, line)| (file.to_owned(), line, column)));
+            let (file, line, column) = location.unwrap_or_else(|| (code.origin().path().to_owned(), 0, 0));
+            ShaderError::Compile(file, line, column, e.message)
+        })
     }
 
-    pub fn vs_state(&self, buffers: &'a [wgpu::VertexBufferLayout]) -> wgpu::VertexState {
+    pub fn module(&self) -> &wgpu::ShaderModule { &self.compiled.module }
+
+    pub fn compiled(&self) -> &Compiled { &self.compiled }
+
+    pub fn origin(&self) -> Option<&ShaderOrigin> { self.compiled.origin.as_ref() }
+
+    pub fn reflection(&self) -> &Reflection { &self.compiled.reflection }
+
+    /// See [`Reflection::check`]
+    pub fn check(&self, entry_point: &str, stage: wgpu::ShaderStages, layouts: Option<&[&[wgpu::BindGroupLayoutEntry]]>) -> Result<(), ShaderError> {
+        self.compiled.check(entry_point, stage, layouts)
+    }
+
+    pub fn vs_state<'s>(&'s self, buffers: &'s [wgpu::VertexBufferLayout<'s>]) -> VertexStage<'s> {
         let entry_point = match self.ty {
             ShaderType::Vertex(e) => e,
             ShaderType::Fragment(e) => {
@@ -59,15 +135,15 @@ impl <'a> Shader<'a> {
                 e
             },
             ShaderType::VertexFragment(e, _) => e,
+            ShaderType::Compute(e) => {
+                log::error!("Shader type set as compute, but tried to get a vertex state! This is probably not what you wanted.");
+                e
+            },
         };
-        wgpu::VertexState {
-            module: &self.module,
-            entry_point,
-            buffers,
-        }
+        VertexStage { shader: self, entry_point, buffers }
     }
 
-    pub fn fs_state(&self, targets: &'a[Option<wgpu::ColorTargetState>]) -> wgpu::FragmentState {
+    pub fn fs_state<'s>(&'s self, targets: &'s [Option<wgpu::ColorTargetState>]) -> FragmentStage<'s> {
         let entry_point = match self.ty {
             ShaderType::Vertex(e) => {
                 log::error!("Shader type set as vertex, but tried to get a fragment state! This is probably not what you wanted.");
@@ -75,13 +151,55 @@ impl <'a> Shader<'a> {
             },
             ShaderType::Fragment(e) => e,
             ShaderType::VertexFragment(_, e) => e,
+            ShaderType::Compute(e) => {
+                log::error!("Shader type set as compute, but tried to get a fragment state! This is probably not what you wanted.");
+                e
+            },
+        };
+        FragmentStage { shader: self, entry_point, targets }
+    }
+
+    /// Compute pipeline of its compute entry point, checked against `layouts`.
+    /// Not reloaded with the render pipelines of a [`PipelineCache`](crate::client::renderer::pipeline::PipelineCache).
+    pub fn compute_pipeline(&self, state: &State, layouts: &[&Layout], label: &str) -> Result<wgpu::ComputePipeline, ShaderError> {
+        let entry_point = match self.ty {
+            ShaderType::Compute(e) => e,
+            _ => return Err(ShaderError::Mismatch(self.compiled.path.clone(), "Not imported as a compute shader".to_owned())),
         };
-        wgpu::FragmentState {
-            module: &self.module,
+        let entries: Vec<_> = layouts.iter().map(|l| l.entries()).collect();
+        self.check(entry_point, wgpu::ShaderStages::COMPUTE, Some(&entries))?;
+
+        let bind_group_layouts: Vec<_> = layouts.iter().map(|l| &l.0).collect();
+        let layout = state.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts: &bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+        Ok(state.device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(label),
+            layout: Some(&layout),
+            module: self.module(),
             entry_point,
-            targets,
-        }
+        }))
     }
+
+    /// Fragment state of another entry point in the same module, for files holding several passes
+    pub fn fs_entry_state<'s>(&'s self, entry_point: &'s str, targets: &'s [Option<wgpu::ColorTargetState>]) -> FragmentStage<'s> {
+        FragmentStage { shader: self, entry_point, targets }
+    }
+}
+
+/// Vertex stage of a pipeline, keeping the shader so the pipeline can be rebuilt from its files
+pub struct VertexStage<'s> {
+    pub shader: &'s Shader<'s>,
+    pub entry_point: &'s str,
+    pub buffers: &'s [wgpu::VertexBufferLayout<'s>],
+}
+
+pub struct FragmentStage<'s> {
+    pub shader: &'s Shader<'s>,
+    pub entry_point: &'s str,
+    pub targets: &'s [Option<wgpu::ColorTargetState>],
 }
 
 /// Shader type with entry point(s)
@@ -92,5 +210,7 @@ pub enum ShaderType<'a> {
     Fragment(&'a str),
     /// 1 - Vertex entry, 2 - Fragment entry
     VertexFragment(&'a str, &'a str),
+    /// Inner value - compute entry
+    Compute(&'a str),
 }
 
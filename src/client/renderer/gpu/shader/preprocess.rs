@@ -0,0 +1,323 @@
+//! `#include`, `#define` and conditional blocks for WGSL.
+//!
+//! Directives take a whole line starting with `#`:
+//! - `#include "file.wgsl"` pastes a file from the shader directory, once per shader
+//! - `#define NAME [value]` and `#undef NAME`; names with a value are replaced in the code that follows
+//! - `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif`, which don't span files
+
+use std::{collections::{BTreeMap, HashMap}, path::{Path, PathBuf}};
+
+use crate::client::PathManager;
+
+use super::ShaderError;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Preprocessor {
+    include_dir: PathBuf,
+    defines: BTreeMap<String, String>,
+}
+
+/// WGSL after preprocessing, with the origin of every line
+pub struct ShaderCode {
+    pub code: String,
+    /// Every file read, the main one first
+    pub files: Vec<PathBuf>,
+    /// Index into `files` and line in that file of each line of `code`, both starting at 1 for lines
+    lines: Vec<(usize, u32)>,
+    origin: ShaderOrigin,
+}
+
+/// How a shader was preprocessed, to do it again once one of its files changes
+#[derive(Clone, PartialEq, Debug)]
+pub struct ShaderOrigin {
+    preprocessor: Preprocessor,
+    /// Relative to the shader directory
+    path: PathBuf,
+    /// Every file read, canonicalized like the paths of [`ShaderRegistry`](super::ShaderRegistry)
+    files: Vec<PathBuf>,
+}
+
+impl ShaderOrigin {
+    /// Whether any of `changed`, which are canonical, was read
+    pub fn depends_on(&self, changed: &[PathBuf]) -> bool {
+        self.files.iter().any(|f| changed.contains(f))
+    }
+
+    /// Reads the files again
+    pub fn process(&self) -> Result<ShaderCode, ShaderError> {
+        self.preprocessor.process(&self.path)
+    }
+
+    pub fn path(&self) -> &Path { &self.path }
+
+    pub fn preprocessor(&self) -> &Preprocessor { &self.preprocessor }
+}
+
+impl ShaderCode {
+    /// File and line a line of `code` came from, counting from 1
+    pub fn locate(&self, line: u32) -> Option<(&Path, u32)> {
+        let &(file, line) = self.lines.get((line as usize).checked_sub(1)?)?;
+        Some((&self.files[file], line))
+    }
+
+    pub fn origin(&self) -> &ShaderOrigin { &self.origin }
+}
+
+impl Preprocessor {
+    /// Includes are looked up in the shader directory of `path_m`
+    pub fn new(path_m: &PathManager) -> Self {
+        Self { include_dir: path_m.shader(""), defines: BTreeMap::new() }
+    }
+
+    /// Defines `name` before the first line, to build permutations of a shader
+    pub fn with_define(mut self, name: &str, value: &str) -> Self {
+        self.defines.insert(name.to_owned(), value.to_owned()); self
+    }
+
+    /// Reads `path`, relative to the shader directory, and everything it includes
+    pub fn process<T: AsRef<Path>>(&self, path: T) -> Result<ShaderCode, ShaderError> {
+        let origin = ShaderOrigin { preprocessor: self.clone(), path: path.as_ref().to_owned(), files: Vec::new() };
+        let mut state = State {
+            out: ShaderCode { code: String::new(), files: Vec::new(), lines: Vec::new(), origin },
+            defines: self.defines.clone().into_iter().collect(),
+            stack: Vec::new(),
+        };
+        let path = self.include_dir.join(path);
+        let source = crate::files::read_file(&path)?.0;
+        self.process_file(path, &source, &mut state)?;
+
+        let mut out = state.out;
+        out.origin.files = out.files.iter().map(|f| canonical(f)).collect();
+        Ok(out)
+    }
+
+    fn process_file(&self, path: PathBuf, source: &str, state: &mut State) -> Result<(), ShaderError> {
+        let file = state.out.files.len();
+        state.out.files.push(path.clone());
+        state.stack.push(canonical(&path));
+
+        // Innermost last: whether the block is taken, whether `#else` was seen and the line opening it
+        let mut conditions: Vec<(bool, bool, u32)> = Vec::new();
+
+        for (line, text) in (1..).zip(source.lines()) {
+            let active = conditions.iter().all(|c| c.0);
+            let err = |msg: String| ShaderError::Directive(path.clone(), line, msg);
+
+            let Some(directive) = text.trim_start().strip_prefix('#') else {
+                if active {
+                    state.out.code.push_str(&substitute(text, &state.defines));
+                    state.out.code.push('\n');
+                    state.out.lines.push((file, line));
+                }
+                continue
+            };
+
+            let (name, args) = directive.split_once(char::is_whitespace).unwrap_or((directive, ""));
+            let args = args.trim();
+            match name {
+                "ifdef" | "ifndef" => {
+                    let defined = state.defines.contains_key(identifier(args).map_err(err)?);
+                    conditions.push((defined == (name == "ifdef"), false, line));
+                }
+                "else" => match conditions.last_mut() {
+                    Some((_, true, _)) => return Err(err("#else after #else".to_owned())),
+                    Some((taken, seen_else, _)) => { *taken = !*taken; *seen_else = true }
+                    None => return Err(err("#else without #ifdef".to_owned())),
+                },
+                "endif" => if conditions.pop().is_none() { return Err(err("#endif without #ifdef".to_owned())) },
+                _ if !active => {}
+                "define" => {
+                    let (define, value) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+                    state.defines.insert(identifier(define).map_err(err)?.to_owned(), value.trim().to_owned());
+                }
+                "undef" => { state.defines.remove(identifier(args).map_err(err)?); }
+                "include" => {
+                    let include = args.strip_prefix('"').and_then(|a| a.strip_suffix('"'))
+                        .ok_or_else(|| err(format!("Expected a quoted file name, found '{args}'")))?;
+                    let include = self.include_dir.join(include);
+                    let canonical = canonical(&include);
+
+                    if let Some(start) = state.stack.iter().position(|p| *p == canonical) {
+                        let mut cycle = state.stack[start..].to_vec();
+                        cycle.push(canonical);
+                        return Err(ShaderError::IncludeCycle(cycle))
+                    }
+                    // Pasting a file twice would redeclare everything in it
+                    if state.out.files.iter().any(|p| self::canonical(p) == canonical) { continue }
+
+                    let source = crate::files::read_file(&include)
+                        .map_err(|e| err(format!("Couldn't include '{}': {e}", include.display())))?.0;
+                    self.process_file(include, &source, state)?;
+                }
+                _ => return Err(err(format!("Unknown directive '#{name}'"))),
+            }
+        }
+
+        if let Some(&(_, _, line)) = conditions.last() {
+            return Err(ShaderError::Directive(path, line, "#ifdef without #endif".to_owned()))
+        }
+        state.stack.pop();
+        Ok(())
+    }
+}
+
+struct State {
+    out: ShaderCode,
+    defines: HashMap<String, String>,
+    /// Files being included, to detect cycles
+    stack: Vec<PathBuf>,
+}
+
+/// Same file reached through different relative paths compares equal
+pub fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
+
+fn identifier(name: &str) -> Result<&str, String> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid { Ok(name) } else { Err(format!("Invalid name '{name}'")) }
+}
+
+/// Replaces every identifier defined with a value, once, without expanding the value again
+fn substitute(line: &str, defines: &HashMap<String, String>) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        // Identifiers don't start in the middle of a number like `1e5` or `0x1f`
+        let prefix = &rest[..start];
+        let in_number = prefix.chars().next_back().is_some_and(|c| c.is_ascii_alphanumeric());
+        let len = rest[start..].find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len() - start);
+        let word = &rest[start..start + len];
+
+        out.push_str(prefix);
+        match defines.get(word) {
+            Some(value) if !value.is_empty() && !in_number => out.push_str(value),
+            _ => out.push_str(word),
+        }
+        rest = &rest[start + len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Shader directory with `files`, removed when dropped
+    struct Dir(PathBuf);
+
+    impl Dir {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let dir = std::env::temp_dir().join(format!("engine-preprocess-{}-{name}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            for (file, source) in files { std::fs::write(dir.join(file), source).unwrap() }
+            Self(dir)
+        }
+
+        fn preprocessor(&self) -> Preprocessor {
+            Preprocessor { include_dir: self.0.clone(), defines: BTreeMap::new() }
+        }
+    }
+
+    impl Drop for Dir {
+        fn drop(&mut self) { let _ = std::fs::remove_dir_all(&self.0); }
+    }
+
+    #[test]
+    fn nested_conditionals() {
+        let dir = Dir::new("conditionals", &[("main.wgsl", "\
+#ifdef A
+a
+#ifndef B
+not b
+#else
+b
+#endif
+#else
+not a
+#endif
+end
+")]);
+        let code = |p: Preprocessor| p.process("main.wgsl").unwrap_or_else(|e| panic!("{e}")).code;
+        assert_eq!(code(dir.preprocessor()), "not a\nend\n");
+        assert_eq!(code(dir.preprocessor().with_define("A", "")), "a\nnot b\nend\n");
+        assert_eq!(code(dir.preprocessor().with_define("A", "").with_define("B", "")), "a\nb\nend\n");
+    }
+
+    #[test]
+    fn unbalanced_conditionals() {
+        let dir = Dir::new("unbalanced", &[
+            ("open.wgsl", "x\n#ifdef A\n"),
+            ("else.wgsl", "#else\n"),
+            ("twice.wgsl", "#ifdef A\n#else\n#else\n#endif\n"),
+        ]);
+        let p = dir.preprocessor();
+        assert!(matches!(p.process("open.wgsl"), Err(ShaderError::Directive(_, 2, _))));
+        assert!(matches!(p.process("else.wgsl"), Err(ShaderError::Directive(_, 1, _))));
+        assert!(matches!(p.process("twice.wgsl"), Err(ShaderError::Directive(_, 3, _))));
+    }
+
+    #[test]
+    fn includes_once_and_locates_lines() {
+        let dir = Dir::new("includes", &[
+            ("main.wgsl", "#include \"a.wgsl\"\nmain\n#include \"b.wgsl\"\n#include \"a.wgsl\"\n"),
+            ("a.wgsl", "a1\na2\n"),
+            ("b.wgsl", "#include \"a.wgsl\"\nb1\n"),
+        ]);
+        let out = dir.preprocessor().process("main.wgsl").unwrap_or_else(|e| panic!("{e}"));
+        assert_eq!(out.code, "a1\na2\nmain\nb1\n");
+
+        let names: Vec<_> = out.files.iter().map(|f| f.file_name().unwrap().to_str().unwrap()).collect();
+        assert_eq!(names, ["main.wgsl", "a.wgsl", "b.wgsl"]);
+        let locate = |line| out.locate(line).map(|(file, line)| (file.file_name().unwrap().to_str().unwrap(), line));
+        assert_eq!(locate(1), Some(("a.wgsl", 1)));
+        assert_eq!(locate(3), Some(("main.wgsl", 2)));
+        assert_eq!(locate(4), Some(("b.wgsl", 2)));
+        assert_eq!(locate(0), None);
+        assert_eq!(locate(5), None);
+    }
+
+    #[test]
+    fn include_cycle() {
+        let dir = Dir::new("cycle", &[
+            ("main.wgsl", "#include \"a.wgsl\"\n"),
+            ("a.wgsl", "#include \"b.wgsl\"\n"),
+            ("b.wgsl", "#include \"a.wgsl\"\n"),
+        ]);
+        match dir.preprocessor().process("main.wgsl") {
+            Err(ShaderError::IncludeCycle(files)) => {
+                let names: Vec<_> = files.iter().map(|f| f.file_name().unwrap().to_str().unwrap()).collect();
+                assert_eq!(names, ["a.wgsl", "b.wgsl", "a.wgsl"]);
+            }
+            Err(e) => panic!("{e}"),
+            Ok(_) => panic!("Cycle not detected"),
+        }
+    }
+
+    #[test]
+    fn defines() {
+        let dir = Dir::new("defines", &[("main.wgsl", "#define N 4\nN\n#undef N\nN\n")]);
+        let out = dir.preprocessor().process("main.wgsl").unwrap_or_else(|e| panic!("{e}"));
+        assert_eq!(out.code, "4\nN\n");
+    }
+
+    #[test]
+    fn substitutes_whole_identifiers() {
+        let defines: HashMap<String, String> = [("N", "4"), ("e5", "X"), ("x1f", "Y"), ("A", "B"), ("B", "C"), ("EMPTY", "")]
+            .into_iter().map(|(k, v)| (k.to_owned(), v.to_owned())).collect();
+        assert_eq!(substitute("let a = 1e5 + 0x1f * N;", &defines), "let a = 1e5 + 0x1f * 4;");
+        assert_eq!(substitute("N_2 + f(N) + _N", &defines), "N_2 + f(4) + _N");
+        assert_eq!(substitute("A EMPTY", &defines), "B EMPTY");
+    }
+
+    #[test]
+    fn identifiers() {
+        assert_eq!(identifier("_name2"), Ok("_name2"));
+        assert!(identifier("").is_err());
+        assert!(identifier("2name").is_err());
+        assert!(identifier("na-me").is_err());
+    }
+}
@@ -0,0 +1,194 @@
+//! Entry points and resource bindings of a WGSL module, read from naga after validating it
+
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+
+/// What a shader declares, to check pipelines against it before wgpu does
+#[derive(Clone, Debug)]
+pub struct Reflection {
+    pub entry_points: Vec<EntryPoint>,
+    pub bindings: Vec<Binding>,
+}
+
+#[derive(Clone, Debug)]
+pub struct EntryPoint {
+    pub name: String,
+    pub stage: wgpu::ShaderStages,
+}
+
+#[derive(Clone, Debug)]
+pub struct Binding {
+    pub group: u32,
+    pub binding: u32,
+    pub name: Option<String>,
+    pub kind: BindingKind,
+    /// Entry points reading or writing it
+    pub used_by: Vec<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BindingKind {
+    Uniform,
+    Storage { writable: bool },
+    Sampler { comparison: bool },
+    /// `sample_type` is always filterable for floats, only its variant is compared
+    Texture { sample_type: wgpu::TextureSampleType, dimension: wgpu::TextureViewDimension, multisampled: bool },
+    StorageTexture { dimension: wgpu::TextureViewDimension },
+}
+
+/// Message and 1-based line and column of a rejected module
+pub struct Invalid {
+    pub message: String,
+    pub location: Option<(u32, u32)>,
+}
+
+/// Parses and validates `code`
+pub fn validate(code: &str) -> Result<Reflection, Invalid> {
+    let module = naga::front::wgsl::parse_str(code).map_err(|e| {
+        let mut message = e.message().to_owned();
+        if let Some((_, label)) = e.labels().next().filter(|(_, l)| !l.is_empty() && !e.message().contains(l)) {
+            message = format!("{message}: {label}");
+        }
+        Invalid { message, location: e.location(code).map(|l| (l.line_number, l.line_position)) }
+    })?;
+
+    // wgpu checks the capabilities of the device itself
+    let info = Validator::new(ValidationFlags::all(), Capabilities::all()).validate(&module).map_err(|e| {
+        Invalid { message: chain(e.as_inner()), location: e.location(code).map(|l| (l.line_number, l.line_position)) }
+    })?;
+
+    let entry_points = module.entry_points.iter().map(|e| EntryPoint {
+        name: e.name.clone(),
+        stage: match e.stage {
+            naga::ShaderStage::Vertex => wgpu::ShaderStages::VERTEX,
+            naga::ShaderStage::Fragment => wgpu::ShaderStages::FRAGMENT,
+            naga::ShaderStage::Compute => wgpu::ShaderStages::COMPUTE,
+        },
+    }).collect();
+
+    let bindings = module.global_variables.iter().filter_map(|(handle, var)| {
+        let binding = var.binding.as_ref()?;
+        let used_by = module.entry_points.iter().enumerate()
+            .filter(|(i, _)| !info.get_entry_point(*i)[handle].is_empty())
+            .map(|(_, e)| e.name.clone())
+            .collect();
+        Some(Binding {
+            group: binding.group,
+            binding: binding.binding,
+            name: var.name.clone(),
+            kind: kind(&module, var)?,
+            used_by,
+        })
+    }).collect();
+
+    Ok(Reflection { entry_points, bindings })
+}
+
+impl Reflection {
+    pub fn entry_point(&self, name: &str) -> Option<&EntryPoint> {
+        self.entry_points.iter().find(|e| e.name == name)
+    }
+
+    /// Checks that `entry_point` is a `stage` entry point and, if given, that `layouts`, indexed by group,
+    /// hold every binding it uses with a matching type and visibility
+    pub fn check(&self, entry_point: &str, stage: wgpu::ShaderStages, layouts: Option<&[&[wgpu::BindGroupLayoutEntry]]>) -> Result<(), String> {
+        match self.entry_point(entry_point) {
+            Some(e) if e.stage == stage => {}
+            Some(e) => return Err(format!("Entry point '{entry_point}' is a {} entry point, not {}", stage_name(e.stage), stage_name(stage))),
+            None => return Err(format!("No entry point '{entry_point}'")),
+        }
+        let Some(layouts) = layouts else { return Ok(()) };
+
+        for b in self.bindings.iter().filter(|b| b.used_by.iter().any(|e| e == entry_point)) {
+            let name = format!("@group({}) @binding({}) '{}'", b.group, b.binding, b.name.as_deref().unwrap_or("?"));
+            let layout = layouts.get(b.group as usize)
+                .ok_or_else(|| format!("{name} used by '{entry_point}' has no bind group layout"))?;
+            let entry = layout.iter().find(|e| e.binding == b.binding)
+                .ok_or_else(|| format!("{name} used by '{entry_point}' is missing from its bind group layout"))?;
+            if !entry.visibility.contains(stage) {
+                return Err(format!("{name} isn't visible to the {} stage", stage_name(stage)))
+            }
+            if !b.kind.accepts(&entry.ty) {
+                return Err(format!("{name} is {:?} in the shader but {:?} in the layout", b.kind, entry.ty))
+            }
+        }
+        Ok(())
+    }
+}
+
+impl BindingKind {
+    /// Whether a layout entry of type `ty` can be bound to it
+    pub fn accepts(&self, ty: &wgpu::BindingType) -> bool {
+        use wgpu::{BindingType as Ty, BufferBindingType as Buf};
+        match (*self, ty) {
+            (Self::Uniform, Ty::Buffer { ty: Buf::Uniform, .. }) => true,
+            (Self::Storage { writable }, Ty::Buffer { ty: Buf::Storage { read_only }, .. }) => !(writable && *read_only),
+            (Self::Sampler { comparison }, Ty::Sampler(s)) => comparison == (*s == wgpu::SamplerBindingType::Comparison),
+            (Self::Texture { sample_type, dimension, multisampled }, Ty::Texture { sample_type: t, view_dimension, multisampled: m }) =>
+                std::mem::discriminant(&sample_type) == std::mem::discriminant(t) && dimension == *view_dimension && multisampled == *m,
+            (Self::StorageTexture { dimension }, Ty::StorageTexture { view_dimension, .. }) => dimension == *view_dimension,
+            _ => false,
+        }
+    }
+}
+
+fn kind(module: &naga::Module, var: &naga::GlobalVariable) -> Option<BindingKind> {
+    let mut inner = &module.types[var.ty].inner;
+    if let naga::TypeInner::BindingArray { base, .. } = inner {
+        inner = &module.types[*base].inner;
+    }
+
+    match var.space {
+        naga::AddressSpace::Uniform => return Some(BindingKind::Uniform),
+        naga::AddressSpace::Storage { access } => return Some(BindingKind::Storage { writable: access.contains(naga::StorageAccess::STORE) }),
+        naga::AddressSpace::Handle => {}
+        _ => return None,
+    }
+
+    match *inner {
+        naga::TypeInner::Sampler { comparison } => Some(BindingKind::Sampler { comparison }),
+        naga::TypeInner::Image { dim, arrayed, class } => {
+            let dimension = match (dim, arrayed) {
+                (naga::ImageDimension::D1, _) => wgpu::TextureViewDimension::D1,
+                (naga::ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+                (naga::ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+                (naga::ImageDimension::D3, _) => wgpu::TextureViewDimension::D3,
+                (naga::ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+                (naga::ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+            };
+            Some(match class {
+                naga::ImageClass::Sampled { kind, multi } => BindingKind::Texture {
+                    sample_type: match kind {
+                        naga::ScalarKind::Sint => wgpu::TextureSampleType::Sint,
+                        naga::ScalarKind::Uint => wgpu::TextureSampleType::Uint,
+                        _ => wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    dimension,
+                    multisampled: multi,
+                },
+                naga::ImageClass::Depth { multi } => BindingKind::Texture { sample_type: wgpu::TextureSampleType::Depth, dimension, multisampled: multi },
+                naga::ImageClass::Storage { .. } => BindingKind::StorageTexture { dimension },
+            })
+        }
+        _ => None,
+    }
+}
+
+fn stage_name(stage: wgpu::ShaderStages) -> &'static str {
+    match stage {
+        wgpu::ShaderStages::VERTEX => "vertex",
+        wgpu::ShaderStages::FRAGMENT => "fragment",
+        wgpu::ShaderStages::COMPUTE => "compute",
+        _ => "combined",
+    }
+}
+
+/// Error and its sources, which hold the details of validation errors
+fn chain(e: &dyn std::error::Error) -> String {
+    let mut message = e.to_string();
+    let mut source = e.source();
+    while let Some(s) = source {
+        message = format!("{message}: {s}");
+        source = s.source();
+    }
+    message
+}
@@ -0,0 +1,65 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::client::PathManager;
+
+use super::preprocess::canonical;
+
+/// Watches the shader directory by polling modification times, so pipelines can be rebuilt
+/// with [`Pipeline::reload`](crate::client::renderer::Pipeline::reload) while the engine runs
+pub struct ShaderRegistry {
+    dir: PathBuf,
+    interval: Duration,
+    last_poll: Instant,
+    /// Canonical path of every file under `dir`
+    stamps: HashMap<PathBuf, SystemTime>,
+}
+
+impl ShaderRegistry {
+    pub fn new(path_m: &PathManager) -> Self {
+        let dir = path_m.shader("");
+        let mut stamps = HashMap::new();
+        scan(&dir, &mut stamps);
+        Self { dir, interval: Duration::from_millis(500), last_poll: Instant::now(), stamps }
+    }
+
+    /// Minimum time between two scans of the directory
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval; self
+    }
+
+    /// Canonical paths of the files created, modified or removed since the last scan.
+    /// Empty until the interval passed.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < self.interval { return Vec::new() }
+        self.last_poll = Instant::now();
+
+        let mut stamps = HashMap::with_capacity(self.stamps.len());
+        scan(&self.dir, &mut stamps);
+
+        let mut changed: Vec<PathBuf> = stamps.iter()
+            .filter(|(path, time)| self.stamps.get(*path) != Some(time))
+            .map(|(path, _)| path.clone())
+            .collect();
+        changed.extend(self.stamps.keys().filter(|path| !stamps.contains_key(*path)).cloned());
+
+        self.stamps = stamps;
+        changed
+    }
+}
+
+fn scan(dir: &Path, stamps: &mut HashMap<PathBuf, SystemTime>) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(metadata) = entry.metadata() else { continue };
+        if metadata.is_dir() {
+            scan(&path, stamps);
+        } else if let Ok(modified) = metadata.modified() {
+            stamps.insert(canonical(&path), modified);
+        }
+    }
+}
@@ -1,11 +1,10 @@
 use super::Renderer;
 
-use crate::{math::{Zero, Vec3, Quat, Angle}, client::{renderer::{resources::image::{CubeMap, texture::TextureEntry}, pipeline::PipelineBuilder}, PathManager}};
+use crate::{math::Vec2, client::{renderer::{resources::image::CubeMap, pipeline::{PipelineBuilder, PipelineCache}, light::environment::{Environment, EnvironmentSettings}}, PathManager}};
 
 use {
     crate::client::Window,
     super::{
-        pipeline,
         err::RendererError,
         framebuffer::FrameBuffer,
         resources::{
@@ -14,68 +13,37 @@ use {
         },
         gpu::shader,
     },
-    wgpu::util::DeviceExt,
-    crate::instance::Instance,
-    super::state::State,
+    super::{state::State, scene::Scene},
 };
 
-const NUM_INSTANCES_PER_ROW: u32 = 10;
-
 impl Renderer {
     pub async fn new(window: &Window, path_m: &PathManager) -> Result<Self, RendererError> {
         let state = State::new(window).await?;
+        Self::from_state(state, path_m)
+    }
 
+    /// Creates a renderer that draws into an offscreen texture instead of a window surface.
+    /// Frames can be read back with [`Renderer::capture_frame`].
+    pub async fn new_headless(size: Vec2<u32>, path_m: &PathManager) -> Result<Self, RendererError> {
+        let state = State::new_headless(size).await?;
+        Self::from_state(state, path_m)
+    }
 
-        let instances = Self::init_instances();
-
-        let instance_data = instances.iter().map(Instance::to_raw).collect::<Vec<_>>();
-        let instance_buffer = state.device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Instance Buffer"),
-                contents: bytemuck::cast_slice(&instance_data),
-                usage: wgpu::BufferUsages::VERTEX,
-            }
-        );
-
+    fn from_state(state: State, path_m: &PathManager) -> Result<Self, RendererError> {
 
         let depth_texture = image::Texture::create_depth_texture(&state.device, &state.config);
 
-        let vpath = std::path::Path::new("assets/shaders/vertex.wgsl");
-        let fpath = std::path::Path::new("assets/shaders/fragment.wgsl");
+        let mut pipelines = PipelineCache::new(path_m);
 
-        let vertex_shader = shader::Shader::import_vert(&state, "vs_main", vpath, "Vertex Geometry Shader")?;
-        let fragment_shader = shader::Shader::import_frag(&state, "fs_main",fpath, "Fragment Geometry Shader")?;
+        let vertex_shader = pipelines.import_vert(&state, "vs_main", "vertex.wgsl", "Vertex Geometry Shader")?;
+        let fragment_shader = pipelines.import_frag(&state, "fs_main", "fragment.wgsl", "Fragment Geometry Shader")?;
 
         use crate::client::renderer::gpu::uniform::Uniform;
         let uniform_layout_v = Uniform::<u8>::create_layout(&state.device, "Template layout", wgpu::ShaderStages::VERTEX);
         let uniform_layout_vf = Uniform::<u8>::create_layout(&state.device, "Template layout", wgpu::ShaderStages::VERTEX_FRAGMENT);
         let _uniform_layout_f = Uniform::<u8>::create_layout(&state.device, "Template layout", wgpu::ShaderStages::FRAGMENT);
 
-        let material_layout = {
-            use super::resources::material::*;
-            Material::<()>::layout(&state, &[TextureEntry::DIFFUSE_MAP_ENTRY, TextureEntry::NORMAL_MAP_ENTRY])
-        };
-
-        let light = super::light::Light::new(&state, &uniform_layout_vf.0)?;
-
-        let model = model::load_model("assets/models/barrel.obj", &state, path_m)?;
-
-        let pipeline = PipelineBuilder::new(
-            vertex_shader.vs_state(&[model::ModelVertex::desc(), crate::instance::InstanceRaw::desc()]),
-            Some(fragment_shader.fs_state(&[Some(wgpu::ColorTargetState {
-                format: super::framebuffer::FRAMEBUFFER_FORMAT,
-                blend: Some(wgpu::BlendState::REPLACE),
-                write_mask: wgpu::ColorWrites::ALL,
-            })])),
-        ).enable_depth().with_bg_layouts(&[&material_layout.0, &uniform_layout_vf.0, light.layout(), &uniform_layout_v.0]).construct(&state);
-
-        let framebuffer = FrameBuffer::new(&state, &state.config)?;
-
-        let postfx: Vec<Box<dyn super::postfx::PostFx>> = vec![
-            Box::new(super::postfx::chromatic_aberration::ChromaticAberration::new(&state, framebuffer.target_tex.bg.layout(), 0.005)?),
-            Box::new(super::postfx::box_blur::BoxBlur::new(&state, framebuffer.target_tex.bg.layout(), 3)?)
-        ];
-
+        let material_layout = super::resources::material::Material::geometry_layout(&state);
 
         let cubemap = CubeMap::from_paths(
             &state,
@@ -90,43 +58,51 @@ impl Renderer {
                 ],
                 "Sky Cubemap"
             )?;
+        let environment = Environment::from_cubemap(&state, &mut pipelines, &cubemap, EnvironmentSettings::default(), "Sky Environment")?;
+
+        let lights = super::light::LightManager::new(&state, &mut pipelines, &uniform_layout_vf, environment)?;
+
+        let geometry_targets = [Some(wgpu::ColorTargetState {
+            format: super::framebuffer::FRAMEBUFFER_FORMAT,
+            blend: Some(wgpu::BlendState::REPLACE),
+            write_mask: wgpu::ColorWrites::ALL,
+        })];
+        let geometry_layouts = [&material_layout, &uniform_layout_vf, lights.layout(), &uniform_layout_v];
+        let geometry_buffers = [model::ModelVertex::desc(), crate::instance::InstanceRaw::desc()];
+
+        let pipeline = pipelines.get(&state, PipelineBuilder::new(
+            vertex_shader.vs_state(&geometry_buffers),
+            Some(fragment_shader.fs_state(&geometry_targets)),
+        ).with_label("Blinn-Phong Pipeline").enable_depth().with_layouts(&geometry_layouts))?;
+
+        // Same inputs, Cook-Torrance shading for metallic-roughness materials
+        let pbr_pipeline = pipelines.get(&state, PipelineBuilder::new(
+            vertex_shader.vs_state(&geometry_buffers),
+            Some(fragment_shader.fs_entry_state("fs_pbr", &geometry_targets)),
+        ).with_label("PBR Pipeline").enable_depth().with_layouts(&geometry_layouts))?;
 
-        let sky_shader = shader::Shader::import_combined(&state, ("vs_main", "fs_main"), std::path::Path::new("assets/shaders/sky.wgsl"), "Sky cubemap shader")?;
+        let framebuffer = FrameBuffer::new(&state, &mut pipelines, &state.config)?;
 
-        let sky_pipeline = PipelineBuilder::new(
+        let postfx = super::postfx::PostFxStack::new();
+
+
+        let sky_shader = pipelines.import_combined(&state, ("vs_main", "fs_main"), "sky.wgsl", "Sky cubemap shader")?;
+
+        let sky_pipeline = pipelines.get(&state, PipelineBuilder::new(
             sky_shader.vs_state(&[]),
                 Some(sky_shader.fs_state(&[Some(wgpu::ColorTargetState {
                     format: super::framebuffer::FRAMEBUFFER_FORMAT,
                     blend: Some(wgpu::BlendState::REPLACE),
                     write_mask: wgpu::ColorWrites::ALL,
                 })]))
-            ).enable_depth().with_depth_compare_function(wgpu::CompareFunction::LessEqual)
-            .with_bg_layouts(&[&uniform_layout_vf.0, &cubemap.bg.layout.0])
-            .construct(&state);
+            ).with_label("Sky Pipeline")
+            // Drawn from inside the cube
+            .with_cull(Some(wgpu::Face::Front))
+            .enable_depth().with_depth_compare_function(wgpu::CompareFunction::LessEqual)
+            .with_layouts(&[&uniform_layout_vf, &lights.environment().sky.bg.layout]))?;
 
         log::info!("Renderer configured");
-        Ok(Self { state, pipeline, instances, instance_buffer, depth_texture, model, light, framebuffer, postfx, cubemap, sky_pipeline })
-    }
-
-    fn init_instances() -> Vec<Instance> {
-        const SPACE_BETWEEN: f32 = 3.0;
-        (0..NUM_INSTANCES_PER_ROW).flat_map(|z| {
-            (0..NUM_INSTANCES_PER_ROW).map(move |x| {
-                let x = SPACE_BETWEEN * (x as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
-                let z = SPACE_BETWEEN * (z as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
-
-                let position = Vec3::new(x, 0., z);
-
-                let rotation = if position.is_zero() {
-                    Quat::from_axis_angle(Vec3::unit_z(), Angle::zero())
-                } else {
-                    Quat::from_axis_angle(position.normalize(), Angle::from_deg(45.))
-                };
-
-                Instance {
-                    position, rotation,
-                }
-            })
-        }).collect::<Vec<_>>()
+        Ok(Self { state, pipeline, pbr_pipeline, depth_texture, scene: Scene::new(), lights, light_marker: None, framebuffer, postfx, sky_pipeline, pipelines, shaders: shader::ShaderRegistry::new(path_m),
+            anti_aliasing: super::AntiAliasing::None, msaa: None, taa: None, fxaa: None })
     }
 }
\ No newline at end of file
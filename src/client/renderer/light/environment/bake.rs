@@ -0,0 +1,238 @@
+//! GPU passes filling the sky cubemap and the lighting maps of an [`Environment`](super::Environment)
+
+use crate::client::renderer::{
+    gpu::{bind_group::Layout, buffer::Buffer, err::GpuResourceError, shader::{Shader, ShaderError}},
+    pipeline::{Pipeline, PipelineBuilder, PipelineCache},
+    state::State,
+};
+
+use super::EnvironmentSettings;
+
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const BRDF_LUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+
+pub enum Source<'a> {
+    /// 2D view of an equirectangular image
+    Equirect(&'a wgpu::TextureView),
+    /// Cube view of a cubemap
+    Cube(&'a wgpu::TextureView),
+}
+
+pub struct Maps {
+    /// Sky cubemap with a full mip chain
+    pub radiance: wgpu::Texture,
+    pub irradiance: wgpu::Texture,
+    /// One mip level per roughness step
+    pub specular: wgpu::Texture,
+    pub brdf_lut: wgpu::Texture,
+}
+
+/// Per draw parameters, every draw reads its own at a dynamic offset
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BakeParams {
+    face: u32,
+    roughness: f32,
+    sample_count: u32,
+    source_size: f32,
+}
+
+pub fn bake(state: &State, pipelines: &mut PipelineCache, source: Source, settings: &EnvironmentSettings) -> Result<Maps, GpuResourceError> {
+    let device = &state.device;
+    let shader = pipelines.import_combined(state, ("vs_main", "fs_copy"), "environment.wgsl", "Environment bake shader")?;
+
+    let params_size = std::mem::size_of::<BakeParams>() as wgpu::BufferAddress;
+    let params_layout = Layout::new(device, &[wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: true,
+            min_binding_size: wgpu::BufferSize::new(params_size),
+        },
+        count: None,
+    }], "Environment Bake Params Bind Group Layout");
+
+    let sampler_entry = wgpu::BindGroupLayoutEntry {
+        binding: 1,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        count: None,
+    };
+    let texture_entry = |binding, view_dimension| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension,
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+        },
+        count: None,
+    };
+    let equirect_layout = Layout::new(device, &[texture_entry(0, wgpu::TextureViewDimension::D2), sampler_entry], "Environment Equirect Bind Group Layout");
+    let cube_layout = Layout::new(device, &[sampler_entry, texture_entry(2, wgpu::TextureViewDimension::Cube)], "Environment Cube Bind Group Layout");
+
+    let equirect_pipeline = pipeline(state, &shader, "fs_equirect", &[&params_layout, &equirect_layout], FORMAT)?;
+    let copy_pipeline = pipeline(state, &shader, "fs_copy", &[&params_layout, &cube_layout], FORMAT)?;
+    let irradiance_pipeline = pipeline(state, &shader, "fs_irradiance", &[&params_layout, &cube_layout], FORMAT)?;
+    let prefilter_pipeline = pipeline(state, &shader, "fs_prefilter", &[&params_layout, &cube_layout], FORMAT)?;
+    let brdf_pipeline = pipeline(state, &shader, "fs_brdf", &[&params_layout], BRDF_LUT_FORMAT)?;
+
+    // Equirectangular images wrap around horizontally
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("Environment Bake Sampler"),
+        address_mode_u: wgpu::AddressMode::Repeat,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        mipmap_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+
+    let radiance_levels = settings.sky_size.ilog2() + 1;
+    let radiance = create_texture(device, settings.sky_size, radiance_levels, 6, FORMAT, "Environment Radiance Texture");
+    let irradiance = create_texture(device, settings.irradiance_size, 1, 6, FORMAT, "Environment Irradiance Texture");
+    let specular = create_texture(device, settings.specular_size, settings.specular_levels, 6, FORMAT, "Environment Specular Texture");
+    let brdf_lut = create_texture(device, settings.brdf_lut_size, 1, 1, BRDF_LUT_FORMAT, "Environment BRDF LUT Texture");
+
+    let draws = 6 * (radiance_levels + 1 + settings.specular_levels) as wgpu::BufferAddress + 1;
+    let align = device.limits().min_uniform_buffer_offset_alignment as wgpu::BufferAddress;
+    let stride = params_size.div_ceil(align) * align;
+    let params_buf = Buffer::empty(device, stride * draws, "Environment Bake Params Buffer", wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST);
+    let params_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Environment Bake Params Bind Group"),
+        layout: &params_layout.0,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &params_buf.0,
+                offset: 0,
+                size: wgpu::BufferSize::new(params_size),
+            }),
+        }],
+    });
+    let source_bg = |layout: &Layout, binding: u32, view: &wgpu::TextureView| device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Environment Bake Source Bind Group"),
+        layout: &layout.0,
+        entries: &[
+            wgpu::BindGroupEntry { binding, resource: wgpu::BindingResource::TextureView(view) },
+            wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&sampler) },
+        ],
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Environment Bake Encoder") });
+    let mut params = Vec::new();
+    let mut draw = |encoder: &mut wgpu::CommandEncoder, pipeline: &Pipeline, source: Option<&wgpu::BindGroup>, target: &wgpu::TextureView, p: BakeParams| {
+        let offset = (params.len() as wgpu::BufferAddress * stride) as wgpu::DynamicOffset;
+        params.push(p);
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Environment Bake Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        pass.set_pipeline(&pipeline.pipeline);
+        pass.set_bind_group(0, &params_bg, &[offset]);
+        if let Some(source) = source { pass.set_bind_group(1, source, &[]) }
+        pass.draw(0..3, 0..1);
+    };
+    let face_params = |face, roughness, source_size| BakeParams { face, roughness, sample_count: settings.sample_count, source_size };
+
+    // Top level of the sky from the source, then every mip level from the one above
+    let (top_pipeline, top_bg) = match source {
+        Source::Equirect(view) => (&equirect_pipeline, source_bg(&equirect_layout, 0, view)),
+        Source::Cube(view) => (&copy_pipeline, source_bg(&cube_layout, 2, view)),
+    };
+    for face in 0..6 {
+        draw(&mut encoder, top_pipeline, Some(&top_bg), &face_view(&radiance, 0, face), face_params(face, 0., 0.));
+    }
+    for level in 1..radiance_levels {
+        let above = source_bg(&cube_layout, 2, &cube_view(&radiance, level - 1, 1));
+        for face in 0..6 {
+            draw(&mut encoder, &copy_pipeline, Some(&above), &face_view(&radiance, level, face), face_params(face, 0., 0.));
+        }
+    }
+
+    let sky_bg = source_bg(&cube_layout, 2, &cube_view(&radiance, 0, radiance_levels));
+    let sky_size = settings.sky_size as f32;
+    for face in 0..6 {
+        draw(&mut encoder, &irradiance_pipeline, Some(&sky_bg), &face_view(&irradiance, 0, face), face_params(face, 0., sky_size));
+    }
+    for level in 0..settings.specular_levels {
+        let roughness = level as f32 / (settings.specular_levels - 1).max(1) as f32;
+        for face in 0..6 {
+            draw(&mut encoder, &prefilter_pipeline, Some(&sky_bg), &face_view(&specular, level, face), face_params(face, roughness, sky_size));
+        }
+    }
+    draw(&mut encoder, &brdf_pipeline, None, &face_view(&brdf_lut, 0, 0), face_params(0, 0., 0.));
+
+    let mut bytes = vec![0; (stride * params.len() as wgpu::BufferAddress) as usize];
+    for (i, p) in params.iter().enumerate() {
+        let start = i * stride as usize;
+        bytes[start..start + params_size as usize].copy_from_slice(bytemuck::bytes_of(p));
+    }
+    state.queue.write_buffer(&params_buf.0, 0, &bytes);
+    state.queue.submit(std::iter::once(encoder.finish()));
+
+    Ok(Maps { radiance, irradiance, specular, brdf_lut })
+}
+
+/// Used for a single bake with its own layouts, so only the shader module is cached
+fn pipeline(state: &State, shader: &Shader, entry: &str, layouts: &[&Layout], format: wgpu::TextureFormat) -> Result<Pipeline, ShaderError> {
+    let targets = [Some(wgpu::ColorTargetState {
+        format,
+        blend: None,
+        write_mask: wgpu::ColorWrites::ALL,
+    })];
+    PipelineBuilder::new(shader.vs_state(&[]), Some(shader.fs_entry_state(entry, &targets)))
+        .with_layouts(layouts)
+        .construct(state)
+}
+
+fn create_texture(device: &wgpu::Device, size: u32, levels: u32, layers: u32, format: wgpu::TextureFormat, label: &str) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: layers,
+        },
+        mip_level_count: levels,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
+
+/// Render target of a single face and mip level
+fn face_view(texture: &wgpu::Texture, level: u32, face: u32) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("Environment Bake Face View"),
+        dimension: Some(wgpu::TextureViewDimension::D2),
+        base_mip_level: level,
+        mip_level_count: Some(1),
+        base_array_layer: face,
+        array_layer_count: Some(1),
+        ..Default::default()
+    })
+}
+
+fn cube_view(texture: &wgpu::Texture, base_level: u32, levels: u32) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("Environment Bake Cube View"),
+        dimension: Some(wgpu::TextureViewDimension::Cube),
+        base_mip_level: base_level,
+        mip_level_count: Some(levels),
+        ..Default::default()
+    })
+}
@@ -0,0 +1,202 @@
+mod bake;
+
+use crate::client::renderer::{
+    gpu::{buffer::Buffer, err::GpuResourceError},
+    pipeline::PipelineCache,
+    resources::image::{texture::load_texture, CubeMap, RawImage},
+    state::State,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct EnvironmentSettings {
+    /// Face size of the sky cubemap an equirectangular image is converted to
+    pub sky_size: u32,
+    /// Face size of the diffuse irradiance map
+    pub irradiance_size: u32,
+    /// Face size of the top level of the prefiltered specular map
+    pub specular_size: u32,
+    /// Mip levels of the prefiltered specular map, spread evenly from roughness 0 to 1
+    pub specular_levels: u32,
+    pub brdf_lut_size: u32,
+    /// Samples taken per texel when convolving the maps
+    pub sample_count: u32,
+    /// Scale of the light coming from the environment
+    pub intensity: f32,
+}
+
+impl Default for EnvironmentSettings {
+    fn default() -> Self {
+        Self {
+            sky_size: 512,
+            irradiance_size: 32,
+            specular_size: 128,
+            specular_levels: 6,
+            brdf_lut_size: 256,
+            sample_count: 512,
+            intensity: 1.,
+        }
+    }
+}
+
+/// Sky cubemap and the maps lighting the scene from it: diffuse irradiance,
+/// specular radiance prefiltered per roughness, and the BRDF lookup table of the split sum approximation
+pub struct Environment {
+    settings: EnvironmentSettings,
+    /// Drawn by the sky pass
+    pub sky: CubeMap,
+    _irradiance: wgpu::Texture,
+    irradiance_view: wgpu::TextureView,
+    _specular: wgpu::Texture,
+    specular_view: wgpu::TextureView,
+    _brdf_lut: wgpu::Texture,
+    brdf_lut_view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    uniform_buf: Buffer,
+}
+
+impl Environment {
+    /// Converts an equirectangular image, usually a Radiance `.hdr`, to the sky cubemap on the GPU and bakes its lighting
+    pub fn from_equirect(state: &State, pipelines: &mut PipelineCache, image: &RawImage, settings: EnvironmentSettings, label: &str) -> Result<Self, GpuResourceError> {
+        // 8 bit images are sRGB like any other color texture
+        let texture = load_texture(&state.device, &state.queue, image, label, wgpu::TextureFormat::Rgba8UnormSrgb, false);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Self::bake(state, pipelines, bake::Source::Equirect(&view), settings, label)
+    }
+
+    /// Bakes the lighting of an existing cubemap, which is copied into the HDR sky cubemap
+    pub fn from_cubemap(state: &State, pipelines: &mut PipelineCache, cubemap: &CubeMap, settings: EnvironmentSettings, label: &str) -> Result<Self, GpuResourceError> {
+        let settings = EnvironmentSettings { sky_size: cubemap.texture.width(), ..settings };
+        Self::bake(state, pipelines, bake::Source::Cube(&cubemap.view), settings, label)
+    }
+
+    fn bake(state: &State, pipelines: &mut PipelineCache, source: bake::Source, settings: EnvironmentSettings, label: &str) -> Result<Self, GpuResourceError> {
+        let specular_size = settings.specular_size.max(1);
+        let settings = EnvironmentSettings {
+            sky_size: settings.sky_size.max(1),
+            irradiance_size: settings.irradiance_size.max(1),
+            specular_size,
+            specular_levels: settings.specular_levels.clamp(1, specular_size.ilog2() + 1),
+            brdf_lut_size: settings.brdf_lut_size.max(1),
+            ..settings
+        };
+        let maps = bake::bake(state, pipelines, source, &settings)?;
+
+        let cube_view = |texture: &wgpu::Texture| texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(label),
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        let irradiance_view = cube_view(&maps.irradiance);
+        let specular_view = cube_view(&maps.specular);
+        let brdf_lut_view = maps.brdf_lut.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let sampler = state.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Environment Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let uniform_buf = Buffer::new_uniform(&state.device, &[EnvironmentRaw::new(&settings)], "Environment Uniform Buffer");
+
+        Ok(Self {
+            settings,
+            sky: CubeMap::from_texture(state, maps.radiance, wgpu::FilterMode::Linear, label),
+            _irradiance: maps.irradiance,
+            irradiance_view,
+            _specular: maps.specular,
+            specular_view,
+            _brdf_lut: maps.brdf_lut,
+            brdf_lut_view,
+            sampler,
+            uniform_buf,
+        })
+    }
+
+    pub fn settings(&self) -> &EnvironmentSettings { &self.settings }
+
+    pub fn set_intensity(&mut self, queue: &wgpu::Queue, intensity: f32) {
+        self.settings.intensity = intensity;
+        queue.write_buffer(&self.uniform_buf.0, 0, bytemuck::bytes_of(&EnvironmentRaw::new(&self.settings)));
+    }
+
+    /// Layout entries of the lighting maps, appended to the lights bind group starting at `first_binding`
+    pub fn layout_entries(first_binding: u32) -> [wgpu::BindGroupLayoutEntry; 5] {
+        let texture = |binding, view_dimension| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        [
+            texture(first_binding, wgpu::TextureViewDimension::Cube),
+            texture(first_binding + 1, wgpu::TextureViewDimension::Cube),
+            texture(first_binding + 2, wgpu::TextureViewDimension::D2),
+            wgpu::BindGroupLayoutEntry {
+                binding: first_binding + 3,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: first_binding + 4,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ]
+    }
+
+    pub fn entries(&self, first_binding: u32) -> [wgpu::BindGroupEntry<'_>; 5] {
+        [
+            wgpu::BindGroupEntry {
+                binding: first_binding,
+                resource: wgpu::BindingResource::TextureView(&self.irradiance_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: first_binding + 1,
+                resource: wgpu::BindingResource::TextureView(&self.specular_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: first_binding + 2,
+                resource: wgpu::BindingResource::TextureView(&self.brdf_lut_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: first_binding + 3,
+                resource: wgpu::BindingResource::Sampler(&self.sampler),
+            },
+            self.uniform_buf.entry(first_binding + 4),
+        ]
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct EnvironmentRaw {
+    intensity: f32,
+    /// Mip level of the prefiltered map at roughness 1
+    specular_max_lod: f32,
+    _padding: [f32; 2],
+}
+
+impl EnvironmentRaw {
+    fn new(settings: &EnvironmentSettings) -> Self {
+        Self {
+            intensity: settings.intensity,
+            specular_max_lod: settings.specular_levels.saturating_sub(1) as f32,
+            _padding: [0.; 2],
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use crate::client::renderer::{
+    resources::model::{self, Vertex},
+    gpu::{
+        bind_group::{BindGroup, Layout},
+        err::GpuResourceError,
+    },
+    pipeline::{PipelineBuilder, PipelineCache},
+    state::State,
+};
+
+use super::{
+    LightManager, SlotMap,
+    environment::Environment,
+    occlusion::{AmbientOcclusion, SsaoSettings},
+    shadow::{ShadowMaps, ShadowSettings},
+};
+
+const INITIAL_CAPACITY: usize = 16;
+
+impl LightManager {
+    pub fn new(state: &State, pipelines: &mut PipelineCache, camera_layout: &Layout, environment: Environment) -> Result<Self, GpuResourceError> {
+        let buf = Self::create_buffer(state, INITIAL_CAPACITY);
+
+        let shadows = ShadowMaps::new(state, pipelines, ShadowSettings::default())?;
+        let [shadow_uniform, shadow_tex, shadow_sampler] = ShadowMaps::layout_entries(1);
+        let [u, t, s] = shadows.entries(1);
+        let [irradiance_layout, specular_layout, brdf_lut_layout, sampler_layout, environment_layout] = Environment::layout_entries(4);
+        let [irradiance, specular, brdf_lut, sampler, environment_uniform] = environment.entries(4);
+        let occlusion = AmbientOcclusion::new(state, pipelines, camera_layout, SsaoSettings::default())?;
+
+        let bg = BindGroup::new(
+            &state.device,
+            &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }, shadow_uniform, shadow_tex, shadow_sampler,
+            irradiance_layout, specular_layout, brdf_lut_layout, sampler_layout, environment_layout,
+            AmbientOcclusion::layout_entry(9)],
+            &[buf.entry(0), u, t, s, irradiance, specular, brdf_lut, sampler, environment_uniform, occlusion.entry(9)],
+            "Lights",
+        );
+
+        let vshader = pipelines.import_vert(state, "vs_main", "light_vertex.wgsl", "Light vertex shader")?;
+        let fshader = pipelines.import_frag(state, "fs_main", "light_fragment.wgsl", "Light fragment shader")?;
+
+        let pipeline = pipelines.get(state, PipelineBuilder::new(
+            vshader.vs_state(&[model::ModelVertex::desc()]),
+            Some(fshader.fs_state(&[Some(wgpu::ColorTargetState {
+                format: super::super::framebuffer::FRAMEBUFFER_FORMAT,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })])),
+        ).enable_depth().with_depth_compare_function(wgpu::CompareFunction::LessEqual)
+        .with_layouts(&[camera_layout, &bg.layout]))?;
+
+        let mut lights = Self {
+            lights: SlotMap::with_capacity(INITIAL_CAPACITY),
+            buf,
+            bg,
+            capacity: INITIAL_CAPACITY,
+            dirty: true,
+            pipeline,
+            shadows,
+            environment,
+            occlusion,
+        };
+        lights.update_buffer(state);
+
+        Ok(lights)
+    }
+}
@@ -0,0 +1,284 @@
+mod init;
+pub mod shadow;
+pub mod environment;
+pub mod occlusion;
+
+use std::sync::Arc;
+
+use crate::{
+    client::renderer::{
+        gpu::{bind_group::{BindGroup, Layout}, buffer::Buffer},
+        pipeline::{Pipeline, PipelineCache},
+        state::State,
+    },
+    common::slot_map::{Handle, SlotMap},
+    math::{Angle, Vec3},
+};
+
+use {
+    environment::Environment,
+    occlusion::{AmbientOcclusion, SsaoSettings},
+    shadow::{ShadowMaps, ShadowSettings},
+};
+
+pub type LightHandle = Handle<Light>;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LightKind {
+    Point = 0,
+    Directional = 1,
+    Spot = 2,
+}
+
+/// Description of a single light source.
+/// `position` is ignored by directional lights, `direction` by point lights.
+#[derive(Clone, Copy, Debug)]
+pub struct Light {
+    pub kind: LightKind,
+    pub position: Vec3<f32>,
+    pub direction: Vec3<f32>,
+    pub color: Vec3<f32>,
+    pub intensity: f32,
+    /// Distance at which the light's contribution falls off to zero
+    pub range: f32,
+    /// Spot lights are at full intensity inside the inner cone and fade out towards the outer cone
+    pub inner_cone: Angle<f32>,
+    pub outer_cone: Angle<f32>,
+    /// Only directional and spot lights can cast shadows
+    pub cast_shadows: bool,
+}
+
+impl Light {
+    pub fn point<V: Into<Vec3<f32>>, C: Into<Vec3<f32>>>(position: V, color: C, intensity: f32, range: f32) -> Self {
+        Self {
+            kind: LightKind::Point,
+            position: position.into(),
+            direction: -Vec3::unit_y(),
+            color: color.into(),
+            intensity,
+            range,
+            inner_cone: Angle::from_deg(0.),
+            outer_cone: Angle::from_deg(0.),
+            cast_shadows: false,
+        }
+    }
+
+    pub fn directional<V: Into<Vec3<f32>>, C: Into<Vec3<f32>>>(direction: V, color: C, intensity: f32) -> Self {
+        Self {
+            kind: LightKind::Directional,
+            position: Vec3::default(),
+            direction: direction.into().normalize(),
+            color: color.into(),
+            intensity,
+            range: 0.,
+            inner_cone: Angle::from_deg(0.),
+            outer_cone: Angle::from_deg(0.),
+            cast_shadows: false,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn spot<P: Into<Vec3<f32>>, D: Into<Vec3<f32>>, C: Into<Vec3<f32>>>(
+        position: P,
+        direction: D,
+        color: C,
+        intensity: f32,
+        range: f32,
+        inner_cone: Angle<f32>,
+        outer_cone: Angle<f32>,
+    ) -> Self {
+        Self {
+            kind: LightKind::Spot,
+            position: position.into(),
+            direction: direction.into().normalize(),
+            color: color.into(),
+            intensity,
+            range,
+            inner_cone,
+            outer_cone,
+            cast_shadows: false,
+        }
+    }
+
+    pub fn with_shadows(mut self) -> Self { self.cast_shadows = true; self }
+
+    fn to_raw(self, shadow_index: i32) -> LightRaw {
+        LightRaw {
+            position: self.position.into(),
+            kind: self.kind as u32,
+            direction: self.direction.into(),
+            range: self.range,
+            color: self.color.into(),
+            intensity: self.intensity,
+            cos_inner: self.inner_cone.cos(),
+            cos_outer: self.outer_cone.cos(),
+            shadow_index,
+            _padding: 0.,
+        }
+    }
+}
+
+/// Owns every light in the scene and mirrors them into a storage buffer
+pub struct LightManager {
+    lights: SlotMap<Light>,
+    buf: Buffer,
+    bg: BindGroup,
+    capacity: usize,
+    dirty: bool,
+    pipeline: Arc<Pipeline>,
+    shadows: ShadowMaps,
+    environment: Environment,
+    occlusion: AmbientOcclusion,
+}
+
+impl LightManager {
+    pub fn add(&mut self, light: Light) -> LightHandle {
+        self.dirty = true;
+        self.lights.insert(light)
+    }
+
+    pub fn remove(&mut self, handle: LightHandle) -> Option<Light> {
+        let light = self.lights.remove(handle);
+        self.dirty |= light.is_some();
+        light
+    }
+
+    /// Applies `f` to the light. Returns false if the handle is no longer valid.
+    pub fn update<F: FnOnce(&mut Light)>(&mut self, handle: LightHandle, f: F) -> bool {
+        match self.lights.get_mut(handle) {
+            Some(light) => { f(light); self.dirty = true; true }
+            None => false,
+        }
+    }
+
+    pub fn get(&self, handle: LightHandle) -> Option<&Light> { self.lights.get(handle) }
+
+    pub fn get_mut(&mut self, handle: LightHandle) -> Option<&mut Light> {
+        let light = self.lights.get_mut(handle);
+        self.dirty |= light.is_some();
+        light
+    }
+
+    pub fn clear(&mut self) {
+        self.lights.clear();
+        self.dirty = true;
+    }
+
+    pub fn shadow_settings(&self) -> &ShadowSettings { &self.shadows.settings }
+
+    pub fn set_shadow_settings(&mut self, settings: ShadowSettings) {
+        self.shadows.settings = settings;
+        self.dirty = true;
+    }
+
+    pub fn shadows(&self) -> &ShadowMaps { &self.shadows }
+
+    pub fn environment(&self) -> &Environment { &self.environment }
+
+    pub fn environment_mut(&mut self) -> &mut Environment { &mut self.environment }
+
+    /// Replaces the sky and the ambient light it casts
+    pub fn set_environment(&mut self, state: &State, environment: Environment) {
+        self.environment = environment;
+        self.replace_group(&state.device);
+    }
+
+    pub fn occlusion(&self) -> &AmbientOcclusion { &self.occlusion }
+
+    pub fn occlusion_settings(&self) -> &SsaoSettings { self.occlusion.settings() }
+
+    pub fn set_occlusion_settings(&mut self, state: &State, settings: SsaoSettings) {
+        let toggled = settings.enabled != self.occlusion.settings().enabled;
+        self.occlusion.set_settings(&state.queue, settings);
+        if toggled { self.replace_group(&state.device) }
+    }
+
+    /// Follows the size of the frame with the screen space occlusion
+    pub fn resize(&mut self, state: &State, width: u32, height: u32) {
+        self.occlusion.resize(&state.device, width, height);
+        self.replace_group(&state.device);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (LightHandle, &Light)> { self.lights.iter() }
+
+    pub fn len(&self) -> usize { self.lights.len() }
+    pub fn is_empty(&self) -> bool { self.lights.is_empty() }
+
+    /// Uploads the lights to the GPU if anything changed since the last call, growing the buffer when needed
+    pub fn update_buffer(&mut self, state: &State) {
+        if !self.dirty { return }
+        self.dirty = false;
+
+        let mut replace_group = self.shadows.apply_map_size(&state.device);
+        if self.lights.len() > self.capacity {
+            self.capacity = self.lights.len().next_power_of_two();
+            self.buf = Self::create_buffer(state, self.capacity);
+            replace_group = true;
+        }
+        if replace_group { self.replace_group(&state.device) }
+
+        let shadow_layers = self.shadows.update(&state.queue, self.lights.values());
+
+        let header = LightsHeader { count: self.lights.len() as u32, _padding: [0; 3] };
+        let raw: Vec<LightRaw> = self.lights.values().zip(shadow_layers).map(|(l, layer)| l.to_raw(layer)).collect();
+        state.queue.write_buffer(&self.buf.0, 0, bytemuck::bytes_of(&header));
+        if !raw.is_empty() {
+            state.queue.write_buffer(&self.buf.0, std::mem::size_of::<LightsHeader>() as wgpu::BufferAddress, bytemuck::cast_slice(&raw));
+        }
+    }
+
+    pub fn layout(&self) -> &Layout { &self.bg.layout }
+    pub fn bg(&self) -> &wgpu::BindGroup { &self.bg.group }
+    pub fn pipeline(&self) -> &wgpu::RenderPipeline { &self.pipeline.pipeline }
+
+    /// Picks up the light marker, shadow and occlusion pipelines rebuilt by [`PipelineCache::reload`]
+    pub fn refresh_pipelines(&mut self, pipelines: &PipelineCache) {
+        pipelines.refresh(&mut self.pipeline);
+        self.shadows.refresh_pipelines(pipelines);
+        self.occlusion.refresh_pipelines(pipelines);
+    }
+
+    /// Draws the light markers to scene targets with `count` samples
+    pub fn set_sample_count(&mut self, state: &State, pipelines: &mut PipelineCache, count: u32) {
+        self.pipeline = pipelines.with_multisample(state, &self.pipeline, count);
+    }
+
+    fn replace_group(&mut self, device: &wgpu::Device) {
+        let [u, t, s] = self.shadows.entries(1);
+        let [irradiance, specular, brdf_lut, sampler, environment] = self.environment.entries(4);
+        self.bg.replace_group(device, &[self.buf.entry(0), u, t, s, irradiance, specular, brdf_lut, sampler, environment, self.occlusion.entry(9)], "Lights");
+    }
+
+    fn create_buffer(state: &State, capacity: usize) -> Buffer {
+        let size = std::mem::size_of::<LightsHeader>() + capacity * std::mem::size_of::<LightRaw>();
+        Buffer::empty(
+            &state.device,
+            size as wgpu::BufferAddress,
+            "Lights Storage Buffer",
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        )
+    }
+}
+
+/// Precedes the light array in the storage buffer; padded to the array's 16 byte alignment
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightsHeader {
+    count: u32,
+    _padding: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightRaw {
+    position: [f32; 3],
+    kind: u32,
+    direction: [f32; 3],
+    range: f32,
+    color: [f32; 3],
+    intensity: f32,
+    cos_inner: f32,
+    cos_outer: f32,
+    shadow_index: i32,
+    _padding: f32,
+}
@@ -0,0 +1,350 @@
+use std::sync::Arc;
+
+use crate::{
+    client::renderer::{
+        gpu::{
+            bind_group::{BindGroup, Layout},
+            buffer::Buffer,
+            err::GpuResourceError,
+        },
+        pipeline::{Pipeline, PipelineBuilder, PipelineCache},
+        resources::{image::Texture, model::{self, Vertex}},
+        state::{RenderState, State},
+    },
+    instance::InstanceRaw,
+};
+
+/// Largest sample count of the hemisphere kernel
+pub const MAX_SSAO_SAMPLES: usize = 64;
+
+/// Width and height of the tiled texture rotating the kernel per pixel
+const NOISE_SIZE: u32 = 4;
+
+const GBUFFER_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const OCCLUSION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SsaoSettings {
+    /// Without it the passes are skipped and ambient light is left as is
+    pub enabled: bool,
+    /// World space radius of the hemisphere sampled around every surface
+    pub radius: f32,
+    /// Depth difference below which a sample isn't occluded, against self occlusion on flat surfaces
+    pub bias: f32,
+    /// Samples per pixel, up to [`MAX_SSAO_SAMPLES`]
+    pub samples: u32,
+    /// Exponent applied to the result; higher darkens occluded areas more
+    pub intensity: f32,
+}
+
+impl Default for SsaoSettings {
+    fn default() -> Self {
+        Self { enabled: true, radius: 0.5, bias: 0.025, samples: 16, intensity: 1.0 }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SsaoRaw {
+    kernel: [[f32; 4]; MAX_SSAO_SAMPLES],
+    radius: f32,
+    bias: f32,
+    samples: u32,
+    intensity: f32,
+}
+
+impl SsaoSettings {
+    fn raw(&self) -> SsaoRaw {
+        let samples = self.samples.clamp(1, MAX_SSAO_SAMPLES as u32);
+        let mut kernel = [[0.0; 4]; MAX_SSAO_SAMPLES];
+        for (i, offset) in kernel.iter_mut().enumerate().take(samples as usize) {
+            let index = i as u32 + 1;
+            let direction = [halton(index, 2) * 2.0 - 1.0, halton(index, 3) * 2.0 - 1.0, halton(index, 5)];
+            let length = direction.iter().map(|c| c * c).sum::<f32>().sqrt();
+            // Later samples reach further, so most of them stay close to the surface
+            let t = i as f32 / samples as f32;
+            let scale = halton(index, 7) * (0.1 + 0.9 * t * t) / length;
+            *offset = [direction[0] * scale, direction[1] * scale, direction[2] * scale, 0.0];
+        }
+
+        SsaoRaw {
+            kernel,
+            radius: self.radius.max(0.0),
+            bias: self.bias,
+            samples,
+            intensity: self.intensity.max(0.0),
+        }
+    }
+}
+
+/// Low discrepancy sequence spreading the kernel and noise evenly without a random number generator
+fn halton(mut index: u32, base: u32) -> f32 {
+    let (mut fraction, mut result) = (1.0, 0.0);
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+/// Target of an occlusion pass, sampled through `bg` by the next one
+struct Target {
+    view: wgpu::TextureView,
+    bg: wgpu::BindGroup,
+}
+
+/// Screen space ambient occlusion: positions and normals of the scene are drawn in a prepass,
+/// occlusion is estimated from them and blurred, then darkens the ambient light of the geometry pass
+pub struct AmbientOcclusion {
+    prepass: Arc<Pipeline>,
+    ssao: Arc<Pipeline>,
+    blur: [Arc<Pipeline>; 2],
+    params: Buffer,
+    noise: wgpu::TextureView,
+    depth: wgpu::TextureView,
+    position: wgpu::TextureView,
+    normal: wgpu::TextureView,
+    /// Prepass results, noise and parameters
+    gbuffer: BindGroup,
+    target_layout: Layout,
+    /// The occlusion is estimated into the first and blurred into the second and back
+    targets: [Target; 2],
+    /// Bound instead of the targets while disabled
+    white: wgpu::TextureView,
+    settings: SsaoSettings,
+}
+
+impl AmbientOcclusion {
+    pub fn new(state: &State, pipelines: &mut PipelineCache, camera_layout: &Layout, settings: SsaoSettings) -> Result<Self, GpuResourceError> {
+        let (width, height) = (state.config.width, state.config.height);
+        let params = Buffer::new_uniform(&state.device, &[settings.raw()], "SSAO Uniform Buffer");
+        let noise = Self::create_noise(state);
+        let white = Self::create_white(state);
+        let (depth, position, normal) = Self::create_gbuffer(&state.device, width, height);
+
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            },
+            count: None,
+        };
+        let gbuffer = BindGroup::new(
+            &state.device,
+            &[texture_entry(0), texture_entry(1), texture_entry(2), wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            &Self::gbuffer_entries(&position, &normal, &noise, &params),
+            "SSAO ",
+        );
+        let target_layout = Layout::new(&state.device, &[texture_entry(0)], "SSAO Target Bind Group Layout");
+        let targets = Self::create_targets(&state.device, &target_layout, width, height);
+
+        let prepass_shader = pipelines.import_combined(state, ("vs_prepass", "fs_prepass"), "ssao.wgsl", "SSAO shader")?;
+        let gbuffer_target = Some(wgpu::ColorTargetState {
+            format: GBUFFER_FORMAT,
+            blend: None,
+            write_mask: wgpu::ColorWrites::ALL,
+        });
+        let gbuffer_targets = [gbuffer_target.clone(), gbuffer_target];
+        let prepass = pipelines.get(state, PipelineBuilder::new(
+            prepass_shader.vs_state(&[model::ModelVertex::desc(), InstanceRaw::desc()]),
+            Some(prepass_shader.fs_state(&gbuffer_targets)),
+        ).with_label("SSAO Prepass Pipeline").enable_depth().with_depth_format(Texture::DEPTH_FORMAT).with_layouts(&[camera_layout]))?;
+
+        let shader = pipelines.import_combined(state, ("vs_main", "fs_ssao"), "ssao.wgsl", "SSAO shader")?;
+        let occlusion_target = [Some(wgpu::ColorTargetState {
+            format: OCCLUSION_FORMAT,
+            blend: None,
+            write_mask: wgpu::ColorWrites::ALL,
+        })];
+        let mut pipeline = |entry: &str, label: &str, layouts: &[&Layout]| pipelines.get(state,
+            PipelineBuilder::new(shader.vs_state(&[]), Some(shader.fs_entry_state(entry, &occlusion_target)))
+                .with_label(label)
+                .with_layouts(layouts)
+        );
+        let ssao = pipeline("fs_ssao", "SSAO Pipeline", &[camera_layout, &gbuffer.layout])?;
+        let blur = [
+            pipeline("fs_blur_horizontal", "SSAO Horizontal Blur Pipeline", &[camera_layout, &gbuffer.layout, &target_layout])?,
+            pipeline("fs_blur_vertical", "SSAO Vertical Blur Pipeline", &[camera_layout, &gbuffer.layout, &target_layout])?,
+        ];
+
+        Ok(Self {
+            prepass, ssao, blur, params, noise,
+            depth, position, normal, gbuffer,
+            target_layout, targets, white,
+            settings,
+        })
+    }
+
+    pub fn settings(&self) -> &SsaoSettings { &self.settings }
+
+    /// Uploads the new parameters; the lights bind group has to be replaced after toggling `enabled`
+    pub fn set_settings(&mut self, queue: &wgpu::Queue, settings: SsaoSettings) {
+        self.settings = settings;
+        queue.write_buffer(&self.params.0, 0, bytemuck::bytes_of(&settings.raw()));
+    }
+
+    /// Layout entry of the occlusion, appended to the lights bind group at `binding`
+    pub fn layout_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            },
+            count: None,
+        }
+    }
+
+    pub fn entry(&self, binding: u32) -> wgpu::BindGroupEntry<'_> {
+        let view = if self.settings.enabled { &self.targets[0].view } else { &self.white };
+        wgpu::BindGroupEntry { binding, resource: wgpu::BindingResource::TextureView(view) }
+    }
+
+    /// Pass drawing the positions and normals of the scene, bound to the prepass pipeline and camera.
+    /// The scene is drawn into it with the model and instance vertex buffers, before [`AmbientOcclusion::render`].
+    pub fn prepass<'a>(&'a self, render_state: &'a mut RenderState, camera_bg: &'a wgpu::BindGroup) -> wgpu::RenderPass<'a> {
+        // Nothing drawn leaves a view depth of 0, which the later passes skip
+        let clear = wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT), store: true };
+        let attachment = |view| Some(wgpu::RenderPassColorAttachment { view, resolve_target: None, ops: clear });
+        let mut render_pass = render_state.encoder().begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("SSAO Prepass"),
+            color_attachments: &[attachment(&self.position), attachment(&self.normal)],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: false,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        render_pass.set_pipeline(&self.prepass.pipeline);
+        render_pass.set_bind_group(0, camera_bg, &[]);
+        render_pass
+    }
+
+    /// Estimates the occlusion from the prepass and blurs it
+    pub fn render(&self, render_state: &mut RenderState, camera_bg: &wgpu::BindGroup) {
+        let mut pass = |label, pipeline: &Pipeline, target: &wgpu::TextureView, source: Option<&wgpu::BindGroup>| {
+            let mut render_pass = render_state.render_pass(Some(label), Some(target), Some(wgpu::Color::WHITE), None);
+            render_pass.set_pipeline(&pipeline.pipeline);
+            render_pass.set_bind_group(0, camera_bg, &[]);
+            render_pass.set_bind_group(1, &self.gbuffer.group, &[]);
+            if let Some(source) = source { render_pass.set_bind_group(2, source, &[]) }
+            render_pass.draw(0..3, 0..1);
+        };
+        let [estimate, blurred] = &self.targets;
+        pass("SSAO Pass", &self.ssao, &estimate.view, None);
+        pass("SSAO Horizontal Blur Pass", &self.blur[0], &blurred.view, Some(&estimate.bg));
+        pass("SSAO Vertical Blur Pass", &self.blur[1], &estimate.view, Some(&blurred.bg));
+    }
+
+    /// Follows the size of the frame; the lights bind group has to be replaced afterwards
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        (self.depth, self.position, self.normal) = Self::create_gbuffer(device, width, height);
+        self.gbuffer.replace_group(device, &Self::gbuffer_entries(&self.position, &self.normal, &self.noise, &self.params), "SSAO ");
+        self.targets = Self::create_targets(device, &self.target_layout, width, height);
+    }
+
+    pub fn refresh_pipelines(&mut self, pipelines: &PipelineCache) {
+        let [horizontal, vertical] = &mut self.blur;
+        for pipeline in [&mut self.prepass, &mut self.ssao, horizontal, vertical] {
+            pipelines.refresh(pipeline);
+        }
+    }
+
+    fn gbuffer_entries<'a>(position: &'a wgpu::TextureView, normal: &'a wgpu::TextureView, noise: &'a wgpu::TextureView, params: &'a Buffer) -> [wgpu::BindGroupEntry<'a>; 4] {
+        [
+            wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(position) },
+            wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(normal) },
+            wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(noise) },
+            params.entry(3),
+        ]
+    }
+
+    fn create_view(device: &wgpu::Device, width: u32, height: u32, format: wgpu::TextureFormat, label: &str) -> wgpu::TextureView {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        }).create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    /// Depth, position and normal targets of the prepass. Its own depth, so it doesn't depend on multisampling.
+    fn create_gbuffer(device: &wgpu::Device, width: u32, height: u32) -> (wgpu::TextureView, wgpu::TextureView, wgpu::TextureView) {
+        (
+            Self::create_view(device, width, height, Texture::DEPTH_FORMAT, "SSAO Depth Texture"),
+            Self::create_view(device, width, height, GBUFFER_FORMAT, "SSAO Position Texture"),
+            Self::create_view(device, width, height, GBUFFER_FORMAT, "SSAO Normal Texture"),
+        )
+    }
+
+    fn create_targets(device: &wgpu::Device, layout: &Layout, width: u32, height: u32) -> [Target; 2] {
+        [(); 2].map(|_| {
+            let view = Self::create_view(device, width, height, OCCLUSION_FORMAT, "SSAO Texture");
+            let bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("SSAO Target Bind Group"),
+                layout: &layout.0,
+                entries: &[wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&view) }],
+            });
+            Target { view, bg }
+        })
+    }
+
+    /// Rotation vectors around the normal, tiled over the screen
+    fn create_noise(state: &State) -> wgpu::TextureView {
+        let pixels: Vec<[f32; 4]> = (1..=NOISE_SIZE * NOISE_SIZE)
+            .map(|i| [halton(i, 2) * 2.0 - 1.0, halton(i, 3) * 2.0 - 1.0, 0.0, 0.0])
+            .collect();
+        Self::create_filled(state, NOISE_SIZE, wgpu::TextureFormat::Rgba32Float, bytemuck::cast_slice(&pixels), "SSAO Noise Texture")
+    }
+
+    fn create_white(state: &State) -> wgpu::TextureView {
+        Self::create_filled(state, 1, OCCLUSION_FORMAT, &[u8::MAX], "SSAO Disabled Texture")
+    }
+
+    fn create_filled(state: &State, size: u32, format: wgpu::TextureFormat, data: &[u8], label: &str) -> wgpu::TextureView {
+        let extent = wgpu::Extent3d { width: size, height: size, depth_or_array_layers: 1 };
+        let texture = state.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        state.queue.write_texture(
+            texture.as_image_copy(),
+            data,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(data.len() as u32 / size),
+                rows_per_image: Some(size),
+            },
+            extent,
+        );
+        texture.create_view(&wgpu::TextureViewDescriptor::default())
+    }
+}
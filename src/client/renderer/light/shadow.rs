@@ -0,0 +1,309 @@
+use std::sync::Arc;
+
+use crate::{
+    client::renderer::{
+        gpu::{
+            bind_group::BindGroup,
+            buffer::Buffer,
+            err::GpuResourceError,
+        },
+        pipeline::{Pipeline, PipelineBuilder, PipelineCache},
+        resources::{image::Texture, model::{self, Vertex}},
+        state::State,
+    },
+    instance::InstanceRaw,
+    math::{Mat4, Vec3},
+};
+
+use super::{Light, LightKind};
+
+/// Number of lights that can cast shadows at the same time; one shadow map layer each
+pub const MAX_SHADOW_CASTERS: usize = 4;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ShadowSettings {
+    /// Width and height of every shadow map layer
+    pub map_size: u32,
+    /// Constant offset subtracted from the fragment depth before comparing
+    pub depth_bias: f32,
+    /// World space distance the lookup position is pushed along the surface normal
+    pub normal_bias: f32,
+    /// Kernel radius in texels for PCF; 0 only uses the hardware 2x2 filter
+    pub pcf_radius: u32,
+    /// Center of the area covered by directional shadows
+    pub focus: Vec3<f32>,
+    /// Half size of the square covered by directional shadows
+    pub directional_extent: f32,
+    /// Depth range of the directional shadow volume, centered on `focus`
+    pub directional_depth: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            map_size: 2048,
+            depth_bias: 0.0005,
+            normal_bias: 0.02,
+            pcf_radius: 1,
+            focus: Vec3::default(),
+            directional_extent: 20.,
+            directional_depth: 80.,
+        }
+    }
+}
+
+/// Depth texture array with one layer per shadow casting light, and the pipeline that renders into it
+pub struct ShadowMaps {
+    pub settings: ShadowSettings,
+    texture: wgpu::Texture,
+    array_view: wgpu::TextureView,
+    layer_views: Vec<wgpu::TextureView>,
+    sampler: wgpu::Sampler,
+    uniform_buf: Buffer,
+    pass_buf: Buffer,
+    pass_bg: BindGroup,
+    pass_stride: wgpu::BufferAddress,
+    pipeline: Arc<Pipeline>,
+    casters: usize,
+}
+
+impl ShadowMaps {
+    pub fn new(state: &State, pipelines: &mut PipelineCache, settings: ShadowSettings) -> Result<Self, GpuResourceError> {
+        let (texture, array_view, layer_views) = Self::create_texture(&state.device, settings.map_size);
+
+        let sampler = state.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Map Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let uniform_buf = Buffer::new_uniform(&state.device, &[ShadowRaw::zeroed()], "Shadow Uniform Buffer");
+
+        // Every layer's matrix sits at its own dynamic offset
+        let align = state.device.limits().min_uniform_buffer_offset_alignment as wgpu::BufferAddress;
+        let matrix_size = std::mem::size_of::<[[f32; 4]; 4]>() as wgpu::BufferAddress;
+        let pass_stride = matrix_size.div_ceil(align) * align;
+        let pass_buf = Buffer::empty(
+            &state.device,
+            pass_stride * MAX_SHADOW_CASTERS as wgpu::BufferAddress,
+            "Shadow Pass Buffer",
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        );
+
+        let pass_bg = BindGroup::new(
+            &state.device,
+            &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(matrix_size),
+                },
+                count: None,
+            }],
+            &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &pass_buf.0,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(matrix_size),
+                }),
+            }],
+            "Shadow Pass",
+        );
+
+        let shader = pipelines.import_vert(state, "vs_main", "shadow.wgsl", "Shadow vertex shader")?;
+        let pipeline = pipelines.get(state, PipelineBuilder::new(
+            shader.vs_state(&[model::ModelVertex::desc(), InstanceRaw::desc()]),
+            None,
+        ).enable_depth().with_depth_format(Texture::DEPTH_FORMAT).with_layouts(&[&pass_bg.layout]))?;
+
+        Ok(Self {
+            settings,
+            texture,
+            array_view,
+            layer_views,
+            sampler,
+            uniform_buf,
+            pass_buf,
+            pass_bg,
+            pass_stride,
+            pipeline,
+            casters: 0,
+        })
+    }
+
+    /// Number of shadow map layers rendered this frame
+    pub fn casters(&self) -> usize { self.casters }
+
+    pub fn layer_view(&self, layer: usize) -> &wgpu::TextureView { &self.layer_views[layer] }
+    pub fn pass_bg(&self) -> &wgpu::BindGroup { &self.pass_bg.group }
+    pub fn pass_offset(&self, layer: usize) -> wgpu::DynamicOffset { (layer as wgpu::BufferAddress * self.pass_stride) as wgpu::DynamicOffset }
+    pub fn pipeline(&self) -> &wgpu::RenderPipeline { &self.pipeline.pipeline }
+
+    pub fn refresh_pipelines(&mut self, pipelines: &PipelineCache) {
+        pipelines.refresh(&mut self.pipeline);
+    }
+
+    /// Layout entries of the shadow resources, appended to the lights bind group starting at `first_binding`
+    pub fn layout_entries(first_binding: u32) -> [wgpu::BindGroupLayoutEntry; 3] {
+        [
+            wgpu::BindGroupLayoutEntry {
+                binding: first_binding,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: first_binding + 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    sample_type: wgpu::TextureSampleType::Depth,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: first_binding + 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                count: None,
+            },
+        ]
+    }
+
+    pub fn entries(&self, first_binding: u32) -> [wgpu::BindGroupEntry<'_>; 3] {
+        [
+            self.uniform_buf.entry(first_binding),
+            wgpu::BindGroupEntry {
+                binding: first_binding + 1,
+                resource: wgpu::BindingResource::TextureView(&self.array_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: first_binding + 2,
+                resource: wgpu::BindingResource::Sampler(&self.sampler),
+            },
+        ]
+    }
+
+    /// Recreates the shadow maps if `settings.map_size` changed. Returns true if the texture was replaced.
+    pub fn apply_map_size(&mut self, device: &wgpu::Device) -> bool {
+        if self.texture.width() == self.settings.map_size { return false }
+        (self.texture, self.array_view, self.layer_views) = Self::create_texture(device, self.settings.map_size);
+        true
+    }
+
+    /// Assigns shadow map layers to shadow casting lights and uploads their matrices.
+    /// Returns the layer of every light, in the same order, or -1 for lights without a shadow.
+    pub fn update<'a, I: Iterator<Item = &'a Light>>(&mut self, queue: &wgpu::Queue, lights: I) -> Vec<i32> {
+        let mut raw = ShadowRaw::zeroed();
+        let mut layers = Vec::new();
+        self.casters = 0;
+
+        for light in lights {
+            let view_proj = match light.cast_shadows && self.casters < MAX_SHADOW_CASTERS {
+                true => self.light_view_proj(light),
+                false => None,
+            };
+
+            match view_proj {
+                Some(view_proj) => {
+                    raw.view_proj[self.casters] = view_proj.into();
+                    queue.write_buffer(&self.pass_buf.0, self.pass_offset(self.casters) as wgpu::BufferAddress, bytemuck::cast_slice(&raw.view_proj[self.casters]));
+                    layers.push(self.casters as i32);
+                    self.casters += 1;
+                }
+                None => layers.push(-1),
+            }
+        }
+
+        raw.depth_bias = self.settings.depth_bias;
+        raw.normal_bias = self.settings.normal_bias;
+        raw.pcf_radius = self.settings.pcf_radius;
+        raw.texel_size = 1. / self.settings.map_size as f32;
+        queue.write_buffer(&self.uniform_buf.0, 0, bytemuck::bytes_of(&raw));
+
+        layers
+    }
+
+    fn light_view_proj(&self, light: &Light) -> Option<Mat4<f32>> {
+        let dir = light.direction;
+        // look_to_rh degenerates when the direction is parallel to up
+        let up = if dir.y.abs() > 0.99 { Vec3::unit_z() } else { Vec3::unit_y() };
+
+        match light.kind {
+            LightKind::Directional => {
+                let s = &self.settings;
+                let eye = s.focus - dir * (s.directional_depth * 0.5);
+                let view = Mat4::look_to_rh(eye, dir, up);
+                let proj = Mat4::orthographic(-s.directional_extent, s.directional_extent, -s.directional_extent, s.directional_extent, 0., s.directional_depth);
+                Some(proj * view)
+            }
+            LightKind::Spot => {
+                let view = Mat4::look_to_rh(light.position, dir, up);
+                let proj = Mat4::perspective(light.outer_cone * 2., 1., 0.05, light.range.max(0.1));
+                Some(proj * view)
+            }
+            LightKind::Point => None,
+        }
+    }
+
+    fn create_texture(device: &wgpu::Device, size: u32) -> (wgpu::Texture, wgpu::TextureView, Vec<wgpu::TextureView>) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow Map Texture"),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: MAX_SHADOW_CASTERS as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Texture::DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let array_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Shadow Map Array View"),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+
+        let layer_views = (0..MAX_SHADOW_CASTERS as u32).map(|layer| texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Shadow Map Layer View"),
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            base_array_layer: layer,
+            array_layer_count: Some(1),
+            ..Default::default()
+        })).collect();
+
+        (texture, array_view, layer_views)
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShadowRaw {
+    view_proj: [[[f32; 4]; 4]; MAX_SHADOW_CASTERS],
+    depth_bias: f32,
+    normal_bias: f32,
+    pcf_radius: u32,
+    texel_size: f32,
+}
+
+impl ShadowRaw {
+    fn zeroed() -> Self { bytemuck::Zeroable::zeroed() }
+}
@@ -5,10 +5,14 @@ pub mod err;
 pub mod state;
 pub mod pipeline;
 
+pub mod light;
+pub mod scene;
+
+pub mod postfx;
+pub mod antialias;
+
 mod render;
-mod light;
 mod framebuffer;
-mod postfx;
 
 pub use err::RendererError;
 
@@ -16,26 +20,47 @@ pub use {
     pipeline::Pipeline,
     framebuffer::FRAMEBUFFER_FORMAT,
 };
+pub use framebuffer::grading::{ColorSettings, Tonemapper, Exposure, AutoExposure};
+pub use antialias::AntiAliasing;
+pub use light::occlusion::SsaoSettings;
+
+use std::{path::PathBuf, sync::Arc};
 
 use {
-    crate::instance::Instance,
-    resources::{image::{Texture, CubeMap}, model::Model},
+    crate::{client::PathManager, math::Vec2},
+    antialias::{MsaaTargets, Taa},
+    gpu::shader::ShaderRegistry,
+    light::environment::Environment,
+    resources::{image::{Texture, RawImage, Lut}, model},
+    scene::{Scene, ModelHandle},
     state::State,
-    crate::client::Time,
 };
 
 pub struct Renderer {
     pub state: State,
-    pipeline: pipeline::Pipeline,
-    instances: Vec<Instance>,
-    instance_buffer: wgpu::Buffer,
+    /// Draws Blinn-Phong materials
+    pipeline: Arc<pipeline::Pipeline>,
+    /// Draws metallic-roughness materials
+    pbr_pipeline: Arc<pipeline::Pipeline>,
     depth_texture: Texture,
-    model: Model,
-    light: light::Light,
+    pub scene: Scene,
+    pub lights: light::LightManager,
+    /// Model drawn at the position of every light
+    pub light_marker: Option<ModelHandle>,
     framebuffer: framebuffer::FrameBuffer,
-    postfx: Vec<Box<dyn postfx::PostFx>>,
-    cubemap: CubeMap,
-    sky_pipeline: pipeline::Pipeline,
+    /// Effects applied to the frame before it is presented
+    pub postfx: postfx::PostFxStack,
+    sky_pipeline: Arc<pipeline::Pipeline>,
+    /// Shared by every subsystem, so identical pipelines and shader modules are built once
+    pub pipelines: pipeline::PipelineCache,
+    /// Watches the shader directory for [`Renderer::reload_shaders`]
+    pub shaders: ShaderRegistry,
+    anti_aliasing: AntiAliasing,
+    /// Scene targets while multisampling
+    msaa: Option<MsaaTargets>,
+    taa: Option<Taa>,
+    /// Effect added to [`Renderer::postfx`] for [`AntiAliasing::Fxaa`]
+    fxaa: Option<postfx::FxHandle<postfx::Fxaa>>,
 }
 
 impl Renderer {
@@ -44,10 +69,143 @@ impl Renderer {
             self.state.resize((new_size.width, new_size.height).into());
             self.depth_texture.resize(&self.state.device, new_size.width, new_size.height, "Depth texture");
             self.framebuffer.resize(&self.state.device, new_size.width, new_size.height);
+            self.postfx.resize(&self.state, new_size.width, new_size.height);
+            self.lights.resize(&self.state, new_size.width, new_size.height);
+            if let Some(msaa) = &mut self.msaa {
+                *msaa = MsaaTargets::new(&self.state.device, new_size.width, new_size.height, msaa.sample_count);
+            }
+            if let Some(taa) = &mut self.taa {
+                taa.resize(&self.state, &self.depth_texture, new_size.width, new_size.height);
+            }
+        }
+    }
+
+    pub fn update(&mut self) {
+        self.scene.update(&self.state);
+        self.lights.update_buffer(&self.state);
+        self.postfx.update_buffers(&self.state);
+        if let Some(taa) = &mut self.taa {
+            taa.update_buffer(&self.state);
+        }
+    }
+
+    /// Rebuilds the pipelines whose shader files changed on disk since the last poll of [`Renderer::shaders`].
+    /// Returns the changed files; pipelines held elsewhere are picked up with [`PipelineCache::refresh`](pipeline::PipelineCache::refresh).
+    pub fn reload_shaders(&mut self) -> Vec<PathBuf> {
+        let changed = self.shaders.poll();
+        if changed.is_empty() { return changed }
+
+        self.pipelines.reload(&self.state, &changed);
+        for pipeline in [&mut self.pipeline, &mut self.pbr_pipeline, &mut self.sky_pipeline] {
+            self.pipelines.refresh(pipeline);
         }
+        self.lights.refresh_pipelines(&self.pipelines);
+        self.framebuffer.refresh_pipelines(&self.pipelines);
+        self.postfx.refresh_pipelines(&self.pipelines);
+        if let Some(taa) = &mut self.taa {
+            taa.refresh_pipelines(&self.pipelines);
+        }
+        changed
+    }
+
+    /// Builds an effect and appends it to [`Renderer::postfx`]
+    pub fn add_postfx<T: postfx::Effect>(&mut self, settings: T::Settings) -> Result<postfx::FxHandle<T>, RendererError> {
+        let fx = T::new(&self.state, &mut self.pipelines, &self.framebuffer.target_tex.bg.layout, settings)?;
+        Ok(self.postfx.push(fx))
+    }
+
+    /// Loads a model file and registers it in the scene
+    pub fn load_model(&mut self, file_name: &str, path_m: &PathManager) -> Result<ModelHandle, RendererError> {
+        let model = model::load_model(file_name, &self.state, path_m)?;
+        Ok(self.scene.add_model(model))
+    }
+
+    /// Loads an equirectangular image from the cubemap directory, usually a Radiance `.hdr`,
+    /// as the sky and the ambient light of the scene
+    pub fn load_environment(&mut self, file_name: &str, path_m: &PathManager) -> Result<(), RendererError> {
+        let image = RawImage::import(path_m.cubemap(file_name))?;
+        let environment = Environment::from_equirect(&self.state, &mut self.pipelines, &image, *self.lights.environment().settings(), file_name)?;
+        self.lights.set_environment(&self.state, environment);
+        Ok(())
+    }
+
+    /// Tonemapping, exposure and grading of the final pass
+    pub fn color_settings(&self) -> &ColorSettings { self.framebuffer.settings() }
+
+    pub fn set_color_settings(&mut self, settings: ColorSettings) {
+        self.framebuffer.set_settings(settings);
+    }
+
+    /// Loads a `.cube` 3D LUT from the texture directory to grade every frame with,
+    /// blended in by [`ColorSettings::lut_strength`]
+    pub fn load_lut(&mut self, file_name: &str, path_m: &PathManager) -> Result<(), RendererError> {
+        let lut = Lut::import(path_m.texture(file_name))?;
+        self.framebuffer.set_lut(&self.state, Some(&lut));
+        Ok(())
+    }
+
+    pub fn clear_lut(&mut self) {
+        self.framebuffer.set_lut(&self.state, None);
+    }
+
+    /// Screen space ambient occlusion darkening the ambient light of the scene
+    pub fn ssao_settings(&self) -> &SsaoSettings { self.lights.occlusion_settings() }
+
+    pub fn set_ssao_settings(&mut self, settings: SsaoSettings) {
+        self.lights.set_occlusion_settings(&self.state, settings);
+    }
+
+    pub fn anti_aliasing(&self) -> &AntiAliasing { &self.anti_aliasing }
+
+    /// Switches the anti-aliasing of the scene, building the pipelines and targets it needs
+    pub fn set_anti_aliasing(&mut self, anti_aliasing: AntiAliasing) -> Result<(), RendererError> {
+        let anti_aliasing = match anti_aliasing {
+            AntiAliasing::Msaa(count) => {
+                let supported = self.state.sample_counts.iter().copied().filter(|c| *c <= count).max().unwrap_or(1);
+                if supported != count {
+                    log::warn!("{count}x MSAA isn't supported, using {supported}x");
+                }
+                if supported > 1 { AntiAliasing::Msaa(supported) } else { AntiAliasing::None }
+            }
+            other => other,
+        };
+
+        match anti_aliasing {
+            AntiAliasing::Taa(settings) => match &mut self.taa {
+                Some(taa) => taa.set_settings(settings),
+                None => {
+                    let camera_layout = gpu::uniform::Uniform::<u8>::create_layout(&self.state.device, "Camera", wgpu::ShaderStages::VERTEX_FRAGMENT);
+                    self.taa = Some(Taa::new(&self.state, &mut self.pipelines, &self.framebuffer.target_tex.bg.layout, &camera_layout, &self.depth_texture, settings)?);
+                }
+            },
+            _ => self.taa = None,
+        }
+
+        let fxaa = self.fxaa.take().filter(|h| self.postfx.contains(*h));
+        match (anti_aliasing, fxaa) {
+            (AntiAliasing::Fxaa(settings), Some(handle)) => {
+                self.postfx.update(handle, |s| *s = settings);
+                self.fxaa = Some(handle);
+            }
+            (AntiAliasing::Fxaa(settings), None) => self.fxaa = Some(self.add_postfx(settings)?),
+            (_, Some(handle)) => { self.postfx.remove(handle); }
+            (_, None) => {}
+        }
+
+        let samples = anti_aliasing.sample_count();
+        for pipeline in [&mut self.pipeline, &mut self.pbr_pipeline, &mut self.sky_pipeline] {
+            *pipeline = self.pipelines.with_multisample(&self.state, pipeline, samples);
+        }
+        self.lights.set_sample_count(&self.state, &mut self.pipelines, samples);
+        self.msaa = (samples > 1).then(|| MsaaTargets::new(&self.state.device, self.state.config.width, self.state.config.height, samples));
+
+        self.anti_aliasing = anti_aliasing;
+        Ok(())
     }
 
-    pub fn update(&mut self, dt: &Time) {
-        self.light.update(&self.state.queue, dt);
+    /// Offset of the projection this frame, in normalized device coordinates, for the camera to apply.
+    /// Zero unless temporal anti-aliasing is on.
+    pub fn jitter(&self) -> Vec2<f32> {
+        self.taa.as_ref().map_or_else(Vec2::default, Taa::jitter)
     }
 }
\ No newline at end of file
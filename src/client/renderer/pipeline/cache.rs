@@ -0,0 +1,148 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, sync::Arc};
+
+use crate::client::{
+    renderer::{gpu::shader::{Preprocessor, Shader, ShaderCache, ShaderError, ShaderType}, state::State},
+    PathManager,
+};
+
+use super::{Pipeline, PipelineBuilder, Recipe};
+
+/// Shares the pipelines built from identical requests and the shader modules they are built from,
+/// and rebuilds them when their files change
+pub struct PipelineCache {
+    preprocessor: Preprocessor,
+    pub shaders: ShaderCache,
+    pipelines: HashMap<Key, Arc<Pipeline>>,
+    /// Pipelines replaced by [`PipelineCache::reload`] with their replacement, until nothing else holds them
+    superseded: Vec<(Arc<Pipeline>, Arc<Pipeline>)>,
+}
+
+/// Request without its label, shaders and layouts compared by identity
+#[derive(PartialEq, Eq, Hash)]
+struct Key {
+    vertex: (wgpu::Id<wgpu::ShaderModule>, String),
+    buffers: Vec<(wgpu::BufferAddress, wgpu::VertexStepMode, Vec<wgpu::VertexAttribute>)>,
+    fragment: Option<(wgpu::Id<wgpu::ShaderModule>, String, Vec<Option<wgpu::ColorTargetState>>)>,
+    primitive: wgpu::PrimitiveState,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    multisample: wgpu::MultisampleState,
+    push_constants: Vec<wgpu::PushConstantRange>,
+    layouts: Vec<wgpu::Id<wgpu::BindGroupLayout>>,
+}
+
+impl Key {
+    fn new(bg_layouts: &[&wgpu::BindGroupLayout], recipe: &Recipe) -> Self {
+        Self::with_layouts(bg_layouts.iter().map(|l| l.global_id()).collect(), recipe)
+    }
+
+    fn with_layouts(layouts: Vec<wgpu::Id<wgpu::BindGroupLayout>>, recipe: &Recipe) -> Self {
+        Self {
+            vertex: (recipe.vertex.compiled.module.global_id(), recipe.vertex.entry_point.clone()),
+            buffers: recipe.buffers.clone(),
+            fragment: recipe.fragment.as_ref().map(|(stage, targets)| (stage.compiled.module.global_id(), stage.entry_point.clone(), targets.clone())),
+            primitive: recipe.primitive,
+            depth_stencil: recipe.depth_stencil.clone(),
+            multisample: recipe.multisample,
+            push_constants: recipe.push_constants.clone(),
+            layouts,
+        }
+    }
+}
+
+impl PipelineCache {
+    /// Shaders are read from the shader directory of `path_m`
+    pub fn new(path_m: &PathManager) -> Self {
+        Self { preprocessor: Preprocessor::new(path_m), shaders: ShaderCache::new(), pipelines: HashMap::new(), superseded: Vec::new() }
+    }
+
+    pub fn import_vert<'a, T: AsRef<Path>>(&mut self, state: &State, entry: &'a str, path: T, label: &str) -> Result<Shader<'a>, ShaderError> {
+        self.shaders.import(state, &self.preprocessor, ShaderType::Vertex(entry), path, label)
+    }
+
+    pub fn import_frag<'a, T: AsRef<Path>>(&mut self, state: &State, entry: &'a str, path: T, label: &str) -> Result<Shader<'a>, ShaderError> {
+        self.shaders.import(state, &self.preprocessor, ShaderType::Fragment(entry), path, label)
+    }
+
+    pub fn import_combined<'a, T: AsRef<Path>>(&mut self, state: &State, entry: (&'a str, &'a str), path: T, label: &str) -> Result<Shader<'a>, ShaderError> {
+        self.shaders.import(state, &self.preprocessor, ShaderType::VertexFragment(entry.0, entry.1), path, label)
+    }
+
+    /// See [`Shader::compute_pipeline`]
+    pub fn import_compute<'a, T: AsRef<Path>>(&mut self, state: &State, entry: &'a str, path: T, label: &str) -> Result<Shader<'a>, ShaderError> {
+        self.shaders.import(state, &self.preprocessor, ShaderType::Compute(entry), path, label)
+    }
+
+    /// Pipeline built from `builder`, shared with every identical request
+    pub fn get(&mut self, state: &State, builder: PipelineBuilder) -> Result<Arc<Pipeline>, ShaderError> {
+        let (bg_layouts, recipe) = builder.recipe(state.device.features())?;
+        let key = Key::new(&bg_layouts, &recipe);
+        if let Some(pipeline) = self.pipelines.get(&key) {
+            return Ok(pipeline.clone())
+        }
+
+        let pipeline = Arc::new(Pipeline::create(state, &bg_layouts, recipe));
+        self.pipelines.insert(key, pipeline.clone());
+        Ok(pipeline)
+    }
+
+    /// Variant of `pipeline` drawing to targets with `count` samples, shared like the ones from [`PipelineCache::get`].
+    /// Pipelines the cache didn't build get a variant of their own.
+    pub fn with_multisample(&mut self, state: &State, pipeline: &Arc<Pipeline>, count: u32) -> Arc<Pipeline> {
+        if pipeline.sample_count() == count { return pipeline.clone() }
+        let Some(layouts) = self.pipelines.iter().find(|(_, p)| Arc::ptr_eq(p, pipeline)).map(|(key, _)| key.layouts.clone()) else {
+            return Arc::new(pipeline.with_multisample(state, count))
+        };
+
+        let mut recipe = pipeline.recipe.clone();
+        recipe.multisample.count = count;
+        let key = Key::with_layouts(layouts, &recipe);
+        self.pipelines.entry(key).or_insert_with(|| Arc::new(pipeline.with_multisample(state, count))).clone()
+    }
+
+    /// Recompiles the shaders reading any of `changed` and rebuilds the pipelines using them.
+    /// Failures are logged and keep the previous shader or pipeline.
+    /// Holders pick up the new pipelines with [`PipelineCache::refresh`].
+    pub fn reload(&mut self, state: &State, changed: &[PathBuf]) {
+        if changed.is_empty() { return }
+        self.superseded.retain(|(old, _)| Arc::strong_count(old) > 1);
+        self.shaders.reload(state, changed);
+
+        let shaders = &self.shaders;
+        let mut pipelines = HashMap::with_capacity(self.pipelines.len());
+        for (key, pipeline) in self.pipelines.drain() {
+            let vertex = shaders.current(&pipeline.recipe.vertex.compiled);
+            let fragment = pipeline.recipe.fragment.as_ref().map(|(stage, _)| shaders.current(&stage.compiled));
+
+            let unchanged = Arc::ptr_eq(&vertex.module, &pipeline.recipe.vertex.compiled.module)
+                && pipeline.recipe.fragment.as_ref().zip(fragment.as_ref()).is_none_or(|((stage, _), f)| Arc::ptr_eq(&f.module, &stage.compiled.module));
+            if unchanged {
+                pipelines.insert(key, pipeline);
+                continue
+            }
+
+            let label = pipeline.recipe.label.as_deref().unwrap_or("unnamed");
+            match pipeline.with_shaders(state, vertex, fragment) {
+                Ok(rebuilt) => {
+                    log::info!("Reloaded pipeline '{label}'");
+                    let rebuilt = Arc::new(rebuilt);
+                    // Requests made with the new modules find it too
+                    let key = Key::with_layouts(key.layouts, &rebuilt.recipe);
+                    self.superseded.push((pipeline, rebuilt.clone()));
+                    pipelines.insert(key, rebuilt);
+                }
+                Err(e) => {
+                    log::error!("Keeping the previous pipeline '{label}': {e}");
+                    pipelines.insert(key, pipeline);
+                }
+            }
+        }
+        self.pipelines = pipelines;
+    }
+
+    /// Replaces a pipeline rebuilt by [`PipelineCache::reload`] with its latest version
+    pub fn refresh(&self, pipeline: &mut Arc<Pipeline>) {
+        while let Some((_, rebuilt)) = self.superseded.iter().find(|(old, _)| Arc::ptr_eq(old, pipeline)) {
+            *pipeline = rebuilt.clone();
+        }
+    }
+}
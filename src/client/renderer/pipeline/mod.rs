@@ -0,0 +1,406 @@
+mod cache;
+
+pub use cache::PipelineCache;
+
+use std::{path::PathBuf, sync::Arc};
+
+use crate::client::renderer::{
+    resources::image,
+    gpu::{bind_group::Layout, shader::{Compiled, FragmentStage, Shader, ShaderError, VertexStage}},
+};
+
+use super::state::State;
+
+pub struct Pipeline {
+    pub pipeline: wgpu::RenderPipeline,
+    layout: Arc<wgpu::PipelineLayout>,
+    recipe: Recipe,
+}
+
+impl Pipeline {
+    /// Shorthand for a [`PipelineBuilder`] culling back faces, testing depth with `LessEqual` when enabled
+    pub fn new(state: &State, bg_layouts: &[&wgpu::BindGroupLayout], vertex: VertexStage, fragment: Option<FragmentStage>, depth_enabled: bool) -> Result<Self, ShaderError> {
+        let builder = PipelineBuilder::new(vertex, fragment)
+            .with_label("Render Pipeline")
+            .with_bg_layouts(bg_layouts);
+        let builder = if depth_enabled {
+            builder.enable_depth().with_depth_compare_function(wgpu::CompareFunction::LessEqual)
+        } else { builder };
+        builder.construct(state)
+    }
+
+    fn create(state: &State, bg_layouts: &[&wgpu::BindGroupLayout], recipe: Recipe) -> Self {
+        let layout = state.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(recipe.label.as_deref().unwrap_or("Render Pipeline Layout")),
+            bind_group_layouts: bg_layouts,
+            push_constant_ranges: &recipe.push_constants,
+        });
+        let pipeline = recipe.create(&state.device, &layout);
+        Self { pipeline, layout: Arc::new(layout), recipe }
+    }
+
+    /// Whether one of its shaders read any of `changed`,
+    /// canonical paths as returned by [`ShaderRegistry::poll`](super::gpu::shader::ShaderRegistry::poll)
+    pub fn depends_on(&self, changed: &[PathBuf]) -> bool {
+        self.recipe.stages().any(|s| s.compiled.origin.as_ref().is_some_and(|o| o.depends_on(changed)))
+    }
+
+    /// Recompiles the shaders and recreates the pipeline if they read any of `changed`.
+    /// When that fails the previous pipeline is kept and the error logged; a failed pipeline retries on every change.
+    /// Pipelines shared through a [`PipelineCache`] are reloaded by it instead.
+    pub fn reload(&mut self, state: &State, changed: &[PathBuf]) {
+        if changed.is_empty() || !(self.recipe.failed || self.depends_on(changed)) { return }
+
+        let label = self.recipe.label.clone().unwrap_or_else(|| "unnamed".to_owned());
+        let compile = |stage: &StageRecipe| match &stage.compiled.origin {
+            Some(origin) if self.recipe.failed || origin.depends_on(changed) => Shader::compile(state, &label, &origin.process()?),
+            _ => Ok(stage.compiled.clone()),
+        };
+
+        let rebuilt = compile(&self.recipe.vertex).and_then(|vertex| {
+            let fragment = match &self.recipe.fragment {
+                // Files holding both stages are compiled once
+                Some((stage, _)) if Arc::ptr_eq(&stage.compiled.module, &self.recipe.vertex.compiled.module) => Some(vertex.clone()),
+                Some((stage, _)) => Some(compile(stage)?),
+                None => None,
+            };
+            self.with_shaders(state, vertex, fragment)
+        });
+
+        match rebuilt {
+            Ok(pipeline) => {
+                *self = pipeline;
+                log::info!("Reloaded pipeline '{label}'");
+            }
+            Err(e) => {
+                self.recipe.failed = true;
+                log::error!("Keeping the previous pipeline '{label}': {e}");
+            }
+        }
+    }
+
+    /// Same pipeline drawing to targets with `count` samples, prefer [`PipelineCache::with_multisample`]
+    pub fn with_multisample(&self, state: &State, count: u32) -> Self {
+        let mut recipe = self.recipe.clone();
+        recipe.multisample.count = count;
+        let pipeline = recipe.create(&state.device, &self.layout);
+        Self { pipeline, layout: self.layout.clone(), recipe }
+    }
+
+    pub fn sample_count(&self) -> u32 { self.recipe.multisample.count }
+
+    /// Same pipeline built from other modules, checked against the layouts when they are known
+    fn with_shaders(&self, state: &State, vertex: Compiled, fragment: Option<Compiled>) -> Result<Self, ShaderError> {
+        let mut recipe = self.recipe.clone();
+        let layouts: Option<Vec<&[wgpu::BindGroupLayoutEntry]>> = recipe.layouts.as_ref().map(|l| l.iter().map(Vec::as_slice).collect());
+
+        vertex.check(&recipe.vertex.entry_point, wgpu::ShaderStages::VERTEX, layouts.as_deref())?;
+        recipe.vertex.compiled = vertex;
+        if let (Some((stage, _)), Some(fragment)) = (&mut recipe.fragment, fragment) {
+            fragment.check(&stage.entry_point, wgpu::ShaderStages::FRAGMENT, layouts.as_deref())?;
+            stage.compiled = fragment;
+        }
+        recipe.failed = false;
+
+        state.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipeline = recipe.create(&state.device, &self.layout);
+        if let Some(e) = pollster::block_on(state.device.pop_error_scope()) {
+            return Err(ShaderError::Rejected(recipe.vertex.compiled.path.clone(), e.to_string()))
+        }
+        Ok(Self { pipeline, layout: self.layout.clone(), recipe })
+    }
+}
+
+/// Everything needed to create the pipeline again from recompiled shaders
+#[derive(Clone)]
+struct Recipe {
+    label: Option<String>,
+    vertex: StageRecipe,
+    /// Stride, step mode and attributes of each vertex buffer
+    buffers: Vec<(wgpu::BufferAddress, wgpu::VertexStepMode, Vec<wgpu::VertexAttribute>)>,
+    fragment: Option<(StageRecipe, Vec<Option<wgpu::ColorTargetState>>)>,
+    primitive: wgpu::PrimitiveState,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    multisample: wgpu::MultisampleState,
+    push_constants: Vec<wgpu::PushConstantRange>,
+    /// Entries of each bind group layout, when known, to check reloaded shaders against
+    layouts: Option<Vec<Vec<wgpu::BindGroupLayoutEntry>>>,
+    /// Whether the last reload failed
+    failed: bool,
+}
+
+#[derive(Clone)]
+struct StageRecipe {
+    compiled: Compiled,
+    entry_point: String,
+}
+
+impl Recipe {
+    fn new(
+        label: Option<&str>,
+        vertex: &VertexStage,
+        fragment: Option<&FragmentStage>,
+        primitive: wgpu::PrimitiveState,
+        depth_stencil: Option<wgpu::DepthStencilState>,
+        multisample: wgpu::MultisampleState,
+    ) -> Self {
+        Self {
+            label: label.map(str::to_owned),
+            vertex: StageRecipe { compiled: vertex.shader.compiled().clone(), entry_point: vertex.entry_point.to_owned() },
+            buffers: vertex.buffers.iter().map(|b| (b.array_stride, b.step_mode, b.attributes.to_vec())).collect(),
+            fragment: fragment.map(|f| (
+                StageRecipe { compiled: f.shader.compiled().clone(), entry_point: f.entry_point.to_owned() },
+                f.targets.to_vec(),
+            )),
+            primitive,
+            depth_stencil,
+            multisample,
+            push_constants: Vec::new(),
+            layouts: None,
+            failed: false,
+        }
+    }
+
+    fn stages(&self) -> impl Iterator<Item = &StageRecipe> {
+        std::iter::once(&self.vertex).chain(self.fragment.as_ref().map(|f| &f.0))
+    }
+
+    fn create(&self, device: &wgpu::Device, layout: &wgpu::PipelineLayout) -> wgpu::RenderPipeline {
+        let buffers: Vec<_> = self.buffers.iter().map(|(array_stride, step_mode, attributes)| wgpu::VertexBufferLayout {
+            array_stride: *array_stride,
+            step_mode: *step_mode,
+            attributes,
+        }).collect();
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: self.label.as_deref(),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: &self.vertex.compiled.module,
+                entry_point: &self.vertex.entry_point,
+                buffers: &buffers,
+            },
+            fragment: self.fragment.as_ref().map(|(stage, targets)| wgpu::FragmentState {
+                module: &stage.compiled.module,
+                entry_point: &stage.entry_point,
+                targets,
+            }),
+            primitive: self.primitive,
+            depth_stencil: self.depth_stencil.clone(),
+            multisample: self.multisample,
+            multiview: None,
+        })
+    }
+}
+
+/// Blend states for [`PipelineBuilder::with_blend`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Blend {
+    /// Overwrites the target
+    Replace,
+    /// Mixes with the target by the source alpha
+    Alpha,
+    /// Like `Alpha` for colors already multiplied by their alpha
+    Premultiplied,
+    /// Adds to the target, for light accumulation and glow
+    Additive,
+    /// Multiplies the target, for darkening like decals
+    Multiply,
+}
+
+impl Blend {
+    pub fn state(self) -> wgpu::BlendState {
+        use wgpu::{BlendComponent, BlendFactor as F, BlendOperation::Add};
+        let component = |src_factor, dst_factor| BlendComponent { src_factor, dst_factor, operation: Add };
+        match self {
+            Blend::Replace => wgpu::BlendState::REPLACE,
+            Blend::Alpha => wgpu::BlendState::ALPHA_BLENDING,
+            Blend::Premultiplied => wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            Blend::Additive => wgpu::BlendState { color: component(F::One, F::One), alpha: component(F::One, F::One) },
+            Blend::Multiply => wgpu::BlendState { color: component(F::Dst, F::Zero), alpha: component(F::DstAlpha, F::Zero) },
+        }
+    }
+}
+
+pub struct PipelineBuilder<'a> {
+    label: Option<&'a str>,
+    bg_layouts: Vec<&'a wgpu::BindGroupLayout>,
+    /// Set by [`PipelineBuilder::with_layouts`], to check the shaders against
+    layout_entries: Option<Vec<&'a [wgpu::BindGroupLayoutEntry]>>,
+    push_constants: Vec<wgpu::PushConstantRange>,
+    vertex_state: Option<VertexStage<'a>>,
+    fragment_state: Option<FragmentStage<'a>>,
+    /// Overrides the blend of the fragment targets, by index
+    blends: Vec<(usize, Blend)>,
+    depth: bool,
+    depth_compare: wgpu::CompareFunction,
+    depth_write: bool,
+    depth_format: wgpu::TextureFormat,
+    depth_bias: wgpu::DepthBiasState,
+    stencil: wgpu::StencilState,
+    multisample: wgpu::MultisampleState,
+    front_face: wgpu::FrontFace,
+    cull: Option<wgpu::Face>,
+    polygon_mode: wgpu::PolygonMode,
+    topology: wgpu::PrimitiveTopology,
+}
+
+impl <'a> PipelineBuilder<'a> {
+    pub fn new(vertex_state: VertexStage<'a>, fragment_state: Option<FragmentStage<'a>>) -> PipelineBuilder<'a> {
+        Self {
+            vertex_state: Some(vertex_state),
+            fragment_state,
+            ..Default::default()
+        }
+    }
+
+    /// Names the pipeline and its layout in graphics debuggers and validation errors
+    pub fn with_label(mut self, label: &'a str) -> Self {
+        self.label = Some(label); self
+    }
+
+    pub fn with_topology(mut self, t: wgpu::PrimitiveTopology) -> Self {
+        self.topology = t; self
+    }
+
+    /// Layouts the shaders can't be checked against, prefer [`PipelineBuilder::with_layouts`]
+    pub fn with_bg_layouts(mut self, layouts: &[&'a wgpu::BindGroupLayout]) -> Self {
+        self.bg_layouts = layouts.to_vec();
+        self.layout_entries = None;
+        self
+    }
+
+    /// Bind group layouts, indexed by group, which `construct` checks the shaders against
+    pub fn with_layouts(mut self, layouts: &[&'a Layout]) -> Self {
+        self.bg_layouts = layouts.iter().map(|l| &l.0).collect();
+        self.layout_entries = Some(layouts.iter().map(|l| l.entries()).collect());
+        self
+    }
+
+    /// Needs the `PUSH_CONSTANTS` feature on the device, `construct` fails without it
+    pub fn with_push_constants(mut self, ranges: &[wgpu::PushConstantRange]) -> Self {
+        self.push_constants = ranges.to_vec(); self
+    }
+
+    /// Replaces the blend of the fragment target at `target`
+    pub fn with_blend(mut self, target: usize, blend: Blend) -> Self {
+        self.blends.retain(|(t, _)| *t != target);
+        self.blends.push((target, blend));
+        self
+    }
+
+    pub fn enable_depth(mut self) -> Self { self.depth = true; self }
+
+    pub fn with_depth_write(mut self, depth_write: bool) -> Self { self.depth_write = depth_write; self }
+
+    pub fn with_depth_compare_function(mut self, f: wgpu::CompareFunction) -> Self { self.depth_compare = f; self }
+
+    /// Format of the depth-stencil attachment, which needs a stencil aspect for [`PipelineBuilder::with_stencil`]
+    pub fn with_depth_format(mut self, format: wgpu::TextureFormat) -> Self { self.depth_format = format; self }
+
+    /// Offsets the depth written, against acne in shadow maps and z-fighting of decals
+    pub fn with_depth_bias(mut self, bias: wgpu::DepthBiasState) -> Self { self.depth_bias = bias; self }
+
+    /// Uses the depth-stencil attachment even without [`PipelineBuilder::enable_depth`].
+    /// `construct` fails if the depth format has no stencil aspect.
+    pub fn with_stencil(mut self, stencil: wgpu::StencilState) -> Self { self.stencil = stencil; self }
+
+    /// Sample count of the targets, 1 to disable multisampling
+    pub fn with_multisample(mut self, count: u32) -> Self { self.multisample.count = count; self }
+
+    pub fn with_alpha_to_coverage(mut self, enabled: bool) -> Self { self.multisample.alpha_to_coverage_enabled = enabled; self }
+
+    pub fn with_front_face(mut self, f: wgpu::FrontFace) -> Self { self.front_face = f; self }
+
+    /// Faces to discard, `None` to draw both
+    pub fn with_cull(mut self, cull: Option<wgpu::Face>) -> Self { self.cull = cull; self }
+
+    pub fn with_polygon_mode(mut self, m: wgpu::PolygonMode) -> Self { self.polygon_mode = m; self }
+
+    /// Checks the entry points, and the bindings against layouts given to [`PipelineBuilder::with_layouts`]
+    pub fn construct(self, state: &State) -> Result<Pipeline, ShaderError> {
+        let (bg_layouts, recipe) = self.recipe(state.device.features())?;
+        Ok(Pipeline::create(state, &bg_layouts, recipe))
+    }
+
+    /// Fails on state the device with `features` or the depth format can't support, which would fail validation later
+    fn recipe(self, features: wgpu::Features) -> Result<(Vec<&'a wgpu::BindGroupLayout>, Recipe), ShaderError> {
+        let unsupported = |msg: &str| ShaderError::Unsupported(self.label.unwrap_or("unnamed").to_owned(), msg.to_owned());
+        if !self.push_constants.is_empty() && !features.contains(wgpu::Features::PUSH_CONSTANTS) {
+            return Err(unsupported("Push constants aren't supported by the device"))
+        }
+        if self.stencil.is_enabled() && !self.depth_format.has_stencil_aspect() {
+            return Err(unsupported(&format!("Stencil state on {:?}, which has no stencil", self.depth_format)))
+        }
+
+        let vertex = self.vertex_state.unwrap();
+        let layouts = self.layout_entries.as_deref();
+        vertex.shader.check(vertex.entry_point, wgpu::ShaderStages::VERTEX, layouts)?;
+        if let Some(fragment) = &self.fragment_state {
+            fragment.shader.check(fragment.entry_point, wgpu::ShaderStages::FRAGMENT, layouts)?;
+        }
+
+        let depth_stencil = (self.depth || self.stencil.is_enabled()).then_some(wgpu::DepthStencilState {
+            format: self.depth_format,
+            depth_write_enabled: self.depth && self.depth_write,
+            depth_compare: if self.depth { self.depth_compare } else { wgpu::CompareFunction::Always },
+            stencil: self.stencil,
+            bias: self.depth_bias,
+        });
+
+        let mut recipe = Recipe::new(
+            self.label,
+            &vertex,
+            self.fragment_state.as_ref(),
+            wgpu::PrimitiveState {
+                topology: self.topology,
+                strip_index_format: None,
+                front_face: self.front_face,
+                cull_mode: self.cull,
+                polygon_mode: self.polygon_mode,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil,
+            self.multisample,
+        );
+        recipe.layouts = layouts.map(|l| l.iter().map(|e| e.to_vec()).collect());
+        recipe.push_constants = self.push_constants;
+
+        if let Some((_, targets)) = &mut recipe.fragment {
+            for (target, blend) in self.blends {
+                match targets.get_mut(target) {
+                    Some(Some(t)) => t.blend = Some(blend.state()),
+                    _ => log::error!("No color target {target} to blend in pipeline '{}'", self.label.unwrap_or("unnamed")),
+                }
+            }
+        }
+
+        Ok((self.bg_layouts, recipe))
+    }
+}
+
+impl <'a> std::default::Default for PipelineBuilder<'a> {
+    fn default() -> Self {
+        Self {
+            label: None,
+            bg_layouts: Vec::new(),
+            layout_entries: None,
+            push_constants: Vec::new(),
+            vertex_state: None,
+            fragment_state: None,
+            blends: Vec::new(),
+            depth: false,
+            depth_compare: wgpu::CompareFunction::Less,
+            depth_write: true,
+            depth_format: image::Texture::DEPTH_STENCIL_FORMAT,
+            depth_bias: wgpu::DepthBiasState::default(),
+            stencil: wgpu::StencilState::default(),
+            multisample: wgpu::MultisampleState { count: 1, mask: !0, alpha_to_coverage_enabled: false },
+            front_face: wgpu::FrontFace::Ccw,
+            cull: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std::sync::Arc;
+
+use crate::client::renderer::state::{RenderState, State};
+
+use {
+    crate::client::renderer::{
+        pipeline::{Blend, Pipeline, PipelineBuilder, PipelineCache},
+        gpu::{
+            bind_group::Layout,
+            uniform::Uniform,
+            err::GpuResourceError,
+        },
+        framebuffer::FRAMEBUFFER_FORMAT,
+    },
+    super::{Effect, PostFx},
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct BloomSettings {
+    /// Brightness above which pixels bloom, in the linear HDR range of the frame
+    pub threshold: f32,
+    /// Fraction of the threshold below it where pixels start blooming gradually
+    pub knee: f32,
+    /// Amount of the blurred chain added to the frame
+    pub intensity: f32,
+    /// Spread of the upsampling filter, in texels of each level
+    pub radius: f32,
+    /// Number of levels in the chain, the first at half the resolution of the frame.
+    /// More levels spread the glow further.
+    pub levels: u32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self { threshold: 1.0, knee: 0.5, intensity: 0.1, radius: 1.0, levels: 6 }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BloomRaw {
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+    radius: f32,
+}
+
+impl BloomSettings {
+    fn raw(&self) -> BloomRaw {
+        BloomRaw {
+            threshold: self.threshold,
+            knee: self.threshold * self.knee.clamp(0.0, 1.0),
+            intensity: self.intensity,
+            radius: self.radius,
+        }
+    }
+}
+
+/// Level of the chain, half the size of the one above, drawn to through `view` and sampled through `bg`
+struct Mip {
+    view: wgpu::TextureView,
+    bg: wgpu::BindGroup,
+}
+
+/// Glow around the parts of the frame brighter than a threshold
+pub struct Bloom {
+    prefilter: Arc<Pipeline>,
+    downsample: Arc<Pipeline>,
+    upsample: Arc<Pipeline>,
+    composite: Arc<Pipeline>,
+    params: Uniform<BloomRaw>,
+    /// Same entries as the framebuffer layout, so levels bind where the frame does
+    mip_layout: Layout,
+    sampler: wgpu::Sampler,
+    mips: Vec<Mip>,
+    size: (u32, u32),
+    settings: BloomSettings,
+    dirty: bool,
+}
+
+impl Effect for Bloom {
+    type Settings = BloomSettings;
+
+    fn new(state: &State, pipelines: &mut PipelineCache, framebuffer_layout: &Layout, settings: BloomSettings) -> Result<Self, GpuResourceError> {
+        let shader = pipelines.import_combined(state, ("vs_main", "fs_composite"), "bloom.wgsl", "Bloom shader")?;
+
+        let params = Uniform::new(&state.device, settings.raw(), "Bloom", wgpu::ShaderStages::FRAGMENT);
+        let mip_layout = Layout::new(&state.device, framebuffer_layout.entries(), "Bloom Mip Bind Group Layout");
+
+        let targets = [Some(wgpu::ColorTargetState {
+            format: FRAMEBUFFER_FORMAT,
+            blend: Some(wgpu::BlendState::REPLACE),
+            write_mask: wgpu::ColorWrites::ALL,
+        })];
+        let mut pipeline = |entry: &str, label: &str, blend: Blend, layouts: &[&Layout]| pipelines.get(state,
+            PipelineBuilder::new(shader.vs_state(&[]), Some(shader.fs_entry_state(entry, &targets)))
+                .with_label(label)
+                .with_blend(0, blend)
+                .with_layouts(layouts)
+        );
+        let prefilter = pipeline("fs_prefilter", "Bloom Prefilter Pipeline", Blend::Replace, &[framebuffer_layout, &params.bg.layout])?;
+        let downsample = pipeline("fs_downsample", "Bloom Downsample Pipeline", Blend::Replace, &[&mip_layout])?;
+        let upsample = pipeline("fs_upsample", "Bloom Upsample Pipeline", Blend::Additive, &[&mip_layout, &params.bg.layout])?;
+        let composite = pipeline("fs_composite", "Bloom Composite Pipeline", Blend::Replace, &[framebuffer_layout, &params.bg.layout, &mip_layout])?;
+
+        let sampler = state.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Bloom Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let mut bloom = Self {
+            prefilter, downsample, upsample, composite,
+            params, mip_layout, sampler,
+            mips: Vec::new(),
+            size: (state.config.width, state.config.height),
+            settings,
+            dirty: false,
+        };
+        bloom.create_mips(&state.device);
+        Ok(bloom)
+    }
+
+    fn settings(&self) -> &BloomSettings { &self.settings }
+
+    fn set_settings(&mut self, settings: BloomSettings) {
+        self.settings = settings;
+        self.dirty = true;
+    }
+}
+
+impl Bloom {
+    /// Levels the chain can hold at the current size, down to a single texel
+    fn level_count(&self) -> u32 {
+        let smallest = (self.size.0 / 2).min(self.size.1 / 2).max(1);
+        self.settings.levels.clamp(1, smallest.ilog2() + 1)
+    }
+
+    /// One texture per level rather than mips of a single one, which the GL backend can't sample level by level
+    fn create_mips(&mut self, device: &wgpu::Device) {
+        let (width, height) = ((self.size.0 / 2).max(1), (self.size.1 / 2).max(1));
+        self.mips = (0..self.level_count()).map(|level| {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Bloom Texture"),
+                size: wgpu::Extent3d {
+                    width: (width >> level).max(1),
+                    height: (height >> level).max(1),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: FRAMEBUFFER_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Bloom Bind Group"),
+                layout: &self.mip_layout.0,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&view) },
+                    wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&self.sampler) },
+                ],
+            });
+            Mip { view, bg }
+        }).collect();
+    }
+}
+
+impl PostFx for Bloom {
+    fn label(&self) -> &'static str {"Bloom"}
+
+    fn render(&self, render_state: &mut RenderState, input: &wgpu::BindGroup, output: &wgpu::TextureView) {
+        // The top level is only bound to composite, other passes draw to it
+        let mut pass = |label, pipeline: &Pipeline, target, source: &wgpu::BindGroup, clear, bloom: Option<&wgpu::BindGroup>| {
+            let mut render_pass = render_state.render_pass(Some(label), Some(target), clear, None);
+            render_pass.set_pipeline(&pipeline.pipeline);
+            render_pass.set_bind_group(0, source, &[]);
+            self.params.set(1, &mut render_pass);
+            if let Some(bloom) = bloom { render_pass.set_bind_group(2, bloom, &[]) }
+            render_pass.draw(0..3, 0..1);
+        };
+        let clear = Some(wgpu::Color::BLACK);
+
+        pass("Bloom Prefilter Pass", &self.prefilter, &self.mips[0].view, input, clear, None);
+        for level in 1..self.mips.len() {
+            pass("Bloom Downsample Pass", &self.downsample, &self.mips[level].view, &self.mips[level - 1].bg, clear, None);
+        }
+        // Every level adds itself, blurred, onto the downsampled level above
+        for level in (1..self.mips.len()).rev() {
+            pass("Bloom Upsample Pass", &self.upsample, &self.mips[level - 1].view, &self.mips[level].bg, None, None);
+        }
+        pass("Bloom Composite Pass", &self.composite, output, input, None, Some(&self.mips[0].bg));
+    }
+
+    fn resize(&mut self, state: &State, width: u32, height: u32) {
+        self.size = (width, height);
+        self.create_mips(&state.device);
+    }
+
+    fn refresh_pipelines(&mut self, pipelines: &PipelineCache) {
+        for pipeline in [&mut self.prefilter, &mut self.downsample, &mut self.upsample, &mut self.composite] {
+            pipelines.refresh(pipeline);
+        }
+    }
+
+    fn update_buffer(&mut self, state: &State) {
+        if !self.dirty { return }
+        self.dirty = false;
+        self.params.data = self.settings.raw();
+        self.params.update(&state.queue);
+        if self.mips.len() as u32 != self.level_count() {
+            self.create_mips(&state.device);
+        }
+    }
+}
@@ -1,45 +1,65 @@
-use crate::client::renderer::{state::State};
+use std::sync::Arc;
+
+use crate::client::renderer::state::State;
 
 use {
     crate::client::renderer::{
-        pipeline::Pipeline,
+        pipeline::{Pipeline, PipelineBuilder, PipelineCache},
         gpu::{
+            bind_group::Layout,
             uniform::Uniform,
-            shader::Shader,
             err::GpuResourceError,
         },
         framebuffer::FRAMEBUFFER_FORMAT,
     },
-    super::PostFx,
+    super::{Effect, PostFx},
 };
 
+#[derive(Clone, Copy, Debug)]
+pub struct BoxBlurSettings {
+    /// Pixels averaged on each side of the sampled one
+    pub radius: u32,
+}
+
+impl Default for BoxBlurSettings {
+    fn default() -> Self { Self { radius: 3 } }
+}
+
 pub struct BoxBlur {
-    pipeline: Pipeline,
+    pipeline: Arc<Pipeline>,
     radius: Uniform<u32>,
+    settings: BoxBlurSettings,
+    dirty: bool,
 }
 
-impl BoxBlur {
-    pub fn new(state: &State, framebuffer_layout: &wgpu::BindGroupLayout, radius: u32) -> Result<Self, GpuResourceError> {
-        let vpath = std::path::Path::new("assets/shaders/framebuffer_vertex.wgsl");
-        let fpath = std::path::Path::new("assets/shaders/boxblur_fragment.wgsl");
-        let vshader = Shader::import_vert(state, "vs_main", vpath, "Framebuffer vertex shader")?;
-        let fshader = Shader::import_frag(state, "fs_main", fpath, "Chromatic aberration fragment shader")?;
+impl Effect for BoxBlur {
+    type Settings = BoxBlurSettings;
+
+    fn new(state: &State, pipelines: &mut PipelineCache, framebuffer_layout: &Layout, settings: BoxBlurSettings) -> Result<Self, GpuResourceError> {
+        let vpath = "framebuffer_vertex.wgsl";
+        let fpath = "boxblur_fragment.wgsl";
+        let vshader = pipelines.import_vert(state, "vs_main", vpath, "Framebuffer vertex shader")?;
+        let fshader = pipelines.import_frag(state, "fs_main", fpath, "Box blur fragment shader")?;
 
-        let radius = Uniform::new(&state.device, radius, "Box Blur", wgpu::ShaderStages::FRAGMENT);
+        let radius = Uniform::new(&state.device, settings.radius, "Box Blur", wgpu::ShaderStages::FRAGMENT);
 
-        let pipeline = Pipeline::new(
-            state,
-            &[framebuffer_layout, radius.bg.layout()],
+        let pipeline = pipelines.get(state, PipelineBuilder::new(
             vshader.vs_state(&[]),
             Some(fshader.fs_state(&[Some(wgpu::ColorTargetState {
                 format: FRAMEBUFFER_FORMAT,
                 blend: Some(wgpu::BlendState::REPLACE),
                 write_mask: wgpu::ColorWrites::ALL,
             })])),
-            false,
-        );
+        ).with_label("Box Blur Pipeline").with_layouts(&[framebuffer_layout, &radius.bg.layout]))?;
 
-        Ok(Self {pipeline, radius})
+        Ok(Self {pipeline, radius, settings, dirty: false})
+    }
+
+    fn settings(&self) -> &BoxBlurSettings { &self.settings }
+
+    fn set_settings(&mut self, settings: BoxBlurSettings) {
+        self.settings = settings;
+        self.dirty = true;
     }
 }
 
@@ -49,4 +69,13 @@ impl PostFx for BoxBlur {
         self.radius.set(1, render_pass);
     }
     fn label(&self) -> &'static str {"Box Blur"}
-}
\ No newline at end of file
+    fn refresh_pipelines(&mut self, pipelines: &PipelineCache) {
+        pipelines.refresh(&mut self.pipeline);
+    }
+    fn update_buffer(&mut self, state: &State) {
+        if !self.dirty { return }
+        self.dirty = false;
+        self.radius.data = self.settings.radius;
+        self.radius.update(&state.queue);
+    }
+}
@@ -1,46 +1,73 @@
-use crate::client::renderer::{state::State};
+use std::sync::Arc;
+
+use crate::client::renderer::state::State;
 
 use {
     crate::client::renderer::{
-        pipeline::Pipeline,
+        pipeline::{Pipeline, PipelineBuilder, PipelineCache},
         gpu::{
+            bind_group::Layout,
             uniform::Uniform,
-            shader::Shader,
             err::GpuResourceError,
         },
         framebuffer::FRAMEBUFFER_FORMAT,
     },
-    super::PostFx,
+    super::{Effect, PostFx},
 };
 
+#[derive(Clone, Copy, Debug)]
+pub struct ChromaticAberrationSettings {
+    /// Distance the red and blue channels are shifted by, in UV units
+    pub offset: f32,
+}
+
+impl Default for ChromaticAberrationSettings {
+    fn default() -> Self { Self { offset: 0.005 } }
+}
+
+impl ChromaticAberrationSettings {
+    fn offsets(self) -> [f32; 6] {
+        let off = self.offset;
+        [-off, -off, 0.0, 0.0, off, off]
+    }
+}
+
 pub struct ChromaticAberration {
-    pipeline: Pipeline,
+    pipeline: Arc<Pipeline>,
     offsets: Uniform<[f32; 6]>,
+    settings: ChromaticAberrationSettings,
+    dirty: bool,
 }
 
-impl ChromaticAberration {
-    pub fn new(state: &State, framebuffer_layout: &wgpu::BindGroupLayout, off: f32) -> Result<Self, GpuResourceError> {
-        let vpath = std::path::Path::new("assets/shaders/framebuffer_vertex.wgsl");
-        let fpath = std::path::Path::new("assets/shaders/chrab_fragment.wgsl");
+impl Effect for ChromaticAberration {
+    type Settings = ChromaticAberrationSettings;
 
-        let vshader = Shader::import_vert(state, "vs_main", vpath, "Framebuffer vertex shader")?;
-        let fshader = Shader::import_frag(state, "fs_main", fpath, "Chromatic aberration fragment shader")?;
+    fn new(state: &State, pipelines: &mut PipelineCache, framebuffer_layout: &Layout, settings: ChromaticAberrationSettings) -> Result<Self, GpuResourceError> {
+        let vpath = "framebuffer_vertex.wgsl";
+        let fpath = "chrab_fragment.wgsl";
 
-        let offsets = Uniform::new(&state.device, [-off, -off, 0.0, 0.0, off, off], "Chromatic Aberration", wgpu::ShaderStages::FRAGMENT);
+        let vshader = pipelines.import_vert(state, "vs_main", vpath, "Framebuffer vertex shader")?;
+        let fshader = pipelines.import_frag(state, "fs_main", fpath, "Chromatic aberration fragment shader")?;
 
-        let pipeline = Pipeline::new(
-            state,
-            &[framebuffer_layout, offsets.bg.layout()],
+        let offsets = Uniform::new(&state.device, settings.offsets(), "Chromatic Aberration", wgpu::ShaderStages::FRAGMENT);
+
+        let pipeline = pipelines.get(state, PipelineBuilder::new(
             vshader.vs_state(&[]),
             Some(fshader.fs_state(&[Some(wgpu::ColorTargetState {
                 format: FRAMEBUFFER_FORMAT,
                 blend: Some(wgpu::BlendState::REPLACE),
                 write_mask: wgpu::ColorWrites::ALL,
             })])),
-            false,
-        );
+        ).with_label("Chromatic Aberration Pipeline").with_layouts(&[framebuffer_layout, &offsets.bg.layout]))?;
+
+        Ok(Self {pipeline, offsets, settings, dirty: false})
+    }
+
+    fn settings(&self) -> &ChromaticAberrationSettings { &self.settings }
 
-        Ok(Self {pipeline, offsets})
+    fn set_settings(&mut self, settings: ChromaticAberrationSettings) {
+        self.settings = settings;
+        self.dirty = true;
     }
 }
 
@@ -50,4 +77,13 @@ impl PostFx for ChromaticAberration {
         self.offsets.set(1, render_pass);
     }
     fn label(&self) -> &'static str {"Chromatic Aberration"}
-}
\ No newline at end of file
+    fn refresh_pipelines(&mut self, pipelines: &PipelineCache) {
+        pipelines.refresh(&mut self.pipeline);
+    }
+    fn update_buffer(&mut self, state: &State) {
+        if !self.dirty { return }
+        self.dirty = false;
+        self.offsets.data = self.settings.offsets();
+        self.offsets.update(&state.queue);
+    }
+}
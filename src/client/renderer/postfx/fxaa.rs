@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use crate::client::renderer::state::State;
+
+use {
+    crate::client::renderer::{
+        pipeline::{Pipeline, PipelineBuilder, PipelineCache},
+        gpu::{
+            bind_group::Layout,
+            uniform::Uniform,
+            err::GpuResourceError,
+        },
+        framebuffer::FRAMEBUFFER_FORMAT,
+    },
+    super::{Effect, PostFx},
+};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FxaaSettings {
+    /// Amount of blending of details a single pixel wide, 0 keeps them sharp
+    pub subpixel: f32,
+    /// Contrast in luma below which a pixel isn't treated as an edge, relative to its brightest neighbour
+    pub edge_threshold: f32,
+    /// Absolute contrast below which a pixel isn't treated as an edge, so dark areas are left alone
+    pub edge_threshold_min: f32,
+}
+
+impl Default for FxaaSettings {
+    fn default() -> Self {
+        Self { subpixel: 0.75, edge_threshold: 0.166, edge_threshold_min: 0.0833 }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct FxaaRaw {
+    subpixel: f32,
+    edge_threshold: f32,
+    edge_threshold_min: f32,
+    _padding: f32,
+}
+
+impl FxaaSettings {
+    fn raw(&self) -> FxaaRaw {
+        FxaaRaw {
+            subpixel: self.subpixel.clamp(0.0, 1.0),
+            edge_threshold: self.edge_threshold,
+            edge_threshold_min: self.edge_threshold_min,
+            _padding: 0.0,
+        }
+    }
+}
+
+/// Smooths the edges of the frame in a single pass
+pub struct Fxaa {
+    pipeline: Arc<Pipeline>,
+    params: Uniform<FxaaRaw>,
+    settings: FxaaSettings,
+    dirty: bool,
+}
+
+impl Effect for Fxaa {
+    type Settings = FxaaSettings;
+
+    fn new(state: &State, pipelines: &mut PipelineCache, framebuffer_layout: &Layout, settings: FxaaSettings) -> Result<Self, GpuResourceError> {
+        let shader = pipelines.import_combined(state, ("vs_main", "fs_main"), "fxaa.wgsl", "FXAA shader")?;
+
+        let params = Uniform::new(&state.device, settings.raw(), "FXAA", wgpu::ShaderStages::FRAGMENT);
+
+        let pipeline = pipelines.get(state, PipelineBuilder::new(
+            shader.vs_state(&[]),
+            Some(shader.fs_state(&[Some(wgpu::ColorTargetState {
+                format: FRAMEBUFFER_FORMAT,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })])),
+        ).with_label("FXAA Pipeline").with_layouts(&[framebuffer_layout, &params.bg.layout]))?;
+
+        Ok(Self { pipeline, params, settings, dirty: false })
+    }
+
+    fn settings(&self) -> &FxaaSettings { &self.settings }
+
+    fn set_settings(&mut self, settings: FxaaSettings) {
+        self.settings = settings;
+        self.dirty = true;
+    }
+}
+
+impl PostFx for Fxaa {
+    fn set_effect<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_pipeline(&self.pipeline.pipeline);
+        self.params.set(1, render_pass);
+    }
+    fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.draw(0..3, 0..1);
+    }
+    fn label(&self) -> &'static str {"FXAA"}
+    fn refresh_pipelines(&mut self, pipelines: &PipelineCache) {
+        pipelines.refresh(&mut self.pipeline);
+    }
+    fn update_buffer(&mut self, state: &State) {
+        if !self.dirty { return }
+        self.dirty = false;
+        self.params.data = self.settings.raw();
+        self.params.update(&state.queue);
+    }
+}
@@ -1,10 +1,203 @@
 pub mod chromatic_aberration;
 pub mod box_blur;
+pub mod bloom;
+pub mod fxaa;
 
-pub trait PostFx {
-    fn set_effect<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>);
+pub use chromatic_aberration::{ChromaticAberration, ChromaticAberrationSettings};
+pub use box_blur::{BoxBlur, BoxBlurSettings};
+pub use bloom::{Bloom, BloomSettings};
+pub use fxaa::{Fxaa, FxaaSettings};
+
+use std::{any::Any, marker::PhantomData};
+
+use crate::common::slot_map::{Handle, SlotMap};
+
+use super::{
+    gpu::{bind_group::Layout, err::GpuResourceError},
+    pipeline::PipelineCache,
+    state::{RenderState, State},
+};
+
+pub trait PostFx: Any {
+    /// Binds the pipeline and the groups after the frame for the pass of the default [`PostFx::render`]
+    fn set_effect<'a>(&'a self, _render_pass: &mut wgpu::RenderPass<'a>) {}
     fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
         render_pass.draw(0..6, 0..1);
     }
     fn label(&self) -> &'static str {"Post-Processing effect"}
-}
\ No newline at end of file
+    /// Writes the effect applied to `input`, the frame bound with the framebuffer layout, to `output`.
+    /// Defaults to a single pass; effects with render targets of their own override it.
+    fn render(&self, render_state: &mut RenderState, input: &wgpu::BindGroup, output: &wgpu::TextureView) {
+        let mut render_pass = render_state.render_pass(
+            Some(&(self.label().to_owned() + " Render Pass")),
+            Some(output),
+            None,
+            None
+        );
+
+        render_pass.set_bind_group(0, input, &[]);
+        self.set_effect(&mut render_pass);
+        self.draw(&mut render_pass);
+    }
+    /// Follows the size of the frame, for effects with render targets of their own
+    fn resize(&mut self, _state: &State, _width: u32, _height: u32) {}
+    /// Picks up pipelines rebuilt by [`PipelineCache::reload`]
+    fn refresh_pipelines(&mut self, _pipelines: &PipelineCache) {}
+    /// Uploads settings changed since the last call
+    fn update_buffer(&mut self, _state: &State) {}
+}
+
+/// Effect built by [`Renderer::add_postfx`](super::Renderer::add_postfx), with parameters changed through [`PostFxStack::update`]
+pub trait Effect: PostFx + Sized {
+    type Settings: Clone;
+
+    /// `framebuffer_layout` is the layout of the frame sampled at group 0
+    fn new(state: &State, pipelines: &mut PipelineCache, framebuffer_layout: &Layout, settings: Self::Settings) -> Result<Self, GpuResourceError>;
+
+    fn settings(&self) -> &Self::Settings;
+
+    /// Uploaded by the next [`PostFx::update_buffer`]
+    fn set_settings(&mut self, settings: Self::Settings);
+}
+
+struct Entry {
+    fx: Box<dyn PostFx>,
+    enabled: bool,
+}
+
+/// Reference to an effect in a [`PostFxStack`], typed to reach its settings
+pub struct FxHandle<T> {
+    handle: Handle<Entry>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl <T> Clone for FxHandle<T> {
+    fn clone(&self) -> Self { *self }
+}
+impl <T> Copy for FxHandle<T> {}
+
+impl <T> PartialEq for FxHandle<T> {
+    fn eq(&self, other: &Self) -> bool { self.handle == other.handle }
+}
+impl <T> Eq for FxHandle<T> {}
+
+impl <T> std::fmt::Debug for FxHandle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Fx{:?}", self.handle)
+    }
+}
+
+/// Effects applied in order to the frame after the scene is drawn
+pub struct PostFxStack {
+    effects: SlotMap<Entry>,
+    order: Vec<Handle<Entry>>,
+}
+
+impl PostFxStack {
+    pub fn new() -> Self {
+        Self { effects: SlotMap::new(), order: Vec::new() }
+    }
+
+    /// Appends an enabled effect, applied after every other one
+    pub fn push<T: PostFx>(&mut self, fx: T) -> FxHandle<T> {
+        let handle = self.effects.insert(Entry { fx: Box::new(fx), enabled: true });
+        self.order.push(handle);
+        FxHandle { handle, _marker: PhantomData }
+    }
+
+    pub fn remove<T: PostFx>(&mut self, handle: FxHandle<T>) -> Option<T> {
+        let entry = self.effects.remove(handle.handle)?;
+        self.order.retain(|h| *h != handle.handle);
+        let fx: Box<dyn Any> = entry.fx;
+        fx.downcast().ok().map(|fx| *fx)
+    }
+
+    pub fn clear(&mut self) {
+        self.effects.clear();
+        self.order.clear();
+    }
+
+    pub fn contains<T>(&self, handle: FxHandle<T>) -> bool { self.effects.contains(handle.handle) }
+
+    pub fn get<T: PostFx>(&self, handle: FxHandle<T>) -> Option<&T> {
+        let fx: &dyn Any = self.effects.get(handle.handle)?.fx.as_ref();
+        fx.downcast_ref()
+    }
+
+    pub fn get_mut<T: PostFx>(&mut self, handle: FxHandle<T>) -> Option<&mut T> {
+        let fx: &mut dyn Any = self.effects.get_mut(handle.handle)?.fx.as_mut();
+        fx.downcast_mut()
+    }
+
+    pub fn settings<T: Effect>(&self, handle: FxHandle<T>) -> Option<&T::Settings> {
+        self.get(handle).map(Effect::settings)
+    }
+
+    /// Applies `f` to the settings of the effect. Returns false if the handle is no longer valid.
+    pub fn update<T: Effect, F: FnOnce(&mut T::Settings)>(&mut self, handle: FxHandle<T>, f: F) -> bool {
+        let Some(fx) = self.get_mut(handle) else { return false };
+        let mut settings = fx.settings().clone();
+        f(&mut settings);
+        fx.set_settings(settings);
+        true
+    }
+
+    /// Disabled effects keep their place and settings but are skipped. Returns false if the handle is no longer valid.
+    pub fn set_enabled<T>(&mut self, handle: FxHandle<T>, enabled: bool) -> bool {
+        match self.effects.get_mut(handle.handle) {
+            Some(entry) => { entry.enabled = enabled; true }
+            None => false,
+        }
+    }
+
+    pub fn is_enabled<T>(&self, handle: FxHandle<T>) -> bool {
+        self.effects.get(handle.handle).is_some_and(|e| e.enabled)
+    }
+
+    /// Index of the effect in the order they are applied
+    pub fn position<T>(&self, handle: FxHandle<T>) -> Option<usize> {
+        self.order.iter().position(|h| *h == handle.handle)
+    }
+
+    /// Moves the effect to `index` in the order they are applied, clamped to the last place.
+    /// Returns false if the handle is no longer valid.
+    pub fn move_to<T>(&mut self, handle: FxHandle<T>, index: usize) -> bool {
+        let Some(from) = self.position(handle) else { return false };
+        let handle = self.order.remove(from);
+        self.order.insert(index.min(self.order.len()), handle);
+        true
+    }
+
+    pub fn len(&self) -> usize { self.effects.len() }
+    pub fn is_empty(&self) -> bool { self.effects.is_empty() }
+
+    /// Enabled effects in the order they are applied
+    pub fn enabled(&self) -> impl Iterator<Item = &dyn PostFx> {
+        self.order.iter()
+            .filter_map(|h| self.effects.get(*h))
+            .filter(|e| e.enabled)
+            .map(|e| e.fx.as_ref())
+    }
+
+    pub fn update_buffers(&mut self, state: &State) {
+        for (_, entry) in self.effects.iter_mut() {
+            entry.fx.update_buffer(state);
+        }
+    }
+
+    pub fn resize(&mut self, state: &State, width: u32, height: u32) {
+        for (_, entry) in self.effects.iter_mut() {
+            entry.fx.resize(state, width, height);
+        }
+    }
+
+    pub fn refresh_pipelines(&mut self, pipelines: &PipelineCache) {
+        for (_, entry) in self.effects.iter_mut() {
+            entry.fx.refresh_pipelines(pipelines);
+        }
+    }
+}
+
+impl Default for PostFxStack {
+    fn default() -> Self { Self::new() }
+}
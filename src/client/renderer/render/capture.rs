@@ -0,0 +1,10 @@
+use crate::client::renderer::{Renderer, RendererError};
+
+impl Renderer {
+    /// Reads back the last rendered frame of a headless renderer and writes it to `path` as a PNG
+    pub fn capture_frame<T: AsRef<std::path::Path>>(&self, path: T) -> Result<(), RendererError> {
+        let pixels = self.state.read_offscreen()?;
+        crate::files::write_png(path, self.state.size.x, self.state.size.y, &pixels)?;
+        Ok(())
+    }
+}
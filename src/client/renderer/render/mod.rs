@@ -1,73 +1,103 @@
-use crate::client::{InputManager, Gui};
+mod capture;
+mod occlusion;
+mod shadow;
+
+use crate::client::Gui;
 
 use {
     crate::client::renderer::{
-        Renderer, resources::model::{DrawModel, DrawLight}, state::RenderState,
+        Renderer, resources::{model::{DrawModel, DrawLight}, material::ShadingModel}, state::RenderState,
+        framebuffer::FrameBuffer, postfx::PostFx,
     },
     crate::client::Time
 };
 
 
 impl Renderer {
-    pub fn render(&mut self, camera_bg: &wgpu::BindGroup, time: &Time, inp: &InputManager, gui: &Gui) -> Result<(), wgpu::SurfaceError> {
+    pub fn render(&mut self, camera_bg: &wgpu::BindGroup, time: &Time, gui: &Gui) -> Result<(), wgpu::SurfaceError> {
         let mut render_state = RenderState::new(&self.state)?;
 
+        self.render_shadows(&mut render_state);
+        self.render_occlusion(&mut render_state, camera_bg);
+
+        // While multisampling the scene is drawn to its own targets, resolved into the framebuffer by the last pass
+        let (scene_view, scene_depth, resolve_target) = match &self.msaa {
+            Some(msaa) => (&msaa.color, &msaa.depth, Some(self.framebuffer.target_view())),
+            None => (self.framebuffer.target_view(), self.depth_texture.attachment_view(), None),
+        };
+
         {
             let mut render_pass = render_state.render_pass(
                 Some("Geometry Render Pass"),
-                Some(self.framebuffer.target_view()),
+                Some(scene_view),
                 Some(wgpu::Color {r: 0.1, g: 0.2, b: 0.3, a: 1.0}),
                 Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture.view,
+                    view: scene_depth,
                     depth_ops: Some(wgpu::Operations {
                         load: wgpu::LoadOp::Clear(1.0),
                         store: true,
                     }),
-                    stencil_ops: None,
+                    stencil_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(0),
+                        store: true,
+                    }),
                 })
             );
 
 
             render_pass.set_bind_group(3, &time.uf.bg.group, &[]);
 
-            render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-
-            render_pass.set_pipeline(self.light.pipeline());
-            render_pass.draw_light_model(&self.model, camera_bg, self.light.bg());
+            if let Some(marker) = self.light_marker.and_then(|m| self.scene.model(m)) {
+                render_pass.set_pipeline(self.lights.pipeline());
+                render_pass.draw_light_model_instanced(marker, 0..self.lights.len() as u32, camera_bg, self.lights.bg());
+            }
 
-            render_pass.set_pipeline(&self.pipeline.pipeline);
-            render_pass.draw_model_instanced(&self.model, 0..self.instances.len() as u32, camera_bg, self.light.bg());
+            for (pipeline, shading) in [(&self.pipeline, ShadingModel::BlinnPhong), (&self.pbr_pipeline, ShadingModel::MetallicRoughness)] {
+                render_pass.set_pipeline(&pipeline.pipeline);
+                for model in self.scene.instanced_models() {
+                    let Some(instances) = model.instance_buffer() else { continue };
+                    render_pass.set_vertex_buffer(1, instances.slice(..));
+                    render_pass.draw_model_shaded_instanced(&model.model, shading, 0..model.instance_count(), camera_bg, self.lights.bg());
+                }
+            }
         }
 
         {
-            let mut render_pass = render_state.render_pass(
+            let mut render_pass = render_state.resolve_pass(
                 Some("Skybox Render Pass"),
-                Some(self.framebuffer.target_view()),
+                Some(scene_view),
+                resolve_target,
                 None,
                 Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture.view,
+                    view: scene_depth,
                     depth_ops: Some(wgpu::Operations {
                         load: wgpu::LoadOp::Load,
                         store: true,
                     }),
-                    stencil_ops: None,
+                    stencil_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    }),
                 })
             );
             render_pass.set_bind_group(0, camera_bg, &[]);
-            render_pass.set_bind_group(1, &self.cubemap.bg.group, &[]);
+            render_pass.set_bind_group(1, &self.lights.environment().sky.bg.group, &[]);
             render_pass.set_pipeline(&self.sky_pipeline.pipeline);
             render_pass.draw(0..36, 0..1);
         }
 
         self.framebuffer.swap_buffers();
 
-        if inp.mouse.button.left {
-            for i in 0..self.postfx.len() {
-                self.render_fx(&mut render_state, i);
-            }
+        if let Some(taa) = &mut self.taa {
+            taa.render(&mut render_state, self.framebuffer.sample_bg(), self.framebuffer.target_view(), camera_bg);
+            self.framebuffer.swap_buffers();
         }
 
-        self.render_framebuffer(&mut render_state);
+        for fx in self.postfx.enabled() {
+            Self::render_fx(&mut render_state, &mut self.framebuffer, fx);
+        }
+
+        self.framebuffer.render(&self.state, &mut render_state, time.dt32);
 
         gui.render(&mut render_state);
 
@@ -76,31 +106,8 @@ impl Renderer {
         Ok(())
     }
 
-    fn render_fx(&mut self, render_state: &mut RenderState, fx_index: usize) {
-        {
-            let fx = &self.postfx[fx_index];
-
-            let mut render_pass = render_state.render_pass(
-                Some(&(fx.label().to_owned() + " Render Pass")),
-                Some(self.framebuffer.target_view()),
-                None,
-                None
-            );
-
-            render_pass.set_bind_group(0, self.framebuffer.sample_bg(), &[]);
-            fx.set_effect(&mut render_pass);
-            fx.draw(&mut render_pass);
-        }
-        self.framebuffer.swap_buffers();
-    }
-
-    fn render_framebuffer(&mut self, render_state: &mut RenderState) {
-        let mut render_pass = render_state.render_pass(
-            Some("Framebuffer and Color Correction Render Pass"),
-            None, None, None);
-
-        render_pass.set_pipeline(self.framebuffer.pipeline());
-        render_pass.set_bind_group(0, self.framebuffer.sample_bg(), &[]);
-        render_pass.draw(0..6, 0..1);
+    fn render_fx(render_state: &mut RenderState, framebuffer: &mut FrameBuffer, fx: &dyn PostFx) {
+        fx.render(render_state, framebuffer.sample_bg(), framebuffer.target_view());
+        framebuffer.swap_buffers();
     }
 }
\ No newline at end of file
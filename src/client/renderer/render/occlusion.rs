@@ -0,0 +1,21 @@
+use crate::client::renderer::{
+    Renderer, resources::model::DrawShadow, state::RenderState,
+};
+
+impl Renderer {
+    /// Draws the positions and normals of the scene and estimates the ambient occlusion the geometry pass reads
+    pub(super) fn render_occlusion(&self, render_state: &mut RenderState, camera_bg: &wgpu::BindGroup) {
+        let occlusion = self.lights.occlusion();
+        if !occlusion.settings().enabled { return }
+
+        {
+            let mut render_pass = occlusion.prepass(render_state, camera_bg);
+            for model in self.scene.instanced_models() {
+                let Some(instances) = model.instance_buffer() else { continue };
+                render_pass.set_vertex_buffer(1, instances.slice(..));
+                render_pass.draw_shadow_model_instanced(&model.model, 0..model.instance_count());
+            }
+        }
+        occlusion.render(render_state, camera_bg);
+    }
+}
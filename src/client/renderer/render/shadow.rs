@@ -0,0 +1,22 @@
+use crate::client::renderer::{
+    Renderer, resources::model::DrawShadow, state::RenderState,
+};
+
+impl Renderer {
+    /// Renders the scene's depth from every shadow casting light into its shadow map layer
+    pub(super) fn render_shadows(&self, render_state: &mut RenderState) {
+        let shadows = self.lights.shadows();
+
+        for layer in 0..shadows.casters() {
+            let mut render_pass = render_state.depth_pass(Some("Shadow Render Pass"), shadows.layer_view(layer));
+
+            render_pass.set_pipeline(shadows.pipeline());
+            render_pass.set_bind_group(0, shadows.pass_bg(), &[shadows.pass_offset(layer)]);
+            for model in self.scene.instanced_models() {
+                let Some(instances) = model.instance_buffer() else { continue };
+                render_pass.set_vertex_buffer(1, instances.slice(..));
+                render_pass.draw_shadow_model_instanced(&model.model, 0..model.instance_count());
+            }
+        }
+    }
+}
@@ -0,0 +1,524 @@
+//! ASTC with LDR endpoints. Blocks using HDR endpoint modes, or otherwise invalid, decode to the error color.
+
+use {
+    super::Bits,
+    crate::client::renderer::resources::image::pixel::f16_to_f32,
+};
+
+/// Magenta, what the specification has decoders output for invalid blocks
+const ERROR: [f32; 4] = [1., 0., 1., 1.];
+
+/// Quantization ranges of endpoint values, from the fewest levels usable
+const COLOR_RANGES: [u32; 17] = [6, 8, 10, 12, 16, 20, 24, 32, 40, 48, 64, 80, 96, 128, 160, 192, 256];
+/// Quantization ranges of weights, indexed by the block mode
+const WEIGHT_RANGES: [u32; 12] = [2, 3, 4, 5, 6, 8, 10, 12, 16, 20, 24, 32];
+
+pub fn decode(block: &[u8], width: u32, height: u32, hdr: bool, out: &mut [[f32; 4]]) {
+    let out = &mut out[..(width * height) as usize];
+    if decode_block(&Bits::new(block), width, height, hdr, out).is_none() {
+        out.fill(ERROR);
+    }
+}
+
+struct BlockMode {
+    grid_width: u32,
+    grid_height: u32,
+    dual_plane: bool,
+    weight_range: u32,
+}
+
+fn decode_block(bits: &Bits, width: u32, height: u32, hdr: bool, out: &mut [[f32; 4]]) -> Option<()> {
+    let mode = bits.peek(0, 11);
+    if mode & 0x1FF == 0x1FC { return void_extent(bits, mode & 0x200 != 0, hdr, out) }
+
+    let mode = block_mode(mode)?;
+    if mode.grid_width > width || mode.grid_height > height { return None }
+    let planes = 1 + mode.dual_plane as u32;
+    let weight_count = mode.grid_width * mode.grid_height * planes;
+    let weight_bits = ise_bits(weight_count, mode.weight_range);
+    if weight_count > 64 || !(24..=96).contains(&weight_bits) { return None }
+
+    let partitions = bits.peek(11, 2) + 1;
+    if mode.dual_plane && partitions == 4 { return None }
+
+    let mut below_weights = 128 - weight_bits;
+    let mut modes = [0; 4];
+    let (seed, color_start) = match partitions {
+        1 => { modes[0] = bits.peek(13, 4); (0, 17) }
+        _ => {
+            let cem = bits.peek(23, 6);
+            if cem & 3 == 0 {
+                modes = [cem >> 2; 4];
+            } else {
+                // Per partition classes and modes, the bits that don't fit are stored below the weights
+                let extra = 3 * partitions - 4;
+                below_weights -= extra;
+                let encoded = (cem | bits.peek(below_weights, extra) << 6) >> 2;
+                let base_class = (cem & 3) - 1;
+                for (i, mode) in modes.iter_mut().enumerate().take(partitions as usize) {
+                    let class = base_class + ((encoded >> i) & 1);
+                    *mode = class << 2 | ((encoded >> (partitions as usize + i * 2)) & 3);
+                }
+            }
+            (bits.peek(13, 10), 29)
+        }
+    };
+    let modes = &modes[..partitions as usize];
+
+    let plane2_component = match mode.dual_plane {
+        true => { below_weights -= 2; Some(bits.peek(below_weights, 2) as usize) }
+        false => None,
+    };
+
+    // HDR endpoints aren't supported
+    if modes.iter().any(|&m| matches!(m, 2 | 3 | 7 | 11 | 14 | 15)) { return None }
+
+    let value_count: u32 = modes.iter().map(|m| ((m >> 2) + 1) * 2).sum();
+    if value_count > 18 || below_weights < color_start { return None }
+    let color_bits = below_weights - color_start;
+    if color_bits < (13 * value_count).div_ceil(5) { return None }
+    let color_range = *COLOR_RANGES.iter().rev().find(|&&r| ise_bits(value_count, r) <= color_bits)?;
+
+    let values: Vec<i32> = ise(bits, color_start, value_count, color_range).into_iter()
+        .map(|v| unquantize_color(v, color_range) as i32)
+        .collect();
+    let mut endpoints = [[[0i32; 4]; 2]; 4];
+    let mut offset = 0;
+    for (endpoints, &m) in endpoints.iter_mut().zip(modes) {
+        let count = ((m >> 2) + 1) as usize * 2;
+        *endpoints = decode_endpoints(m, &values[offset..offset + count]);
+        offset += count;
+    }
+
+    // Weights are stored bit reversed from the top of the block
+    let reversed = Bits { value: bits.value.reverse_bits(), pos: 0 };
+    let weights: Vec<u32> = ise(&reversed, 0, weight_count, mode.weight_range).into_iter()
+        .map(|w| unquantize_weight(w, mode.weight_range))
+        .collect();
+
+    let small_block = width * height < 31;
+    for t in 0..height {
+        for s in 0..width {
+            let partition = match partitions {
+                1 => 0,
+                _ => select_partition(seed, s, t, partitions, small_block),
+            };
+            let [e0, e1] = endpoints[partition];
+            let plane_weights = [0, 1].map(|plane| infill(&weights, &mode, planes, plane, s, t, width, height));
+
+            let mut texel = [0.; 4];
+            for (c, value) in texel.iter_mut().enumerate() {
+                let w = plane_weights[(plane2_component == Some(c)) as usize] as i32;
+                // Endpoints are expanded to 16 bits before interpolating
+                let (c0, c1) = (e0[c] * 257, e1[c] * 257);
+                *value = ((c0 * (64 - w) + c1 * w + 32) >> 6) as f32 / 65535.;
+            }
+            out[(t * width + s) as usize] = texel;
+        }
+    }
+    Some(())
+}
+
+/// A single color for the whole block, UNORM16 or half floats
+fn void_extent(bits: &Bits, hdr_block: bool, hdr: bool, out: &mut [[f32; 4]]) -> Option<()> {
+    let channels: [u32; 4] = std::array::from_fn(|c| bits.peek(64 + c as u32 * 16, 16));
+    let color = match hdr_block {
+        true if hdr => channels.map(|v| f16_to_f32(v as u16)),
+        true => return None,
+        false => channels.map(|v| v as f32 / 65535.),
+    };
+    out.fill(color);
+    Some(())
+}
+
+fn block_mode(mode: u32) -> Option<BlockMode> {
+    let mut range_index = (mode >> 4) & 1;
+    let mut high_precision = (mode >> 9) & 1;
+    let mut dual_plane = (mode >> 10) & 1;
+    let a = (mode >> 5) & 3;
+
+    let (grid_width, grid_height) = if mode & 3 != 0 {
+        range_index |= (mode & 3) << 1;
+        let b = (mode >> 7) & 3;
+        match (mode >> 2) & 3 {
+            0 => (b + 4, a + 2),
+            1 => (b + 8, a + 2),
+            2 => (a + 2, b + 8),
+            _ if mode & 0x100 != 0 => ((b & 1) + 2, a + 2),
+            _ => (a + 2, (b & 1) + 6),
+        }
+    } else {
+        range_index |= ((mode >> 2) & 3) << 1;
+        if (mode >> 2) & 3 == 0 { return None }
+        let b = (mode >> 9) & 3;
+        match (mode >> 7) & 3 {
+            0 => (12, a + 2),
+            1 => (a + 2, 12),
+            2 => {
+                high_precision = 0;
+                dual_plane = 0;
+                (a + 6, b + 6)
+            }
+            _ => match a {
+                0 => (6, 10),
+                1 => (10, 6),
+                _ => return None,
+            },
+        }
+    };
+
+    let weight_range = WEIGHT_RANGES[(range_index - 2 + 6 * high_precision) as usize];
+    Some(BlockMode { grid_width, grid_height, dual_plane: dual_plane == 1, weight_range })
+}
+
+/// Trits, quints and plain bits of each value in an integer sequence of `range` levels
+fn ise_params(range: u32) -> (bool, bool, u32) {
+    match range {
+        _ if range.is_multiple_of(3) => (true, false, (range / 3).trailing_zeros()),
+        _ if range.is_multiple_of(5) => (false, true, (range / 5).trailing_zeros()),
+        _ => (false, false, range.trailing_zeros()),
+    }
+}
+
+fn ise_bits(count: u32, range: u32) -> u32 {
+    let (trits, quints, bits) = ise_params(range);
+    count * bits
+        + if trits { (8 * count).div_ceil(5) } else { 0 }
+        + if quints { (7 * count).div_ceil(3) } else { 0 }
+}
+
+/// Decodes `count` values of an integer sequence starting at bit `start`
+fn ise(bits: &Bits, start: u32, count: u32, range: u32) -> Vec<u32> {
+    let (trits, quints, m) = ise_params(range);
+    let end = start + ise_bits(count, range);
+    let mut pos = start;
+    // Groups are cut short after the last value, missing bits read as zero
+    let mut read = |n: u32| {
+        let available = end.saturating_sub(pos).min(n);
+        let v = bits.peek(pos, available);
+        pos += n;
+        v
+    };
+
+    let mut values = Vec::with_capacity(count as usize + 4);
+    while values.len() < count as usize {
+        if trits {
+            let mut low = [0; 5];
+            let mut t = 0;
+            for (i, (shift, len)) in [(0, 2), (2, 2), (4, 1), (5, 2), (7, 1)].into_iter().enumerate() {
+                low[i] = read(m);
+                t |= read(len) << shift;
+            }
+            values.extend(decode_trits(t).iter().zip(low).map(|(&d, v)| d << m | v));
+        } else if quints {
+            let mut low = [0; 3];
+            let mut q = 0;
+            for (i, (shift, len)) in [(0, 3), (3, 2), (5, 2)].into_iter().enumerate() {
+                low[i] = read(m);
+                q |= read(len) << shift;
+            }
+            values.extend(decode_quints(q).iter().zip(low).map(|(&d, v)| d << m | v));
+        } else {
+            values.push(read(m));
+        }
+    }
+    values.truncate(count as usize);
+    values
+}
+
+fn decode_trits(t: u32) -> [u32; 5] {
+    let bit = |v: u32, i: u32| (v >> i) & 1;
+    let (c, t4, t3) = match (t >> 2) & 7 {
+        7 => (((t >> 5) & 7) << 2 | (t & 3), 2, 2),
+        _ => {
+            let c = t & 0x1F;
+            match (t >> 5) & 3 {
+                3 => (c, 2, bit(t, 7)),
+                t3 => (c, bit(t, 7), t3),
+            }
+        }
+    };
+    let (t2, t1, t0) = if c & 3 == 3 {
+        (2, bit(c, 4), bit(c, 3) << 1 | (bit(c, 2) & (bit(c, 3) ^ 1)))
+    } else if (c >> 2) & 3 == 3 {
+        (2, 2, c & 3)
+    } else {
+        (bit(c, 4), (c >> 2) & 3, bit(c, 1) << 1 | (bit(c, 0) & (bit(c, 1) ^ 1)))
+    };
+    [t0, t1, t2, t3, t4]
+}
+
+fn decode_quints(q: u32) -> [u32; 3] {
+    let bit = |v: u32, i: u32| (v >> i) & 1;
+    if (q >> 1) & 3 == 3 && (q >> 5) & 3 == 0 {
+        let not_q0 = bit(q, 0) ^ 1;
+        let q2 = bit(q, 0) << 2 | (bit(q, 4) & not_q0) << 1 | (bit(q, 3) & not_q0);
+        return [4, 4, q2]
+    }
+    let (q2, c) = match (q >> 1) & 3 {
+        3 => (4, ((q >> 3) & 3) << 3 | (!(q >> 5) & 3) << 1 | bit(q, 0)),
+        _ => ((q >> 5) & 3, q & 0x1F),
+    };
+    let (q1, q0) = match c & 7 {
+        5 => (4, (c >> 3) & 3),
+        _ => ((c >> 3) & 3, c & 7),
+    };
+    [q0, q1, q2]
+}
+
+/// Endpoint value to 0..255
+fn unquantize_color(v: u32, range: u32) -> u32 {
+    let (trits, quints, m) = ise_params(range);
+    if !trits && !quints { return replicate(v, m, 8) }
+
+    let d = v >> m;
+    let low = v & ((1 << m) - 1);
+    let a = if low & 1 == 1 { 0x1FF } else { 0 };
+    let b = low >> 1;
+    let (b, c) = match (trits, m) {
+        (true, 1) => (0, 204),
+        (true, 2) => (b << 8 | b << 4 | b << 2 | b << 1, 93),
+        (true, 3) => (b << 7 | b << 2 | b, 44),
+        (true, 4) => (b << 6 | b, 22),
+        (true, 5) => (b << 5 | b >> 2, 11),
+        (true, _) => (b << 4 | b >> 4, 5),
+        (false, 1) => (0, 113),
+        (false, 2) => (b << 8 | b << 3 | b << 2, 54),
+        (false, 3) => (b << 7 | b << 1 | b >> 1, 26),
+        (false, 4) => (b << 6 | b >> 1, 13),
+        (false, _) => (b << 5 | b >> 3, 6),
+    };
+    let t = (d * c + b) ^ a;
+    (a & 0x80) | (t >> 2)
+}
+
+/// Weight value to 0..64
+fn unquantize_weight(v: u32, range: u32) -> u32 {
+    let (trits, quints, m) = ise_params(range);
+    let w = match (trits, quints, m) {
+        (false, false, _) => replicate(v, m, 6),
+        (true, _, 0) => [0, 32, 63][v as usize],
+        (_, true, 0) => [0, 16, 32, 47, 63][v as usize],
+        _ => {
+            let d = v >> m;
+            let low = v & ((1 << m) - 1);
+            let a = if low & 1 == 1 { 0x7F } else { 0 };
+            let b = low >> 1;
+            let (b, c) = match (trits, m) {
+                (true, 1) => (0, 50),
+                (true, 2) => (b << 6 | b << 2 | b, 23),
+                (true, _) => (b << 5 | b, 11),
+                (false, 1) => (0, 28),
+                (false, _) => (b << 6 | b << 1, 13),
+            };
+            let t = (d * c + b) ^ a;
+            (a & 0x20) | (t >> 2)
+        }
+    };
+    if w > 32 { w + 1 } else { w }
+}
+
+/// Repeats the `bits` bit value to fill `to` bits
+fn replicate(v: u32, bits: u32, to: u32) -> u32 {
+    if bits == 0 { return 0 }
+    let mut result = 0;
+    let mut filled = 0;
+    while filled < to {
+        result = result << bits | v;
+        filled += bits;
+    }
+    result >> (filled - to)
+}
+
+/// Bilinear interpolation of the weight grid at texel (s, t)
+#[allow(clippy::too_many_arguments)]
+fn infill(weights: &[u32], mode: &BlockMode, planes: u32, plane: u32, s: u32, t: u32, width: u32, height: u32) -> u32 {
+    if plane >= planes { return 0 }
+    let (n, m) = (mode.grid_width, mode.grid_height);
+    let ds = (1024 + width / 2) / (width - 1);
+    let dt = (1024 + height / 2) / (height - 1);
+    let gs = (ds * s * (n - 1) + 32) >> 6;
+    let gt = (dt * t * (m - 1) + 32) >> 6;
+    let (js, fs) = (gs >> 4, gs & 0xF);
+    let (jt, ft) = (gt >> 4, gt & 0xF);
+
+    let w11 = (fs * ft + 8) >> 4;
+    let w10 = ft - w11;
+    let w01 = fs - w11;
+    let w00 = 16 - fs - ft + w11;
+
+    let weight = |x: u32, y: u32| match x < n && y < m {
+        true => weights[((y * n + x) * planes + plane) as usize],
+        false => 0,
+    };
+    (weight(js, jt) * w00 + weight(js + 1, jt) * w01 + weight(js, jt + 1) * w10 + weight(js + 1, jt + 1) * w11 + 8) >> 4
+}
+
+fn select_partition(seed: u32, x: u32, y: u32, partitions: u32, small_block: bool) -> usize {
+    let (x, y, z) = if small_block { (x << 1, y << 1, 0) } else { (x, y, 0) };
+    let seed = seed + (partitions - 1) * 1024;
+    let rnum = hash52(seed);
+
+    let mut seeds: [u32; 12] = std::array::from_fn(|i| {
+        let shift = [0, 4, 8, 12, 16, 20, 24, 28, 18, 22, 26][..].get(i).copied();
+        let s = match shift {
+            Some(shift) => (rnum >> shift) & 0xF,
+            None => rnum.rotate_left(2) & 0xF,
+        };
+        s * s
+    });
+
+    let (sh1, sh2) = match seed & 1 {
+        1 => (if seed & 2 != 0 { 4 } else { 5 }, if partitions == 3 { 6 } else { 5 }),
+        _ => (if partitions == 3 { 6 } else { 5 }, if seed & 2 != 0 { 4 } else { 5 }),
+    };
+    let sh3 = if seed & 0x10 != 0 { sh1 } else { sh2 };
+    for (i, s) in seeds.iter_mut().enumerate() {
+        *s >>= match i { 0..=7 if i % 2 == 0 => sh1, 0..=7 => sh2, _ => sh3 };
+    }
+
+    let [s1, s2, s3, s4, s5, s6, s7, s8, s9, s10, s11, s12] = seeds;
+    let a = (s1 * x + s2 * y + s11 * z + (rnum >> 14)) & 0x3F;
+    let b = (s3 * x + s4 * y + s12 * z + (rnum >> 10)) & 0x3F;
+    let c = if partitions >= 3 { (s5 * x + s6 * y + s9 * z + (rnum >> 6)) & 0x3F } else { 0 };
+    let d = if partitions >= 4 { (s7 * x + s8 * y + s10 * z + (rnum >> 2)) & 0x3F } else { 0 };
+
+    if a >= b && a >= c && a >= d { 0 }
+    else if b >= c && b >= d { 1 }
+    else if c >= d { 2 }
+    else { 3 }
+}
+
+fn hash52(mut p: u32) -> u32 {
+    p ^= p >> 15;
+    p = p.wrapping_sub(p << 17);
+    p = p.wrapping_add(p << 7);
+    p = p.wrapping_add(p << 4);
+    p ^= p >> 5;
+    p = p.wrapping_add(p << 16);
+    p ^= p >> 7;
+    p ^= p >> 3;
+    p ^= p << 6;
+    p ^= p >> 17;
+    p
+}
+
+/// Moves bits from the first value of a pair to the second; the first becomes a signed 6 bit offset
+fn bit_transfer_signed(a: i32, b: i32) -> (i32, i32) {
+    let b = (b >> 1) | (a & 0x80);
+    let a = (a >> 1) & 0x3F;
+    let a = if a & 0x20 != 0 { a - 0x40 } else { a };
+    (a, b)
+}
+
+fn blue_contract([r, g, b, a]: [i32; 4]) -> [i32; 4] {
+    [(r + b) >> 1, (g + b) >> 1, b, a]
+}
+
+/// Both endpoints of an LDR endpoint mode, 0..255 per channel
+fn decode_endpoints(mode: u32, v: &[i32]) -> [[i32; 4]; 2] {
+    let clamp = |e: [i32; 4]| e.map(|c| c.clamp(0, 255));
+    match mode {
+        // Luminance
+        0 => [[v[0], v[0], v[0], 255], [v[1], v[1], v[1], 255]],
+        1 => {
+            let l0 = (v[0] >> 2) | (v[1] & 0xC0);
+            let l1 = (l0 + (v[1] & 0x3F)).min(255);
+            [[l0, l0, l0, 255], [l1, l1, l1, 255]]
+        }
+        // Luminance and alpha
+        4 => [[v[0], v[0], v[0], v[2]], [v[1], v[1], v[1], v[3]]],
+        5 => {
+            let (d0, b0) = bit_transfer_signed(v[1], v[0]);
+            let (d2, b2) = bit_transfer_signed(v[3], v[2]);
+            [clamp([b0, b0, b0, b2]), clamp([b0 + d0, b0 + d0, b0 + d0, b2 + d2])]
+        }
+        // RGB scaled, optionally with alpha
+        6 | 10 => {
+            let (a0, a1) = if mode == 10 { (v[4], v[5]) } else { (255, 255) };
+            [[(v[0] * v[3]) >> 8, (v[1] * v[3]) >> 8, (v[2] * v[3]) >> 8, a0], [v[0], v[1], v[2], a1]]
+        }
+        // RGB(A) direct
+        8 | 12 => {
+            let (a0, a1) = if mode == 12 { (v[6], v[7]) } else { (255, 255) };
+            let e0 = [v[0], v[2], v[4], a0];
+            let e1 = [v[1], v[3], v[5], a1];
+            if v[1] + v[3] + v[5] >= v[0] + v[2] + v[4] { [e0, e1] } else { [blue_contract(e1), blue_contract(e0)] }
+        }
+        // RGB(A) base plus offset
+        9 | 13 => {
+            let pairs: [(i32, i32); 4] = std::array::from_fn(|c| match c < 3 || mode == 13 {
+                true => bit_transfer_signed(v[c * 2 + 1], v[c * 2]),
+                false => (0, 255),
+            });
+            let base = pairs.map(|(_, b)| b);
+            let offset = pairs.map(|(d, b)| b + d);
+            let sum: i32 = pairs[..3].iter().map(|(d, _)| d).sum();
+            if sum >= 0 { [clamp(base), clamp(offset)] } else { [clamp(blue_contract(offset)), clamp(blue_contract(base))] }
+        }
+        _ => unreachable!("HDR endpoint mode {mode}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::assert_texels;
+
+    /// Void extent block of a constant color, without extent coordinates
+    fn void_extent_block(hdr: bool, color: [u16; 4]) -> [u8; 16] {
+        let mut bits: u128 = 0x1FC | (0b11 << 10) | (((1u128 << 52) - 1) << 12);
+        if hdr { bits |= 1 << 9 }
+        for (c, v) in color.into_iter().enumerate() { bits |= (v as u128) << (64 + c * 16) }
+        bits.to_le_bytes()
+    }
+
+    /// Normal 4x4 block: one partition, RGBA direct endpoints and a 4x4 grid of 2 bit weights
+    ///
+    /// Block mode 0x42 is the 4x4 grid, weight range 4, one plane. With 32 weight bits, 79 bits
+    /// remain for the 8 endpoint values, enough for the 256 level range, so they're stored as is.
+    fn rgba_direct_block(endpoints: [u8; 8], weights: [u8; 16]) -> [u8; 16] {
+        let mut bits: u128 = 0x42 | (12 << 13);
+        for (i, v) in endpoints.into_iter().enumerate() { bits |= (v as u128) << (17 + i * 8) }
+        for (i, w) in weights.into_iter().enumerate() {
+            bits |= ((w as u128 & 1) << (127 - i * 2)) | ((w as u128 >> 1) << (126 - i * 2));
+        }
+        bits.to_le_bytes()
+    }
+
+    #[test]
+    fn single_partition_ldr() {
+        // Endpoints (0, 255, 32, 0) and (255, 0, 224, 255), the second has the larger RGB sum so
+        // no blue contraction. Weights 0..3 unquantize to 0, 21, 43, 64 and the grid matches the
+        // block, so each texel is (c0 * 257 * (64 - w) + c1 * 257 * w + 32) >> 6 over 65535.
+        let block = rgba_direct_block(
+            [0, 255, 255, 0, 32, 224, 0, 255],
+            [0, 1, 2, 3, 3, 2, 1, 0, 1, 1, 2, 2, 0, 3, 0, 3],
+        );
+        let mut out = [[0.; 4]; 16];
+        decode(&block, 4, 4, false, &mut out);
+        let texel = |c: [u32; 4]| c.map(|v| v as f32 / 65535.);
+        assert_texels(&out, &[
+            (0, texel([0, 65535, 8224, 0])),
+            (1, texel([21504, 44031, 24415, 21504])),
+            (2, texel([44031, 21504, 41377, 44031])),
+            (3, texel([65535, 0, 57568, 65535])),
+            (5, texel([44031, 21504, 41377, 44031])),
+            (13, texel([65535, 0, 57568, 65535])),
+        ]);
+    }
+
+    #[test]
+    fn void_extent_ldr() {
+        let mut out = [[0.; 4]; 16];
+        decode(&void_extent_block(false, [0xFFFF, 0, 0x8080, 0xFFFF]), 4, 4, false, &mut out);
+        assert_texels(&out, &[(0, [1., 0., 128. / 255., 1.]), (15, [1., 0., 128. / 255., 1.])]);
+    }
+
+    #[test]
+    fn void_extent_hdr() {
+        let mut out = [[0.; 4]; 36];
+        decode(&void_extent_block(true, [0x3C00, 0x4000, 0, 0x3C00]), 6, 6, true, &mut out);
+        assert_texels(&out, &[(0, [1., 2., 0., 1.]), (35, [1., 2., 0., 1.])]);
+    }
+}
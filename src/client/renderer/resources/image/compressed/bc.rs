@@ -0,0 +1,165 @@
+//! BC1 to BC5, the S3TC and RGTC formats
+
+pub fn bc1(block: &[u8], out: &mut [[f32; 4]]) {
+    color(block, true, out);
+}
+
+/// BC1 color with explicit 4 bit alpha
+pub fn bc2(block: &[u8], out: &mut [[f32; 4]]) {
+    color(&block[8..], false, out);
+    let alpha = u64::from_le_bytes(block[..8].try_into().unwrap());
+    for (i, texel) in out.iter_mut().enumerate() {
+        texel[3] = ((alpha >> (i * 4)) & 0xF) as f32 / 15.;
+    }
+}
+
+/// BC1 color with BC4 alpha
+pub fn bc3(block: &[u8], out: &mut [[f32; 4]]) {
+    color(&block[8..], false, out);
+    let alpha = channel(&block[..8], false);
+    for (texel, a) in out.iter_mut().zip(alpha) { texel[3] = a }
+}
+
+pub fn bc4(block: &[u8], signed: bool, out: &mut [[f32; 4]]) {
+    let red = channel(block, signed);
+    for (texel, r) in out.iter_mut().zip(red) { *texel = [r, 0., 0., 1.] }
+}
+
+pub fn bc5(block: &[u8], signed: bool, out: &mut [[f32; 4]]) {
+    let red = channel(&block[..8], signed);
+    let green = channel(&block[8..], signed);
+    for (i, texel) in out.iter_mut().enumerate() { *texel = [red[i], green[i], 0., 1.] }
+}
+
+/// Whether a BC1 block is in three color mode and has texels of its transparent color
+pub fn punch_through(block: &[u8]) -> bool {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let indices = u32::from_le_bytes(block[4..8].try_into().unwrap());
+    c0 <= c1 && (0..16).any(|i| (indices >> (i * 2)) & 3 == 3)
+}
+
+/// Two RGB565 endpoints and 2 bit indices.
+/// With `punch_through`, blocks whose first endpoint isn't greater use three colors and transparent black.
+fn color(block: &[u8], punch_through: bool, out: &mut [[f32; 4]]) {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (e0, e1) = (rgb565(c0), rgb565(c1));
+    let mix = |w0: f32, w1: f32, d: f32| [0, 1, 2].map(|c| (e0[c] * w0 + e1[c] * w1) / d);
+
+    let [r2, g2, b2] = if c0 > c1 || !punch_through { mix(2., 1., 3.) } else { mix(1., 1., 2.) };
+    let palette = [
+        [e0[0], e0[1], e0[2], 1.],
+        [e1[0], e1[1], e1[2], 1.],
+        [r2, g2, b2, 1.],
+        match c0 > c1 || !punch_through {
+            true => { let [r, g, b] = mix(1., 2., 3.); [r, g, b, 1.] }
+            false => [0.; 4],
+        },
+    ];
+
+    let indices = u32::from_le_bytes(block[4..8].try_into().unwrap());
+    for (i, texel) in out.iter_mut().enumerate() {
+        *texel = palette[(indices >> (i * 2)) as usize & 3];
+    }
+}
+
+/// 0..1 per channel, bits replicated like the hardware does
+fn rgb565(c: u16) -> [f32; 3] {
+    let (r, g, b) = ((c >> 11) & 31, (c >> 5) & 63, c & 31);
+    [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2)].map(|v| v as f32 / 255.)
+}
+
+/// A single channel from two 8 bit endpoints and 3 bit indices, -1..1 if `signed`
+fn channel(block: &[u8], signed: bool) -> [f32; 16] {
+    let (a0, a1, descending) = match signed {
+        true => {
+            let (a0, a1) = (block[0] as i8, block[1] as i8);
+            // -128 is the same as -127
+            (a0.max(-127) as f32 / 127., a1.max(-127) as f32 / 127., a0 > a1)
+        }
+        false => (block[0] as f32 / 255., block[1] as f32 / 255., block[0] > block[1]),
+    };
+
+    let mut palette = [a0, a1, 0., 0., 0., 0., 0., 0.];
+    if descending {
+        for i in 1..7 { palette[i + 1] = ((7 - i) as f32 * a0 + i as f32 * a1) / 7. }
+    } else {
+        for i in 1..5 { palette[i + 1] = ((5 - i) as f32 * a0 + i as f32 * a1) / 5. }
+        palette[6] = if signed { -1. } else { 0. };
+        palette[7] = 1.;
+    }
+
+    let mut bytes = [0; 8];
+    bytes[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bytes);
+    std::array::from_fn(|i| palette[(indices >> (i * 3)) as usize & 7])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::assert_texels;
+
+    /// Pure red and pure blue endpoints
+    const RED: [u8; 2] = 0xF800u16.to_le_bytes();
+    const BLUE: [u8; 2] = 0x001Fu16.to_le_bytes();
+
+    #[test]
+    fn bc1_four_colors() {
+        let block = [RED[0], RED[1], BLUE[0], BLUE[1], 0xE4, 0, 0, 0];
+        let mut out = [[0.; 4]; 16];
+        bc1(&block, &mut out);
+        assert_texels(&out, &[
+            (0, [1., 0., 0., 1.]),
+            (1, [0., 0., 1., 1.]),
+            (2, [2. / 3., 0., 1. / 3., 1.]),
+            (3, [1. / 3., 0., 2. / 3., 1.]),
+            (4, [1., 0., 0., 1.]),
+        ]);
+        assert!(!punch_through(&block));
+    }
+
+    #[test]
+    fn bc1_three_colors() {
+        let block = [BLUE[0], BLUE[1], RED[0], RED[1], 0x0E, 0, 0, 0];
+        let mut out = [[0.; 4]; 16];
+        bc1(&block, &mut out);
+        assert_texels(&out, &[(0, [0.5, 0., 0.5, 1.]), (1, [0.; 4]), (2, [0., 0., 1., 1.])]);
+        assert!(punch_through(&block));
+    }
+
+    #[test]
+    fn bc2_explicit_alpha() {
+        let block = [0x0F, 0, 0, 0, 0, 0, 0, 0, RED[0], RED[1], BLUE[0], BLUE[1], 0, 0, 0, 0];
+        let mut out = [[0.; 4]; 16];
+        bc2(&block, &mut out);
+        assert_texels(&out, &[(0, [1., 0., 0., 1.]), (1, [1., 0., 0., 0.])]);
+    }
+
+    #[test]
+    fn bc3_interpolated_alpha() {
+        let block = [255, 0, 0x88, 0, 0, 0, 0, 0, RED[0], RED[1], BLUE[0], BLUE[1], 0, 0, 0, 0];
+        let mut out = [[0.; 4]; 16];
+        bc3(&block, &mut out);
+        assert_texels(&out, &[(0, [1., 0., 0., 1.]), (1, [1., 0., 0., 0.]), (2, [1., 0., 0., 6. / 7.])]);
+    }
+
+    #[test]
+    fn bc4_eight_and_six_values() {
+        let mut out = [[0.; 4]; 16];
+        bc4(&[255, 0, 0x88, 0, 0, 0, 0, 0], false, &mut out);
+        assert_texels(&out, &[(0, [1., 0., 0., 1.]), (1, [0., 0., 0., 1.]), (2, [6. / 7., 0., 0., 1.])]);
+
+        // Ascending endpoints leave indices 6 and 7 for the extremes
+        bc4(&[(-64i8) as u8, 64, 0x3E, 0, 0, 0, 0, 0], true, &mut out);
+        assert_texels(&out, &[(0, [-1., 0., 0., 1.]), (1, [1., 0., 0., 1.])]);
+    }
+
+    #[test]
+    fn bc5_two_channels() {
+        let mut out = [[0.; 4]; 16];
+        bc5(&[255, 0, 0, 0, 0, 0, 0, 0, 0, 255, 0, 0, 0, 0, 0, 0], false, &mut out);
+        assert_texels(&out, &[(0, [1., 0., 0., 1.]), (15, [1., 0., 0., 1.])]);
+    }
+}
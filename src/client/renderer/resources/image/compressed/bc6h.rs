@@ -0,0 +1,164 @@
+//! BC6H, half float RGB in fourteen modes of one or two regions
+
+use {
+    super::{subset_2, weights, Bits, ANCHORS_2},
+    crate::client::renderer::resources::image::pixel::f16_to_f32,
+};
+
+/// Endpoint components: w and x are the first region's endpoints, y and z the second's
+const RW: u8 = 0; const GW: u8 = 1; const BW: u8 = 2;
+const RX: u8 = 3; const GX: u8 = 4; const BX: u8 = 5;
+const RY: u8 = 6; const GY: u8 = 7; const BY: u8 = 8;
+const RZ: u8 = 9; const GZ: u8 = 10; const BZ: u8 = 11;
+
+struct Mode {
+    value: u32,
+    bits: u32,
+    /// Endpoints after the first are stored as deltas
+    transformed: bool,
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    regions: usize,
+    /// Component, bit the first read bit goes to and bit the last goes to, in stream order.
+    /// A few high bits are stored reversed.
+    layout: &'static [(u8, u8, u8)],
+}
+
+const MODES: [Mode; 14] = [
+    Mode { value: 0b00, bits: 2, transformed: true, endpoint_bits: 10, delta_bits: [5, 5, 5], regions: 2,
+        layout: &[(GY, 4, 4), (BY, 4, 4), (BZ, 4, 4), (RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 4), (GZ, 4, 4), (GY, 0, 3), (GX, 0, 4), (BZ, 0, 0), (GZ, 0, 3), (BX, 0, 4), (BZ, 1, 1), (BY, 0, 3), (RY, 0, 4), (BZ, 2, 2), (RZ, 0, 4), (BZ, 3, 3)] },
+    Mode { value: 0b01, bits: 2, transformed: true, endpoint_bits: 7, delta_bits: [6, 6, 6], regions: 2,
+        layout: &[(GY, 5, 5), (GZ, 4, 4), (GZ, 5, 5), (RW, 0, 6), (BZ, 0, 0), (BZ, 1, 1), (BY, 4, 4), (GW, 0, 6), (BY, 5, 5), (BZ, 2, 2), (GY, 4, 4), (BW, 0, 6), (BZ, 3, 3), (BZ, 5, 5), (BZ, 4, 4), (RX, 0, 5), (GY, 0, 3), (GX, 0, 5), (GZ, 0, 3), (BX, 0, 5), (BY, 0, 3), (RY, 0, 5), (RZ, 0, 5)] },
+    Mode { value: 0b00010, bits: 5, transformed: true, endpoint_bits: 11, delta_bits: [5, 4, 4], regions: 2,
+        layout: &[(RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 4), (RW, 10, 10), (GY, 0, 3), (GX, 0, 3), (GW, 10, 10), (BZ, 0, 0), (GZ, 0, 3), (BX, 0, 3), (BW, 10, 10), (BZ, 1, 1), (BY, 0, 3), (RY, 0, 4), (BZ, 2, 2), (RZ, 0, 4), (BZ, 3, 3)] },
+    Mode { value: 0b00110, bits: 5, transformed: true, endpoint_bits: 11, delta_bits: [4, 5, 4], regions: 2,
+        layout: &[(RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 3), (RW, 10, 10), (GZ, 4, 4), (GY, 0, 3), (GX, 0, 4), (GW, 10, 10), (GZ, 0, 3), (BX, 0, 3), (BW, 10, 10), (BZ, 1, 1), (BY, 0, 3), (RY, 0, 3), (BZ, 0, 0), (BZ, 2, 2), (RZ, 0, 3), (GY, 4, 4), (BZ, 3, 3)] },
+    Mode { value: 0b01010, bits: 5, transformed: true, endpoint_bits: 11, delta_bits: [4, 4, 5], regions: 2,
+        layout: &[(RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 3), (RW, 10, 10), (BY, 4, 4), (GY, 0, 3), (GX, 0, 3), (GW, 10, 10), (BZ, 0, 0), (GZ, 0, 3), (BX, 0, 4), (BW, 10, 10), (BY, 0, 3), (RY, 0, 3), (BZ, 1, 1), (BZ, 2, 2), (RZ, 0, 3), (BZ, 4, 4), (BZ, 3, 3)] },
+    Mode { value: 0b01110, bits: 5, transformed: true, endpoint_bits: 9, delta_bits: [5, 5, 5], regions: 2,
+        layout: &[(RW, 0, 8), (BY, 4, 4), (GW, 0, 8), (GY, 4, 4), (BW, 0, 8), (BZ, 4, 4), (RX, 0, 4), (GZ, 4, 4), (GY, 0, 3), (GX, 0, 4), (BZ, 0, 0), (GZ, 0, 3), (BX, 0, 4), (BZ, 1, 1), (BY, 0, 3), (RY, 0, 4), (BZ, 2, 2), (RZ, 0, 4), (BZ, 3, 3)] },
+    Mode { value: 0b10010, bits: 5, transformed: true, endpoint_bits: 8, delta_bits: [6, 5, 5], regions: 2,
+        layout: &[(RW, 0, 7), (GZ, 4, 4), (BY, 4, 4), (GW, 0, 7), (BZ, 2, 2), (GY, 4, 4), (BW, 0, 7), (BZ, 3, 3), (BZ, 4, 4), (RX, 0, 5), (GY, 0, 3), (GX, 0, 4), (BZ, 0, 0), (GZ, 0, 3), (BX, 0, 4), (BZ, 1, 1), (BY, 0, 3), (RY, 0, 5), (RZ, 0, 5)] },
+    Mode { value: 0b10110, bits: 5, transformed: true, endpoint_bits: 8, delta_bits: [5, 6, 5], regions: 2,
+        layout: &[(RW, 0, 7), (BZ, 0, 0), (BY, 4, 4), (GW, 0, 7), (GY, 5, 5), (GY, 4, 4), (BW, 0, 7), (GZ, 5, 5), (BZ, 4, 4), (RX, 0, 4), (GZ, 4, 4), (GY, 0, 3), (GX, 0, 5), (GZ, 0, 3), (BX, 0, 4), (BZ, 1, 1), (BY, 0, 3), (RY, 0, 4), (BZ, 2, 2), (RZ, 0, 4), (BZ, 3, 3)] },
+    Mode { value: 0b11010, bits: 5, transformed: true, endpoint_bits: 8, delta_bits: [5, 5, 6], regions: 2,
+        layout: &[(RW, 0, 7), (BZ, 1, 1), (BY, 4, 4), (GW, 0, 7), (BY, 5, 5), (GY, 4, 4), (BW, 0, 7), (BZ, 5, 5), (BZ, 4, 4), (RX, 0, 4), (GZ, 4, 4), (GY, 0, 3), (GX, 0, 4), (BZ, 0, 0), (GZ, 0, 3), (BX, 0, 5), (BY, 0, 3), (RY, 0, 4), (BZ, 2, 2), (RZ, 0, 4), (BZ, 3, 3)] },
+    Mode { value: 0b11110, bits: 5, transformed: false, endpoint_bits: 6, delta_bits: [6, 6, 6], regions: 2,
+        layout: &[(RW, 0, 5), (GZ, 4, 4), (BZ, 0, 0), (BZ, 1, 1), (BY, 4, 4), (GW, 0, 5), (GY, 5, 5), (BY, 5, 5), (BZ, 2, 2), (GY, 4, 4), (BW, 0, 5), (GZ, 5, 5), (BZ, 3, 3), (BZ, 5, 5), (BZ, 4, 4), (RX, 0, 5), (GY, 0, 3), (GX, 0, 5), (GZ, 0, 3), (BX, 0, 5), (BY, 0, 3), (RY, 0, 5), (RZ, 0, 5)] },
+    Mode { value: 0b00011, bits: 5, transformed: false, endpoint_bits: 10, delta_bits: [10, 10, 10], regions: 1,
+        layout: &[(RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 9), (GX, 0, 9), (BX, 0, 9)] },
+    Mode { value: 0b00111, bits: 5, transformed: true, endpoint_bits: 11, delta_bits: [9, 9, 9], regions: 1,
+        layout: &[(RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 8), (RW, 10, 10), (GX, 0, 8), (GW, 10, 10), (BX, 0, 8), (BW, 10, 10)] },
+    Mode { value: 0b01011, bits: 5, transformed: true, endpoint_bits: 12, delta_bits: [8, 8, 8], regions: 1,
+        layout: &[(RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 7), (RW, 11, 10), (GX, 0, 7), (GW, 11, 10), (BX, 0, 7), (BW, 11, 10)] },
+    Mode { value: 0b01111, bits: 5, transformed: true, endpoint_bits: 16, delta_bits: [4, 4, 4], regions: 1,
+        layout: &[(RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 3), (RW, 15, 10), (GX, 0, 3), (GW, 15, 10), (BX, 0, 3), (BW, 15, 10)] },
+];
+
+pub fn decode(block: &[u8], signed: bool, out: &mut [[f32; 4]]) {
+    let mut bits = Bits::new(block);
+    let (value, count) = match bits.peek(0, 2) {
+        low @ (0 | 1) => (low, 2),
+        _ => (bits.peek(0, 5), 5),
+    };
+    bits.pos = count;
+    // Reserved modes decode to black
+    let Some(mode) = MODES.iter().find(|m| m.bits == count && m.value == value) else {
+        out.fill([0., 0., 0., 1.]);
+        return
+    };
+
+    let mut endpoints = [0i32; 12];
+    for &(component, first, last) in mode.layout {
+        let (first, last) = (first as i32, last as i32);
+        let step = if last >= first { 1 } else { -1 };
+        for i in 0..=(last - first).abs() {
+            endpoints[component as usize] |= (bits.read(1) as i32) << (first + i * step);
+        }
+    }
+    let partition = if mode.regions == 2 { bits.read(5) as usize } else { 0 };
+
+    let count = mode.regions * 6;
+    let epb = mode.endpoint_bits;
+    if signed {
+        for e in &mut endpoints[..3] { *e = sign_extend(*e, epb) }
+    }
+    if mode.transformed || signed {
+        for (i, e) in endpoints[..count].iter_mut().enumerate().skip(3) {
+            *e = sign_extend(*e, if mode.transformed { mode.delta_bits[i % 3] } else { epb });
+        }
+    }
+    if mode.transformed {
+        for i in 3..count {
+            endpoints[i] = (endpoints[i % 3] + endpoints[i]) & ((1 << epb) - 1);
+            if signed { endpoints[i] = sign_extend(endpoints[i], epb) }
+        }
+    }
+    let endpoints = endpoints.map(|e| unquantize(e, epb, signed));
+
+    let index_bits = if mode.regions == 2 { 3 } else { 4 };
+    let weights = weights(index_bits);
+    for (texel, out) in out.iter_mut().enumerate() {
+        let (region, anchor) = match mode.regions {
+            2 => (subset_2(partition, texel), texel == 0 || texel == ANCHORS_2[partition] as usize),
+            _ => (0, texel == 0),
+        };
+        let w = weights[bits.read(index_bits - anchor as u32) as usize] as i32;
+        let [r, g, b] = [0, 1, 2].map(|c| {
+            let (e0, e1) = (endpoints[region * 6 + c], endpoints[region * 6 + 3 + c]);
+            f16_to_f32(finish((e0 * (64 - w) + e1 * w + 32) >> 6, signed))
+        });
+        *out = [r, g, b, 1.];
+    }
+}
+
+fn sign_extend(v: i32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    (v << shift) >> shift
+}
+
+/// Scales an endpoint to the full 16 bit range before interpolation
+fn unquantize(v: i32, bits: u32, signed: bool) -> i32 {
+    if !signed {
+        return match v {
+            _ if bits >= 15 => v,
+            0 => 0,
+            _ if v == (1 << bits) - 1 => 0xFFFF,
+            _ => ((v << 16) + 0x8000) >> bits,
+        }
+    }
+
+    if bits >= 16 { return v }
+    let magnitude = match v.abs() {
+        0 => 0,
+        m if m >= (1 << (bits - 1)) - 1 => 0x7FFF,
+        m => ((m << 15) + 0x4000) >> (bits - 1),
+    };
+    if v < 0 { -magnitude } else { magnitude }
+}
+
+/// Scales an interpolated value to half float bits
+fn finish(v: i32, signed: bool) -> u16 {
+    match signed {
+        false => ((v * 31) >> 6) as u16,
+        true if v < 0 => 0x8000 | ((-v * 31) >> 5) as u16,
+        true => ((v * 31) >> 5) as u16,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::assert_texels;
+
+    #[test]
+    fn mode_11_unsigned_endpoint() {
+        // Mode 11: a single region with 10 bit endpoints; every index 0 selects the first endpoint
+        let bits: u128 = 0b00011 | (0x3FF << 5) | (512 << 15);
+        let mut out = [[0.; 4]; 16];
+        decode(&bits.to_le_bytes(), false, &mut out);
+        // Full scale maps to the largest half float, 512 to 0x3E0F
+        let texel = [65504., 1. + 527. / 1024., 0., 1.];
+        assert_texels(&out, &[(0, texel), (15, texel)]);
+    }
+}
@@ -0,0 +1,181 @@
+//! BC7, eight modes of one to three subsets with optional separate alpha
+
+use super::{subset_2, weights, Bits, ANCHORS_2};
+
+struct Mode {
+    subsets: u32,
+    partition_bits: u32,
+    rotation_bits: u32,
+    selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    /// One p-bit per endpoint
+    endpoint_pbits: bool,
+    /// One p-bit per subset
+    shared_pbits: bool,
+    index_bits: u32,
+    /// Second index set, for alpha or color depending on the selection bit
+    index_bits_2: u32,
+}
+
+#[allow(clippy::too_many_arguments)]
+const fn mode(subsets: u32, partition_bits: u32, rotation_bits: u32, selection_bits: u32, color_bits: u32, alpha_bits: u32,
+    endpoint_pbits: bool, shared_pbits: bool, index_bits: u32, index_bits_2: u32) -> Mode {
+    Mode { subsets, partition_bits, rotation_bits, selection_bits, color_bits, alpha_bits, endpoint_pbits, shared_pbits, index_bits, index_bits_2 }
+}
+
+const MODES: [Mode; 8] = [
+    mode(3, 4, 0, 0, 4, 0, true, false, 3, 0),
+    mode(2, 6, 0, 0, 6, 0, false, true, 3, 0),
+    mode(3, 6, 0, 0, 5, 0, false, false, 2, 0),
+    mode(2, 6, 0, 0, 7, 0, true, false, 2, 0),
+    mode(1, 0, 2, 1, 5, 6, false, false, 2, 3),
+    mode(1, 0, 2, 0, 7, 8, false, false, 2, 2),
+    mode(1, 0, 0, 0, 7, 7, true, false, 4, 0),
+    mode(2, 6, 0, 0, 5, 5, true, false, 2, 0),
+];
+
+/// Subset of each texel for three subsets, two bits per texel
+const PARTITIONS_3: [u32; 64] = [
+    0xAA685050, 0x6A5A5040, 0x5A5A4200, 0x5450A0A8, 0xA5A50000, 0xA0A05050, 0x5555A0A0, 0x5A5A5050,
+    0xAA550000, 0xAA555500, 0xAAAA5500, 0x90909090, 0x94949494, 0xA4A4A4A4, 0xA9A59450, 0x2A0A4250,
+    0xA5945040, 0x0A425054, 0xA5A5A500, 0x55A0A0A0, 0xA8A85454, 0x6A6A4040, 0xA4A45000, 0x1A1A0500,
+    0x0050A4A4, 0xAAA59090, 0x14696914, 0x69691400, 0xA08585A0, 0xAA821414, 0x50A4A450, 0x6A5A0200,
+    0xA9A58000, 0x5090A0A8, 0xA8A09050, 0x24242424, 0x00AA5500, 0x24924924, 0x24499224, 0x50A50A50,
+    0x500AA550, 0xAAAA4444, 0x66660000, 0xA5A0A5A0, 0x50A050A0, 0x69286928, 0x44AAAA44, 0x66666600,
+    0xAA444444, 0x54A854A8, 0x95809580, 0x96969600, 0xA85454A8, 0x80959580, 0xAA141414, 0x96960000,
+    0xAAAA1414, 0xA05050A0, 0xA0A5A5A0, 0x96000000, 0x40804080, 0xA9A8A9A8, 0xAAAAAA44, 0x2A4A5254,
+];
+
+/// Anchor texels of the second and third of three subsets
+const ANCHORS_3: [[u8; 64]; 2] = [[
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3,
+    3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5, 15, 15,
+    8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15,
+    3, 15, 5, 5, 5, 8, 5, 10, 5, 10, 8, 13, 15, 12, 3, 3,
+], [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8,
+    15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6, 10, 15, 15, 10, 8,
+    15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8,
+    15, 3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+]];
+
+pub fn decode(block: &[u8], out: &mut [[f32; 4]]) {
+    let mut bits = Bits::new(block);
+    // The mode is the number of zeros before the first set bit
+    let Some(mode_index) = (0..8).find(|_| bits.read(1) == 1) else {
+        out.fill([0.; 4]);
+        return
+    };
+    let mode = &MODES[mode_index];
+
+    let partition = bits.read(mode.partition_bits) as usize;
+    let rotation = bits.read(mode.rotation_bits);
+    let selection = bits.read(mode.selection_bits);
+
+    let endpoint_count = mode.subsets as usize * 2;
+    let mut endpoints = [[0u32; 4]; 6];
+    for c in 0..3 {
+        for endpoint in &mut endpoints[..endpoint_count] { endpoint[c] = bits.read(mode.color_bits) }
+    }
+    for endpoint in &mut endpoints[..endpoint_count] {
+        endpoint[3] = if mode.alpha_bits > 0 { bits.read(mode.alpha_bits) } else { 255 };
+    }
+
+    let mut pbits = [None; 6];
+    if mode.endpoint_pbits {
+        for pbit in &mut pbits[..endpoint_count] { *pbit = Some(bits.read(1)) }
+    }
+    if mode.shared_pbits {
+        for subset in 0..mode.subsets as usize {
+            let pbit = bits.read(1);
+            pbits[subset * 2] = Some(pbit);
+            pbits[subset * 2 + 1] = Some(pbit);
+        }
+    }
+
+    for (endpoint, pbit) in endpoints[..endpoint_count].iter_mut().zip(pbits) {
+        let channels = if mode.alpha_bits > 0 { 4 } else { 3 };
+        for (c, value) in endpoint.iter_mut().enumerate().take(channels) {
+            let precision = if c < 3 { mode.color_bits } else { mode.alpha_bits };
+            let (value_bits, precision) = match pbit {
+                Some(p) => ((*value << 1) | p, precision + 1),
+                None => (*value, precision),
+            };
+            *value = expand(value_bits, precision);
+        }
+    }
+
+    let subset = |texel: usize| match mode.subsets {
+        1 => 0,
+        2 => subset_2(partition, texel),
+        _ => (PARTITIONS_3[partition] >> (texel * 2)) as usize & 3,
+    };
+    let is_anchor = |texel: usize| texel == 0 || match mode.subsets {
+        1 => false,
+        2 => ANCHORS_2[partition] as usize == texel,
+        _ => ANCHORS_3[0][partition] as usize == texel || ANCHORS_3[1][partition] as usize == texel,
+    };
+
+    let primary: [u32; 16] = std::array::from_fn(|texel| bits.read(mode.index_bits - is_anchor(texel) as u32));
+    let secondary: [u32; 16] = std::array::from_fn(|texel| match mode.index_bits_2 {
+        0 => 0,
+        n => bits.read(n - (texel == 0) as u32),
+    });
+
+    // The selection bit swaps which index set drives color and which alpha
+    let (color_bits, alpha_bits) = match (mode.index_bits_2, selection) {
+        (0, _) => (mode.index_bits, mode.index_bits),
+        (n, 0) => (mode.index_bits, n),
+        (n, _) => (n, mode.index_bits),
+    };
+
+    for (texel, out) in out.iter_mut().enumerate() {
+        let s = subset(texel);
+        let (e0, e1) = (endpoints[s * 2], endpoints[s * 2 + 1]);
+        let (color_index, alpha_index) = match (mode.index_bits_2, selection) {
+            (0, _) => (primary[texel], primary[texel]),
+            (_, 0) => (primary[texel], secondary[texel]),
+            _ => (secondary[texel], primary[texel]),
+        };
+        let color_weight = weights(color_bits)[color_index as usize];
+        let alpha_weight = weights(alpha_bits)[alpha_index as usize];
+
+        let mut texel: [u32; 4] = std::array::from_fn(|c| {
+            let w = if c < 3 { color_weight } else { alpha_weight };
+            ((64 - w) * e0[c] + w * e1[c] + 32) >> 6
+        });
+        match rotation {
+            1 => texel.swap(0, 3),
+            2 => texel.swap(1, 3),
+            3 => texel.swap(2, 3),
+            _ => (),
+        }
+        *out = texel.map(|v| v as f32 / 255.);
+    }
+}
+
+/// Replicates the top bits of a `precision` bit value to fill 8 bits
+fn expand(value: u32, precision: u32) -> u32 {
+    let value = value << (8 - precision);
+    value | (value >> precision)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::assert_texels;
+
+    #[test]
+    fn mode_6_endpoints_and_p_bits() {
+        // Mode 6: one subset of 7 bit RGBA endpoints, a p-bit each and 4 bit indices
+        let bits: u128 = (1 << 6) | (127 << 7) | (64 << 35) | (127 << 49) | (1 << 63) | (15 << 68);
+        let mut out = [[0.; 4]; 16];
+        decode(&bits.to_le_bytes(), &mut out);
+        assert_texels(&out, &[
+            (0, [1., 1. / 255., 129. / 255., 1.]),
+            (1, [0.; 4]),
+            (2, [1., 1. / 255., 129. / 255., 1.]),
+        ]);
+    }
+}
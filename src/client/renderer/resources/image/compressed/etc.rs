@@ -0,0 +1,229 @@
+//! ETC2 color and EAC single channel formats. Blocks are big endian and texels indexed column major.
+
+/// Intensity modifiers of the individual and differential modes, the large one is negated for index 3
+const MODIFIERS: [[i32; 2]; 8] = [[2, 8], [5, 17], [9, 29], [13, 42], [18, 60], [24, 80], [33, 106], [47, 183]];
+
+/// Distances of the T and H modes
+const DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+/// RGB, with `punch_through` the differential bit instead marks blocks that may have transparent texels
+pub fn etc2(block: &[u8], punch_through: bool, out: &mut [[f32; 4]]) {
+    let b = u64::from_be_bytes(block[..8].try_into().unwrap());
+    let field = |shift: u32, bits: u32| ((b >> shift) & ((1 << bits) - 1)) as i32;
+    let bit = |shift: u32| field(shift, 1);
+
+    let differential = bit(33) == 1;
+    // Punch-through blocks are always differential, the bit says whether they are opaque
+    let (differential, opaque) = if punch_through { (true, differential) } else { (differential, true) };
+
+    // Index of texel (x, y) from its two bit planes
+    let index = |x: usize, y: usize| {
+        let i = x * 4 + y;
+        (((b >> (16 + i)) & 1) << 1 | ((b >> i) & 1)) as usize
+    };
+
+    if !differential {
+        let base = [[field(60, 4), field(52, 4), field(44, 4)], [field(56, 4), field(48, 4), field(40, 4)]]
+            .map(|c| c.map(|v| v << 4 | v));
+        return sub_blocks(b, base, punch_through && !opaque, &index, out)
+    }
+
+    let (r, g, bl) = (field(59, 5), field(51, 5), field(43, 5));
+    let delta = |shift: u32| (field(shift, 3) << 29) >> 29;
+    let (r2, g2, b2) = (r + delta(56), g + delta(48), bl + delta(40));
+
+    if !(0..32).contains(&r2) {
+        // T mode
+        let c1 = [field(59, 2) << 2 | field(56, 2), field(52, 4), field(48, 4)].map(|v| v << 4 | v);
+        let c2 = [field(44, 4), field(40, 4), field(36, 4)].map(|v| v << 4 | v);
+        let d = DISTANCES[(field(34, 2) << 1 | bit(32)) as usize];
+        let paint = [c1, c2.map(|c| c + d), c2, c2.map(|c| c - d)];
+        return paint_colors(paint, !opaque, &index, out)
+    }
+    if !(0..32).contains(&g2) {
+        // H mode
+        let c1 = [field(59, 4), field(56, 3) << 1 | bit(52), bit(51) << 3 | field(47, 3)];
+        let c2 = [field(43, 4), field(39, 4), field(35, 4)];
+        let order = (c1[0] << 8 | c1[1] << 4 | c1[2]) >= (c2[0] << 8 | c2[1] << 4 | c2[2]);
+        let d = DISTANCES[(bit(34) << 2 | bit(32) << 1 | order as i32) as usize];
+        let (c1, c2) = (c1.map(|v| v << 4 | v), c2.map(|v| v << 4 | v));
+        let paint = [c1.map(|c| c + d), c1.map(|c| c - d), c2.map(|c| c + d), c2.map(|c| c - d)];
+        return paint_colors(paint, !opaque, &index, out)
+    }
+    if !(0..32).contains(&b2) {
+        // Planar mode, always opaque
+        let six = |v: i32| v << 2 | v >> 4;
+        let seven = |v: i32| v << 1 | v >> 6;
+        let o = [six(field(57, 6)), seven(bit(56) << 6 | field(49, 6)), six(bit(48) << 5 | field(43, 2) << 3 | field(39, 3))];
+        let h = [six(field(34, 5) << 1 | bit(32)), seven(field(25, 7)), six(field(19, 6))];
+        let v = [six(field(13, 6)), seven(field(6, 7)), six(field(0, 6))];
+        for y in 0..4 {
+            for x in 0..4 {
+                let (xi, yi) = (x as i32, y as i32);
+                let rgb = [0, 1, 2].map(|c| (xi * (h[c] - o[c]) + yi * (v[c] - o[c]) + 4 * o[c] + 2) >> 2);
+                out[y * 4 + x] = rgba(rgb);
+            }
+        }
+        return
+    }
+
+    let base = [[r, g, bl], [r2, g2, b2]].map(|c| c.map(|v| v << 3 | v >> 2));
+    sub_blocks(b, base, punch_through && !opaque, &index, out)
+}
+
+fn rgba(rgb: [i32; 3]) -> [f32; 4] {
+    let [r, g, b] = rgb.map(|c| c.clamp(0, 255) as f32 / 255.);
+    [r, g, b, 1.]
+}
+
+/// Individual and differential modes: two sub-blocks, each a base color plus an intensity modifier
+fn sub_blocks(
+    b: u64,
+    base: [[i32; 3]; 2],
+    transparent: bool,
+    index: &impl Fn(usize, usize) -> usize,
+    out: &mut [[f32; 4]],
+) {
+    let tables = [(b >> 37) & 7, (b >> 34) & 7].map(|t| MODIFIERS[t as usize]);
+    let flip = (b >> 32) & 1 == 1;
+    for y in 0..4 {
+        for x in 0..4 {
+            let sub = if flip { y / 2 } else { x / 2 };
+            let [small, large] = tables[sub];
+            let i = index(x, y);
+            // Without the opaque bit, index 2 is transparent and index 0 keeps the base color
+            if transparent && i == 2 {
+                out[y * 4 + x] = [0.; 4];
+                continue
+            }
+            let modifier = match i {
+                0 if transparent => 0,
+                0 => small,
+                1 => large,
+                2 => -small,
+                _ => -large,
+            };
+            out[y * 4 + x] = rgba(base[sub].map(|c| c + modifier));
+        }
+    }
+}
+
+/// T and H modes: the index picks one of four colors, index 2 is transparent without the opaque bit
+fn paint_colors(
+    paint: [[i32; 3]; 4],
+    transparent: bool,
+    index: &impl Fn(usize, usize) -> usize,
+    out: &mut [[f32; 4]],
+) {
+    for y in 0..4 {
+        for x in 0..4 {
+            let i = index(x, y);
+            out[y * 4 + x] = if transparent && i == 2 { [0.; 4] } else { rgba(paint[i]) };
+        }
+    }
+}
+
+/// ETC2 color with EAC alpha in front
+pub fn etc2_eac(block: &[u8], out: &mut [[f32; 4]]) {
+    etc2(&block[8..], false, out);
+    let alpha = eac(&block[..8]);
+    for (texel, a) in out.iter_mut().zip(alpha) {
+        texel[3] = a.clamp(0, 255) as f32 / 255.;
+    }
+}
+
+pub fn eac_r11(block: &[u8], signed: bool, out: &mut [[f32; 4]]) {
+    let red = eac11(block, signed);
+    for (texel, r) in out.iter_mut().zip(red) { *texel = [r, 0., 0., 1.] }
+}
+
+pub fn eac_rg11(block: &[u8], signed: bool, out: &mut [[f32; 4]]) {
+    let red = eac11(&block[..8], signed);
+    let green = eac11(&block[8..], signed);
+    for (i, texel) in out.iter_mut().enumerate() { *texel = [red[i], green[i], 0., 1.] }
+}
+
+/// Base value, multiplier and modifier table, returning the raw 3 bit indices in row major order
+fn eac_fields(block: &[u8]) -> (i32, i32, &'static [i32; 8], [usize; 16]) {
+    let b = u64::from_be_bytes(block[..8].try_into().unwrap());
+    let base = (b >> 56) as u8 as i32;
+    let multiplier = ((b >> 52) & 0xF) as i32;
+    let table = &EAC_MODIFIERS[((b >> 48) & 0xF) as usize];
+    let indices = std::array::from_fn(|i| {
+        let (x, y) = (i % 4, i / 4);
+        ((b >> (45 - 3 * (x * 4 + y))) & 7) as usize
+    });
+    (base, multiplier, table, indices)
+}
+
+/// 8 bit alpha, unclamped
+fn eac(block: &[u8]) -> [i32; 16] {
+    let (base, multiplier, table, indices) = eac_fields(block);
+    indices.map(|i| base + table[i] * multiplier)
+}
+
+/// 11 bit channel normalized to 0..1, or -1..1 if `signed`
+fn eac11(block: &[u8], signed: bool) -> [f32; 16] {
+    let (base, multiplier, table, indices) = eac_fields(block);
+    match signed {
+        false => indices.map(|i| {
+            let modifier = if multiplier == 0 { table[i] } else { table[i] * multiplier * 8 };
+            (base * 8 + 4 + modifier).clamp(0, 2047) as f32 / 2047.
+        }),
+        true => {
+            // -128 is the same as -127
+            let base = (base as u8 as i8).max(-127) as i32;
+            indices.map(|i| {
+                let modifier = if multiplier == 0 { table[i] } else { table[i] * multiplier * 8 };
+                (base * 8 + modifier).clamp(-1023, 1023) as f32 / 1023.
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::assert_texels;
+
+    #[test]
+    fn etc2_individual_mode() {
+        // 4 bit colors F, 8 and 0 in both halves, modifier table 0; the first texel takes the large negative modifier
+        let block = [0xFF, 0x88, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01];
+        let mut out = [[0.; 4]; 16];
+        etc2(&block, false, &mut out);
+        assert_texels(&out, &[
+            (0, [247. / 255., 128. / 255., 0., 1.]),
+            (1, [1., 138. / 255., 2. / 255., 1.]),
+        ]);
+    }
+
+    #[test]
+    fn eac_r11_unsigned() {
+        // Base 128, multiplier 1, table 0 and index 4 everywhere, a modifier of +2
+        let block = [0x80, 0x10, 146, 73, 36, 146, 73, 36];
+        let mut out = [[0.; 4]; 16];
+        eac_r11(&block, false, &mut out);
+        let red = (128. * 8. + 4. + 2. * 8.) / 2047.;
+        assert_texels(&out, &[(0, [red, 0., 0., 1.]), (15, [red, 0., 0., 1.])]);
+    }
+}
@@ -0,0 +1,153 @@
+//! CPU decoders for block compressed formats, used when the device can't sample them
+
+mod bc;
+mod bc6h;
+mod bc7;
+mod etc;
+mod astc;
+
+use {
+    super::PixelFormat,
+    crate::math::Vec2,
+    wgpu::TextureFormat as F,
+};
+
+/// Decodes a whole level into tightly packed pixels.
+/// Unsigned LDR formats decode to `Rgba8`; HDR and signed formats to `Rgba16Float`, keeping their range.
+/// Channels missing from the format read as 0, alpha as 1, like when sampling the compressed texture.
+pub fn decompress(format: wgpu::TextureFormat, data: &[u8], size: Vec2<u32>) -> (Vec<u8>, PixelFormat) {
+    let format = format.remove_srgb_suffix();
+    let out_format = match is_float(format) {
+        true => PixelFormat::Rgba16Float,
+        false => PixelFormat::Rgba8,
+    };
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format.block_size(None).unwrap_or(16) as usize;
+    let pixel_size = out_format.block_size() as usize;
+    let blocks_x = size.x.div_ceil(block_width);
+    let blocks_y = size.y.div_ceil(block_height);
+
+    let mut bytes = vec![0; (size.x * size.y) as usize * pixel_size];
+    let mut texels = vec![[0.; 4]; (block_width * block_height) as usize];
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            let start = (by * blocks_x + bx) as usize * block_size;
+            // Truncated data decodes as zeros rather than failing the whole texture
+            let mut block = [0; 16];
+            if let Some(src) = data.get(start..start + block_size) { block[..block_size].copy_from_slice(src) }
+            decode_block(format, &block[..block_size], &mut texels);
+
+            for ty in 0..block_height {
+                let y = by * block_height + ty;
+                if y >= size.y { break }
+                for tx in 0..block_width {
+                    let x = bx * block_width + tx;
+                    if x >= size.x { break }
+                    let offset = (y * size.x + x) as usize * pixel_size;
+                    out_format.store(texels[(ty * block_width + tx) as usize], &mut bytes[offset..]);
+                }
+            }
+        }
+    }
+    (bytes, out_format)
+}
+
+/// Whether any block of BC1 data uses the transparent fourth color of three color blocks
+pub fn bc1_punch_through(data: &[u8]) -> bool {
+    data.chunks_exact(8).any(bc::punch_through)
+}
+
+fn is_float(format: wgpu::TextureFormat) -> bool {
+    matches!(format, F::Bc4RSnorm | F::Bc5RgSnorm | F::Bc6hRgbUfloat | F::Bc6hRgbFloat | F::EacR11Snorm | F::EacRg11Snorm
+        | F::Astc { channel: wgpu::AstcChannel::Hdr, .. })
+}
+
+/// Texels of one block in row major order
+fn decode_block(format: wgpu::TextureFormat, block: &[u8], out: &mut [[f32; 4]]) {
+    match format {
+        F::Bc1RgbaUnorm => bc::bc1(block, out),
+        F::Bc2RgbaUnorm => bc::bc2(block, out),
+        F::Bc3RgbaUnorm => bc::bc3(block, out),
+        F::Bc4RUnorm | F::Bc4RSnorm => bc::bc4(block, format == F::Bc4RSnorm, out),
+        F::Bc5RgUnorm | F::Bc5RgSnorm => bc::bc5(block, format == F::Bc5RgSnorm, out),
+        F::Bc6hRgbUfloat | F::Bc6hRgbFloat => bc6h::decode(block, format == F::Bc6hRgbFloat, out),
+        F::Bc7RgbaUnorm => bc7::decode(block, out),
+        F::Etc2Rgb8Unorm => etc::etc2(block, false, out),
+        F::Etc2Rgb8A1Unorm => etc::etc2(block, true, out),
+        F::Etc2Rgba8Unorm => etc::etc2_eac(block, out),
+        F::EacR11Unorm | F::EacR11Snorm => etc::eac_r11(block, format == F::EacR11Snorm, out),
+        F::EacRg11Unorm | F::EacRg11Snorm => etc::eac_rg11(block, format == F::EacRg11Snorm, out),
+        F::Astc { channel, .. } => {
+            let (width, height) = format.block_dimensions();
+            astc::decode(block, width, height, channel == wgpu::AstcChannel::Hdr, out)
+        }
+        f => unreachable!("{f:?} isn't block compressed"),
+    }
+}
+
+/// Reads bit fields of a little endian block, lowest bits first
+struct Bits {
+    value: u128,
+    pos: u32,
+}
+
+impl Bits {
+    fn new(block: &[u8]) -> Self {
+        let mut bytes = [0; 16];
+        bytes[..block.len()].copy_from_slice(block);
+        Self { value: u128::from_le_bytes(bytes), pos: 0 }
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        let value = self.peek(self.pos, count);
+        self.pos += count;
+        value
+    }
+
+    /// `count` bits starting at `pos`, zeros past the end of the block
+    fn peek(&self, pos: u32, count: u32) -> u32 {
+        if count == 0 || pos >= 128 { return 0 }
+        ((self.value >> pos) & ((1u128 << count) - 1)) as u32
+    }
+}
+
+/// Interpolation weights of BC6H and BC7 indices, out of 64
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+fn weights(index_bits: u32) -> &'static [u32] {
+    match index_bits {
+        2 => &WEIGHTS_2,
+        3 => &WEIGHTS_3,
+        _ => &WEIGHTS_4,
+    }
+}
+
+/// Subset of each texel for two subsets, bit `i` is texel `i`. Shared by BC6H and BC7.
+const PARTITIONS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80, 0xC800, 0xFFEC, 0xFE80, 0xE800, 0xFFE8, 0xFF00, 0xFFF0, 0xF000,
+    0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE, 0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C,
+    0xAAAA, 0xF0F0, 0x5A5A, 0x33CC, 0x3C3C, 0x55AA, 0x9696, 0xA55A, 0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C, 0x9336, 0x9CC6, 0x817E, 0xE718, 0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+/// Texel whose index drops its top bit, for the second of two subsets
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+    15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2,
+    15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6,
+    6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+fn subset_2(partition: usize, texel: usize) -> usize {
+    (PARTITIONS_2[partition] >> texel) as usize & 1
+}
+
+#[cfg(test)]
+fn assert_texels(actual: &[[f32; 4]], expected: &[(usize, [f32; 4])]) {
+    for &(i, texel) in expected {
+        let close = actual[i].iter().zip(texel).all(|(a, b)| (a - b).abs() < 1e-5);
+        assert!(close, "texel {i} is {:?}, expected {texel:?}", actual[i]);
+    }
+}
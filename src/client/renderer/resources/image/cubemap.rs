@@ -1,5 +1,5 @@
 use crate::client::{renderer::{gpu::bind_group::BindGroup, state::State}, PathManager};
-use super::RawImage;
+use {super::RawImage, std::borrow::Cow};
 
 pub struct CubeMap {
     pub bg: BindGroup,
@@ -14,7 +14,7 @@ impl CubeMap {
     pub fn from_paths<T: AsRef<std::path::Path>>(state: &State, path_m: &PathManager, src: [T; 6], label: &str) -> Result<Self, super::ImageError> {
         let mut error = None;
         let src = src.map(
-            |path| super::RawImage::import_png(path_m.cubemap(path.as_ref()))
+            |path| super::RawImage::import(path_m.cubemap(path.as_ref()))
             .unwrap_or_else(
                 |e| {
                     error = Some(e);
@@ -26,19 +26,25 @@ impl CubeMap {
         Ok(Self::from_raw(state, src, label))
     }
 
-    /// Face order: right, left, top, bottom, front, back
+    /// Face order: right, left, top, bottom, front, back.
+    /// Every face must have the size and pixel format of the first.
     pub fn from_raw(state: &State, src: [RawImage; 6], label: &str) -> Self {
-
+        let features = state.device.features();
+        let src = src.map(|img| match img.for_upload(features) {
+            Cow::Owned(uploaded) => uploaded,
+            Cow::Borrowed(_) => img,
+        });
+        let format = src[0].format.texture_format(wgpu::TextureFormat::Rgba8Unorm);
 
         let size = src[0].texture_size(6);
-        let layer_size = src[0].texture_size(1);
+        let layer_size = src[0].texture_size(1).physical_size(format);
 
         let desc = wgpu::TextureDescriptor {
             size,
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
+            format,
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
             label: Some(label),
             view_formats: &[],
@@ -55,6 +61,11 @@ impl CubeMap {
             )
         }
 
+        Self::from_texture(state, texture, wgpu::FilterMode::Nearest, label)
+    }
+
+    /// Wraps a texture of six layers, e.g. one rendered on the GPU, sampled with `filter`
+    pub fn from_texture(state: &State, texture: wgpu::Texture, filter: wgpu::FilterMode, label: &str) -> Self {
         let view = texture.create_view(&wgpu::TextureViewDescriptor {
             label: Some(label),
             dimension: Some(wgpu::TextureViewDimension::Cube),
@@ -65,9 +76,9 @@ impl CubeMap {
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: filter,
             ..Default::default()
         });
 
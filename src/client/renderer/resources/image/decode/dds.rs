@@ -0,0 +1,174 @@
+use {
+    super::{Bytes, super::{ImageError, PixelFormat, RawImage}},
+    std::path::Path,
+};
+
+const MAGIC: &[u8] = b"DDS ";
+const HEADER_SIZE: u32 = 124;
+
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_ALPHA: u32 = 0x2;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDPF_LUMINANCE: u32 = 0x20000;
+
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_VOLUME: u32 = 0x20_0000;
+const D3D10_RESOURCE_DIMENSION_TEXTURE2D: u32 = 3;
+
+/// How the pixels of the top level are stored
+enum Layout {
+    /// Legacy RGB, luminance or alpha formats described by bit masks
+    Masked { bits: u32, masks: [u32; 4], luminance: bool },
+    Rgba8,
+    Bgra8 { alpha: bool },
+    Rgba16Unorm,
+    Rgba16Float,
+    Rgb32Float,
+    Rgba32Float,
+}
+
+impl Layout {
+    fn bytes_per_pixel(&self) -> usize {
+        match self {
+            Self::Masked { bits, .. } => *bits as usize / 8,
+            Self::Rgba8 | Self::Bgra8 { .. } => 4,
+            Self::Rgba16Unorm | Self::Rgba16Float => 8,
+            Self::Rgb32Float => 12,
+            Self::Rgba32Float => 16,
+        }
+    }
+}
+
+/// Uncompressed 2D textures, legacy or with a DX10 header; only the top mip level is read
+pub fn decode(data: &[u8], path: &Path) -> Result<RawImage, ImageError> {
+    let unsupported = |what: String| ImageError::Unsupported(path.to_owned(), what);
+    let mut r = Bytes::new(data, path);
+
+    if r.take(4)? != MAGIC { return Err(ImageError::Decode(path.to_owned(), "Not a DDS file")) }
+    if r.u32()? != HEADER_SIZE { return Err(ImageError::Decode(path.to_owned(), "Invalid header size")) }
+    let _flags = r.u32()?;
+    let height = r.u32()?;
+    let width = r.u32()?;
+    // Pitch or linear size, often wrong in the wild; rows of uncompressed formats are tightly packed
+    let _pitch = r.u32()?;
+    let _depth = r.u32()?;
+    let _mip_count = r.u32()?;
+    r.skip(11 * 4)?;
+
+    let _pf_size = r.u32()?;
+    let pf_flags = r.u32()?;
+    let fourcc = r.take(4)?;
+    let bits = r.u32()?;
+    let masks = [r.u32()?, r.u32()?, r.u32()?, r.u32()?];
+    let _caps = r.u32()?;
+    let caps2 = r.u32()?;
+    r.skip(3 * 4)?;
+
+    if caps2 & DDSCAPS2_CUBEMAP != 0 { return Err(unsupported("Cube map DDS".into())) }
+    if caps2 & DDSCAPS2_VOLUME != 0 { return Err(unsupported("Volume DDS".into())) }
+
+    let layout = if pf_flags & DDPF_FOURCC != 0 {
+        match fourcc {
+            b"DX10" => {
+                let dxgi_format = r.u32()?;
+                let dimension = r.u32()?;
+                let _misc = r.u32()?;
+                let array_size = r.u32()?;
+                r.skip(4)?;
+                if dimension != D3D10_RESOURCE_DIMENSION_TEXTURE2D || array_size > 1 {
+                    return Err(unsupported("DDS texture that isn't a single 2D texture".into()))
+                }
+                match dxgi_format {
+                    // R8G8B8A8_UNORM(_SRGB), the sRGB flag is up to the texture entry
+                    28 | 29 => Layout::Rgba8,
+                    // B8G8R8A8_UNORM(_SRGB)
+                    87 | 91 => Layout::Bgra8 { alpha: true },
+                    // B8G8R8X8_UNORM(_SRGB)
+                    88 | 93 => Layout::Bgra8 { alpha: false },
+                    10 => Layout::Rgba16Float,
+                    11 => Layout::Rgba16Unorm,
+                    6 => Layout::Rgb32Float,
+                    2 => Layout::Rgba32Float,
+                    f => return Err(unsupported(format!("DXGI format {f}"))),
+                }
+            }
+            // D3DFMT_A16B16G16R16, D3DFMT_A16B16G16R16F and D3DFMT_A32B32G32R32F
+            [36, 0, 0, 0] => Layout::Rgba16Unorm,
+            [113, 0, 0, 0] => Layout::Rgba16Float,
+            [116, 0, 0, 0] => Layout::Rgba32Float,
+            f => return Err(unsupported(format!("DDS FourCC {:?}", String::from_utf8_lossy(f)))),
+        }
+    } else if pf_flags & (DDPF_RGB | DDPF_LUMINANCE | DDPF_ALPHA) != 0 {
+        if !matches!(bits, 8 | 16 | 24 | 32) { return Err(unsupported(format!("{bits} bit DDS pixels"))) }
+        let has_alpha = pf_flags & (DDPF_ALPHAPIXELS | DDPF_ALPHA) != 0;
+        let [r, g, b, a] = masks;
+        let masks = match pf_flags & (DDPF_RGB | DDPF_LUMINANCE) {
+            0 => [0, 0, 0, a],
+            _ => [r, g, b, if has_alpha { a } else { 0 }],
+        };
+        Layout::Masked { bits, masks, luminance: pf_flags & DDPF_LUMINANCE != 0 }
+    } else {
+        return Err(ImageError::Decode(path.to_owned(), "Unknown pixel format"))
+    };
+
+    let len = (width as usize).checked_mul(height as usize)
+        .and_then(|n| n.checked_mul(layout.bytes_per_pixel()))
+        .ok_or_else(|| ImageError::Decode(path.to_owned(), "Image too large"))?;
+    let pixels = r.take(len)?;
+    let size = (width, height).into();
+
+    let le_u16 = |b: &[u8]| u16::from_le_bytes([b[0], b[1]]);
+    let le_f32 = |b: &[u8]| f32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+    let bpp = layout.bytes_per_pixel();
+    let image = match layout {
+        Layout::Masked { masks, luminance, .. } => {
+            let bytes = pixels.chunks_exact(bpp).flat_map(|px| {
+                let v = px.iter().rev().fold(0u32, |v, &b| (v << 8) | b as u32);
+                let [r, g, b, a] = masks.map(|m| channel(v, m));
+                let alpha = if masks[3] == 0 { 255 } else { a };
+                match (luminance, masks[0..3] == [0; 3]) {
+                    (true, _) => [r, r, r, alpha],
+                    // Alpha only
+                    (false, true) => [255, 255, 255, alpha],
+                    (false, false) => [r, g, b, alpha],
+                }
+            }).collect();
+            RawImage::from_rgba8(bytes, size)
+        }
+        Layout::Rgba8 => RawImage::from_rgba8(pixels.to_vec(), size),
+        Layout::Bgra8 { alpha } => {
+            let bytes = pixels.chunks_exact(4).flat_map(|px| [px[2], px[1], px[0], if alpha { px[3] } else { 255 }]).collect();
+            RawImage::from_rgba8(bytes, size)
+        }
+        Layout::Rgba16Float => {
+            let bytes = pixels.chunks_exact(2).flat_map(|c| le_u16(c).to_ne_bytes()).collect();
+            RawImage::from_pixels(bytes, size, PixelFormat::Rgba16Float)
+        }
+        Layout::Rgba16Unorm => {
+            let bytes = pixels.chunks_exact(2).flat_map(|c| (le_u16(c) as f32 / 65535.).to_ne_bytes()).collect();
+            RawImage::from_pixels(bytes, size, PixelFormat::Rgba32Float)
+        }
+        Layout::Rgb32Float => {
+            let bytes = pixels.chunks_exact(12)
+                .flat_map(|px| [le_f32(&px[0..]), le_f32(&px[4..]), le_f32(&px[8..]), 1.])
+                .flat_map(f32::to_ne_bytes)
+                .collect();
+            RawImage::from_pixels(bytes, size, PixelFormat::Rgba32Float)
+        }
+        Layout::Rgba32Float => {
+            let bytes = pixels.chunks_exact(4).flat_map(|c| le_f32(c).to_ne_bytes()).collect();
+            RawImage::from_pixels(bytes, size, PixelFormat::Rgba32Float)
+        }
+    };
+    Ok(image)
+}
+
+/// Extracts the bits of `mask` and rescales them to 8 bits
+fn channel(v: u32, mask: u32) -> u8 {
+    if mask == 0 { return 0 }
+    let shift = mask.trailing_zeros();
+    let max = (mask >> shift) as u64;
+    let value = ((v & mask) >> shift) as u64;
+    ((value * 255 + max / 2) / max) as u8
+}
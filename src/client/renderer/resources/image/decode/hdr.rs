@@ -0,0 +1,111 @@
+use {
+    super::{Bytes, super::{ImageError, PixelFormat, RawImage}},
+    std::path::Path,
+};
+
+/// Radiance RGBE files, flat or run length encoded, to 32 bit float RGBA.
+/// Values are left as stored, `EXPOSURE` is ignored.
+pub fn decode(data: &[u8], path: &Path) -> Result<RawImage, ImageError> {
+    let invalid = |msg| ImageError::Decode(path.to_owned(), msg);
+    let mut r = Bytes::new(data, path);
+
+    if !line(&mut r)?.starts_with("#?") { return Err(invalid("Not a Radiance HDR file")) }
+    loop {
+        let line = line(&mut r)?;
+        if line.is_empty() { break }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format.trim() != "32-bit_rle_rgbe" {
+                return Err(ImageError::Unsupported(path.to_owned(), format!("HDR format {}", format.trim())))
+            }
+        }
+    }
+
+    let resolution: Vec<&str> = line(&mut r)?.split_ascii_whitespace().collect();
+    let (flip, height, width) = match resolution[..] {
+        ["-Y", h, "+X", w] => (false, h, w),
+        ["+Y", h, "+X", w] => (true, h, w),
+        _ => return Err(ImageError::Unsupported(path.to_owned(), format!("HDR orientation {}", resolution.join(" ")))),
+    };
+    let parse = |v: &str| v.parse::<usize>().map_err(|_| invalid("Invalid resolution"));
+    let (width, height) = (parse(width)?, parse(height)?);
+    // Every pixel takes at least a byte, even run length encoded
+    if width.saturating_mul(height) > data.len().saturating_mul(128) { return Err(invalid("Image larger than its data")) }
+
+    let mut rows = Vec::with_capacity(height);
+    let mut scanline = vec![[0u8; 4]; width];
+    for _ in 0..height {
+        read_scanline(&mut r, &mut scanline)?;
+        rows.push(scanline.iter().map(|&rgbe| to_float(rgbe)).collect::<Vec<_>>());
+    }
+    if flip { rows.reverse() }
+
+    let bytes = rows.iter().flatten().flatten().flat_map(|v| v.to_ne_bytes()).collect();
+    Ok(RawImage::from_pixels(bytes, (width as u32, height as u32).into(), PixelFormat::Rgba32Float))
+}
+
+/// Header line without its newline
+fn line<'a>(r: &mut Bytes<'a>) -> Result<&'a str, ImageError> {
+    let mut len = 0;
+    while r.peek(len + 1).ok_or_else(|| ImageError::Decode(r.path.to_owned(), "Unexpected end of header"))?[len] != b'\n' {
+        len += 1;
+    }
+    let line = r.take(len)?;
+    r.skip(1)?;
+    std::str::from_utf8(line).map_err(|_| ImageError::Decode(r.path.to_owned(), "Header isn't text"))
+}
+
+fn read_scanline(r: &mut Bytes, out: &mut [[u8; 4]]) -> Result<(), ImageError> {
+    let width = out.len();
+    let invalid = |msg| ImageError::Decode(r.path.to_owned(), msg);
+
+    // New style RLE stores each channel separately, marked by 2 2 followed by the width
+    match r.peek(4) {
+        Some(&[2, 2, hi, lo]) if (8..0x8000).contains(&width) && hi & 0x80 == 0 => {
+            r.skip(4)?;
+            if ((hi as usize) << 8 | lo as usize) != width { return Err(invalid("Scanline width mismatch")) }
+            for c in 0..4 {
+                let mut x = 0;
+                while x < width {
+                    let count = r.u8()? as usize;
+                    let (len, run) = match count > 128 {
+                        true => (count - 128, Some(r.u8()?)),
+                        false => (count, None),
+                    };
+                    if len == 0 || x + len > width { return Err(invalid("Run crosses the scanline")) }
+                    match run {
+                        Some(v) => out[x..x + len].iter_mut().for_each(|px| px[c] = v),
+                        None => for (px, &v) in out[x..x + len].iter_mut().zip(r.take(len)?) { px[c] = v },
+                    }
+                    x += len;
+                }
+            }
+        }
+        // Flat, with old style runs of 1 1 1 n repeating the previous pixel
+        _ => {
+            let (mut x, mut shift) = (0, 0);
+            while x < width {
+                let px: [u8; 4] = r.take(4)?.try_into().unwrap();
+                if px[..3] != [1, 1, 1] {
+                    out[x] = px;
+                    x += 1;
+                    shift = 0;
+                    continue
+                }
+                if x == 0 || shift > 16 { return Err(invalid("Run without a pixel to repeat")) }
+                let len = (px[3] as usize) << shift;
+                if x + len > width { return Err(invalid("Run crosses the scanline")) }
+                let prev = out[x - 1];
+                out[x..x + len].fill(prev);
+                x += len;
+                shift += 8;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn to_float([r, g, b, e]: [u8; 4]) -> [f32; 4] {
+    if e == 0 { return [0., 0., 0., 1.] }
+    let scale = 2f32.powi(e as i32 - (128 + 8));
+    [r as f32 * scale, g as f32 * scale, b as f32 * scale, 1.]
+}
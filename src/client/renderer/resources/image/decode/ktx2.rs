@@ -0,0 +1,174 @@
+use {
+    super::{Bytes, super::{compressed, ImageError, PixelFormat, RawImage}},
+    std::path::Path,
+    wgpu::{AstcBlock, AstcChannel, TextureFormat as F},
+};
+
+const IDENTIFIER: &[u8] = b"\xABKTX 20\xBB\r\n\x1A\n";
+/// VK_FORMAT_ASTC_4x4_SFLOAT_BLOCK, the first of the HDR ASTC formats
+const VK_FORMAT_ASTC_HDR: u32 = 1_000_066_000;
+
+const ASTC_BLOCKS: [AstcBlock; 14] = [
+    AstcBlock::B4x4, AstcBlock::B5x4, AstcBlock::B5x5, AstcBlock::B6x5, AstcBlock::B6x6, AstcBlock::B8x5, AstcBlock::B8x6,
+    AstcBlock::B8x8, AstcBlock::B10x5, AstcBlock::B10x6, AstcBlock::B10x8, AstcBlock::B10x10, AstcBlock::B12x10, AstcBlock::B12x12,
+];
+
+/// Single 2D textures without supercompression, with every mip level stored in the file
+pub fn decode(data: &[u8], path: &Path) -> Result<RawImage, ImageError> {
+    let unsupported = |what: String| ImageError::Unsupported(path.to_owned(), what);
+    let mut r = Bytes::new(data, path);
+
+    if r.take(IDENTIFIER.len())? != IDENTIFIER { return Err(ImageError::Decode(path.to_owned(), "Not a KTX2 file")) }
+    let vk_format = r.u32()?;
+    let _type_size = r.u32()?;
+    let width = r.u32()?;
+    let height = r.u32()?;
+    let depth = r.u32()?;
+    let layers = r.u32()?;
+    let faces = r.u32()?;
+    let levels = r.u32()?.max(1);
+    let supercompression = r.u32()?;
+    // Data format descriptor, key/value data and supercompression global data, none needed without supercompression
+    r.skip(4 * 4 + 2 * 8)?;
+
+    if width == 0 || height == 0 || depth > 1 { return Err(unsupported("KTX2 texture that isn't 2D".into())) }
+    if levels > 32 { return Err(ImageError::Decode(path.to_owned(), "More mip levels than a 32 bit size can have")) }
+    if layers > 1 || faces > 1 { return Err(unsupported("KTX2 array or cube map".into())) }
+    if supercompression != 0 { return Err(unsupported(format!("KTX2 supercompression scheme {supercompression}"))) }
+    let format = pixel_format(vk_format).ok_or_else(|| unsupported(format!("Vulkan format {vk_format}")))?;
+
+    let levels = (0..levels).map(|level| {
+        let offset = r.u64()?;
+        let len = r.u64()?;
+        let _uncompressed_len = r.u64()?;
+        let size = ((width >> level).max(1), (height >> level).max(1)).into();
+        let bytes = usize::try_from(offset).ok().zip(usize::try_from(len).ok())
+            .and_then(|(offset, len)| data.get(offset..offset.checked_add(len)?))
+            .ok_or_else(|| ImageError::Decode(path.to_owned(), "Mip level out of bounds"))?;
+        Ok((bytes, size))
+    }).collect::<Result<Vec<_>, ImageError>>()?;
+
+    // BC1 RGB has no alpha, but the GPU reads transparent black for the fourth color of three color blocks.
+    // Images using it are decoded with that color made opaque instead.
+    let opaque = matches!(vk_format, 131 | 132) && levels.iter().any(|(bytes, _)| compressed::bc1_punch_through(bytes));
+    let mut images: Vec<RawImage> = levels.into_iter().map(|(bytes, size)| match opaque {
+        true => {
+            let (mut pixels, pixel_format) = compressed::decompress(F::Bc1RgbaUnorm, bytes, size);
+            for pixel in pixels.chunks_exact_mut(4) { pixel[3] = u8::MAX }
+            RawImage::from_pixels(pixels, size, pixel_format)
+        }
+        false => RawImage::from_pixels(native_endian(bytes, format), size, format),
+    }).collect();
+
+    let mips = images.split_off(1);
+    let image = images.pop().unwrap().with_mips(mips);
+    if !image.is_packed() || image.mips.iter().any(|mip| !mip.is_packed()) {
+        return Err(ImageError::Decode(path.to_owned(), "Mip level size doesn't match its dimensions"))
+    }
+    Ok(image)
+}
+
+/// KTX2 stores multi-byte components little endian
+fn native_endian(bytes: &[u8], format: PixelFormat) -> Vec<u8> {
+    match format {
+        PixelFormat::Rgba16Float => bytes.chunks_exact(2).flat_map(|c| u16::from_le_bytes([c[0], c[1]]).to_ne_bytes()).collect(),
+        PixelFormat::Rgba32Float => bytes.chunks_exact(4).flat_map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]).to_ne_bytes()).collect(),
+        _ => bytes.to_vec(),
+    }
+}
+
+/// sRGB and linear pairs map to the same format, the sRGB flag is up to the texture entry
+fn pixel_format(vk_format: u32) -> Option<PixelFormat> {
+    let compressed = match vk_format {
+        // R8G8B8A8_UNORM and _SRGB
+        37 | 43 => return Some(PixelFormat::Rgba8),
+        // R16G16B16A16_SFLOAT
+        97 => return Some(PixelFormat::Rgba16Float),
+        // R32G32B32A32_SFLOAT
+        109 => return Some(PixelFormat::Rgba32Float),
+        // BC1 RGB and RGBA
+        131..=134 => F::Bc1RgbaUnorm,
+        135 | 136 => F::Bc2RgbaUnorm,
+        137 | 138 => F::Bc3RgbaUnorm,
+        139 => F::Bc4RUnorm,
+        140 => F::Bc4RSnorm,
+        141 => F::Bc5RgUnorm,
+        142 => F::Bc5RgSnorm,
+        143 => F::Bc6hRgbUfloat,
+        144 => F::Bc6hRgbFloat,
+        145 | 146 => F::Bc7RgbaUnorm,
+        147 | 148 => F::Etc2Rgb8Unorm,
+        149 | 150 => F::Etc2Rgb8A1Unorm,
+        151 | 152 => F::Etc2Rgba8Unorm,
+        153 => F::EacR11Unorm,
+        154 => F::EacR11Snorm,
+        155 => F::EacRg11Unorm,
+        156 => F::EacRg11Snorm,
+        // UNORM and SRGB of each block size in turn
+        157..=184 => F::Astc { block: ASTC_BLOCKS[(vk_format - 157) as usize / 2], channel: AstcChannel::Unorm },
+        _ => {
+            let block = *ASTC_BLOCKS.get(vk_format.checked_sub(VK_FORMAT_ASTC_HDR)? as usize)?;
+            F::Astc { block, channel: AstcChannel::Hdr }
+        }
+    };
+    Some(PixelFormat::Compressed(compressed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// KTX2 file with the given header fields and levels stored back to back after the level index
+    fn ktx2(vk_format: u32, width: u32, height: u32, levels: &[&[u8]]) -> Vec<u8> {
+        let mut file = IDENTIFIER.to_vec();
+        let level_count = levels.len() as u32;
+        for field in [vk_format, 1, width, height, 0, 0, 1, level_count, 0] { file.extend(field.to_le_bytes()) }
+        file.extend([0; 4 * 4 + 2 * 8]);
+
+        let mut offset = (file.len() + levels.len() * 3 * 8) as u64;
+        for level in levels {
+            for field in [offset, level.len() as u64, level.len() as u64] { file.extend(field.to_le_bytes()) }
+            offset += level.len() as u64;
+        }
+        for level in levels { file.extend_from_slice(level) }
+        file
+    }
+
+    #[test]
+    fn rgba8_levels() {
+        let top: Vec<u8> = (0..16).collect();
+        let file = ktx2(37, 2, 2, &[&top, &[255, 128, 0, 255]]);
+        let image = decode(&file, Path::new("test.ktx2")).unwrap();
+
+        assert_eq!(image.format, PixelFormat::Rgba8);
+        assert_eq!((image.size.x, image.size.y), (2, 2));
+        assert_eq!(image.pixel(1, 1), [12., 13., 14., 15.].map(|c| c / 255.));
+        assert_eq!(image.mips.len(), 1);
+        assert_eq!((image.mips[0].size.x, image.mips[0].size.y), (1, 1));
+        assert_eq!(image.mips[0].pixel(0, 0), [1., 128. / 255., 0., 1.]);
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let path = Path::new("test.ktx2");
+        assert!(decode(&ktx2(37, 0, 2, &[&[]]), path).is_err());
+        let levels = vec![&[0u8; 4][..]; 33];
+        assert!(decode(&ktx2(37, 1, 1, &levels), path).is_err());
+        // Level data past the end of the file
+        let mut file = ktx2(37, 1, 1, &[&[0; 4]]);
+        file.truncate(file.len() - 1);
+        assert!(decode(&file, path).is_err());
+    }
+
+    #[test]
+    fn bc1_rgb_stays_opaque() {
+        // Three color block whose second texel is the transparent color
+        let block = [0x1F, 0x00, 0x00, 0xF8, 0x0C, 0, 0, 0];
+        let image = decode(&ktx2(131, 4, 4, &[&block]), Path::new("test.ktx2")).unwrap();
+        assert_eq!(image.format, PixelFormat::Rgba8);
+        assert_eq!(image.pixel(1, 0), [0., 0., 0., 1.]);
+
+        let image = decode(&ktx2(133, 4, 4, &[&block]), Path::new("test.ktx2")).unwrap();
+        assert_eq!(image.format, PixelFormat::Compressed(F::Bc1RgbaUnorm));
+    }
+}
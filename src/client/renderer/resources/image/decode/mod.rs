@@ -0,0 +1,46 @@
+//! Decoders turning image files into [`RawImage`](super::RawImage)s of four channels or compressed blocks
+
+pub mod png;
+pub mod tga;
+pub mod dds;
+pub mod hdr;
+pub mod ktx2;
+
+use {
+    super::ImageError,
+    std::path::Path,
+};
+
+/// Little endian reads over a byte slice, failing with the file's path once the data runs out
+struct Bytes<'a> {
+    data: &'a [u8],
+    pos: usize,
+    path: &'a Path,
+}
+
+impl<'a> Bytes<'a> {
+    fn new(data: &'a [u8], path: &'a Path) -> Self {
+        Self { data, pos: 0, path }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ImageError> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.data.len())
+            .ok_or_else(|| ImageError::Decode(self.path.to_owned(), "Unexpected end of file"))?;
+        let slice = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn peek(&self, len: usize) -> Option<&'a [u8]> {
+        self.data.get(self.pos..self.pos.checked_add(len)?)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), ImageError> {
+        self.take(len).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8, ImageError> { Ok(self.take(1)?[0]) }
+    fn u16(&mut self) -> Result<u16, ImageError> { Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap())) }
+    fn u32(&mut self) -> Result<u32, ImageError> { Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap())) }
+    fn u64(&mut self) -> Result<u64, ImageError> { Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap())) }
+}
@@ -0,0 +1,38 @@
+use {
+    super::super::{ImageError, RawImage},
+    std::path::Path,
+};
+
+/// Any color type and bit depth, expanded to 8 bit RGBA
+pub fn decode(path: &Path) -> Result<RawImage, ImageError> {
+    let (bytes, info) = crate::files::read_texture(path)?;
+    let size = (info.width, info.height).into();
+    let line_size = info.line_size;
+
+    // Rows may be padded past the pixels, only the first `width` samples are read
+    let channels = match info.color_type {
+        ::png::ColorType::Grayscale => 1,
+        ::png::ColorType::GrayscaleAlpha => 2,
+        ::png::ColorType::Rgb => 3,
+        ::png::ColorType::Rgba => 4,
+        ::png::ColorType::Indexed => return Err(ImageError::Unsupported(path.to_owned(), "Palette wasn't expanded".into())),
+    };
+    if info.bit_depth != ::png::BitDepth::Eight {
+        return Err(ImageError::Unsupported(path.to_owned(), format!("{:?} bit channels", info.bit_depth)))
+    }
+
+    let mut rgba = Vec::with_capacity((info.width * info.height * 4) as usize);
+    for row in bytes.chunks(line_size).take(info.height as usize) {
+        for px in row.chunks_exact(channels).take(info.width as usize) {
+            rgba.extend_from_slice(&match *px {
+                [v] => [v, v, v, 255],
+                [v, a] => [v, v, v, a],
+                [r, g, b] => [r, g, b, 255],
+                [r, g, b, a] => [r, g, b, a],
+                _ => unreachable!(),
+            });
+        }
+    }
+
+    Ok(RawImage::from_rgba8(rgba, size))
+}
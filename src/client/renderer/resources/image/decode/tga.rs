@@ -0,0 +1,126 @@
+use {
+    super::{Bytes, super::{ImageError, RawImage}},
+    std::path::Path,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Mapped,
+    TrueColor,
+    Gray,
+}
+
+/// Color mapped, true color and grayscale images, raw or run length encoded
+pub fn decode(data: &[u8], path: &Path) -> Result<RawImage, ImageError> {
+    let mut r = Bytes::new(data, path);
+    let id_len = r.u8()?;
+    let map_type = r.u8()?;
+    let image_type = r.u8()?;
+    let map_first = r.u16()?;
+    let map_len = r.u16()?;
+    let map_depth = r.u8()?;
+    // Screen origin, irrelevant for a texture
+    r.skip(4)?;
+    let width = r.u16()? as usize;
+    let height = r.u16()? as usize;
+    let depth = r.u8()?;
+    let descriptor = r.u8()?;
+    r.skip(id_len as usize)?;
+
+    let (kind, rle) = match image_type {
+        1 => (Kind::Mapped, false),
+        2 => (Kind::TrueColor, false),
+        3 => (Kind::Gray, false),
+        9 => (Kind::Mapped, true),
+        10 => (Kind::TrueColor, true),
+        11 => (Kind::Gray, true),
+        0 => return Err(ImageError::Decode(path.to_owned(), "No image data")),
+        t => return Err(ImageError::Unsupported(path.to_owned(), format!("TGA image type {t}"))),
+    };
+
+    let supported = match kind {
+        Kind::Mapped => matches!(depth, 8 | 16),
+        Kind::TrueColor => matches!(depth, 15 | 16 | 24 | 32),
+        Kind::Gray => matches!(depth, 8 | 16),
+    };
+    if !supported { return Err(ImageError::Unsupported(path.to_owned(), format!("{depth} bit TGA pixels"))) }
+
+    // Only used by color mapped images, but may be present in any
+    let palette = match map_type {
+        0 => Vec::new(),
+        1 => {
+            if !matches!(map_depth, 15 | 16 | 24 | 32) {
+                return Err(ImageError::Unsupported(path.to_owned(), format!("{map_depth} bit TGA color map")))
+            }
+            let entry = (map_depth as usize).div_ceil(8);
+            (0..map_len).map(|_| Ok(color(r.take(entry)?, map_depth))).collect::<Result<Vec<_>, ImageError>>()?
+        }
+        t => return Err(ImageError::Unsupported(path.to_owned(), format!("TGA color map type {t}"))),
+    };
+    if kind == Kind::Mapped && palette.is_empty() {
+        return Err(ImageError::Decode(path.to_owned(), "Color mapped image without a color map"))
+    }
+
+    let pixel = |px: &[u8]| -> Result<[u8; 4], ImageError> {
+        Ok(match kind {
+            Kind::Mapped => {
+                let index = if px.len() == 2 { u16::from_le_bytes([px[0], px[1]]) } else { px[0] as u16 };
+                *index.checked_sub(map_first)
+                    .and_then(|i| palette.get(i as usize))
+                    .ok_or_else(|| ImageError::Decode(path.to_owned(), "Color map index out of range"))?
+            }
+            Kind::TrueColor => color(px, depth),
+            Kind::Gray => [px[0], px[0], px[0], px.get(1).copied().unwrap_or(255)],
+        })
+    };
+
+    let count = width * height;
+    // A run packet of two bytes expands to at most 128 pixels
+    if count > data.len().saturating_mul(128) { return Err(ImageError::Decode(path.to_owned(), "Image larger than its data")) }
+
+    let bpp = (depth as usize).div_ceil(8);
+    let mut pixels: Vec<[u8; 4]> = Vec::with_capacity(count);
+    while pixels.len() < count {
+        if !rle {
+            pixels.push(pixel(r.take(bpp)?)?);
+            continue
+        }
+
+        let header = r.u8()?;
+        let len = (header & 0x7f) as usize + 1;
+        if header & 0x80 != 0 {
+            let px = pixel(r.take(bpp)?)?;
+            pixels.extend(std::iter::repeat_n(px, len));
+        } else {
+            for _ in 0..len { pixels.push(pixel(r.take(bpp)?)?) }
+        }
+    }
+    // Packets may cross the end of the image
+    pixels.truncate(count);
+
+    // Rows are stored bottom to top unless bit 5 is set, bit 4 stores them right to left
+    if descriptor & 0x10 != 0 {
+        pixels.chunks_mut(width.max(1)).for_each(|row| row.reverse());
+    }
+    let rows: Vec<&[[u8; 4]]> = match descriptor & 0x20 != 0 {
+        true => pixels.chunks(width.max(1)).collect(),
+        false => pixels.chunks(width.max(1)).rev().collect(),
+    };
+
+    let bytes = rows.into_iter().flatten().flatten().copied().collect();
+    Ok(RawImage::from_rgba8(bytes, (width as u32, height as u32).into()))
+}
+
+/// BGR(A) in 15, 16, 24 or 32 bits to RGBA.
+/// The attribute bit of 16 bit colors is ignored, it rarely holds a meaningful alpha.
+fn color(px: &[u8], depth: u8) -> [u8; 4] {
+    match depth {
+        15 | 16 => {
+            let v = u16::from_le_bytes([px[0], px[1]]);
+            let expand = |c: u16| ((c << 3) | (c >> 2)) as u8;
+            [expand((v >> 10) & 31), expand((v >> 5) & 31), expand(v & 31), 255]
+        }
+        24 => [px[2], px[1], px[0], 255],
+        _ => [px[2], px[1], px[0], px[3]],
+    }
+}
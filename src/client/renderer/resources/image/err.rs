@@ -1,15 +1,22 @@
-use crate::files::FileError;
+use {
+    crate::files::FileError,
+    std::path::PathBuf,
+};
 
 use crate::err::macros::*;
 
 pub enum ImageError {
-    File(FileError)
+    File(FileError),
+    Decode(PathBuf, &'static str),
+    Unsupported(PathBuf, String),
 }
 
 impl_error!(ImageError,
-    File(e) => "With file: {}", e
+    File(e) => "With file: {}", e;
+    Decode(path, msg) => "Couldn't decode image '{}': {}", path.display(), msg;
+    Unsupported(path, what) => "Unsupported image '{}': {}", path.display(), what
 );
 
 impl_error_conversion!(ImageError,
     FileError => File
-);
\ No newline at end of file
+);
@@ -0,0 +1,173 @@
+use {
+    super::{ImageError, pixel::f32_to_f16},
+    crate::client::renderer::state::State,
+    std::path::Path,
+};
+
+/// Input colors mapped to the first and last entries of a [`Lut`]
+pub type Domain = ([f32; 3], [f32; 3]);
+
+/// 3D color lookup table, as exported by grading tools in the `.cube` format
+pub struct Lut {
+    /// Entries along each axis
+    pub size: u32,
+    /// RGB entries with red changing fastest, then green, then blue
+    pub data: Vec<[f32; 3]>,
+    pub domain: Domain,
+}
+
+impl Lut {
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+    /// Leaves colors unchanged
+    pub fn identity(size: u32) -> Self {
+        let step = 1.0 / (size - 1).max(1) as f32;
+        let data = (0..size.pow(3))
+            .map(|i| [i % size, i / size % size, i / size / size].map(|c| c as f32 * step))
+            .collect();
+        Self { size, data, domain: ([0.0; 3], [1.0; 3]) }
+    }
+
+    pub fn import<T: AsRef<Path>>(path: T) -> Result<Self, ImageError> {
+        let path = path.as_ref();
+        Self::parse(&crate::files::read_file(path)?.0, path)
+    }
+
+    /// Reads `LUT_3D_SIZE`, `DOMAIN_MIN`, `DOMAIN_MAX`, `LUT_3D_INPUT_RANGE` and the entries.
+    /// `TITLE`, comments and unknown keywords are skipped.
+    pub fn parse(text: &str, path: &Path) -> Result<Self, ImageError> {
+        let invalid = |msg| ImageError::Decode(path.to_owned(), msg);
+        let triple = |values: &[&str]| -> Result<[f32; 3], ImageError> {
+            match values {
+                [r, g, b] => {
+                    let parse = |v: &str| v.parse::<f32>().map_err(|_| invalid("Invalid number"));
+                    Ok([parse(r)?, parse(g)?, parse(b)?])
+                }
+                _ => Err(invalid("Expected three values")),
+            }
+        };
+
+        let mut size = None;
+        let mut domain = ([0.0; 3], [1.0; 3]);
+        let mut data = Vec::new();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let mut words = line.split_ascii_whitespace();
+            let Some(keyword) = words.next() else { continue };
+            let values: Vec<&str> = words.collect();
+            match keyword {
+                "TITLE" => {}
+                "LUT_3D_SIZE" => match values[..] {
+                    [n] => size = Some(n.parse::<u32>().ok().filter(|n| (2..=256).contains(n)).ok_or_else(|| invalid("Invalid LUT_3D_SIZE"))?),
+                    _ => return Err(invalid("Invalid LUT_3D_SIZE")),
+                },
+                "LUT_1D_SIZE" => return Err(ImageError::Unsupported(path.to_owned(), "1D LUT".to_owned())),
+                "DOMAIN_MIN" => domain.0 = triple(&values)?,
+                "DOMAIN_MAX" => domain.1 = triple(&values)?,
+                // Resolve's single range for all channels
+                "LUT_3D_INPUT_RANGE" => match values[..] {
+                    [min, max] => {
+                        let parse = |v: &str| v.parse::<f32>().map_err(|_| invalid("Invalid number"));
+                        domain = ([parse(min)?; 3], [parse(max)?; 3]);
+                    }
+                    _ => return Err(invalid("Invalid LUT_3D_INPUT_RANGE")),
+                },
+                _ if keyword.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+                    log::warn!("{}: skipping unknown keyword '{keyword}'", path.display());
+                }
+                _ => {
+                    let mut entry = vec![keyword];
+                    entry.extend(values);
+                    data.push(triple(&entry)?);
+                }
+            }
+        }
+
+        let size = size.ok_or_else(|| invalid("Missing LUT_3D_SIZE"))?;
+        if data.len() != size.pow(3) as usize { return Err(invalid("Entry count doesn't match LUT_3D_SIZE")) }
+        if (0..3).any(|c| domain.1[c] <= domain.0[c]) { return Err(invalid("Empty domain")) }
+        Ok(Self { size, data, domain })
+    }
+
+    /// 3D texture sampled with linear filtering between entries
+    pub fn create_texture(&self, state: &State, label: &str) -> wgpu::Texture {
+        let size = wgpu::Extent3d { width: self.size, height: self.size, depth_or_array_layers: self.size };
+        let texture = state.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: Self::FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let texels: Vec<u16> = self.data.iter().flat_map(|&[r, g, b]| [r, g, b, 1.0].map(f32_to_f16)).collect();
+        state.queue.write_texture(
+            texture.as_image_copy(),
+            bytemuck::cast_slice(&texels),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(self.size * 8),
+                rows_per_image: Some(self.size),
+            },
+            size,
+        );
+        texture
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Lut, ImageError> {
+        Lut::parse(text, Path::new("test.cube"))
+    }
+
+    fn cube(header: &str, lut: &Lut) -> String {
+        let entries: Vec<String> = lut.data.iter().map(|[r, g, b]| format!("{r} {g} {b}")).collect();
+        format!("{header}\n{}\n", entries.join("\n"))
+    }
+
+    #[test]
+    fn identity_round_trip() {
+        let identity = Lut::identity(3);
+        let lut = parse(&cube("TITLE \"Identity\"\n# Comment\nLUT_3D_SIZE 3", &identity)).unwrap();
+        assert_eq!(lut.size, 3);
+        assert_eq!(lut.data, identity.data);
+        assert_eq!(lut.domain, ([0.; 3], [1.; 3]));
+        // Red changes fastest
+        assert_eq!(lut.data[1], [0.5, 0., 0.]);
+        assert_eq!(lut.data[3], [0., 0.5, 0.]);
+    }
+
+    #[test]
+    fn size_mismatch() {
+        let text = cube("LUT_3D_SIZE 3", &Lut::identity(2));
+        assert!(matches!(parse(&text), Err(ImageError::Decode(_, "Entry count doesn't match LUT_3D_SIZE"))));
+        assert!(matches!(parse("0 0 0"), Err(ImageError::Decode(_, "Missing LUT_3D_SIZE"))));
+    }
+
+    #[test]
+    fn domain() {
+        let identity = Lut::identity(2);
+        let lut = parse(&cube("LUT_3D_SIZE 2\nDOMAIN_MIN -1 0 0.5\nDOMAIN_MAX 2 4 1", &identity)).unwrap();
+        assert_eq!(lut.domain, ([-1., 0., 0.5], [2., 4., 1.]));
+
+        let lut = parse(&cube("LUT_3D_SIZE 2\nLUT_3D_INPUT_RANGE 0 4", &identity)).unwrap();
+        assert_eq!(lut.domain, ([0.; 3], [4.; 3]));
+
+        let empty = cube("LUT_3D_SIZE 2\nDOMAIN_MIN 0 0 1\nDOMAIN_MAX 1 1 1", &identity);
+        assert!(matches!(parse(&empty), Err(ImageError::Decode(_, "Empty domain"))));
+    }
+
+    #[test]
+    fn skips_unknown_keywords() {
+        let identity = Lut::identity(2);
+        let lut = parse(&cube("LUT_3D_SIZE 2\nLUT_IN_VIDEO_RANGE\nCUSTOM_KEY 1 2", &identity)).unwrap();
+        assert_eq!(lut.data, identity.data);
+        assert!(matches!(parse("LUT_1D_SIZE 16"), Err(ImageError::Unsupported(..))));
+    }
+}
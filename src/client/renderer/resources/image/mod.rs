@@ -1,10 +1,16 @@
 pub mod texture;
 pub mod raw;
+pub mod pixel;
+pub mod decode;
+pub mod compressed;
 pub mod err;
 pub mod cubemap;
+pub mod lut;
 
 pub use texture::Texture;
 pub use raw::RawImage;
+pub use pixel::PixelFormat;
 pub use cubemap::CubeMap;
+pub use lut::Lut;
 
 pub use err::ImageError;
\ No newline at end of file
@@ -0,0 +1,137 @@
+/// Layout of the pixels of a [`RawImage`](super::RawImage), four channels unless block compressed
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PixelFormat {
+    Rgba8,
+    /// Half floats, as uploaded for HDR textures
+    Rgba16Float,
+    Rgba32Float,
+    /// Block compressed, stored as the linear variant of the format.
+    /// Whether it is sampled as sRGB is up to the texture entry, like for `Rgba8`.
+    Compressed(wgpu::TextureFormat),
+}
+
+impl PixelFormat {
+    /// Bytes per pixel, or per block of compressed formats
+    pub fn block_size(self) -> u32 {
+        match self {
+            Self::Rgba8 => 4,
+            Self::Rgba16Float => 8,
+            Self::Rgba32Float => 16,
+            Self::Compressed(f) => f.block_size(None).unwrap_or(16),
+        }
+    }
+
+    /// Width and height of a block, 1x1 for uncompressed formats
+    pub fn block_dimensions(self) -> (u32, u32) {
+        match self {
+            Self::Compressed(f) => f.block_dimensions(),
+            _ => (1, 1),
+        }
+    }
+
+    pub const fn is_compressed(self) -> bool {
+        matches!(self, Self::Compressed(_))
+    }
+
+    /// Format the pixels are converted to before uploading.
+    /// 32 bit floats aren't filterable everywhere, so they are uploaded as half floats.
+    pub const fn uploaded(self) -> Self {
+        match self {
+            Self::Rgba32Float => Self::Rgba16Float,
+            f => f,
+        }
+    }
+
+    /// GPU format the pixels are uploaded as; `rgba8` picks between the sRGB and linear 8 bit formats,
+    /// and the same for compressed formats with an sRGB variant
+    pub fn texture_format(self, rgba8: wgpu::TextureFormat) -> wgpu::TextureFormat {
+        match self.uploaded() {
+            Self::Rgba8 => rgba8,
+            Self::Compressed(f) if rgba8.is_srgb() => f.add_srgb_suffix(),
+            Self::Compressed(f) => f,
+            _ => wgpu::TextureFormat::Rgba16Float,
+        }
+    }
+
+    /// Reads one pixel, 8 bit channels are normalized to 0..1
+    pub fn read(self, bytes: &[u8]) -> [f32; 4] {
+        match self {
+            Self::Rgba8 => [0, 1, 2, 3].map(|c| bytes[c] as f32 / 255.),
+            Self::Rgba16Float => [0, 1, 2, 3].map(|c| f16_to_f32(u16::from_ne_bytes([bytes[c * 2], bytes[c * 2 + 1]]))),
+            Self::Rgba32Float => [0, 1, 2, 3].map(|c| f32::from_ne_bytes([bytes[c * 4], bytes[c * 4 + 1], bytes[c * 4 + 2], bytes[c * 4 + 3]])),
+            Self::Compressed(f) => unreachable!("{f:?} pixels are read after decompressing"),
+        }
+    }
+
+    /// Appends one pixel, clamping to 0..1 for 8 bit channels
+    pub fn write(self, pixel: [f32; 4], out: &mut Vec<u8>) {
+        let start = out.len();
+        out.resize(start + self.block_size() as usize, 0);
+        self.store(pixel, &mut out[start..]);
+    }
+
+    /// Overwrites the pixel at the start of `out`
+    pub fn store(self, pixel: [f32; 4], out: &mut [u8]) {
+        match self {
+            Self::Rgba8 => out[..4].copy_from_slice(&pixel.map(|v| (v.clamp(0., 1.) * 255. + 0.5) as u8)),
+            Self::Rgba16Float => for (c, v) in pixel.into_iter().enumerate() {
+                out[c * 2..c * 2 + 2].copy_from_slice(&f32_to_f16(v).to_ne_bytes())
+            },
+            Self::Rgba32Float => for (c, v) in pixel.into_iter().enumerate() {
+                out[c * 4..c * 4 + 4].copy_from_slice(&v.to_ne_bytes())
+            },
+            Self::Compressed(f) => unreachable!("{f:?} pixels can't be written"),
+        }
+    }
+}
+
+/// IEEE 754 binary16 to binary32
+pub fn f16_to_f32(h: u16) -> f32 {
+    let sign = ((h >> 15) as u32) << 31;
+    let exp = ((h >> 10) & 0x1f) as u32;
+    let mant = (h & 0x3ff) as u32;
+
+    let bits = match (exp, mant) {
+        (0, 0) => sign,
+        // Subnormal, renormalize
+        (0, _) => {
+            let shift = mant.leading_zeros() - 21;
+            sign | ((127 - 15 + 1 - shift) << 23) | ((mant << shift) & 0x3ff) << 13
+        }
+        (0x1f, _) => sign | 0x7f80_0000 | (mant << 13),
+        _ => sign | ((exp + 127 - 15) << 23) | (mant << 13),
+    };
+    f32::from_bits(bits)
+}
+
+/// IEEE 754 binary32 to binary16, rounding to nearest even
+pub fn f32_to_f16(f: f32) -> u16 {
+    let bits = f.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xff) as i32;
+    let mant = bits & 0x7f_ffff;
+
+    if exp == 0xff {
+        return sign | 0x7c00 | if mant != 0 { 0x200 } else { 0 }
+    }
+
+    let exp = exp - 127 + 15;
+    if exp >= 0x1f { return sign | 0x7c00 }
+    if exp <= 0 {
+        // Subnormal or zero
+        if exp < -10 { return sign }
+        let mant = mant | 0x80_0000;
+        let shift = (14 - exp) as u32;
+        let half = mant >> shift;
+        let rest = mant & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        let round = (rest > halfway || (rest == halfway && half & 1 == 1)) as u32;
+        return sign | (half + round) as u16
+    }
+
+    let half = ((exp as u32) << 10) | (mant >> 13);
+    let rest = mant & 0x1fff;
+    let round = (rest > 0x1000 || (rest == 0x1000 && half & 1 == 1)) as u32;
+    // A carry out of the mantissa correctly bumps the exponent, up to infinity
+    sign | (half + round) as u16
+}
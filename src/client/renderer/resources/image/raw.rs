@@ -1,13 +1,41 @@
-use crate::math::Vec2;
+use {
+    super::{compressed, decode, ImageError, PixelFormat},
+    crate::math::Vec2,
+    std::{borrow::Cow, path::Path},
+};
 
+#[derive(Clone)]
 pub struct RawImage {
     bytes: Vec<u8>,
     pub size: Vec2<u32>,
     line_size: u32,
+    pub format: PixelFormat,
+    /// Levels below this one stored in the file, empty if they are generated
+    pub mips: Vec<RawImage>,
 }
 
 impl RawImage {
-    pub fn empty() -> Self { Self { bytes: Vec::new(), size: Vec2::default(), line_size: 0 }}
+    pub fn empty() -> Self { Self { bytes: Vec::new(), size: Vec2::default(), line_size: 0, format: PixelFormat::Rgba8, mips: Vec::new() }}
+
+    /// Wraps tightly packed 8 bit RGBA pixels
+    pub fn from_rgba8(bytes: Vec<u8>, size: Vec2<u32>) -> Self {
+        Self::from_pixels(bytes, size, PixelFormat::Rgba8)
+    }
+
+    /// Wraps tightly packed pixels in native byte order, or rows of blocks for compressed formats
+    pub fn from_pixels(bytes: Vec<u8>, size: Vec2<u32>, format: PixelFormat) -> Self {
+        let line_size = size.x.div_ceil(format.block_dimensions().0) * format.block_size();
+        Self { bytes, size, line_size, format, mips: Vec::new() }
+    }
+
+    pub fn with_mips(mut self, mips: Vec<RawImage>) -> Self {
+        self.mips = mips; self
+    }
+
+    /// 1x1 image of a single color, used in place of missing textures
+    pub fn solid(color: [u8; 4]) -> Self {
+        Self::from_rgba8(color.to_vec(), (1, 1).into())
+    }
 
     pub fn mirror_x(&mut self) {
         let sample_size = (self.line_size / self.size.x) as usize;
@@ -26,12 +54,116 @@ impl RawImage {
         }
     }
 
-    pub fn import_png<T: AsRef<std::path::Path>>(path: T) -> Result<Self, super::ImageError> {
-        let (bytes, norm_info) = crate::files::read_texture(path.as_ref())?;
-        let size = (norm_info.width, norm_info.height).into();
-        let line_size = norm_info.line_size as u32;
+    /// Decodes PNG, TGA, DDS, KTX2 or Radiance HDR, picked by the file extension
+    pub fn import<T: AsRef<Path>>(path: T) -> Result<Self, ImageError> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+        let image = match extension.as_str() {
+            "png" => decode::png::decode(path),
+            "tga" => decode::tga::decode(&crate::files::read_binary(path)?, path),
+            "dds" => decode::dds::decode(&crate::files::read_binary(path)?, path),
+            "hdr" => decode::hdr::decode(&crate::files::read_binary(path)?, path),
+            "ktx2" => decode::ktx2::decode(&crate::files::read_binary(path)?, path),
+            _ => Err(ImageError::Unsupported(path.to_owned(), format!("Unknown extension '{extension}'"))),
+        }?;
+        // Textures can't be created empty
+        if image.size.x == 0 || image.size.y == 0 { return Err(ImageError::Decode(path.to_owned(), "Image has no pixels")) }
+        Ok(image)
+    }
+
+    /// Whether the pixels, or blocks, are tightly packed
+    pub fn is_packed(&self) -> bool {
+        let (block_width, block_height) = self.format.block_dimensions();
+        self.line_size == self.size.x.div_ceil(block_width) * self.format.block_size()
+            && self.bytes.len() == (self.line_size * self.size.y.div_ceil(block_height)) as usize
+    }
+
+    /// Whether the size is a multiple of the block size, which the GPU requires of compressed textures
+    pub fn is_block_aligned(&self) -> bool {
+        let (block_width, block_height) = self.format.block_dimensions();
+        self.size.x.is_multiple_of(block_width) && self.size.y.is_multiple_of(block_height)
+    }
+
+    /// Whether the pixels are tightly packed 8 bit RGBA
+    pub fn is_rgba8(&self) -> bool {
+        self.format == PixelFormat::Rgba8 && self.is_packed()
+    }
+
+    /// The pixel at `x`, `y`, see [`PixelFormat::read`]
+    pub fn pixel(&self, x: u32, y: u32) -> [f32; 4] {
+        let start = (y * self.line_size + x * self.format.block_size()) as usize;
+        self.format.read(&self.bytes[start..])
+    }
+
+    /// Same pixels and mip levels in another, uncompressed format, borrowed if already in it.
+    /// Compressed images are decompressed first.
+    pub fn convert(&self, format: PixelFormat) -> Cow<'_, Self> {
+        if format == self.format { return Cow::Borrowed(self) }
+        if self.format.is_compressed() { return Cow::Owned(self.decompress().convert(format).into_owned()) }
+
+        let mut bytes = Vec::with_capacity((self.size.x * self.size.y * format.block_size()) as usize);
+        for y in 0..self.size.y {
+            for x in 0..self.size.x { format.write(self.pixel(x, y), &mut bytes) }
+        }
+        let mips = self.mips.iter().map(|mip| mip.convert(format).into_owned()).collect();
+        Cow::Owned(Self::from_pixels(bytes, self.size, format).with_mips(mips))
+    }
+
+    /// Decodes every level of a block compressed image on the CPU, see [`compressed::decompress`]
+    pub fn decompress(&self) -> Self {
+        let PixelFormat::Compressed(format) = self.format else { return self.clone() };
+        let (bytes, pixel_format) = compressed::decompress(format, &self.bytes, self.size);
+        let mips = self.mips.iter().map(Self::decompress).collect();
+        Self::from_pixels(bytes, self.size, pixel_format).with_mips(mips)
+    }
 
-        Ok(Self { bytes, size, line_size })
+    /// The image as it can be uploaded to a device with `features`: compressed formats the device can't sample are
+    /// decompressed and the rest converted to [`PixelFormat::uploaded`]
+    pub fn for_upload(&self, features: wgpu::Features) -> Cow<'_, Self> {
+        match self.format {
+            PixelFormat::Compressed(f) if features.contains(f.required_features()) && self.is_block_aligned() => Cow::Borrowed(self),
+            PixelFormat::Compressed(_) => Cow::Owned(self.decompress()),
+            f => self.convert(f.uploaded()),
+        }
+    }
+
+    /// Every level below this one down to 1x1, each a 2x2 box filter of the previous.
+    /// Expects packed pixels; with `srgb` the color channels of 8 bit images are averaged in linear space.
+    pub fn mip_chain(&self, srgb: bool) -> Vec<RawImage> {
+        let srgb = srgb && self.format == PixelFormat::Rgba8;
+        // 8 bit channels only take 256 values
+        let lut: Vec<f32> = (0..=255u8).map(|v| {
+            let v = v as f32 / 255.;
+            if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) }
+        }).collect();
+        let to_linear = |v: f32| if srgb { lut[(v * 255. + 0.5) as usize] } else { v };
+        let from_linear = |v: f32| if srgb { if v <= 0.0031308 { v * 12.92 } else { 1.055 * v.powf(1. / 2.4) - 0.055 } } else { v };
+
+        let mut levels: Vec<RawImage> = Vec::new();
+        loop {
+            let prev = levels.last().unwrap_or(self);
+            if prev.size.x <= 1 && prev.size.y <= 1 { break }
+
+            let size = Vec2::new((prev.size.x / 2).max(1), (prev.size.y / 2).max(1));
+            let mut bytes = Vec::with_capacity((size.x * size.y * self.format.block_size()) as usize);
+            for y in 0..size.y {
+                for x in 0..size.x {
+                    // Odd dimensions fold the last row or column into the previous texel
+                    let xs = [x * 2, (x * 2 + 1).min(prev.size.x - 1)];
+                    let ys = [y * 2, (y * 2 + 1).min(prev.size.y - 1)];
+                    let mut sum = [0.; 4];
+                    for sy in ys { for sx in xs {
+                        let px = prev.pixel(sx, sy);
+                        for c in 0..4 { sum[c] += if c < 3 { to_linear(px[c]) } else { px[c] } }
+                    }}
+                    let avg = [0, 1, 2, 3].map(|c| if c < 3 { from_linear(sum[c] / 4.) } else { sum[c] / 4. });
+                    self.format.write(avg, &mut bytes);
+                }
+            }
+
+            levels.push(Self::from_pixels(bytes, size, self.format));
+        }
+        levels
     }
 
     pub fn data(&self) -> &[u8] {
@@ -50,11 +182,11 @@ impl RawImage {
         wgpu::ImageDataLayout {
             offset,
             bytes_per_row: Some(self.line_size),
-            rows_per_image: Some(self.size.y),
+            rows_per_image: Some(self.size.y.div_ceil(self.format.block_dimensions().1)),
         }
     }
 
-    pub fn create_copy_tex<'a>(&'a self, texture: &'a wgpu::Texture, mip_level: u32, depth: u32,) -> wgpu::ImageCopyTexture {
+    pub fn create_copy_tex<'a>(&'a self, texture: &'a wgpu::Texture, mip_level: u32, depth: u32,) -> wgpu::ImageCopyTexture<'a> {
         wgpu::ImageCopyTexture {
             texture,
             mip_level,
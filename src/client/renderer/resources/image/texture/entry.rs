@@ -1,5 +1,7 @@
 use std::num::NonZeroU32;
 
+use super::SamplerConfig;
+
 #[derive(Clone, Copy)]
 pub struct TextureEntry {
     visibility: wgpu::ShaderStages,
@@ -9,12 +11,23 @@ pub struct TextureEntry {
     count: Option<NonZeroU32>,
     sampler_binding_type: wgpu::SamplerBindingType,
     pub format: wgpu::TextureFormat,
+    /// Only used when loading the texture, the layout just needs a filtering sampler
+    pub sampler: SamplerConfig,
+    /// Generate the full mip chain when loading
+    pub mipmaps: bool,
 }
 
 #[allow(unused)]
 impl TextureEntry {
-    pub const NORMAL_MAP_ENTRY: &TextureEntry = &TextureEntry::new().with_format(wgpu::TextureFormat::Rgba8Unorm);
-    pub const DIFFUSE_MAP_ENTRY: &TextureEntry = &TextureEntry::new();
+    pub const NORMAL_MAP_ENTRY: &TextureEntry = &TextureEntry::MATERIAL_MAP.with_format(wgpu::TextureFormat::Rgba8Unorm);
+    pub const DIFFUSE_MAP_ENTRY: &TextureEntry = &TextureEntry::MATERIAL_MAP;
+    pub const SPECULAR_MAP_ENTRY: &TextureEntry = &TextureEntry::MATERIAL_MAP;
+    pub const EMISSIVE_MAP_ENTRY: &TextureEntry = &TextureEntry::MATERIAL_MAP;
+    /// Linear data, such as alpha, shininess, metallic-roughness or occlusion maps
+    pub const SCALAR_MAP_ENTRY: &TextureEntry = &TextureEntry::MATERIAL_MAP.with_format(wgpu::TextureFormat::Rgba8Unorm);
+
+    /// Mipmapped, repeating and anisotropically filtered
+    const MATERIAL_MAP: TextureEntry = TextureEntry::new().with_sampler(SamplerConfig::ANISOTROPIC).with_mipmaps(true);
 
     pub const fn new() -> Self {
         Self {
@@ -25,6 +38,8 @@ impl TextureEntry {
             count: None,
             sampler_binding_type: wgpu::SamplerBindingType::Filtering,
             format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            sampler: SamplerConfig::NEAREST,
+            mipmaps: false,
         }
     }
 
@@ -32,6 +47,14 @@ impl TextureEntry {
         self.format = format; self
     }
 
+    pub const fn with_sampler(mut self, sampler: SamplerConfig) -> Self {
+        self.sampler = sampler; self
+    }
+
+    pub const fn with_mipmaps(mut self, mipmaps: bool) -> Self {
+        self.mipmaps = mipmaps; self
+    }
+
     pub const fn with_visibility(mut self, visibility: wgpu::ShaderStages) -> Self {
         self.visibility = visibility; self
     }
@@ -77,4 +100,8 @@ impl TextureEntry {
             }
         )
     }
+}
+
+impl Default for TextureEntry {
+    fn default() -> Self { Self::new() }
 }
\ No newline at end of file
@@ -81,14 +81,14 @@ impl Texture {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: Self::DEPTH_FORMAT,
+            format: Self::DEPTH_STENCIL_FORMAT,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT
                 | wgpu::TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
         };
         let texture = device.create_texture(&desc);
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let (view, attachment) = Self::create_views(&texture, desc.format);
         let sampler = Self::create_sampler(
             device,
             wgpu::AddressMode::ClampToEdge,
@@ -130,7 +130,7 @@ impl Texture {
             "Depth Texture"
         );
 
-        Self { bg, texture, view, sampler, desc }
+        Self { bg, texture, view, sampler, attachment, desc }
     }
 
     pub fn create_frame_texture(state: &State, config: &wgpu::SurfaceConfiguration) -> Self {
@@ -166,7 +166,7 @@ impl Texture {
             &[
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                     ty: wgpu::BindingType::Texture {
                         multisampled: false,
                         view_dimension: wgpu::TextureViewDimension::D2,
@@ -176,7 +176,7 @@ impl Texture {
                 },
                 wgpu::BindGroupLayoutEntry {
                     binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                     count: None,
                 },
@@ -194,7 +194,7 @@ impl Texture {
             "Framebuffer texture"
         );
 
-        Self { bg, texture, view, sampler, desc }
+        Self { bg, texture, view, sampler, attachment: None, desc }
     }
 
     fn create_sampler(
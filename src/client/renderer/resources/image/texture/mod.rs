@@ -1,14 +1,16 @@
 pub mod entry;
 pub mod raw;
+pub mod sampler;
 
 pub use entry::TextureEntry;
 pub use raw::RawTexture;
+pub use sampler::SamplerConfig;
 
 mod init;
 
 use crate::client::renderer::gpu::bind_group::BindGroup;
 
-use super::RawImage;
+use super::{PixelFormat, RawImage};
 pub use super::err::ImageError;
 
 pub struct Texture {
@@ -16,11 +18,28 @@ pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
+    /// View of every aspect of a depth-stencil texture, whose `view` only covers the depth to be sampled
+    attachment: Option<wgpu::TextureView>,
     desc: wgpu::TextureDescriptor<'static>,
 }
 
 impl Texture {
+    /// Depth-only targets like shadow maps
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    /// Depth of the scene, with a stencil for masking effects like outlines
+    pub const DEPTH_STENCIL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+
+    /// View to render to
+    pub fn attachment_view(&self) -> &wgpu::TextureView {
+        self.attachment.as_ref().unwrap_or(&self.view)
+    }
+
+    /// The sampled view and, for depth-stencil formats, the attachment view
+    fn create_views(texture: &wgpu::Texture, format: wgpu::TextureFormat) -> (wgpu::TextureView, Option<wgpu::TextureView>) {
+        if !format.has_stencil_aspect() { return (texture.create_view(&wgpu::TextureViewDescriptor::default()), None) }
+        let view = texture.create_view(&wgpu::TextureViewDescriptor { aspect: wgpu::TextureAspect::DepthOnly, ..Default::default() });
+        (view, Some(texture.create_view(&wgpu::TextureViewDescriptor::default())))
+    }
 
     pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32, label: &str) {
         let size = wgpu::Extent3d {
@@ -30,7 +49,7 @@ impl Texture {
         };
         self.desc.size = size;
         self.texture = device.create_texture(&self.desc);
-        self.view = self.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (self.view, self.attachment) = Self::create_views(&self.texture, self.desc.format);
         self.bg.replace_group(
             device,
             &[wgpu::BindGroupEntry {
@@ -49,13 +68,29 @@ impl Texture {
 
 
 
-pub fn load_texture(device: &wgpu::Device, queue: &wgpu::Queue, image: &RawImage, label: &str, format: wgpu::TextureFormat) -> wgpu::Texture {
+/// Uploads the image, with its full mip chain if `mipmaps` is set.
+/// Mip levels stored in the file are used as is, the rest are box filtered on the CPU, in linear space for sRGB formats.
+/// `format` applies to 8 bit and compressed images, float images are uploaded as half floats.
+/// Compressed formats the device can't sample are decompressed first.
+pub fn load_texture(device: &wgpu::Device, queue: &wgpu::Queue, image: &RawImage, label: &str, format: wgpu::TextureFormat, mipmaps: bool) -> wgpu::Texture {
+    let uploaded = image.for_upload(device.features());
+    if let (PixelFormat::Compressed(f), false) = (image.format, uploaded.format.is_compressed()) {
+        log::warn!("{label}: {f:?} isn't supported by the device, decompressed on the CPU");
+    }
+    let image = uploaded.as_ref();
+    let format = image.format.texture_format(format);
+    let generated;
+    let levels = match mipmaps {
+        true if !image.mips.is_empty() => &image.mips[..],
+        true if !image.format.is_compressed() && image.is_packed() => { generated = image.mip_chain(format.is_srgb()); &generated }
+        _ => &[][..],
+    };
     let size = image.texture_size(1);
 
     let texture = device.create_texture(
         &wgpu::TextureDescriptor {
             size,
-            mip_level_count: 1,
+            mip_level_count: levels.len() as u32 + 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format,
@@ -65,12 +100,14 @@ pub fn load_texture(device: &wgpu::Device, queue: &wgpu::Queue, image: &RawImage
         }
     );
 
-    queue.write_texture(
-        image.create_copy_tex(&texture, 0, 0),
-        image.data(),
-        image.layout(0),
-        size
-    );
+    for (level, image) in std::iter::once(image).chain(levels).enumerate() {
+        queue.write_texture(
+            image.create_copy_tex(&texture, level as u32, 0),
+            image.data(),
+            image.layout(0),
+            image.texture_size(1).physical_size(format),
+        );
+    }
 
     texture
-}
\ No newline at end of file
+}
@@ -9,7 +9,7 @@ impl RawTexture {
         Self {_tex: tex, sampler, view}
     }
 
-    pub fn bind_group_entries(&self, binding: u32) -> (wgpu::BindGroupEntry, wgpu::BindGroupEntry) {
+    pub fn bind_group_entries(&self, binding: u32) -> (wgpu::BindGroupEntry<'_>, wgpu::BindGroupEntry<'_>) {
         (wgpu::BindGroupEntry {
             binding,
             resource: wgpu::BindingResource::TextureView(&self.view),
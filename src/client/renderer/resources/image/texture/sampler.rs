@@ -0,0 +1,69 @@
+/// Sampler settings of a texture, turned into a `wgpu::Sampler` when the texture is loaded
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SamplerConfig {
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    /// Maximum anisotropy, 1 disables anisotropic filtering.
+    /// Only applied if every filter is linear and the adapter supports it.
+    pub anisotropy: u16,
+}
+
+impl SamplerConfig {
+    /// Point sampling without mipmaps, clamped to the edge
+    pub const NEAREST: Self = Self {
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Nearest,
+        min_filter: wgpu::FilterMode::Nearest,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        anisotropy: 1,
+    };
+
+    /// Linear filtering between and within mip levels, repeating
+    pub const TRILINEAR: Self = Self {
+        address_mode_u: wgpu::AddressMode::Repeat,
+        address_mode_v: wgpu::AddressMode::Repeat,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        mipmap_filter: wgpu::FilterMode::Linear,
+        anisotropy: 1,
+    };
+
+    /// Trilinear with 16x anisotropic filtering, for surfaces seen at grazing angles
+    pub const ANISOTROPIC: Self = Self { anisotropy: 16, ..Self::TRILINEAR };
+
+    /// Sets both axes
+    pub const fn with_address_mode(mut self, address_mode: wgpu::AddressMode) -> Self {
+        self.address_mode_u = address_mode;
+        self.address_mode_v = address_mode;
+        self
+    }
+
+    pub const fn with_anisotropy(mut self, anisotropy: u16) -> Self {
+        self.anisotropy = anisotropy; self
+    }
+
+    pub fn create(&self, device: &wgpu::Device, label: &str) -> wgpu::Sampler {
+        use wgpu::FilterMode::Linear;
+        let all_linear = self.mag_filter == Linear && self.min_filter == Linear && self.mipmap_filter == Linear;
+
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(label),
+            address_mode_u: self.address_mode_u,
+            address_mode_v: self.address_mode_v,
+            address_mode_w: self.address_mode_u,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            anisotropy_clamp: if all_linear { self.anisotropy.clamp(1, 16) } else { 1 },
+            ..Default::default()
+        })
+    }
+}
+
+impl Default for SamplerConfig {
+    fn default() -> Self { Self::NEAREST }
+}
@@ -1,6 +1,142 @@
+use std::borrow::Cow;
+
 use crate::client::{renderer::{gpu::{bind_group::{BindGroup, Layout}, buffer::Buffer}, state::State}, PathManager};
 
-use super::image::{RawImage, ImageError, texture::{TextureEntry, RawTexture}};
+use super::image::{RawImage, ImageError, texture::{TextureEntry, RawTexture, SamplerConfig}};
+
+/// Diffuse and specular color of materials without the map
+pub const WHITE: [u8; 4] = [255; 4];
+/// Normal map of materials without one, pointing straight out of the surface
+pub const FLAT_NORMAL: [u8; 4] = [128, 128, 255, 255];
+
+/// Lighting model selected by [`MaterialUniform::illum`], following the MTL `illum` values
+pub const ILLUM_COLOR: u32 = 0;
+pub const ILLUM_DIFFUSE: u32 = 1;
+pub const ILLUM_SPECULAR: u32 = 2;
+
+/// Pipeline a material is drawn with
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShadingModel {
+    /// Classic Blinn-Phong with diffuse, specular and shininess
+    BlinnPhong,
+    /// Cook-Torrance GGX with base color, metallic and roughness
+    MetallicRoughness,
+}
+
+/// Constants of a material as seen by the geometry shader
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialUniform {
+    /// Multiplied with the diffuse texture
+    pub base_color: [f32; 4],
+    /// Reflectance of the scene's ambient light
+    pub ambient: [f32; 3],
+    /// Multiplied with the blue channel of the metallic-roughness map
+    pub metallic: f32,
+    /// Multiplied with the specular map
+    pub specular: [f32; 3],
+    /// Specular exponent, multiplied with the shininess map
+    pub shininess: f32,
+    /// Multiplied with the emissive map
+    pub emissive: [f32; 3],
+    /// Perceptual roughness, multiplied with the green channel of the metallic-roughness map
+    pub roughness: f32,
+    /// Strength of the normal map's X and Y components
+    pub normal_scale: f32,
+    /// Fragments with a lower alpha are discarded; 0 disables alpha testing
+    pub alpha_cutoff: f32,
+    /// Index of refraction
+    pub ior: f32,
+    pub illum: u32,
+    /// How much of the occlusion map is applied, 0 ignores it
+    pub occlusion_strength: f32,
+    pub _padding: [f32; 3],
+}
+
+impl Default for MaterialUniform {
+    fn default() -> Self {
+        Self {
+            base_color: [1.; 4],
+            ambient: [1.; 3],
+            metallic: 0.,
+            specular: [1.; 3],
+            shininess: 32.,
+            emissive: [0.; 3],
+            roughness: 1.,
+            normal_scale: 1.,
+            alpha_cutoff: 0.,
+            ior: 1.5,
+            illum: ILLUM_SPECULAR,
+            occlusion_strength: 1.,
+            _padding: [0.; 3],
+        }
+    }
+}
+
+/// Images of a geometry material, borrowed or owned; missing maps are replaced with 1x1 textures that leave the uniform's values unchanged
+#[derive(Default)]
+pub struct MaterialMaps<'a> {
+    pub diffuse: Option<Cow<'a, RawImage>>,
+    pub normal: Option<Cow<'a, RawImage>>,
+    pub specular: Option<Cow<'a, RawImage>>,
+    /// Read from the red channel
+    pub alpha: Option<Cow<'a, RawImage>>,
+    /// Read from the red channel
+    pub shininess: Option<Cow<'a, RawImage>>,
+    /// Roughness in the green channel and metallic in the blue one, as in glTF
+    pub metallic_roughness: Option<Cow<'a, RawImage>>,
+    /// Ambient occlusion, read from the red channel
+    pub occlusion: Option<Cow<'a, RawImage>>,
+    pub emissive: Option<Cow<'a, RawImage>>,
+    /// Replace the samplers of [`MaterialMaps::ENTRIES`], in the same order
+    pub samplers: [Option<SamplerConfig>; 8],
+}
+
+impl <'a> MaterialMaps<'a> {
+    /// Texture entries in binding order, the uniform buffer follows them
+    pub const ENTRIES: [&'static TextureEntry; 8] = [
+        TextureEntry::DIFFUSE_MAP_ENTRY,
+        TextureEntry::NORMAL_MAP_ENTRY,
+        TextureEntry::SPECULAR_MAP_ENTRY,
+        TextureEntry::SCALAR_MAP_ENTRY,
+        TextureEntry::SCALAR_MAP_ENTRY,
+        TextureEntry::SCALAR_MAP_ENTRY,
+        TextureEntry::SCALAR_MAP_ENTRY,
+        TextureEntry::EMISSIVE_MAP_ENTRY,
+    ];
+
+    fn into_textures(self) -> [(Cow<'a, RawImage>, TextureEntry); 8] {
+        let or_solid = |img: Option<Cow<'a, RawImage>>, color| img.unwrap_or_else(|| Cow::Owned(RawImage::solid(color)));
+        let mut entries = Self::ENTRIES.map(|e| *e);
+        for (entry, sampler) in entries.iter_mut().zip(self.samplers) {
+            if let Some(sampler) = sampler { entry.sampler = sampler }
+        }
+        let [diffuse, normal, specular, alpha, shininess, metallic_roughness, occlusion, emissive] = entries;
+        [
+            (or_solid(self.diffuse, WHITE), diffuse),
+            (or_solid(self.normal, FLAT_NORMAL), normal),
+            (or_solid(self.specular, WHITE), specular),
+            (or_solid(self.alpha, WHITE), alpha),
+            (or_solid(self.shininess, WHITE), shininess),
+            (or_solid(self.metallic_roughness, WHITE), metallic_roughness),
+            (or_solid(self.occlusion, WHITE), occlusion),
+            (or_solid(self.emissive, WHITE), emissive),
+        ]
+    }
+}
+
+impl Material<MaterialUniform> {
+    /// Material drawn by the geometry pipeline
+    pub fn geometry(state: &State, maps: MaterialMaps, uni: MaterialUniform, shading: ShadingModel, label: &str) -> Self {
+        let textures = maps.into_textures();
+        let textures = textures.iter().map(|(img, entry)| (img.as_ref(), entry)).collect::<Vec<_>>();
+        Self { shading, ..Self::from_raw_textures(state, &textures, uni, label) }
+    }
+
+    pub fn geometry_layout(state: &State) -> Layout {
+        Self::layout(state, &MaterialMaps::ENTRIES)
+    }
+}
 
 pub struct Material<T: bytemuck::Pod> {
     pub name: String,
@@ -8,33 +144,27 @@ pub struct Material<T: bytemuck::Pod> {
     pub tex: Vec<RawTexture>,
     pub uni: T,
     pub buf: Buffer,
+    /// Pipeline the material is drawn with
+    pub shading: ShadingModel,
 }
 
 impl <T: bytemuck::Pod> Material<T> {
     pub fn from_paths<U: AsRef<std::path::Path>>(state: &State, tex: &[(U, &TextureEntry)], uni: T, label: &str, path_m: &PathManager) -> Result<Self, ImageError> {
         let mut error = None;
-        let tex: Vec<_> = tex.iter().map(|(path, entry)| {
-            let img = RawImage::import_png(path_m.texture(path.as_ref())).unwrap_or_else(|e| {error = Some(e); RawImage::empty()});
-            (img, *entry)
+        let images: Vec<_> = tex.iter().map(|(path, _)| {
+            RawImage::import(path_m.texture(path.as_ref())).unwrap_or_else(|e| {error = Some(e); RawImage::empty()})
         }).collect();
         if let Some(e) = error { return Err(e) }
 
+        let tex: Vec<_> = images.iter().zip(tex).map(|(img, (_, entry))| (img, *entry)).collect();
         Ok(Self::from_raw_textures(state, tex.as_slice(), uni, label))
     }
 
-    pub fn from_raw_textures(state: &State, tex: &[(RawImage, &TextureEntry)], uni: T, label: &str) -> Self {
+    pub fn from_raw_textures(state: &State, tex: &[(&RawImage, &TextureEntry)], uni: T, label: &str) -> Self {
         let images: Vec<RawTexture> = tex.iter().enumerate().map(
             |(ind, (tex, entry))| {
-                let tex = super::image::texture::load_texture(&state.device, &state.queue, tex, &format!("Image {}", ind), entry.format);
-                let sampler = state.device.create_sampler(&wgpu::SamplerDescriptor {
-                    address_mode_u: wgpu::AddressMode::ClampToEdge,
-                    address_mode_v: wgpu::AddressMode::ClampToEdge,
-                    address_mode_w: wgpu::AddressMode::ClampToEdge,
-                    mag_filter: wgpu::FilterMode::Nearest,
-                    min_filter: wgpu::FilterMode::Nearest,
-                    mipmap_filter: wgpu::FilterMode::Nearest,
-                    ..Default::default()
-                });
+                let tex = super::image::texture::load_texture(&state.device, &state.queue, tex, &format!("Image {}", ind), entry.format, entry.mipmaps);
+                let sampler = entry.sampler.create(&state.device, &format!("{label} sampler {ind}"));
                 let view = tex.create_view(&wgpu::TextureViewDescriptor::default());
                 RawTexture::new(tex, sampler, view)
             }
@@ -44,18 +174,18 @@ impl <T: bytemuck::Pod> Material<T> {
         let entries: Vec<_> = tex.iter().map(|(_, e)| *e).collect();
         let layout = Self::layout(state, &entries[..]);
 
-        let mut entries: Vec<_> = images.iter().enumerate().map(|(ind, img)| {
+        let mut entries: Vec<_> = images.iter().enumerate().flat_map(|(ind, img)| {
                 let entries = img.bind_group_entries(ind as u32 * 2);
                 [entries.0, entries.1]
             }
-        ).flatten().collect();
+        ).collect();
 
         let buf = Buffer::new_uniform(&state.device, &[uni], &(label.to_owned() + " Uniform Buffer"));
         entries.push(buf.entry(entries.len() as u32));
 
         let bg = BindGroup::with_layout(&state.device, layout, &entries, &format!("{} bind group", label));
 
-        Self { name: label.to_owned(), bg, tex: images, uni, buf }
+        Self { name: label.to_owned(), bg, tex: images, uni, buf, shading: ShadingModel::BlinnPhong }
     }
 
     pub fn layout(state: &State, tex_entries: &[&TextureEntry]) -> Layout {
@@ -0,0 +1,398 @@
+use {
+    super::ModelVertex,
+    crate::client::renderer::resources::material::{MaterialUniform, ShadingModel},
+    std::{
+        io::{BufReader, BufWriter, Read, Write},
+        path::{Path, PathBuf},
+        time::{Duration, SystemTime},
+    },
+};
+
+pub use err::CacheError;
+
+const MAGIC: [u8; 4] = *b"EMSH";
+/// Bumped whenever the layout of the file, a vertex or the material uniform changes
+pub const VERSION: u32 = 5;
+/// Written in native byte order; a cache from a machine of the other endianness reads as stale
+const BYTE_ORDER: u32 = 0x0102_0304;
+
+/// Extension appended to the source's file name
+pub const EXTENSION: &str = "mesh";
+
+/// Model ready to be uploaded: deduplicated vertices with tangents and resolved materials
+pub struct ModelData {
+    /// Files the model was built from; the cache is stale once any of them changes
+    pub sources: Vec<SourceStamp>,
+    pub materials: Vec<MaterialData>,
+    pub meshes: Vec<MeshData>,
+}
+
+pub struct MaterialData {
+    pub name: String,
+    pub uniform: MaterialUniform,
+    pub shading: ShadingModel,
+    /// Texture paths relative to the texture directory, in [`MaterialMaps::ENTRIES`](crate::client::renderer::resources::material::MaterialMaps::ENTRIES) order
+    pub maps: [Option<String>; 8],
+    /// Maps clamped to the edge instead of repeating
+    pub clamp: [bool; 8],
+}
+
+pub struct MeshData {
+    pub name: String,
+    /// Index into `ModelData::materials`
+    pub material: u32,
+    pub vertices: Vec<ModelVertex>,
+    pub indices: Vec<[u32; 3]>,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub struct SourceStamp {
+    pub path: PathBuf,
+    pub len: u64,
+    pub modified: Duration,
+}
+
+impl SourceStamp {
+    pub fn new(path: &Path) -> Result<Self, CacheError> {
+        let meta = std::fs::metadata(path).map_err(|e| CacheError::Io(e, path.to_owned()))?;
+        let modified = meta.modified()
+            .ok()
+            .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        Ok(Self { path: path.to_owned(), len: meta.len(), modified })
+    }
+
+    fn is_current(&self) -> bool {
+        Self::new(&self.path).is_ok_and(|s| s == *self)
+    }
+}
+
+/// `model.obj` is cached as `model.obj.mesh`
+pub fn cache_path(source: &Path) -> PathBuf {
+    let mut name = source.as_os_str().to_owned();
+    name.push(".");
+    name.push(EXTENSION);
+    PathBuf::from(name)
+}
+
+impl ModelData {
+    /// Reads the cache of `source` if it exists and every file it was built from is unchanged
+    pub fn read_fresh(source: &Path) -> Option<Self> {
+        let path = cache_path(source);
+        if !path.exists() { return None }
+
+        match Self::read(&path) {
+            Ok(data) if data.sources.iter().all(SourceStamp::is_current) => Some(data),
+            Ok(_) => None,
+            Err(e) => { log::warn!("Ignoring mesh cache: {e}"); None }
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Self, CacheError> {
+        let file = std::fs::File::open(path).map_err(|e| CacheError::Io(e, path.to_owned()))?;
+        let len = file.metadata().map_err(|e| CacheError::Io(e, path.to_owned()))?.len();
+        let mut r = Reader { inner: BufReader::new(file), path, len };
+
+        if r.bytes::<4>()? != MAGIC { return Err(CacheError::Format(path.to_owned(), "Not a mesh cache")) }
+        let version = r.u32()?;
+        if version != VERSION { return Err(CacheError::Version(path.to_owned(), version)) }
+        if r.u32()? != BYTE_ORDER { return Err(CacheError::Format(path.to_owned(), "Written with a different byte order")) }
+
+        let sources = (0..r.u32()?).map(|_| Ok(SourceStamp {
+            path: PathBuf::from(r.string()?),
+            len: r.u64()?,
+            modified: Duration::new(r.u64()?, r.u32()?),
+        })).collect::<Result<_, CacheError>>()?;
+
+        let materials = (0..r.u32()?).map(|_| {
+            let name = r.string()?;
+            let uniform = r.pod::<MaterialUniform>(1)?[0];
+            let shading = match r.bytes::<1>()?[0] {
+                0 => ShadingModel::BlinnPhong,
+                1 => ShadingModel::MetallicRoughness,
+                _ => return Err(CacheError::Format(path.to_owned(), "Unknown shading model")),
+            };
+            let mut maps: [Option<String>; 8] = Default::default();
+            let mut clamp = [false; 8];
+            for (map, clamp) in maps.iter_mut().zip(&mut clamp) {
+                let path = r.string()?;
+                *map = (!path.is_empty()).then_some(path);
+                *clamp = r.bytes::<1>()?[0] != 0;
+            }
+            Ok(MaterialData { name, uniform, shading, maps, clamp })
+        }).collect::<Result<_, CacheError>>()?;
+
+        let meshes = (0..r.u32()?).map(|_| {
+            let name = r.string()?;
+            let material = r.u32()?;
+            let vertex_count = r.u32()? as usize;
+            let vertices = r.pod(vertex_count)?;
+            let triangle_count = r.u32()? as usize;
+            let indices = r.pod(triangle_count)?;
+            Ok(MeshData { name, material, vertices, indices })
+        }).collect::<Result<Vec<MeshData>, CacheError>>()?;
+
+        let data = Self { sources, materials, meshes };
+        data.validate(path)?;
+        Ok(data)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), CacheError> {
+        let file = std::fs::File::create(path).map_err(|e| CacheError::Io(e, path.to_owned()))?;
+        let mut w = Writer { inner: BufWriter::new(file), path };
+
+        w.bytes(&MAGIC)?;
+        w.u32(VERSION)?;
+        w.u32(BYTE_ORDER)?;
+
+        w.u32(self.sources.len() as u32)?;
+        for source in &self.sources {
+            w.string(&source.path.to_string_lossy())?;
+            w.u64(source.len)?;
+            w.u64(source.modified.as_secs())?;
+            w.u32(source.modified.subsec_nanos())?;
+        }
+
+        w.u32(self.materials.len() as u32)?;
+        for material in &self.materials {
+            w.string(&material.name)?;
+            w.bytes(bytemuck::bytes_of(&material.uniform))?;
+            w.bytes(&[material.shading as u8])?;
+            for (map, clamp) in material.maps.iter().zip(material.clamp) {
+                w.string(map.as_deref().unwrap_or_default())?;
+                w.bytes(&[clamp as u8])?;
+            }
+        }
+
+        w.u32(self.meshes.len() as u32)?;
+        for mesh in &self.meshes {
+            w.string(&mesh.name)?;
+            w.u32(mesh.material)?;
+            w.u32(mesh.vertices.len() as u32)?;
+            w.bytes(bytemuck::cast_slice(&mesh.vertices))?;
+            w.u32(mesh.indices.len() as u32)?;
+            w.bytes(bytemuck::cast_slice(&mesh.indices))?;
+        }
+
+        w.inner.flush().map_err(|e| CacheError::Io(e, path.to_owned()))
+    }
+
+    /// Checks that every reference stays in bounds, so a corrupted file can't make drawing fail
+    fn validate(&self, path: &Path) -> Result<(), CacheError> {
+        for mesh in &self.meshes {
+            if mesh.material as usize >= self.materials.len() {
+                return Err(CacheError::Format(path.to_owned(), "Material index out of range"))
+            }
+            if mesh.indices.iter().flatten().any(|&i| i as usize >= mesh.vertices.len()) {
+                return Err(CacheError::Format(path.to_owned(), "Vertex index out of range"))
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Reader<'a> {
+    inner: BufReader<std::fs::File>,
+    path: &'a Path,
+    len: u64,
+}
+
+impl Reader<'_> {
+    /// Guards allocations against corrupted lengths
+    fn check_len(&self, bytes: usize) -> Result<(), CacheError> {
+        if bytes as u64 > self.len { return Err(CacheError::Format(self.path.to_owned(), "Length exceeds the file")) }
+        Ok(())
+    }
+
+    fn fill(&mut self, buf: &mut [u8]) -> Result<(), CacheError> {
+        self.inner.read_exact(buf).map_err(|e| CacheError::Io(e, self.path.to_owned()))
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], CacheError> {
+        let mut buf = [0; N];
+        self.fill(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn u32(&mut self) -> Result<u32, CacheError> { Ok(u32::from_ne_bytes(self.bytes()?)) }
+    fn u64(&mut self) -> Result<u64, CacheError> { Ok(u64::from_ne_bytes(self.bytes()?)) }
+
+    fn string(&mut self) -> Result<String, CacheError> {
+        let len = self.u32()? as usize;
+        self.check_len(len)?;
+        let mut buf = vec![0; len];
+        self.fill(&mut buf)?;
+        String::from_utf8(buf).map_err(|_| CacheError::Format(self.path.to_owned(), "Invalid string"))
+    }
+
+    /// Reads `count` values straight into their final allocation
+    fn pod<T: bytemuck::Pod>(&mut self, count: usize) -> Result<Vec<T>, CacheError> {
+        self.check_len(count.saturating_mul(std::mem::size_of::<T>()))?;
+        let mut values = vec![T::zeroed(); count];
+        self.fill(bytemuck::cast_slice_mut(&mut values))?;
+        Ok(values)
+    }
+}
+
+struct Writer<'a> {
+    inner: BufWriter<std::fs::File>,
+    path: &'a Path,
+}
+
+impl Writer<'_> {
+    fn bytes(&mut self, bytes: &[u8]) -> Result<(), CacheError> {
+        self.inner.write_all(bytes).map_err(|e| CacheError::Io(e, self.path.to_owned()))
+    }
+
+    fn u32(&mut self, v: u32) -> Result<(), CacheError> { self.bytes(&v.to_ne_bytes()) }
+    fn u64(&mut self, v: u64) -> Result<(), CacheError> { self.bytes(&v.to_ne_bytes()) }
+
+    fn string(&mut self, s: &str) -> Result<(), CacheError> {
+        self.u32(s.len() as u32)?;
+        self.bytes(s.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// File in the temporary directory, removed when dropped
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            Self(std::env::temp_dir().join(format!("engine-cache-{}-{name}.mesh", std::process::id())))
+        }
+
+        fn write(name: &str, data: &ModelData) -> Self {
+            let file = Self::new(name);
+            data.write(&file.0).unwrap_or_else(|e| panic!("{e}"));
+            file
+        }
+
+        /// Overwrites the bytes at `offset`
+        fn patch(&self, offset: usize, bytes: &[u8]) {
+            let mut contents = std::fs::read(&self.0).unwrap();
+            contents[offset..offset + bytes.len()].copy_from_slice(bytes);
+            std::fs::write(&self.0, contents).unwrap();
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) { let _ = std::fs::remove_file(&self.0); }
+    }
+
+    fn vertex(x: f32) -> ModelVertex {
+        ModelVertex { position: [x, 1., 2.], tex_coords: [0.5, x], normal: [0., 0., 1.], tan: [1., 0., 0., -1.] }
+    }
+
+    fn sample() -> ModelData {
+        ModelData {
+            sources: vec![SourceStamp { path: PathBuf::from("model.obj"), len: 123, modified: Duration::new(1_700_000_000, 42) }],
+            materials: vec![MaterialData {
+                name: "red".to_owned(),
+                uniform: MaterialUniform { base_color: [1., 0., 0., 1.], ..Default::default() },
+                shading: ShadingModel::MetallicRoughness,
+                maps: [Some("red.png".to_owned()), None, None, None, None, None, None, Some("glow.png".to_owned())],
+                clamp: [true, false, false, false, false, false, false, false],
+            }],
+            meshes: vec![MeshData {
+                name: "quad".to_owned(),
+                material: 0,
+                vertices: (0..4).map(|i| vertex(i as f32)).collect(),
+                indices: vec![[0, 1, 2], [0, 2, 3]],
+            }],
+        }
+    }
+
+    fn format_error(result: Result<ModelData, CacheError>) -> &'static str {
+        match result {
+            Err(CacheError::Format(_, msg)) => msg,
+            Err(e) => panic!("{e}"),
+            Ok(_) => panic!("Read an invalid cache"),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = sample();
+        let file = TempFile::write("round-trip", &data);
+        let read = ModelData::read(&file.0).unwrap_or_else(|e| panic!("{e}"));
+
+        assert_eq!(read.sources, data.sources);
+
+        let (material, expected) = (&read.materials[0], &data.materials[0]);
+        assert_eq!(material.name, expected.name);
+        assert_eq!(bytemuck::bytes_of(&material.uniform), bytemuck::bytes_of(&expected.uniform));
+        assert_eq!(material.shading, expected.shading);
+        assert_eq!(material.maps, expected.maps);
+        assert_eq!(material.clamp, expected.clamp);
+
+        let (mesh, expected) = (&read.meshes[0], &data.meshes[0]);
+        assert_eq!(mesh.name, expected.name);
+        assert_eq!(mesh.material, expected.material);
+        assert_eq!(bytemuck::cast_slice::<_, u8>(&mesh.vertices), bytemuck::cast_slice::<_, u8>(&expected.vertices));
+        assert_eq!(mesh.indices, expected.indices);
+    }
+
+    #[test]
+    fn rejects_out_of_range_indices() {
+        let mut data = sample();
+        data.meshes[0].material = 1;
+        assert_eq!(format_error(ModelData::read(&TempFile::write("material-index", &data).0)), "Material index out of range");
+
+        let mut data = sample();
+        data.meshes[0].indices.push([0, 1, 4]);
+        assert_eq!(format_error(ModelData::read(&TempFile::write("vertex-index", &data).0)), "Vertex index out of range");
+    }
+
+    #[test]
+    fn rejects_corrupted_headers() {
+        let file = TempFile::write("magic", &sample());
+        file.patch(0, b"OBJ ");
+        assert_eq!(format_error(ModelData::read(&file.0)), "Not a mesh cache");
+
+        let file = TempFile::write("version", &sample());
+        file.patch(4, &99u32.to_ne_bytes());
+        assert!(matches!(ModelData::read(&file.0), Err(CacheError::Version(_, 99))));
+
+        let file = TempFile::write("byte-order", &sample());
+        file.patch(8, &BYTE_ORDER.swap_bytes().to_ne_bytes());
+        assert_eq!(format_error(ModelData::read(&file.0)), "Written with a different byte order");
+    }
+
+    #[test]
+    fn guards_lengths() {
+        // Length of the first source path, after the header and the source count
+        let file = TempFile::write("length", &sample());
+        file.patch(16, &u32::MAX.to_ne_bytes());
+        assert_eq!(format_error(ModelData::read(&file.0)), "Length exceeds the file");
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let file = TempFile::write("truncated", &sample());
+        let contents = std::fs::read(&file.0).unwrap();
+        std::fs::write(&file.0, &contents[..contents.len() - 5]).unwrap();
+        assert!(matches!(ModelData::read(&file.0), Err(CacheError::Io(..))));
+    }
+}
+
+pub mod err {
+    use std::path::PathBuf;
+
+    pub enum CacheError {
+        Io(std::io::Error, PathBuf),
+        Format(PathBuf, &'static str),
+        Version(PathBuf, u32),
+    }
+
+    use crate::err::macros::*;
+
+    impl_error!(CacheError,
+        Io(e, path) => "With {}: {}", path.display(), e;
+        Format(path, msg) => "Invalid mesh cache {}: {}", path.display(), msg;
+        Version(path, version) => "Mesh cache {} has version {}, expected {}", path.display(), version, super::VERSION
+    );
+}
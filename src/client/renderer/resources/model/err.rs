@@ -1,6 +1,7 @@
 use {
     crate::files::FileError,
     super::objfile::ObjFileError,
+    super::cache::CacheError,
     super::super::image::ImageError,
 };
 
@@ -12,16 +13,22 @@ pub enum ModelError {
     File(FileError),
     Image(ImageError),
     ObjFile(ObjFileError),
+    Gltf(gltf::Error),
+    Cache(CacheError),
 }
 
 impl_error!(ModelError,
     File(e) => "With file: {}", e;
     Image(e) => "With texture: {}", e;
-    ObjFile(e) => "While importing Wavefront OBJ: {}", e
+    ObjFile(e) => "While importing Wavefront OBJ: {}", e;
+    Gltf(e) => "While importing glTF: {}", e;
+    Cache(e) => "With mesh cache: {}", e
 );
 
 impl_error_conversions!(ModelError,
     FileError => File,
     ObjFileError => ObjFile,
-    ImageError => Image
+    ImageError => Image,
+    gltf::Error => Gltf,
+    CacheError => Cache
 );
\ No newline at end of file
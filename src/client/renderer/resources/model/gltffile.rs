@@ -0,0 +1,282 @@
+use std::{borrow::Cow, path::Path};
+
+use crate::{
+    client::renderer::{
+        gpu::buffer::Buffer,
+        resources::{
+            image::{RawImage, texture::SamplerConfig},
+            material::{Material, MaterialMaps, MaterialUniform, ShadingModel},
+        },
+        state::State,
+    },
+    math::{Mat4, Quat, Transform, Vec3},
+};
+
+use super::{err::ModelError, tangent, Mesh, Model, ModelNode, ModelVertex};
+
+/// Loads a `.gltf` or `.glb` file with its buffers and images, embedded or external.
+/// Every triangle primitive becomes a mesh, pre-transformed by its node's world transform.
+pub fn load(path: &Path, state: &State) -> Result<Model, ModelError> {
+    let (doc, buffers, images) = gltf::import(path)?;
+    let label = path.display().to_string();
+
+    let images: Vec<RawImage> = images.into_iter().map(to_raw_image).collect();
+
+    let mut materials: Vec<_> = doc.materials().map(|m| load_material(state, &m, &images, &label)).collect();
+    // Used by primitives without a material
+    let default_material = materials.len();
+    materials.push(Material::geometry(state, MaterialMaps::default(), MaterialUniform::default(), ShadingModel::BlinnPhong, &format!("{label} default material")));
+
+    let mut loader = Loader { state, buffers: &buffers, label: &label, default_material, meshes: Vec::new(), nodes: Vec::new() };
+
+    let scene = doc.default_scene().or_else(|| doc.scenes().next());
+    let roots = match scene {
+        Some(scene) => scene.nodes().map(|n| loader.load_node(&n, Mat4::identity())).collect(),
+        None => Vec::new(),
+    };
+
+    Ok(Model { meshes: loader.meshes, materials, nodes: loader.nodes, roots })
+}
+
+struct Loader<'a> {
+    state: &'a State,
+    buffers: &'a [gltf::buffer::Data],
+    label: &'a str,
+    default_material: usize,
+    meshes: Vec<Mesh>,
+    nodes: Vec<ModelNode>,
+}
+
+impl Loader<'_> {
+    /// Loads the node and its descendants, returning the node's index
+    fn load_node(&mut self, node: &gltf::Node, parent_world: Mat4<f32>) -> usize {
+        let local: Mat4<f32> = node.transform().matrix().into();
+        let world = parent_world * local;
+
+        let (t, r, s) = node.transform().decomposed();
+        let transform = Transform::new(t.into(), Quat::from_sv(r[3], Vec3::new(r[0], r[1], r[2])), s.into());
+
+        let meshes = match node.mesh() {
+            Some(mesh) => mesh.primitives().filter_map(|p| self.load_primitive(&mesh, &p, world)).collect(),
+            None => Vec::new(),
+        };
+
+        let index = self.nodes.len();
+        self.nodes.push(ModelNode {
+            name: node.name().unwrap_or_default().to_owned(),
+            transform,
+            meshes,
+            children: Vec::new(),
+        });
+
+        let children = node.children().map(|c| self.load_node(&c, world)).collect();
+        self.nodes[index].children = children;
+        index
+    }
+
+    fn load_primitive(&mut self, mesh: &gltf::Mesh, primitive: &gltf::Primitive, world: Mat4<f32>) -> Option<usize> {
+        if primitive.mode() != gltf::mesh::Mode::Triangles {
+            log::warn!("{}: skipping primitive of mesh {} with unsupported mode {:?}", self.label, mesh.index(), primitive.mode());
+            return None
+        }
+
+        let reader = primitive.reader(|b| self.buffers.get(b.index()).map(|d| &d.0[..]));
+        let Some(positions) = reader.read_positions() else {
+            log::warn!("{}: skipping primitive of mesh {} without positions", self.label, mesh.index());
+            return None
+        };
+
+        let positions: Vec<[f32; 3]> = positions.collect();
+        let indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+        if indices.iter().any(|&i| i as usize >= positions.len()) {
+            log::warn!("{}: skipping primitive of mesh {} with out of range indices", self.label, mesh.index());
+            return None
+        }
+        let mut triangles: Vec<[u32; 3]> = indices.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect();
+
+        let tex_coords: Vec<[f32; 2]> = match reader.read_tex_coords(0) {
+            Some(uv) => uv.into_f32().collect(),
+            None => vec![[0.; 2]; positions.len()],
+        };
+        let normals: Vec<[f32; 3]> = match reader.read_normals() {
+            Some(normals) => normals.collect(),
+            None => face_normals(&positions, &triangles),
+        };
+        if normals.len() != positions.len() {
+            log::warn!("{}: skipping primitive of mesh {} with {} normals for {} positions", self.label, mesh.index(), normals.len(), positions.len());
+            return None
+        }
+
+        let linear = world.truncate();
+        let mirrored = linear.determinant() < 0.;
+        // Mirroring reverses the winding of the baked vertices, which would get them culled as back faces
+        if mirrored {
+            for t in &mut triangles { t.swap(1, 2) }
+        }
+        let normal_matrix = linear.normal_matrix();
+
+        let mut vertices: Vec<ModelVertex> = positions.iter().enumerate().map(|(i, &p)| {
+            let position = (world * Mat4::from_translation(p.into())).w;
+            ModelVertex {
+                position: [position.x, position.y, position.z],
+                tex_coords: tex_coords.get(i).copied().unwrap_or_default(),
+                normal: (normal_matrix * Vec3::from(normals[i])).normalize().into(),
+                tan: [0.; 4],
+            }
+        }).collect();
+
+        match reader.read_tangents() {
+            Some(tangents) => {
+                // A mirroring transform flips the handedness
+                let handedness = if mirrored { -1. } else { 1. };
+                for (v, t) in vertices.iter_mut().zip(tangents) {
+                    let tangent = tangent::orthonormalize(linear * Vec3::new(t[0], t[1], t[2]), v.normal.into());
+                    v.tan = [tangent.x, tangent.y, tangent.z, t[3] * handedness];
+                }
+            },
+            None => tangent::generate(&mut vertices, &mut triangles),
+        }
+
+        let name = mesh.name().map_or_else(|| format!("{} mesh {}", self.label, mesh.index()), str::to_owned);
+        let vertex_buffer = Buffer::new_vertex(&self.state.device, &vertices, &format!("{name} Vertex Buffer"));
+        let index_buffer = Buffer::new_index(&self.state.device, &triangles, &format!("{name} Index Buffer"));
+
+        self.meshes.push(Mesh {
+            name,
+            vertex_buffer,
+            index_buffer,
+            num_elements: triangles.len() as u32 * 3,
+            material: primitive.material().index().unwrap_or(self.default_material),
+        });
+        Some(self.meshes.len() - 1)
+    }
+}
+
+fn load_material(state: &State, material: &gltf::Material, images: &[RawImage], label: &str) -> Material<MaterialUniform> {
+    let pbr = material.pbr_metallic_roughness();
+
+    let image = |texture: Option<gltf::Texture>| texture.and_then(|t| images.get(t.source().index()).map(Cow::Borrowed));
+
+    let diffuse = pbr.base_color_texture().map(|i| i.texture());
+    let normal = material.normal_texture().map(|n| n.texture());
+    let metallic_roughness = pbr.metallic_roughness_texture().map(|i| i.texture());
+    let occlusion = material.occlusion_texture().map(|o| o.texture());
+    let emissive = material.emissive_texture().map(|i| i.texture());
+    let sampler = |texture: &Option<gltf::Texture>| texture.as_ref().map(|t| sampler_config(t.sampler()));
+
+    let maps = MaterialMaps {
+        samplers: [
+            sampler(&diffuse), sampler(&normal), None, None, None,
+            sampler(&metallic_roughness), sampler(&occlusion), sampler(&emissive),
+        ],
+        diffuse: image(diffuse),
+        normal: image(normal),
+        metallic_roughness: image(metallic_roughness),
+        occlusion: image(occlusion),
+        emissive: image(emissive),
+        ..Default::default()
+    };
+
+    let uniform = MaterialUniform {
+        base_color: pbr.base_color_factor(),
+        emissive: material.emissive_factor(),
+        metallic: pbr.metallic_factor(),
+        roughness: pbr.roughness_factor(),
+        normal_scale: material.normal_texture().map_or(1., |n| n.scale()),
+        alpha_cutoff: match material.alpha_mode() {
+            gltf::material::AlphaMode::Mask => material.alpha_cutoff().unwrap_or(0.5),
+            _ => 0.,
+        },
+        occlusion_strength: material.occlusion_texture().map_or(1., |o| o.strength()),
+        ..Default::default()
+    };
+
+    let name = material.name().map_or_else(|| format!("{label} material {}", material.index().map_or(-1, |i| i as i64)), str::to_owned);
+    Material::geometry(state, maps, uniform, ShadingModel::MetallicRoughness, &name)
+}
+
+/// Keeps anisotropic filtering unless the file asks for nearest sampling
+fn sampler_config(sampler: gltf::texture::Sampler) -> SamplerConfig {
+    use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+
+    let address_mode = |mode| match mode {
+        WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+        WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+    };
+    let (min_filter, mipmap_filter) = match sampler.min_filter() {
+        Some(MinFilter::Nearest | MinFilter::NearestMipmapNearest) => (wgpu::FilterMode::Nearest, wgpu::FilterMode::Nearest),
+        Some(MinFilter::NearestMipmapLinear) => (wgpu::FilterMode::Nearest, wgpu::FilterMode::Linear),
+        Some(MinFilter::Linear | MinFilter::LinearMipmapNearest) => (wgpu::FilterMode::Linear, wgpu::FilterMode::Nearest),
+        Some(MinFilter::LinearMipmapLinear) | None => (wgpu::FilterMode::Linear, wgpu::FilterMode::Linear),
+    };
+
+    SamplerConfig {
+        address_mode_u: address_mode(sampler.wrap_s()),
+        address_mode_v: address_mode(sampler.wrap_t()),
+        mag_filter: match sampler.mag_filter() {
+            Some(MagFilter::Nearest) => wgpu::FilterMode::Nearest,
+            _ => wgpu::FilterMode::Linear,
+        },
+        min_filter,
+        mipmap_filter,
+        ..SamplerConfig::ANISOTROPIC
+    }
+}
+
+/// Converts any glTF pixel format to 8 bit RGBA
+fn to_raw_image(image: gltf::image::Data) -> RawImage {
+    use gltf::image::Format;
+
+    let to_u8 = |v: f32| (v.clamp(0., 1.) * 255. + 0.5) as u8;
+    let (channels, bytes_per_channel) = match image.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+
+    let mut rgba = Vec::with_capacity((image.width * image.height * 4) as usize);
+    for pixel in image.pixels.chunks_exact(channels * bytes_per_channel) {
+        let channel = |c: usize| {
+            let bytes = &pixel[c * bytes_per_channel..(c + 1) * bytes_per_channel];
+            match bytes_per_channel {
+                1 => bytes[0],
+                // Little endian, keep the high byte
+                2 => bytes[1],
+                _ => to_u8(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+            }
+        };
+        match channels {
+            1 => { let v = channel(0); rgba.extend_from_slice(&[v, v, v, 255]) }
+            2 => { let v = channel(0); rgba.extend_from_slice(&[v, v, v, channel(1)]) }
+            3 => rgba.extend_from_slice(&[channel(0), channel(1), channel(2), 255]),
+            _ => rgba.extend_from_slice(&[channel(0), channel(1), channel(2), channel(3)]),
+        }
+    }
+
+    RawImage::from_rgba8(rgba, (image.width, image.height).into())
+}
+
+/// Flat normals for primitives that don't provide any, averaged over shared vertices
+fn face_normals(positions: &[[f32; 3]], triangles: &[[u32; 3]]) -> Vec<[f32; 3]> {
+    let mut normals = vec![Vec3::<f32>::default(); positions.len()];
+    for t in triangles {
+        let [a, b, c] = t.map(|i| Vec3::from(positions[i as usize]));
+        let n = (b - a).cross(c - a);
+        for &i in t { normals[i as usize] += n }
+    }
+    normals.into_iter().map(|n| {
+        let len = n.magnitude();
+        if len > 0. { (n / len).into() } else { [0., 1., 0.] }
+    }).collect()
+}
@@ -3,10 +3,17 @@ use {
         gpu::buffer::{Buffer, self},
     },
     super::err::ResourceError,
-    super::material::Material,
+    err::ModelError,
+    cache::{ModelData, MaterialData, MeshData, SourceStamp},
+    super::material::{Material, MaterialMaps, MaterialUniform, ShadingModel},
+    crate::math::Transform,
 };
 
 pub mod objfile;
+pub mod mtlfile;
+pub mod cache;
+pub mod tangent;
+pub mod gltffile;
 pub mod err;
 
 pub trait Vertex {
@@ -19,13 +26,13 @@ pub struct ModelVertex {
     pub position: [f32; 3],
     pub tex_coords: [f32; 2],
     pub normal: [f32; 3],
-    pub tan: [f32; 3],
-    pub bitan: [f32; 3],
+    /// Tangent with the bitangent's handedness in `w`, see [`tangent::generate`]
+    pub tan: [f32; 4],
 }
 
 impl Vertex for ModelVertex {
     fn desc() -> wgpu::VertexBufferLayout<'static> {
-        const ATTRIBS: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Float32x3, 3 => Float32x3, 4 => Float32x3];
+        const ATTRIBS: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Float32x3, 3 => Float32x4];
 
         use std::mem;
         wgpu::VertexBufferLayout {
@@ -57,7 +64,21 @@ impl Vertex for Vertex3D {
 
 pub struct Model {
     pub meshes: Vec<Mesh>,
-    pub materials: Vec<Material<f32>>,
+    pub materials: Vec<Material<MaterialUniform>>,
+    /// Hierarchy the model was authored with, empty for formats without one.
+    /// Mesh vertices are already transformed into model space.
+    pub nodes: Vec<ModelNode>,
+    pub roots: Vec<usize>,
+}
+
+pub struct ModelNode {
+    pub name: String,
+    /// Relative to the parent node
+    pub transform: Transform<f32>,
+    /// Indices into `Model::meshes`
+    pub meshes: Vec<usize>,
+    /// Indices into `Model::nodes`
+    pub children: Vec<usize>,
 }
 
 pub struct Mesh {
@@ -68,147 +89,166 @@ pub struct Mesh {
     pub material: usize,
 }
 
+/// Loads a Wavefront OBJ, glTF or binary glTF file, chosen by the extension
 pub fn load_model(
     file_name: &str,
     state: &State,
     path_m: &PathManager
 ) -> Result<Model, ResourceError> {
+    let extension = std::path::Path::new(file_name).extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("gltf" | "glb") => Ok(gltffile::load(std::path::Path::new(file_name), state)?),
+        _ => load_obj(file_name, state, path_m),
+    }
+}
+
+/// Uses the binary cache next to the file while it's current, otherwise bakes and rewrites it
+fn load_obj(
+    file_name: &str,
+    state: &State,
+    path_m: &PathManager
+) -> Result<Model, ResourceError> {
+    let path = std::path::Path::new(file_name);
+    let data = match ModelData::read_fresh(path) {
+        Some(data) => data,
+        None => {
+            let data = bake_obj(file_name)?;
+            if let Err(e) = data.write(&cache::cache_path(path)) {
+                log::warn!("Couldn't write mesh cache of {file_name}: {e}");
+            }
+            data
+        }
+    };
+
+    upload(data, state, path_m)
+}
+
+/// Parses a Wavefront OBJ into deduplicated meshes with tangents, without touching the GPU
+pub fn bake_obj(file_name: &str) -> Result<ModelData, ModelError> {
     let obj = objfile::ObjFile::from_file(file_name)?;
 
+    let mut sources = vec![SourceStamp::new(std::path::Path::new(file_name))?];
+    if let Some(mtl) = &obj.material_library {
+        sources.push(SourceStamp::new(mtl)?);
+    }
+
+    let path = |texture: &Option<mtlfile::MtlTexture>| texture.as_ref().map(|t| t.path.clone());
+    let clamp = |texture: &Option<mtlfile::MtlTexture>| texture.as_ref().is_some_and(|t| t.clamp);
+
     let mut materials = Vec::new();
-    let mut map: HashMap<String, usize> = std::collections::HashMap::with_capacity(obj.materials.len());
-    for (ctr, (name, (dif, nor))) in obj.materials.iter().enumerate() {
-        // let tex = DifNormTexture::from_files(
-        //     device, queue,
-        //     &format!("assets/textures/{dif}"),
-        //     &format!("assets/textures/{nor}"),
-        //     ""
-        // )?;
-
-        map.insert(name.clone(), ctr);
-
-        let material = Material::from_paths(
-            state,
-            &[
-                (dif, TextureEntry::DIFFUSE_MAP_ENTRY),
-                (nor, TextureEntry::NORMAL_MAP_ENTRY)
+    let mut map: HashMap<&str, u32> = HashMap::with_capacity(obj.materials.len());
+    for (name, mtl) in obj.materials.iter() {
+        map.insert(name, materials.len() as u32);
+        materials.push(MaterialData {
+            name: name.clone(),
+            uniform: mtl.to_uniform(),
+            shading: mtl.shading_model(),
+            maps: [
+                path(&mtl.diffuse_map),
+                path(&mtl.normal_map),
+                path(&mtl.specular_map),
+                path(&mtl.alpha_map),
+                path(&mtl.shininess_map),
+                None,
+                None,
+                path(&mtl.emissive_map),
             ],
-            0.1,
-            name,
-            path_m
-        )?;
-
-        materials.push(material)
+            clamp: [
+                clamp(&mtl.diffuse_map),
+                clamp(&mtl.normal_map),
+                clamp(&mtl.specular_map),
+                clamp(&mtl.alpha_map),
+                clamp(&mtl.shininess_map),
+                false,
+                false,
+                clamp(&mtl.emissive_map),
+            ],
+        });
     }
 
+    let mut default = None;
     let mut meshes = Vec::new();
-    for m in obj.faces {
-        let vertices_org = obj.vertices.clone();
-        let indices = m.1;
-
+    for group in &obj.faces {
+        // Only the vertices referenced by the group, reindexed
+        let mut remap: HashMap<u32, u32> = HashMap::new();
         let mut vertices = Vec::new();
-        for vert in vertices_org {
-            vertices.push(
-                ModelVertex {
-                    position: vert.position,
-                    tex_coords: [vert.tex_coords[0], 1.0 - vert.tex_coords[1]],
-                    normal: vert.normal,
-                    tan: vert.tan,
-                    bitan: vert.bitan,
+        let mut indices: Vec<[u32; 3]> = group.indices.iter().map(|t| t.map(|i| *remap.entry(i).or_insert_with(|| {
+            let vert = obj.vertices[i as usize];
+            vertices.push(ModelVertex { tex_coords: [vert.tex_coords[0], 1.0 - vert.tex_coords[1]], ..vert });
+            vertices.len() as u32 - 1
+        }))).collect();
+
+        tangent::generate(&mut vertices, &mut indices);
+
+        let material = match map.get(group.material.as_str()) {
+            Some(&material) => material,
+            None => {
+                if group.material != objfile::DEFAULT_MATERIAL {
+                    log::warn!("{file_name}: material {} isn't declared, using the default", group.material);
                 }
-            );
-        }
-
-        let mut triangles_included = vec![0; vertices.len()];
-
-        use crate::common::math::vec::{Vec3, Vec2};
-
-        for c in indices.iter() {
-            let v0 = vertices[c[0] as usize];
-            let v1 = vertices[c[1] as usize];
-            let v2 = vertices[c[2] as usize];
-
-
-            let pos0: Vec3<_> = v0.position.into();
-            let pos1: Vec3<_> = v1.position.into();
-            let pos2: Vec3<_> = v2.position.into();
-
-            let uv0: Vec2<_> = v0.tex_coords.into();
-            let uv1: Vec2<_> = v1.tex_coords.into();
-            let uv2: Vec2<_> = v2.tex_coords.into();
-
-            // Calculate the edges of the triangle
-            let delta_pos1 = pos1 - pos0;
-            let delta_pos2 = pos2 - pos0;
-
-            // This will give us a direction to calculate the
-            // tangent and bitangent
-            let delta_uv1 = uv1 - uv0;
-            let delta_uv2 = uv2 - uv0;
-
-            // Solving the following system of equations will
-            // give us the tangent and bitangent.
-            //     delta_pos1 = delta_uv1.x * T + delta_u.y * B
-            //     delta_pos2 = delta_uv2.x * T + delta_uv2.y * B
-            // Luckily, the place I found this equation provided
-            // the solution!
-            let r = 1.0 / (delta_uv1.x * delta_uv2.y - delta_uv1.y * delta_uv2.x);
-            let tangent = (delta_pos1 * delta_uv2.y - delta_pos2 * delta_uv1.y) * r;
-            // We flip the bitangent to enable right-handed normal
-            // maps with wgpu texture coordinate system
-            let bitangent = (delta_pos2 * delta_uv1.x - delta_pos1 * delta_uv2.x) * -r;
-
-            // We'll use the same tangent/bitangent for each vertex in the triangle
-            vertices[c[0] as usize].tan =
-                (tangent + Vec3::from(vertices[c[0] as usize].tan)).into();
-            vertices[c[1] as usize].tan =
-                (tangent + Vec3::from(vertices[c[1] as usize].tan)).into();
-            vertices[c[2] as usize].tan =
-                (tangent + Vec3::from(vertices[c[2] as usize].tan)).into();
-            vertices[c[0] as usize].bitan =
-                (bitangent + Vec3::from(vertices[c[0] as usize].bitan)).into();
-            vertices[c[1] as usize].bitan =
-                (bitangent + Vec3::from(vertices[c[1] as usize].bitan)).into();
-            vertices[c[2] as usize].bitan =
-                (bitangent + Vec3::from(vertices[c[2] as usize].bitan)).into();
-
-            // Used to average the tangents/bitangents
-            triangles_included[c[0] as usize] += 1;
-            triangles_included[c[1] as usize] += 1;
-            triangles_included[c[2] as usize] += 1;
-        }
+                *default.get_or_insert_with(|| {
+                    materials.push(MaterialData {
+                        name: format!("{file_name} default material"),
+                        uniform: MaterialUniform::default(),
+                        shading: ShadingModel::BlinnPhong,
+                        maps: Default::default(),
+                        clamp: [false; 8],
+                    });
+                    materials.len() as u32 - 1
+                })
+            }
+        };
 
-        // Average the tangents/bitangents
-        for (i, n) in triangles_included.into_iter().enumerate() {
-            let denom = 1.0 / n as f32;
-            let mut v = &mut vertices[i];
-            v.tan = (Vec3::from(v.tan) * denom).into();
-            v.bitan = (Vec3::from(v.bitan) * denom).into();
-        }
+        let name = if group.name.is_empty() { file_name.to_string() } else { format!("{file_name} {}", group.name) };
+        meshes.push(MeshData { name, material, vertices, indices });
+    }
 
-        let vertex_buffer = Buffer::new_vertex(&state.device, &vertices, &format!("{file_name} Vertex Buffer"));
-        let index_buffer = Buffer::new_index(&state.device, &indices, &format!("{file_name} Index Buffer"));
+    Ok(ModelData { sources, materials, meshes })
+}
 
-        meshes.push(
-            Mesh {
-                name: file_name.to_string(),
-                vertex_buffer,
-                index_buffer,
-                num_elements: indices.len() as u32 * 3,
-                material: *map.get(&m.0).unwrap(),
-            }
-        )
+/// Creates the buffers and loads the textures of baked model data
+fn upload(data: ModelData, state: &State, path_m: &PathManager) -> Result<Model, ResourceError> {
+    let image = |path: &Option<String>| -> Result<Option<Cow<RawImage>>, ResourceError> {
+        match path {
+            Some(path) => Ok(Some(Cow::Owned(RawImage::import(path_m.texture(path))?))),
+            None => Ok(None),
+        }
+    };
+
+    let mut materials = Vec::with_capacity(data.materials.len());
+    for material in &data.materials {
+        let [diffuse, normal, specular, alpha, shininess, metallic_roughness, occlusion, emissive] = &material.maps;
+        let maps = MaterialMaps {
+            diffuse: image(diffuse)?,
+            normal: image(normal)?,
+            specular: image(specular)?,
+            alpha: image(alpha)?,
+            shininess: image(shininess)?,
+            metallic_roughness: image(metallic_roughness)?,
+            occlusion: image(occlusion)?,
+            emissive: image(emissive)?,
+            samplers: material.clamp.map(|c| c.then_some(SamplerConfig::ANISOTROPIC.with_address_mode(wgpu::AddressMode::ClampToEdge))),
+        };
+        materials.push(Material::geometry(state, maps, material.uniform, material.shading, &material.name));
     }
 
-    Ok(Model { meshes, materials })
+    let meshes = data.meshes.iter().map(|mesh| Mesh {
+        name: mesh.name.clone(),
+        vertex_buffer: Buffer::new_vertex(&state.device, &mesh.vertices, &format!("{} Vertex Buffer", mesh.name)),
+        index_buffer: Buffer::new_index(&state.device, &mesh.indices, &format!("{} Index Buffer", mesh.name)),
+        num_elements: mesh.indices.len() as u32 * 3,
+        material: mesh.material as usize,
+    }).collect();
+
+    Ok(Model { meshes, materials, nodes: Vec::new(), roots: Vec::new() })
 }
 
 use core::ops::Range;
-use std::collections::HashMap;
+use std::{borrow::Cow, collections::HashMap};
 
 use crate::client::{renderer::state::State, PathManager};
 
-use super::image::texture::TextureEntry;
+use super::image::{RawImage, texture::SamplerConfig};
 pub trait DrawModel<'a> {
     fn draw_mesh<T: bytemuck::Pod>(
         &mut self,
@@ -239,6 +279,15 @@ pub trait DrawModel<'a> {
         camera_bind_group: &'a wgpu::BindGroup,
         light_bind_group: &'a wgpu::BindGroup,
     );
+    /// Draws only the meshes whose material uses `shading`, for the pipeline of that model
+    fn draw_model_shaded_instanced(
+        &mut self,
+        model: &'a Model,
+        shading: ShadingModel,
+        instances: Range<u32>,
+        camera_bind_group: &'a wgpu::BindGroup,
+        light_bind_group: &'a wgpu::BindGroup,
+    );
 }
 
 impl<'a, 'b> DrawModel<'b> for wgpu::RenderPass<'a>
@@ -281,6 +330,21 @@ where
             self.draw_mesh_instanced(mesh, material, instances.clone(), camera_bind_group, light_bind_group);
         }
     }
+
+    fn draw_model_shaded_instanced(
+        &mut self,
+        model: &'b Model,
+        shading: ShadingModel,
+        instances: core::ops::Range<u32>,
+        camera_bind_group: &'b wgpu::BindGroup,
+        light_bind_group: &'a wgpu::BindGroup,
+    ) {
+        for mesh in &model.meshes {
+            let material = &model.materials[mesh.material];
+            if material.shading != shading { continue }
+            self.draw_mesh_instanced(mesh, material, instances.clone(), camera_bind_group, light_bind_group);
+        }
+    }
 }
 
 
@@ -365,3 +429,26 @@ where
         }
     }
 }
+
+/// Draws geometry only, without materials; used for depth-only passes like shadow maps
+pub trait DrawShadow<'a> {
+    fn draw_shadow_mesh_instanced(&mut self, mesh: &'a Mesh, instances: Range<u32>);
+    fn draw_shadow_model_instanced(&mut self, model: &'a Model, instances: Range<u32>);
+}
+
+impl<'a, 'b> DrawShadow<'b> for wgpu::RenderPass<'a>
+where
+    'b: 'a,
+{
+    fn draw_shadow_mesh_instanced(&mut self, mesh: &'b Mesh, instances: Range<u32>) {
+        self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+        self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        self.draw_indexed(0..mesh.num_elements, 0, instances);
+    }
+
+    fn draw_shadow_model_instanced(&mut self, model: &'b Model, instances: Range<u32>) {
+        for mesh in &model.meshes {
+            self.draw_shadow_mesh_instanced(mesh, instances.clone());
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use {
+    super::objfile::ObjFileError,
+    crate::client::renderer::resources::material::{MaterialUniform, ShadingModel, ILLUM_SPECULAR},
+    std::{
+        collections::HashMap,
+        path::Path,
+    },
+};
+
+/// Material described by a Wavefront MTL file
+#[derive(Debug, Clone)]
+pub struct MtlMaterial {
+    /// `Ka`
+    pub ambient: [f32; 3],
+    /// `Kd`
+    pub diffuse: [f32; 3],
+    /// `Ks`
+    pub specular: [f32; 3],
+    /// `Ke`
+    pub emissive: [f32; 3],
+    /// `Ns`
+    pub shininess: f32,
+    /// `Ni`
+    pub ior: f32,
+    /// `d`, or `1 - Tr`
+    pub dissolve: f32,
+    pub illum: u32,
+    /// `Pm`, from the PBR extension; with `Pr` it makes a metallic-roughness material
+    pub metallic: Option<f32>,
+    /// `Pr`
+    pub roughness: Option<f32>,
+    /// `map_Kd`
+    pub diffuse_map: Option<MtlTexture>,
+    /// `map_Ks`
+    pub specular_map: Option<MtlTexture>,
+    /// `map_d`
+    pub alpha_map: Option<MtlTexture>,
+    /// `map_Ns`
+    pub shininess_map: Option<MtlTexture>,
+    /// `norm`, `map_Bump` or `bump`
+    pub normal_map: Option<MtlTexture>,
+    /// `map_Ke`
+    pub emissive_map: Option<MtlTexture>,
+}
+
+/// Texture map statement with the options the renderer understands
+#[derive(Debug, Clone)]
+pub struct MtlTexture {
+    /// Relative to the texture directory
+    pub path: String,
+    /// `-bm`
+    pub bump_multiplier: f32,
+    /// `-clamp on`
+    pub clamp: bool,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        Self {
+            ambient: [1.; 3],
+            diffuse: [1.; 3],
+            specular: [0.; 3],
+            emissive: [0.; 3],
+            shininess: 32.,
+            ior: 1.5,
+            dissolve: 1.,
+            illum: ILLUM_SPECULAR,
+            metallic: None,
+            roughness: None,
+            diffuse_map: None,
+            specular_map: None,
+            alpha_map: None,
+            shininess_map: None,
+            normal_map: None,
+            emissive_map: None,
+        }
+    }
+}
+
+impl MtlMaterial {
+    pub fn to_uniform(&self) -> MaterialUniform {
+        let [r, g, b] = self.diffuse;
+        MaterialUniform {
+            base_color: [r, g, b, self.dissolve],
+            ambient: self.ambient,
+            specular: self.specular,
+            shininess: self.shininess,
+            emissive: self.emissive,
+            normal_scale: self.normal_map.as_ref().map_or(1., |m| m.bump_multiplier),
+            // Without blending, alpha maps can only cut out
+            alpha_cutoff: if self.alpha_map.is_some() { 0.5 } else { 0. },
+            ior: self.ior,
+            illum: self.illum,
+            metallic: self.metallic.unwrap_or(0.),
+            roughness: self.roughness.unwrap_or(1.),
+            ..Default::default()
+        }
+    }
+
+    /// Materials with PBR extension values are drawn as metallic-roughness
+    pub fn shading_model(&self) -> ShadingModel {
+        if self.metallic.is_some() || self.roughness.is_some() { ShadingModel::MetallicRoughness } else { ShadingModel::BlinnPhong }
+    }
+}
+
+/// Parses every material of an MTL file, keyed by name
+pub fn parse(src: &str, path: &Path) -> Result<HashMap<String, MtlMaterial>, ObjFileError> {
+    use ObjFileError::Mtl;
+
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+
+    for (linectr, line) in src.lines().enumerate() {
+        let linectr = linectr + 1;
+        let line = line.split('#').next().unwrap_or_default();
+        let tokens: Vec<&str> = line.split_ascii_whitespace().collect();
+        if tokens.is_empty() {continue}
+
+        if tokens[0] == "newmtl" {
+            if tokens.len() < 2 { return Err(Mtl(linectr, path.to_owned(), "\n        Material expected")) }
+            if let Some((name, material)) = current.take() { materials.insert(name, material); }
+            current = Some((tokens[1..].join(" "), MtlMaterial::default()));
+            continue
+        }
+
+        let Some((_, material)) = current.as_mut() else {
+            if is_known(tokens[0]) { return Err(Mtl(linectr, path.to_owned(), "\n        Property defined before the first material")) }
+            continue
+        };
+
+        let args = &tokens[1..];
+        match tokens[0] {
+            "Ka" => material.ambient = parse_color(args, linectr, path)?,
+            "Kd" => material.diffuse = parse_color(args, linectr, path)?,
+            "Ks" => material.specular = parse_color(args, linectr, path)?,
+            "Ke" => material.emissive = parse_color(args, linectr, path)?,
+            "Ns" => material.shininess = parse_scalar(args, linectr, path)?,
+            "Ni" => material.ior = parse_scalar(args, linectr, path)?,
+            // `-halo` is the only option of `d`
+            "d" => material.dissolve = parse_scalar(args.strip_prefix(&["-halo"]).unwrap_or(args), linectr, path)?,
+            "Tr" => material.dissolve = 1. - parse_scalar::<f32>(args, linectr, path)?,
+            "illum" => material.illum = parse_scalar(args, linectr, path)?,
+            "Pm" => material.metallic = Some(parse_scalar(args, linectr, path)?),
+            "Pr" => material.roughness = Some(parse_scalar(args, linectr, path)?),
+            "map_Kd" => material.diffuse_map = Some(parse_texture(args, linectr, path)?),
+            "map_Ks" => material.specular_map = Some(parse_texture(args, linectr, path)?),
+            "map_d" => material.alpha_map = Some(parse_texture(args, linectr, path)?),
+            "map_Ns" => material.shininess_map = Some(parse_texture(args, linectr, path)?),
+            "map_Ke" => material.emissive_map = Some(parse_texture(args, linectr, path)?),
+            "norm" | "map_Bump" | "map_bump" | "bump" => material.normal_map = Some(parse_texture(args, linectr, path)?),
+            _ => continue,
+        }
+    }
+
+    if let Some((name, material)) = current { materials.insert(name, material); }
+    Ok(materials)
+}
+
+fn is_known(statement: &str) -> bool {
+    matches!(statement, "Ka" | "Kd" | "Ks" | "Ke" | "Ns" | "Ni" | "d" | "Tr" | "illum" | "Pm" | "Pr"
+        | "map_Kd" | "map_Ks" | "map_d" | "map_Ns" | "map_Ke" | "norm" | "map_Bump" | "map_bump" | "bump")
+}
+
+fn parse_scalar<T: std::str::FromStr>(args: &[&str], linectr: usize, path: &Path) -> Result<T, ObjFileError>
+where T::Err: std::fmt::Display {
+    let arg = args.first().ok_or(ObjFileError::Mismatch(linectr, path.to_owned(), 1, 0))?;
+    arg.parse().map_err(|e: T::Err| ObjFileError::Parsing(linectr, path.to_owned(), e.to_string()))
+}
+
+/// `r g b`, or a single value for gray
+fn parse_color(args: &[&str], linectr: usize, path: &Path) -> Result<[f32; 3], ObjFileError> {
+    if matches!(args.first(), Some(&"spectral" | &"xyz")) {
+        return Err(ObjFileError::Mtl(linectr, path.to_owned(), "\n        Only RGB colors are supported"))
+    }
+    match args.len() {
+        1 => Ok([parse_scalar(args, linectr, path)?; 3]),
+        2 => Err(ObjFileError::Mismatch(linectr, path.to_owned(), 3, 2)),
+        _ => Ok([
+            parse_scalar(&args[0..], linectr, path)?,
+            parse_scalar(&args[1..], linectr, path)?,
+            parse_scalar(&args[2..], linectr, path)?,
+        ]),
+    }
+}
+
+/// Reads the options preceding the file name; the name may contain spaces
+fn parse_texture(args: &[&str], linectr: usize, path: &Path) -> Result<MtlTexture, ObjFileError> {
+    let mut texture = MtlTexture { path: String::new(), bump_multiplier: 1., clamp: false };
+
+    let mut i = 0;
+    while i < args.len() && args[i].starts_with('-') {
+        let option = args[i];
+        i += 1;
+        match option {
+            "-bm" => { texture.bump_multiplier = parse_scalar(&args[i..], linectr, path)?; i += 1 }
+            "-clamp" => { texture.clamp = args.get(i) == Some(&"on"); i += 1 }
+            "-blendu" | "-blendv" | "-boost" | "-texres" | "-imfchan" | "-type" | "-cc" => i += 1,
+            "-mm" => i += 2,
+            // Up to three values, v and w are optional
+            "-o" | "-s" | "-t" => {
+                let values = args[i..].iter().take(3).take_while(|a| a.parse::<f32>().is_ok()).count();
+                i += values;
+            }
+            _ => return Err(ObjFileError::Parsing(linectr, path.to_owned(), format!("Unknown texture option {option}"))),
+        }
+    }
+
+    if i >= args.len() { return Err(ObjFileError::Mtl(linectr, path.to_owned(), "\n        Texture file expected")) }
+    texture.path = args[i..].join(" ");
+    Ok(texture)
+}
@@ -1,31 +1,82 @@
 use {
-    super::ModelVertex,
+    super::{ModelVertex, mtlfile::{self, MtlMaterial}},
+    crate::math::Vec3,
     std::{
         collections::HashMap,
-        path::Path,
+        path::{Path, PathBuf},
     },
 };
 
 pub use err::ObjFileError;
 
+/// Material used by faces that appear before any `usemtl`, or reference an undeclared material
+pub const DEFAULT_MATERIAL: &str = "";
+
 #[derive(Debug)]
 pub struct ObjFile {
     material_path: String,
+    /// The MTL file that was read, if any
+    pub material_library: Option<PathBuf>,
     positions: Vec<[f32; 3]>,
     tex: Vec<[f32; 2]>,
     normals: Vec<[f32; 3]>,
-    pub faces: Vec<(String, Vec<[u32; 3]>)>,
+    pub faces: Vec<FaceGroup>,
     pub vertices: Vec<ModelVertex>,
-    pub materials: HashMap<String, (String, String)>
+    pub materials: HashMap<String, MtlMaterial>
+}
+
+/// Triangles sharing an object/group name and a material
+#[derive(Debug)]
+pub struct FaceGroup {
+    pub name: String,
+    pub material: String,
+    pub indices: Vec<[u32; 3]>,
+}
+
+/// A face corner before deduplication; indices are already resolved to zero-based
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Corner {
+    position: usize,
+    tex: Option<usize>,
+    normal: Option<usize>,
+}
+
+/// Key of a vertex whose normal gets generated.
+/// Smooth shaded corners are shared within their smoothing group, flat shaded ones are unique per face.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Shading {
+    Given,
+    Smooth(u32),
+    Flat(usize),
 }
 
 impl ObjFile {
     pub fn from_file(path: &str) -> Result<Self, ObjFileError> {
         let path = Path::new(path);
         let src = crate::files::read_file(path)?.0;
+        let mut objfile = Self::parse(&src, path)?;
+
+        if objfile.material_path.is_empty() { return Ok(objfile) }
 
+        let mtlpath = Path::new(path).parent().ok_or(ObjFileError::MissingParent(Path::new(path).to_owned()))?.join(Path::new(&objfile.material_path));
+        let mtlsrc = match crate::files::read_file(&mtlpath) {
+            Ok(src) => src.0,
+            Err(e) => {
+                log::warn!("Couldn't read material library of {}, using default materials: {}", path.display(), e);
+                return Ok(objfile)
+            }
+        };
+        objfile.materials = mtlfile::parse(&mtlsrc, &mtlpath)?;
+        objfile.material_library = Some(mtlpath);
+
+        Ok(objfile)
+    }
+
+    /// Parses the geometry of an OBJ file; `path` is only used in errors and the material library is left unread
+    pub fn parse(src: &str, path: &Path) -> Result<Self, ObjFileError> {
         let mut objfile = ObjFile {
             material_path: String::new(),
+            material_library: None,
             positions: Vec::new(),
             tex: Vec::new(),
             normals: Vec::new(),
@@ -34,122 +85,287 @@ impl ObjFile {
             materials: HashMap::new(),
         };
 
-        let lines = src.lines();
+        let mut map: HashMap<(Corner, Shading), u32> = HashMap::new();
+        // Vertices whose normals are accumulated from their faces and normalized at the end
+        let mut generated = Vec::new();
 
-        let mut linectr: usize = 0;
+        let mut group_name = String::new();
+        let mut material = DEFAULT_MATERIAL.to_owned();
+        let mut smoothing: u32 = 0;
+        let mut face_ctr: usize = 0;
 
-        let mut face_vecs: usize = 0;
-        let mut current_vert: u32 = 0;
-
-        let mut map: HashMap<(u32, u32, u32), u32> = HashMap::new();
-
-
-        for line in lines {
-            linectr += 1;
+        for (linectr, line) in src.lines().enumerate() {
+            let linectr = linectr + 1;
+            let line = line.split('#').next().unwrap_or_default();
             let tokens: Vec<&str> = line.split_ascii_whitespace().collect();
             if tokens.is_empty() {continue}
 
             match tokens[0] {
                 "mtllib" => {
-                    Self::check_tokens(tokens.len(), 1, linectr, path)?;
-                    objfile.material_path = tokens[1].to_owned();
+                    Self::check_min_tokens(tokens.len(), 1, linectr, path)?;
+                    objfile.material_path = tokens[1..].join(" ");
                 }
                 "v" => {
-                    Self::check_tokens(tokens.len(), 3, linectr, path)?;
-                    let verts = tokens[1..].iter().map(|v| v.parse::<f32>()
-                        .map_err(|e| ObjFileError::Parsing(linectr, path.to_owned(), e.to_string())))
-                        .collect::<Result<Vec<_>,_>>()?;
-                    objfile.positions.push([ verts[0], verts[1], verts[2] ]);
+                    Self::check_min_tokens(tokens.len(), 3, linectr, path)?;
+                    let v = Self::parse_floats(&tokens[1..4], linectr, path)?;
+                    objfile.positions.push([ v[0], v[1], v[2] ]);
                 },
                 "vt" => {
-                    Self::check_tokens(tokens.len(), 2, linectr, path)?;
-                    let tex = tokens[1..].iter().map(|v| v.parse::<f32>()
-                        .map_err(|e| ObjFileError::Parsing(linectr, path.to_owned(), e.to_string())))
-                        .collect::<Result<Vec<_>,_>>()?;
-                    objfile.tex.push([ tex[0], tex[1] ]);
+                    Self::check_min_tokens(tokens.len(), 1, linectr, path)?;
+                    let tex = Self::parse_floats(&tokens[1..tokens.len().min(3)], linectr, path)?;
+                    objfile.tex.push([ tex[0], tex.get(1).copied().unwrap_or(0.) ]);
                 },
                 "vn" => {
-                    Self::check_tokens(tokens.len(), 3, linectr, path)?;
-                    let normals = tokens[1..].iter().map(|v| v.parse::<f32>()
-                        .map_err(|e| ObjFileError::Parsing(linectr, path.to_owned(), e.to_string())))
-                        .collect::<Result<Vec<_>,_>>()?;
-                    objfile.normals.push([ normals[0], normals[1], normals[2] ]);
+                    Self::check_min_tokens(tokens.len(), 3, linectr, path)?;
+                    let n = Self::parse_floats(&tokens[1..4], linectr, path)?;
+                    objfile.normals.push([ n[0], n[1], n[2] ]);
                 },
+                "o" | "g" => {
+                    group_name = tokens[1..].join(" ");
+                }
+                "s" => {
+                    Self::check_min_tokens(tokens.len(), 1, linectr, path)?;
+                    // Some exporters write `on` instead of a group number
+                    smoothing = match tokens[1] {
+                        "off" => 0,
+                        "on" => 1,
+                        s => s.parse().unwrap_or_else(|_| {
+                            log::warn!("At line {linectr} of {}: invalid smoothing group '{s}', turning smoothing off", path.display());
+                            0
+                        }),
+                    };
+                }
                 "usemtl" => {
-                    Self::check_tokens(tokens.len(), 1, linectr, path)?;
-                    objfile.faces.push((tokens[1].to_string(), Vec::new()));
-                    face_vecs += 1;
+                    Self::check_min_tokens(tokens.len(), 1, linectr, path)?;
+                    material = tokens[1..].join(" ");
                 }
                 "f" => {
-                    Self::check_tokens(tokens.len(), 3, linectr, path)?;
-                    let indvec = tokens[1..].iter().map(|v|
-                        v.split('/')
-                            .map(|i| i.parse::<u32>()
-                            .map_err(|e| ObjFileError::Parsing(linectr, path.to_owned(), e.to_string())))
-                            .collect::<Result<Vec<u32>, _>>())
-                        .collect::<Result<Vec<_>,_>>()?;
-                    let mut face: Vec<u32> = Vec::new();
-                    for indices in indvec {
-                        if indices.len() != 3 { return Err(ObjFileError::Mismatch(linectr, path.to_owned(), 3, indices.len())) }
-
-                        face.push(*map.entry((indices[0], indices[1], indices[2])).or_insert_with(|| -> u32 {
+                    Self::check_min_tokens(tokens.len(), 3, linectr, path)?;
+                    let corners = tokens[1..].iter()
+                        .map(|t| objfile.parse_corner(t, linectr, path))
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    let positions: Vec<Vec3<f32>> = corners.iter().map(|c| objfile.positions[c.position].into()).collect();
+                    let face_normal = polygon_normal(&positions);
+
+                    let indices: Vec<u32> = corners.iter().map(|&corner| {
+                        let shading = match (corner.normal, smoothing) {
+                            (Some(_), _) => Shading::Given,
+                            (None, 0) => Shading::Flat(face_ctr),
+                            (None, group) => Shading::Smooth(group),
+                        };
+
+                        let index = *map.entry((corner, shading)).or_insert_with(|| {
                             objfile.vertices.push(ModelVertex {
-                                position: objfile.positions[(indices[0] - 1) as usize],
-                                tex_coords: objfile.tex[(indices[1] - 1) as usize],
-                                normal: objfile.normals[(indices[2] - 1) as usize],
-                                tan: [0.; 3],
-                                bitan: [0.; 3],
+                                position: objfile.positions[corner.position],
+                                tex_coords: corner.tex.map_or([0.; 2], |t| objfile.tex[t]),
+                                normal: corner.normal.map_or([0.; 3], |n| objfile.normals[n]),
+                                tan: [0.; 4],
                             });
-                            current_vert += 1;
-                            current_vert - 1
-                        }));
-                    }
-                    if objfile.faces.is_empty() {return Err(ObjFileError::MissingMaterial(linectr, Path::new(path).to_owned()))}
-                    objfile.faces[face_vecs - 1].1.push([face[0], face[1], face[2]]);
+                            if shading != Shading::Given { generated.push(objfile.vertices.len() - 1) }
+                            objfile.vertices.len() as u32 - 1
+                        });
+
+                        // Area weighted, as the polygon normal isn't normalized
+                        if shading != Shading::Given {
+                            let v = &mut objfile.vertices[index as usize];
+                            v.normal = (Vec3::from(v.normal) + face_normal).into();
+                        }
+                        index
+                    }).collect();
+                    face_ctr += 1;
+
+                    let triangles = triangulate(&positions, face_normal);
+                    let group = objfile.group(&group_name, &material);
+                    group.indices.extend(triangles.into_iter().map(|t| t.map(|i| indices[i])));
                 }
                 _ => continue,
             };
         }
 
-        let mtlpath = Path::new(path).parent().ok_or(ObjFileError::MissingParent(Path::new(path).to_owned()))?.join(Path::new(&objfile.material_path));
-        let mtlsrc = crate::files::read_file(&mtlpath)?.0;
-        let mtllines = mtlsrc.lines();
+        for i in generated {
+            let normal = Vec3::from(objfile.vertices[i].normal);
+            let len = normal.magnitude();
+            objfile.vertices[i].normal = if len > 0. { (normal / len).into() } else { [0., 1., 0.] };
+        }
 
-        linectr = 0;
-        let mut current_mat = String::new();
-        for line in mtllines {
-            let tokens: Vec<&str> = line.split_ascii_whitespace().collect();
-            linectr += 1;
+        objfile.faces.retain(|g| !g.indices.is_empty());
+        Ok(objfile)
+    }
 
-            if tokens.is_empty() {continue}
-            use ObjFileError::Mtl;
-            match tokens[0] {
-                "newmtl" => {
-                    if tokens.len() < 2 { return Err(Mtl(linectr, mtlpath, "\n        Material expected")) }
-                    objfile.materials.insert(tokens[1].to_owned(), (String::new(), String::new()));
-                    current_mat = tokens[1].to_owned();
-                }
-                "map_Kd" => {
-                    if tokens.len() < 2 { return Err(Mtl(linectr, mtlpath, "\n        Diffuse map expected")) }
-                    objfile.materials.get_mut(&current_mat).ok_or(Mtl(linectr, mtlpath.clone(), "\n        Texture defined before the first material"))?.0 = tokens[1].to_owned();
-                }
-                "map_Bump" => {
-                    if tokens.len() < 2 { return Err(Mtl(linectr, mtlpath, "\n        Normal map expected")) }
-                    objfile.materials.get_mut(&current_mat).ok_or(Mtl(linectr, mtlpath.clone(), "\n        Texture defined before the first material"))?.1 = tokens[1].to_owned();
-                }
-                _ => continue,
-            }
+    /// Returns the group for the name and material, starting a new one if either changed
+    fn group(&mut self, name: &str, material: &str) -> &mut FaceGroup {
+        let current = self.faces.last().is_some_and(|g| g.name == name && g.material == material);
+        if !current {
+            self.faces.push(FaceGroup { name: name.to_owned(), material: material.to_owned(), indices: Vec::new() });
         }
+        self.faces.last_mut().unwrap()
+    }
 
-        Ok(objfile)
+    /// Parses `v`, `v/vt`, `v//vn` or `v/vt/vn`
+    fn parse_corner(&self, token: &str, linectr: usize, path: &Path) -> Result<Corner, ObjFileError> {
+        let mut parts = token.split('/');
+        let position = parts.next().unwrap_or_default();
+        let tex = parts.next().filter(|t| !t.is_empty());
+        let normal = parts.next().filter(|n| !n.is_empty());
+        if parts.next().is_some() { return Err(ObjFileError::Parsing(linectr, path.to_owned(), format!("Invalid face vertex {token}"))) }
+
+        Ok(Corner {
+            position: Self::resolve_index(position, self.positions.len(), linectr, path)?,
+            tex: tex.map(|t| Self::resolve_index(t, self.tex.len(), linectr, path)).transpose()?,
+            normal: normal.map(|n| Self::resolve_index(n, self.normals.len(), linectr, path)).transpose()?,
+        })
     }
 
-    fn check_tokens(len: usize, c: usize, linectr: usize, path: &Path) -> Result<(), ObjFileError> {
-        if len != (c + 1) {Err(ObjFileError::Mismatch(linectr, path.to_owned(), c, len - 1))}
+    /// Converts a one-based or negative (relative to the end) index into a zero-based one
+    fn resolve_index(token: &str, len: usize, linectr: usize, path: &Path) -> Result<usize, ObjFileError> {
+        let index = token.parse::<i64>().map_err(|e| ObjFileError::Parsing(linectr, path.to_owned(), e.to_string()))?;
+        let resolved = if index < 0 { len as i64 + index } else { index - 1 };
+        if resolved < 0 || resolved >= len as i64 {
+            return Err(ObjFileError::Index(linectr, path.to_owned(), index, len))
+        }
+        Ok(resolved as usize)
+    }
+
+    fn parse_floats(tokens: &[&str], linectr: usize, path: &Path) -> Result<Vec<f32>, ObjFileError> {
+        tokens.iter().map(|v| v.parse::<f32>()
+            .map_err(|e| ObjFileError::Parsing(linectr, path.to_owned(), e.to_string())))
+            .collect()
+    }
+
+    fn check_min_tokens(len: usize, c: usize, linectr: usize, path: &Path) -> Result<(), ObjFileError> {
+        if len < (c + 1) {Err(ObjFileError::Mismatch(linectr, path.to_owned(), c, len - 1))}
         else {Ok(())}
     }
 }
 
+/// Newell's method; robust for non-planar and concave polygons. The length is twice the polygon's area.
+fn polygon_normal(positions: &[Vec3<f32>]) -> Vec3<f32> {
+    let mut normal = Vec3::default();
+    for (i, a) in positions.iter().enumerate() {
+        let b = positions[(i + 1) % positions.len()];
+        normal += Vec3::new(
+            (a.y - b.y) * (a.z + b.z),
+            (a.z - b.z) * (a.x + b.x),
+            (a.x - b.x) * (a.y + b.y),
+        );
+    }
+    normal
+}
+
+/// Splits a polygon into triangles by ear clipping, returning indices into `positions`.
+/// Falls back to a fan for degenerate polygons.
+fn triangulate(positions: &[Vec3<f32>], normal: Vec3<f32>) -> Vec<[usize; 3]> {
+    let n = positions.len();
+    let fan = || (1..n - 1).map(|i| [0, i, i + 1]).collect();
+    if n == 3 { return vec![[0, 1, 2]] }
+    if normal.magnitude() == 0. { return fan() }
+
+    let mut remaining: Vec<usize> = (0..n).collect();
+    let mut triangles = Vec::with_capacity(n - 2);
+
+    let is_convex = |a: Vec3<f32>, b: Vec3<f32>, c: Vec3<f32>| (b - a).cross(c - b).dot(normal) > 0.;
+    let contains = |p: Vec3<f32>, a: Vec3<f32>, b: Vec3<f32>, c: Vec3<f32>| {
+        (b - a).cross(p - a).dot(normal) >= 0. &&
+        (c - b).cross(p - b).dot(normal) >= 0. &&
+        (a - c).cross(p - c).dot(normal) >= 0.
+    };
+
+    while remaining.len() > 3 {
+        let len = remaining.len();
+        let ear = (0..len).find(|&i| {
+            let (ia, ib, ic) = (remaining[(i + len - 1) % len], remaining[i], remaining[(i + 1) % len]);
+            let (a, b, c) = (positions[ia], positions[ib], positions[ic]);
+            is_convex(a, b, c) && !remaining.iter()
+                .filter(|&&j| j != ia && j != ib && j != ic)
+                .any(|&j| contains(positions[j], a, b, c))
+        });
+
+        let Some(i) = ear else { return fan() };
+        triangles.push([remaining[(i + len - 1) % len], remaining[i], remaining[(i + 1) % len]]);
+        remaining.remove(i);
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(src: &str) -> ObjFile {
+        ObjFile::parse(src, Path::new("test.obj")).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    fn triangles(obj: &ObjFile) -> Vec<[u32; 3]> {
+        obj.faces.iter().flat_map(|g| g.indices.iter().copied()).collect()
+    }
+
+    /// Twice the signed area in the XY plane
+    fn area(obj: &ObjFile, [a, b, c]: [u32; 3]) -> f32 {
+        let [a, b, c] = [a, b, c].map(|i| Vec3::from(obj.vertices[i as usize].position));
+        (b - a).cross(c - a).z
+    }
+
+    const SQUARE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n";
+
+    #[test]
+    fn quad() {
+        let obj = parse(&format!("{SQUARE}f 1 2 3 4\n"));
+        assert_eq!(obj.vertices.len(), 4);
+        let triangles = triangles(&obj);
+        assert_eq!(triangles.len(), 2);
+        assert!(triangles.iter().all(|&t| area(&obj, t) > 0.));
+        // Generated from the face
+        assert!(obj.vertices.iter().all(|v| v.normal == [0., 0., 1.]));
+    }
+
+    #[test]
+    fn concave_pentagon() {
+        // Starting next to the reflex corner at (1, 1), where a fan would fold over
+        let obj = parse("v 2 2 0\nv 1 1 0\nv 0 2 0\nv 0 0 0\nv 2 0 0\nf 1 2 3 4 5\n");
+        let triangles = triangles(&obj);
+        assert_eq!(triangles.len(), 3);
+        assert!(triangles.iter().all(|&t| area(&obj, t) > 0.));
+        assert_eq!(triangles.iter().map(|&t| area(&obj, t)).sum::<f32>(), 6.);
+    }
+
+    #[test]
+    fn relative_indices_and_corner_formats() {
+        let obj = parse(&format!("{SQUARE}vt 0.5 0.25\nvn 0 0 -1\nf -4 -3 -2\nf 1/1 3/1 4/1\nf 1//1 2//1 3//1\n"));
+        let triangles = triangles(&obj);
+        assert_eq!(triangles.len(), 3);
+
+        let [a, b, c] = triangles[0].map(|i| obj.vertices[i as usize].position);
+        assert_eq!([a, b, c], [[0., 0., 0.], [1., 0., 0.], [1., 1., 0.]]);
+        assert!(triangles[1].iter().all(|&i| obj.vertices[i as usize].tex_coords == [0.5, 0.25]));
+        assert!(triangles[2].iter().all(|&i| obj.vertices[i as usize].normal == [0., 0., -1.]));
+
+        assert!(matches!(ObjFile::parse("v 0 0 0\nf 1 2 -2\n", Path::new("test.obj")), Err(ObjFileError::Index(2, _, 2, 1))));
+    }
+
+    #[test]
+    fn smoothing_groups() {
+        // Two faces of a folded square share the diagonal when smoothed
+        let folded = "v 0 0 0\nv 1 0 0\nv 1 1 1\nv 0 1 0\nf 1 2 3\nf 1 3 4\n";
+        assert_eq!(parse(&format!("s on\n{folded}")).vertices.len(), 4);
+        assert_eq!(parse(&format!("s 2\n{folded}")).vertices.len(), 4);
+        assert_eq!(parse(&format!("s off\n{folded}")).vertices.len(), 6);
+        assert_eq!(parse(&format!("s maybe\n{folded}")).vertices.len(), 6);
+
+        let shared = parse(&format!("s on\n{folded}")).vertices[0].normal;
+        assert!((Vec3::from(shared).magnitude() - 1.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn faces_before_usemtl() {
+        let obj = parse(&format!("{SQUARE}f 1 2 3\nusemtl red\nf 1 3 4\n"));
+        let materials: Vec<_> = obj.faces.iter().map(|g| g.material.as_str()).collect();
+        assert_eq!(materials, [DEFAULT_MATERIAL, "red"]);
+    }
+}
+
 pub mod err {
     use {
         crate::files::FileError,
@@ -163,7 +379,7 @@ pub mod err {
         Mtl(usize, PathBuf, &'static str),
         Parsing(usize, PathBuf, String),
         Mismatch(usize, PathBuf, usize, usize),
-        MissingMaterial(usize, PathBuf),
+        Index(usize, PathBuf, i64, usize),
         MissingParent(PathBuf),
     }
 
@@ -173,11 +389,10 @@ pub mod err {
         File(e) => "With file: {}", e;
         Mtl(line, path, msg) => "At line {} of {}: {}", line, path.display(), msg;
         Parsing(line, path, msg) => "At line {} of {}: Failed parsing: {}", line, path.display(), msg;
-        Mismatch(line, path, expected, received) => "At line {} of {}:\n        Expected {} values; received {}", line, path.display(), expected, received;
-        MissingMaterial(line, path) => "At {} of {}: Material missing", line, path.display();
+        Mismatch(line, path, expected, received) => "At line {} of {}:\n        Expected at least {} values; received {}", line, path.display(), expected, received;
+        Index(line, path, index, len) => "At line {} of {}: Index {} out of range, {} elements defined", line, path.display(), index, len;
         MissingParent(path) => "Couldn't find parent of {}", path.display()
     );
 
     impl_error_conversion!(ObjFileError, FileError => File);
 }
-
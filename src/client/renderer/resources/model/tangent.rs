@@ -0,0 +1,202 @@
+use {
+    super::ModelVertex,
+    crate::math::{Vec2, Vec3},
+};
+
+/// Tangent frame of a single triangle
+struct Face {
+    /// Direction of increasing U, not yet projected onto the vertex normals
+    tangent: Vec3<f32>,
+    /// Whether the UVs wind the same way as the positions
+    preserving: bool,
+    /// Zero UV or position area, the face takes the tangents of its neighbours
+    degenerate: bool,
+}
+
+/// Generates MikkTSpace compatible tangents.
+///
+/// Per-face tangents are orthonormalised against each corner's normal and averaged weighted by the corner angle.
+/// Corners of a vertex are grouped by handedness, and a vertex shared by mirrored faces is split in two.
+/// The tangent's `w` is the handedness: the bitangent, pointing towards the top of the texture, is `cross(normal, tangent) * w`.
+pub fn generate(vertices: &mut Vec<ModelVertex>, indices: &mut [[u32; 3]]) {
+    let faces: Vec<Face> = indices.iter().map(|t| face(vertices, t)).collect();
+
+    // Angle weighted sum per vertex and handedness, indexed by `preserving as usize`
+    let mut sums = vec![[None::<Vec3<f32>>; 2]; vertices.len()];
+    for (t, face) in indices.iter().zip(&faces) {
+        if face.degenerate { continue }
+        for k in 0..3 {
+            let v = t[k] as usize;
+            let normal = Vec3::from(vertices[v].normal);
+            let Some(tangent) = project(face.tangent, normal) else { continue };
+            let angle = corner_angle(vertices, t, k, normal);
+            let sum = &mut sums[v][face.preserving as usize];
+            *sum = Some(sum.unwrap_or_default() + tangent * angle);
+        }
+    }
+
+    // Index of the vertex used for each handedness
+    let mut split = vec![[None::<u32>; 2]; vertices.len()];
+    for v in 0..sums.len() {
+        let normal = Vec3::from(vertices[v].normal);
+        for preserving in [true, false] {
+            let Some(sum) = sums[v][preserving as usize] else { continue };
+            let tangent = orthonormalize(sum, normal);
+            let w = if preserving { 1. } else { -1. };
+
+            let index = match split[v] {
+                [None, None] => v as u32,
+                _ => { vertices.push(vertices[v]); vertices.len() as u32 - 1 }
+            };
+            vertices[index as usize].tan = [tangent.x, tangent.y, tangent.z, w];
+            split[v][preserving as usize] = Some(index);
+        }
+
+        // Only degenerate faces use the vertex
+        if split[v] == [None, None] {
+            let tangent = perpendicular(normal);
+            vertices[v].tan = [tangent.x, tangent.y, tangent.z, 1.];
+        }
+    }
+
+    for (t, face) in indices.iter_mut().zip(&faces) {
+        for i in t.iter_mut() {
+            let [mirrored, preserving] = split[*i as usize];
+            let index = match face.degenerate {
+                // Any group will do, preferring the common orientation
+                true => preserving.or(mirrored),
+                false => if face.preserving { preserving } else { mirrored },
+            };
+            if let Some(index) = index { *i = index }
+        }
+    }
+}
+
+fn face(vertices: &[ModelVertex], t: &[u32; 3]) -> Face {
+    let [p0, p1, p2] = t.map(|i| Vec3::from(vertices[i as usize].position));
+    // MikkTSpace expects the UV origin at the bottom left, texture coordinates here start at the top left
+    let [uv0, uv1, uv2] = t.map(|i| {
+        let [u, v] = vertices[i as usize].tex_coords;
+        Vec2::new(u, 1. - v)
+    });
+
+    let (d1, d2) = (p1 - p0, p2 - p0);
+    let (t21, t31) = (uv1 - uv0, uv2 - uv0);
+    let area = t21.x * t31.y - t21.y * t31.x;
+    let preserving = area > 0.;
+
+    let tangent = (d1 * t31.y - d2 * t21.y) * if preserving { 1. } else { -1. };
+    let degenerate = area.abs() <= f32::EPSILON * f32::EPSILON
+        || d1.cross(d2).magnitude() <= f32::EPSILON * f32::EPSILON
+        || tangent.magnitude() <= f32::EPSILON * f32::EPSILON;
+
+    Face { tangent, preserving, degenerate }
+}
+
+/// Makes a tangent perpendicular to the normal and unit length
+pub fn orthonormalize(tangent: Vec3<f32>, normal: Vec3<f32>) -> Vec3<f32> {
+    project(tangent, normal).unwrap_or_else(|| perpendicular(normal))
+}
+
+/// Removes the component along `normal` and normalizes, None if nothing is left
+fn project(v: Vec3<f32>, normal: Vec3<f32>) -> Option<Vec3<f32>> {
+    let projected = v - normal * normal.dot(v);
+    let len = projected.magnitude();
+    (len > f32::EPSILON && len.is_finite()).then(|| projected / len)
+}
+
+/// Angle of the triangle at corner `k`, measured in the plane of the corner's normal
+fn corner_angle(vertices: &[ModelVertex], t: &[u32; 3], k: usize, normal: Vec3<f32>) -> f32 {
+    let position = |i: usize| Vec3::from(vertices[t[i % 3] as usize].position);
+    let corner = position(k);
+    let (Some(e1), Some(e2)) = (
+        project(position(k + 2) - corner, normal),
+        project(position(k + 1) - corner, normal),
+    ) else { return 0. };
+    e1.dot(e2).clamp(-1., 1.).acos()
+}
+
+/// Any unit vector perpendicular to `normal`
+fn perpendicular(normal: Vec3<f32>) -> Vec3<f32> {
+    let axis = if normal.x.abs() < 0.9 { Vec3::unit_x() } else { Vec3::unit_y() };
+    project(axis, normal).unwrap_or_else(Vec3::unit_x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(position: [f32; 2], tex_coords: [f32; 2], normal: [f32; 3]) -> ModelVertex {
+        ModelVertex { position: [position[0], position[1], 0.], tex_coords, normal, tan: [0.; 4] }
+    }
+
+    fn tangent(v: &ModelVertex) -> Vec3<f32> { Vec3::new(v.tan[0], v.tan[1], v.tan[2]) }
+
+    fn assert_close(actual: Vec3<f32>, expected: Vec3<f32>) {
+        assert!((actual - expected).magnitude() < 1e-5, "{actual:?} != {expected:?}");
+    }
+
+    #[test]
+    fn splits_mirrored_uvs() {
+        // Two quads side by side in the XY plane, the right one with U mirrored around x = 1
+        let z = [0., 0., 1.];
+        let mut vertices = vec![
+            vertex([0., 0.], [0., 1.], z), vertex([1., 0.], [1., 1.], z), vertex([1., 1.], [1., 0.], z),
+            vertex([0., 1.], [0., 0.], z), vertex([2., 0.], [0., 1.], z), vertex([2., 1.], [0., 0.], z),
+        ];
+        let mut indices = [[0, 1, 2], [0, 2, 3], [1, 4, 5], [1, 5, 2]];
+        generate(&mut vertices, &mut indices);
+
+        // The shared edge is split
+        assert_eq!(vertices.len(), 8);
+        for &i in indices[..2].iter().flatten() {
+            assert_close(tangent(&vertices[i as usize]), Vec3::unit_x());
+            assert_eq!(vertices[i as usize].tan[3], 1.);
+        }
+        for &i in indices[2..].iter().flatten() {
+            assert_close(tangent(&vertices[i as usize]), -Vec3::unit_x());
+            assert_eq!(vertices[i as usize].tan[3], -1.);
+        }
+        // Either way the bitangent points towards v = 0, the top of the texture
+        for v in &vertices {
+            assert_close(Vec3::from(v.normal).cross(tangent(v)) * v.tan[3], Vec3::unit_y());
+        }
+    }
+
+    #[test]
+    fn degenerate_uvs() {
+        let normal = [0., 0., 1.];
+        let mut vertices = vec![
+            vertex([0., 0.], [0.5, 0.5], normal), vertex([1., 0.], [0.5, 0.5], normal), vertex([0., 1.], [0.5, 0.5], normal),
+        ];
+        let mut indices = [[0, 1, 2]];
+        generate(&mut vertices, &mut indices);
+
+        assert_eq!(vertices.len(), 3);
+        for v in &vertices {
+            let t = tangent(v);
+            assert!(t.x.is_finite() && t.y.is_finite() && t.z.is_finite());
+            assert!((t.magnitude() - 1.).abs() < 1e-5);
+            assert!(t.dot(Vec3::from(normal)).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn orthonormalizes_against_the_normal() {
+        // Normals tilted away from the face, as on a smoothed edge
+        let normal = Vec3::new(0.6, 0., 0.8);
+        let mut vertices = vec![
+            vertex([0., 0.], [0., 1.], normal.into()), vertex([1., 0.], [1., 1.], normal.into()), vertex([0., 1.], [0., 0.], normal.into()),
+        ];
+        let mut indices = [[0, 1, 2]];
+        generate(&mut vertices, &mut indices);
+
+        for v in &vertices {
+            assert_close(tangent(v), Vec3::new(0.8, 0., -0.6));
+        }
+
+        // A tangent along the normal falls back to any perpendicular
+        let fallback = orthonormalize(Vec3::new(2., 0., 0.), Vec3::unit_x());
+        assert!((fallback.magnitude() - 1.).abs() < 1e-5 && fallback.dot(Vec3::unit_x()).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,238 @@
+mod node;
+
+pub use node::{Node, NodeHandle};
+
+use crate::{
+    client::renderer::{
+        gpu::buffer::Buffer,
+        resources::model::Model,
+        state::State,
+    },
+    common::slot_map::{Handle, SlotMap},
+    instance::{Instance, InstanceRaw},
+    math::{Mat4, Transform},
+};
+
+pub type ModelHandle = Handle<SceneModel>;
+
+/// A model registered in the scene together with the instances of every node using it
+pub struct SceneModel {
+    pub model: Model,
+    instances: Vec<InstanceRaw>,
+    buf: Option<Buffer>,
+    capacity: usize,
+}
+
+impl SceneModel {
+    pub fn instance_count(&self) -> u32 { self.instances.len() as u32 }
+
+    pub fn instance_buffer(&self) -> Option<&Buffer> { self.buf.as_ref() }
+
+    fn upload(&mut self, state: &State, label: &str) {
+        if self.instances.is_empty() { return }
+
+        if self.buf.is_none() || self.instances.len() > self.capacity {
+            self.capacity = self.instances.len().next_power_of_two();
+            self.buf = Some(Buffer::empty(
+                &state.device,
+                (self.capacity * std::mem::size_of::<InstanceRaw>()) as wgpu::BufferAddress,
+                label,
+                wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            ));
+        }
+
+        if let Some(buf) = &self.buf {
+            state.queue.write_buffer(&buf.0, 0, bytemuck::cast_slice(&self.instances));
+        }
+    }
+}
+
+/// Hierarchy of nodes placing models in the world.
+/// Instance buffers are rebuilt in [`Scene::update`] whenever a node changed.
+pub struct Scene {
+    nodes: SlotMap<Node>,
+    roots: Vec<NodeHandle>,
+    models: SlotMap<SceneModel>,
+    dirty: bool,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self {
+            nodes: SlotMap::new(),
+            roots: Vec::new(),
+            models: SlotMap::new(),
+            dirty: false,
+        }
+    }
+
+    pub fn add_model(&mut self, model: Model) -> ModelHandle {
+        self.models.insert(SceneModel { model, instances: Vec::new(), buf: None, capacity: 0 })
+    }
+
+    /// Nodes still referencing the model are kept, but not drawn
+    pub fn remove_model(&mut self, handle: ModelHandle) -> Option<Model> {
+        let model = self.models.remove(handle)?;
+        self.dirty = true;
+        Some(model.model)
+    }
+
+    pub fn model(&self, handle: ModelHandle) -> Option<&Model> {
+        self.models.get(handle).map(|m| &m.model)
+    }
+
+    /// Models with at least one instance
+    pub fn instanced_models(&self) -> impl Iterator<Item = &SceneModel> {
+        self.models.values().filter(|m| !m.instances.is_empty())
+    }
+
+    /// Adds a node at the root of the scene
+    pub fn spawn(&mut self, mut node: Node) -> NodeHandle {
+        node.parent = None;
+        node.children.clear();
+        let handle = self.nodes.insert(node);
+        self.roots.push(handle);
+        self.dirty = true;
+        handle
+    }
+
+    /// Adds a node as a child of `parent`. Returns None if the parent doesn't exist.
+    pub fn spawn_child(&mut self, parent: NodeHandle, mut node: Node) -> Option<NodeHandle> {
+        if !self.nodes.contains(parent) { return None }
+        node.parent = Some(parent);
+        node.children.clear();
+        let handle = self.nodes.insert(node);
+        self.nodes.get_mut(parent)?.children.push(handle);
+        self.dirty = true;
+        Some(handle)
+    }
+
+    /// Removes the node and all of its descendants. Returns false if the node doesn't exist.
+    pub fn despawn(&mut self, handle: NodeHandle) -> bool {
+        let Some(node) = self.nodes.get(handle) else { return false };
+        self.detach(handle, node.parent);
+
+        let mut stack = vec![handle];
+        while let Some(h) = stack.pop() {
+            if let Some(node) = self.nodes.remove(h) {
+                stack.extend(node.children);
+            }
+        }
+        self.dirty = true;
+        true
+    }
+
+    /// Moves the node under a new parent, or to the root if `parent` is None.
+    /// Fails if either node doesn't exist or the new parent is a descendant of the node.
+    pub fn set_parent(&mut self, handle: NodeHandle, parent: Option<NodeHandle>) -> bool {
+        let Some(node) = self.nodes.get(handle) else { return false };
+        let old_parent = node.parent;
+
+        if let Some(p) = parent {
+            if !self.nodes.contains(p) || self.is_ancestor(handle, p) { return false }
+        }
+
+        self.detach(handle, old_parent);
+        match parent {
+            Some(p) => if let Some(parent) = self.nodes.get_mut(p) { parent.children.push(handle) },
+            None => self.roots.push(handle),
+        }
+        if let Some(node) = self.nodes.get_mut(handle) { node.parent = parent }
+        self.dirty = true;
+        true
+    }
+
+    pub fn node(&self, handle: NodeHandle) -> Option<&Node> { self.nodes.get(handle) }
+
+    pub fn node_mut(&mut self, handle: NodeHandle) -> Option<&mut Node> {
+        let node = self.nodes.get_mut(handle);
+        self.dirty |= node.is_some();
+        node
+    }
+
+    /// Changes the model drawn at the node
+    pub fn set_model(&mut self, handle: NodeHandle, model: Option<ModelHandle>) -> bool {
+        match self.nodes.get_mut(handle) {
+            Some(node) => { node.model = model; self.dirty = true; true }
+            None => false,
+        }
+    }
+
+    /// Transform of the node relative to the world, including all of its ancestors
+    pub fn world_transform(&self, handle: NodeHandle) -> Option<Transform<f32>> {
+        let mut node = self.nodes.get(handle)?;
+        let mut transform = node.transform;
+        while let Some(parent) = node.parent.and_then(|p| self.nodes.get(p)) {
+            transform = parent.transform * transform;
+            node = parent;
+        }
+        Some(transform)
+    }
+
+    /// [`Scene::world_transform`] as a matrix
+    pub fn world_matrix(&self, handle: NodeHandle) -> Option<Mat4<f32>> {
+        self.world_transform(handle).map(Transform::to_mat4)
+    }
+
+    pub fn len(&self) -> usize { self.nodes.len() }
+    pub fn is_empty(&self) -> bool { self.nodes.is_empty() }
+
+    /// Removes every node; models stay registered
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.roots.clear();
+        self.dirty = true;
+    }
+
+    /// Propagates world transforms down the hierarchy and uploads the instances if anything changed
+    pub fn update(&mut self, state: &State) {
+        if !self.dirty { return }
+        self.dirty = false;
+
+        for (_, model) in self.models.iter_mut() {
+            model.instances.clear();
+        }
+
+        let mut stack: Vec<(NodeHandle, Transform<f32>)> = self.roots.iter()
+            .map(|&h| (h, Transform::identity()))
+            .collect();
+
+        while let Some((handle, parent_world)) = stack.pop() {
+            let Some(node) = self.nodes.get(handle) else { continue };
+
+            let world = parent_world * node.transform;
+
+            if let Some(model) = node.model.and_then(|m| self.models.get_mut(m)) {
+                model.instances.push(Instance::new(world).to_raw());
+            }
+
+            stack.extend(node.children.iter().map(|&c| (c, world)));
+        }
+
+        for (_, model) in self.models.iter_mut() {
+            model.upload(state, "Scene Instance Buffer");
+        }
+    }
+
+    fn detach(&mut self, handle: NodeHandle, parent: Option<NodeHandle>) {
+        let siblings = match parent.and_then(|p| self.nodes.get_mut(p)) {
+            Some(parent) => &mut parent.children,
+            None => &mut self.roots,
+        };
+        siblings.retain(|&h| h != handle);
+    }
+
+    fn is_ancestor(&self, ancestor: NodeHandle, mut handle: NodeHandle) -> bool {
+        loop {
+            if handle == ancestor { return true }
+            match self.nodes.get(handle).and_then(|n| n.parent) {
+                Some(parent) => handle = parent,
+                None => return false,
+            }
+        }
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self { Self::new() }
+}
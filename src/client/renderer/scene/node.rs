@@ -0,0 +1,49 @@
+use crate::{
+    common::slot_map::Handle,
+    math::{Vec3, Quat, Mat4, Transform},
+};
+
+use super::ModelHandle;
+
+pub type NodeHandle = Handle<Node>;
+
+/// Element of the scene graph. The transform is relative to the parent node, if there is one.
+/// Transforms are composed as in [`Transform::compose`], so a non-uniformly scaled parent doesn't shear rotated children.
+/// Nodes without a model only group and move their children.
+pub struct Node {
+    pub transform: Transform<f32>,
+    pub(super) model: Option<ModelHandle>,
+    pub(super) parent: Option<NodeHandle>,
+    pub(super) children: Vec<NodeHandle>,
+}
+
+impl Node {
+    pub fn new() -> Self {
+        Self {
+            transform: Transform::identity(),
+            model: None,
+            parent: None,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn with_model(model: ModelHandle) -> Self {
+        Self { model: Some(model), ..Self::new() }
+    }
+
+    pub fn set_transform(mut self, transform: Transform<f32>) -> Self { self.transform = transform; self }
+    pub fn set_position<V: Into<Vec3<f32>>>(mut self, position: V) -> Self { self.transform.translation = position.into(); self }
+    pub fn set_rotation(mut self, rotation: Quat<f32>) -> Self { self.transform.rotation = rotation; self }
+    pub fn set_scale<V: Into<Vec3<f32>>>(mut self, scale: V) -> Self { self.transform.scale = scale.into(); self }
+
+    pub fn model(&self) -> Option<ModelHandle> { self.model }
+    pub fn parent(&self) -> Option<NodeHandle> { self.parent }
+    pub fn children(&self) -> &[NodeHandle] { &self.children }
+
+    /// Translation * rotation * scale, relative to the parent
+    pub fn local_matrix(&self) -> Mat4<f32> { self.transform.to_mat4() }
+}
+
+impl Default for Node {
+    fn default() -> Self { Self::new() }
+}
@@ -1,13 +1,16 @@
-use super::State;
+use super::{State, Target};
 
 use crate::{
     common::math::vec::Vec2,
     client::{
         Window,
-        renderer::err::RendererInitError,
+        renderer::{err::RendererInitError, framebuffer::FRAMEBUFFER_FORMAT, resources::image::Texture},
     }
 };
 
+/// Color format of the offscreen target, chosen to match the usual sRGB surface formats
+pub const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
 impl State {
     pub async fn new(window: &Window) -> Result<Self, RendererInitError> {
         let size = window.inner_size();
@@ -24,7 +27,26 @@ impl State {
         let config = Self::init_config(&surface, &adapter, size);
         surface.configure(&device, &config);
 
-        Ok(Self { device, queue, size, surface, config })
+        let sample_counts = Self::sample_counts(&adapter, &device);
+
+        Ok(Self { device, queue, size, target: Target::Surface(surface), config, sample_counts })
+    }
+
+    /// Creates a state without a window, rendering into an offscreen texture.
+    /// Falls back to a software adapter if no hardware one is available.
+    pub async fn new_headless(size: Vec2<u32>) -> Result<Self, RendererInitError> {
+        let instance = Self::init_instance();
+
+        let adapter = Self::init_headless_adapter(&instance).await?;
+
+        let (device, queue) = Self::init_device_q(&adapter).await?;
+
+        let config = Self::init_headless_config(size);
+        let texture = Self::create_offscreen_texture(&device, &config);
+
+        let sample_counts = Self::sample_counts(&adapter, &device);
+
+        Ok(Self { device, queue, size, target: Target::Offscreen(texture), config, sample_counts })
     }
 
     fn init_instance() -> wgpu::Instance {
@@ -52,10 +74,35 @@ impl State {
         Ok)
     }
 
+    async fn init_headless_adapter(instance: &wgpu::Instance) -> Result<wgpu::Adapter, RendererInitError> {
+        for force_fallback_adapter in [false, true] {
+            if let Some(adapter) = instance.request_adapter(
+                &wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::HighPerformance,
+                    compatible_surface: None,
+                    force_fallback_adapter,
+                },
+            ).await {
+                return Ok(adapter)
+            }
+        }
+        instance.enumerate_adapters(wgpu::Backends::all())
+            .next()
+            .ok_or(RendererInitError::Adapter)
+    }
+
+    /// Requests every texture compression family the adapter supports, other compressed textures are decompressed on load.
+    /// Adapter specific format features allow sample counts other than 1 and 4, push constants are requested when available.
     async fn init_device_q(adapter: &wgpu::Adapter) -> Result<(wgpu::Device, wgpu::Queue), RendererInitError> {
+        let optional = wgpu::Features::TEXTURE_COMPRESSION_BC
+            | wgpu::Features::TEXTURE_COMPRESSION_ETC2
+            | wgpu::Features::TEXTURE_COMPRESSION_ASTC
+            | wgpu::Features::TEXTURE_COMPRESSION_ASTC_HDR
+            | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+            | wgpu::Features::PUSH_CONSTANTS;
         Ok(adapter.request_device(
                     &wgpu::DeviceDescriptor {
-                        features: wgpu::Features::empty(),
+                        features: adapter.features() & optional,
                         limits: adapter.limits(),
                         label: Some("Renderer Device"),
                     },
@@ -63,6 +110,17 @@ impl State {
                 ).await?)
     }
 
+    fn sample_counts(adapter: &wgpu::Adapter, device: &wgpu::Device) -> Vec<u32> {
+        if !device.features().contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) {
+            return vec![1, 4]
+        }
+        let color = adapter.get_texture_format_features(FRAMEBUFFER_FORMAT).flags;
+        let depth = adapter.get_texture_format_features(Texture::DEPTH_STENCIL_FORMAT).flags;
+        [1, 2, 4, 8, 16].into_iter()
+            .filter(|&count| color.sample_count_supported(count) && depth.sample_count_supported(count))
+            .collect()
+    }
+
     fn init_config(surface: &wgpu::Surface, adapter: &wgpu::Adapter, size: Vec2<u32>) -> wgpu::SurfaceConfiguration {
         let surface_caps = surface.get_capabilities(adapter);
         let surface_format = surface_caps.formats.iter()
@@ -79,4 +137,17 @@ impl State {
             view_formats: vec![],
         }
     }
-}
\ No newline at end of file
+
+    /// Not used to configure a surface; describes the offscreen target so the rest of the renderer can treat both the same
+    fn init_headless_config(size: Vec2<u32>) -> wgpu::SurfaceConfiguration {
+        wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: OFFSCREEN_FORMAT,
+            width: size.x,
+            height: size.y,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+        }
+    }
+}
@@ -1,6 +1,7 @@
 pub mod render_state;
 
 mod init;
+mod readback;
 
 use crate::common::math::vec::Vec2;
 
@@ -11,8 +12,18 @@ pub struct State {
     pub queue: wgpu::Queue,
     pub size: Vec2<u32>,
 
-    pub surface: wgpu::Surface,
+    pub target: Target,
     pub config: wgpu::SurfaceConfiguration,
+    /// Sample counts the framebuffer and depth formats can be multisampled with, in increasing order
+    pub sample_counts: Vec<u32>,
+}
+
+/// Where the final image of a frame ends up
+pub enum Target {
+    /// Presented to a window surface
+    Surface(wgpu::Surface),
+    /// Rendered into a texture that can be read back; used for headless rendering
+    Offscreen(wgpu::Texture),
 }
 
 impl State {
@@ -20,6 +31,26 @@ impl State {
         self.size = new_size;
         self.config.width = new_size.x;
         self.config.height = new_size.y;
-        self.surface.configure(&self.device, &self.config);
+        match &mut self.target {
+            Target::Surface(surface) => surface.configure(&self.device, &self.config),
+            Target::Offscreen(texture) => *texture = Self::create_offscreen_texture(&self.device, &self.config),
+        }
     }
-}
\ No newline at end of file
+
+    fn create_offscreen_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Target Texture"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: config.usage,
+            view_formats: &[],
+        })
+    }
+}
@@ -0,0 +1,73 @@
+use super::{State, Target};
+
+use crate::client::renderer::err::RendererCaptureError;
+
+impl State {
+    /// Copies the offscreen target back to the CPU as tightly packed 8-bit RGBA rows. Blocks until the copy is done.
+    pub fn read_offscreen(&self) -> Result<Vec<u8>, RendererCaptureError> {
+        let texture = match &self.target {
+            Target::Offscreen(t) => t,
+            Target::Surface(_) => return Err(RendererCaptureError::NotHeadless),
+        };
+
+        let swizzle = match self.config.format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            f => return Err(RendererCaptureError::Format(f)),
+        };
+
+        const BYTES_PER_PIXEL: u32 = 4;
+        let unpadded_row = self.size.x * BYTES_PER_PIXEL;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_row = unpadded_row.div_ceil(align) * align;
+
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Offscreen Readback Buffer"),
+            size: (padded_row * self.size.y) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Readback Encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(self.size.y),
+                },
+            },
+            wgpu::Extent3d {
+                width: self.size.x,
+                height: self.size.y,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (tx, rx) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |r| { let _ = tx.send(r); });
+        self.device.poll(wgpu::Maintain::Wait);
+        rx.recv().unwrap_or(Err(wgpu::BufferAsyncError))?;
+
+        let mut pixels = Vec::with_capacity((unpadded_row * self.size.y) as usize);
+        {
+            let data = slice.get_mapped_range();
+            for row in data.chunks(padded_row as usize) {
+                pixels.extend_from_slice(&row[..unpadded_row as usize]);
+            }
+        }
+        buffer.unmap();
+
+        if swizzle {
+            pixels.chunks_exact_mut(BYTES_PER_PIXEL as usize).for_each(|p| p.swap(0, 2));
+        }
+
+        Ok(pixels)
+    }
+}
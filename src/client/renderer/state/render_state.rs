@@ -1,15 +1,21 @@
-use super::State;
+use super::{State, Target};
 
 pub struct RenderState {
-    out: wgpu::SurfaceTexture,
+    out: Option<wgpu::SurfaceTexture>,
     pub view: wgpu::TextureView,
     encoder: wgpu::CommandEncoder,
 }
 
 impl RenderState {
     pub fn new(state: &State) -> Result<Self, wgpu::SurfaceError> {
-        let out = state.surface.get_current_texture()?;
-        let view = out.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let (out, view) = match &state.target {
+            Target::Surface(surface) => {
+                let out = surface.get_current_texture()?;
+                let view = out.texture.create_view(&wgpu::TextureViewDescriptor::default());
+                (Some(out), view)
+            }
+            Target::Offscreen(texture) => (None, texture.create_view(&wgpu::TextureViewDescriptor::default())),
+        };
         let encoder = state.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Render Encoder"),
         });
@@ -22,12 +28,24 @@ impl RenderState {
         view: Option<&'a wgpu::TextureView>,
         clear_color: Option<wgpu::Color>,
         depth_stencil_attachment: Option<wgpu::RenderPassDepthStencilAttachment<'a>>) -> wgpu::RenderPass<'a>
+    {
+        self.resolve_pass(label, view, None, clear_color, depth_stencil_attachment)
+    }
+
+    /// Render pass drawing to a multisampled `view`, resolved into `resolve_target` at its end if set
+    pub fn resolve_pass<'a>(
+        &'a mut self,
+        label: Option<&str>,
+        view: Option<&'a wgpu::TextureView>,
+        resolve_target: Option<&'a wgpu::TextureView>,
+        clear_color: Option<wgpu::Color>,
+        depth_stencil_attachment: Option<wgpu::RenderPassDepthStencilAttachment<'a>>) -> wgpu::RenderPass<'a>
     {
         self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label,
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                 view: if let Some(v) = view {v} else {&self.view},
-                resolve_target: None,
+                resolve_target,
                 ops: wgpu::Operations {
                     load: if let Some(c) = clear_color {wgpu::LoadOp::Clear(c)} else {wgpu::LoadOp::Load},
                     store: true,
@@ -37,8 +55,33 @@ impl RenderState {
         })
     }
 
+    /// Render pass without color attachments, e.g. for shadow maps
+    pub fn depth_pass<'a>(&'a mut self, label: Option<&str>, view: &'a wgpu::TextureView) -> wgpu::RenderPass<'a> {
+        self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label,
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        })
+    }
+
+    pub fn compute_pass(&mut self, label: Option<&str>) -> wgpu::ComputePass<'_> {
+        self.encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label })
+    }
+
+    /// For copies between passes
+    pub fn encoder(&mut self) -> &mut wgpu::CommandEncoder { &mut self.encoder }
+
     pub fn finish(self, state: &State) {
         state.queue.submit(std::iter::once(self.encoder.finish()));
-        self.out.present();
+        if let Some(out) = self.out {
+            out.present();
+        }
     }
 }
\ No newline at end of file
@@ -26,12 +26,12 @@ impl Time {
 
     /// Executes every *frame* frames
     pub fn every<T: FnMut()>(&self, frame: usize, mut action: T) {
-        if (self.fps.frame % frame) == 0 {
+        if self.fps.frame.is_multiple_of(frame) {
             action()
         }
     }
 
-    /// Executes every *s* seconds (Can be imprecise - can skip or add extra executions)
+    // Executes every *s* seconds (Can be imprecise - can skip or add extra executions)
     // pub fn every_s_approximate<T: FnMut()>(&self, s: f64, mut action: T) {
     //     let div = (s / self.fps.avg_dt) as usize;
     //     if div == 0 || (self.fps.frame % (s / self.fps.avg_dt) as usize) == 0 {
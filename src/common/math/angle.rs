@@ -1,7 +1,7 @@
 use super::num::{Float, Zero};
 
 /// Angle type with internal representation in radians
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub struct Angle<T>(T);
 
 impl <T: Float> Angle<T> {
@@ -1,4 +1,4 @@
-use super::super::{vec::Vec3, num::{Number, One, Zero}};
+use super::super::{vec::Vec3, num::{Number, One, Zero, Float}};
 
 use crate::math::Quat;
 
@@ -6,6 +6,7 @@ use crate::math::Quat;
 pub type Mat3<T> = Vec3<Vec3<T>>;
 
 impl <T> Mat3<T> {
+    #[allow(clippy::too_many_arguments)]
     pub const fn new_mat(
         c0r0: T, c1r0: T, c2r0: T,
         c0r1: T, c1r1: T, c2r1: T,
@@ -23,10 +24,52 @@ impl <T: Number> Mat3<T> {
     pub fn from_unit_quat(q: Quat<T>) -> Self {
         Self::new_mat(
             T::one() - (q.v.y.square() + q.v.z.square()).double(), (q.v.x * q.v.y - q.s * q.v.z).double()               , (q.v.x * q.v.z + q.s * q.v.y).double(),
-            (q.v.x * q.v.y + q.s * q.v.z).double()               , T::one() - (q.v.x.square() + q.v.z.square()).double(), (q.v.y * q.v.z + q.s * q.v.x).double(),
-            (q.v.x * q.v.z - q.s * q.v.y).double()               , (q.v.y * q.v.z - q.s * q.v.x).double()               , T::one() - (q.v.x.square() + q.v.y.square()).double(),
+            (q.v.x * q.v.y + q.s * q.v.z).double()               , T::one() - (q.v.x.square() + q.v.z.square()).double(), (q.v.y * q.v.z - q.s * q.v.x).double(),
+            (q.v.x * q.v.z - q.s * q.v.y).double()               , (q.v.y * q.v.z + q.s * q.v.x).double()               , T::one() - (q.v.x.square() + q.v.y.square()).double(),
         )
     }
+
+    pub fn from_nonuniform_scale(s: Vec3<T>) -> Self {
+        Self::new_mat(
+            s.x      , T::zero(), T::zero(),
+            T::zero(), s.y      , T::zero(),
+            T::zero(), T::zero(), s.z      ,
+        )
+    }
+
+    pub fn determinant(self) -> T {
+        self.x.dot(self.y.cross(self.z))
+    }
+}
+
+impl <T> Mat3<T> {
+    pub fn transpose(self) -> Self {
+        Self::new_mat(
+            self.x.x, self.x.y, self.x.z,
+            self.y.x, self.y.y, self.y.z,
+            self.z.x, self.z.y, self.z.z,
+        )
+    }
+}
+
+impl <T: Float> Mat3<T> {
+    /// None if the matrix is singular
+    pub fn inverse(self) -> Option<Self> {
+        let det = self.determinant();
+        if det == T::zero() { return None }
+
+        // Rows of the inverse are the cross products of the columns
+        let r0 = self.y.cross(self.z) / det;
+        let r1 = self.z.cross(self.x) / det;
+        let r2 = self.x.cross(self.y) / det;
+        Some(Self::new(r0, r1, r2).transpose())
+    }
+
+    /// Inverse transpose, which transforms normals correctly under non-uniform scale.
+    /// Falls back to the matrix itself if it's singular.
+    pub fn normal_matrix(self) -> Self {
+        self.inverse().map_or(self, Self::transpose)
+    }
 }
 
 impl <T: Zero + One> Mat3<T> {
@@ -57,4 +100,19 @@ impl <T: Copy> From<[[T; 3]; 3]> for Mat3<T> {
             Vec3::from(value[2]),
         )
     }
-}
\ No newline at end of file
+}
+
+use std::ops::Mul;
+impl <T: Number> Mul<Mat3<T>> for Mat3<T> {
+    type Output = Self;
+    fn mul(self, rhs: Mat3<T>) -> Self::Output {
+        Self::new(self * rhs.x, self * rhs.y, self * rhs.z)
+    }
+}
+
+impl <T: Number> Mul<Vec3<T>> for Mat3<T> {
+    type Output = Vec3<T>;
+    fn mul(self, rhs: Vec3<T>) -> Self::Output {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
+    }
+}
@@ -1,11 +1,12 @@
 use super::super::{vec::{Vec4, Vec3}, num::{Number, One, Zero, Float, Signed}, angle::Angle};
 
-use crate::math::Quat;
+use crate::math::{Quat, Mat3};
 
 
 pub type Mat4<T> = Vec4<Vec4<T>>;
 
 impl <T> Mat4<T> {
+    #[allow(clippy::too_many_arguments)]
     pub const fn new_mat(
         c0r0: T, c1r0: T, c2r0: T, c3r0: T,
         c0r1: T, c1r1: T, c2r1: T, c3r1: T,
@@ -50,6 +51,26 @@ impl <T: One + Zero> Mat4<T> {
             t.homogeneous_point(),
         )
     }
+
+    pub fn from_nonuniform_scale(s: Vec3<T>) -> Self {
+        Self::new_mat(
+            s.x      , T::zero(), T::zero(), T::zero(),
+            T::zero(), s.y      , T::zero(), T::zero(),
+            T::zero(), T::zero(), s.z      , T::zero(),
+            T::zero(), T::zero(), T::zero(), T::one(),
+        )
+    }
+}
+
+impl <T> Mat4<T> {
+    /// Upper left 3x3 part of the matrix
+    pub fn truncate(self) -> Mat3<T> {
+        Mat3::new(
+            Vec3::new(self.x.x, self.x.y, self.x.z),
+            Vec3::new(self.y.x, self.y.y, self.y.z),
+            Vec3::new(self.z.x, self.z.y, self.z.z),
+        )
+    }
 }
 
 impl <T: Float + One + Zero> Mat4<T> {
@@ -77,6 +98,51 @@ impl <T: Float + One + Zero> Mat4<T> {
             T::zero() , T::zero(), -T::one()       , T::zero(),
         )
     }
+
+    /// Right-handed orthographic projection mapping depth to 0..1
+    pub fn orthographic(left: T, right: T, bottom: T, top: T, near: T, far: T) -> Self {
+        let w = right - left;
+        let h = top - bottom;
+        let d = far - near;
+        Self::new_mat(
+            T::cast(2.) / w, T::zero()      , T::zero()      , -(right + left) / w,
+            T::zero()      , T::cast(2.) / h, T::zero()      , -(top + bottom) / h,
+            T::zero()      , T::zero()      , -T::one() / d  , -near / d,
+            T::zero()      , T::zero()      , T::zero()      , T::one(),
+        )
+    }
+}
+
+impl <T: Float> Mat4<T> {
+    /// None if the matrix is singular
+    pub fn inverse(self) -> Option<Self> {
+        let (a, b, c, d) = (self.x, self.y, self.z, self.w);
+
+        // Determinants of the 2x2 parts of the first two and the last two columns
+        let s0 = a.x * b.y - b.x * a.y;
+        let s1 = a.x * b.z - b.x * a.z;
+        let s2 = a.x * b.w - b.x * a.w;
+        let s3 = a.y * b.z - b.y * a.z;
+        let s4 = a.y * b.w - b.y * a.w;
+        let s5 = a.z * b.w - b.z * a.w;
+        let c5 = c.z * d.w - d.z * c.w;
+        let c4 = c.y * d.w - d.y * c.w;
+        let c3 = c.y * d.z - d.y * c.z;
+        let c2 = c.x * d.w - d.x * c.w;
+        let c1 = c.x * d.z - d.x * c.z;
+        let c0 = c.x * d.y - d.x * c.y;
+
+        let det = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
+        if det == T::zero() { return None }
+
+        let column = |x: T, y: T, z: T, w: T| Vec4::new(x / det, y / det, z / det, w / det);
+        Some(Self::new(
+            column(b.y * c5 - b.z * c4 + b.w * c3, -(a.y * c5) + a.z * c4 - a.w * c3, d.y * s5 - d.z * s4 + d.w * s3, -(c.y * s5) + c.z * s4 - c.w * s3),
+            column(-(b.x * c5) + b.z * c2 - b.w * c1, a.x * c5 - a.z * c2 + a.w * c1, -(d.x * s5) + d.z * s2 - d.w * s1, c.x * s5 - c.z * s2 + c.w * s1),
+            column(b.x * c4 - b.y * c2 + b.w * c0, -(a.x * c4) + a.y * c2 - a.w * c0, d.x * s4 - d.y * s2 + d.w * s0, -(c.x * s4) + c.y * s2 - c.w * s0),
+            column(-(b.x * c3) + b.y * c1 - b.z * c0, a.x * c3 - a.y * c1 + a.z * c0, -(d.x * s3) + d.y * s1 - d.z * s0, c.x * s3 - c.y * s1 + c.z * s0),
+        ))
+    }
 }
 
 impl <T: Float + One + Zero + Signed> Mat4<T> {
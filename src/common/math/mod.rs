@@ -4,9 +4,11 @@ pub mod num;
 pub mod angle;
 pub mod macros;
 pub mod rotation;
+pub mod transform;
 
 pub use num::*;
 pub use vec::*;
 pub use mat::*;
 pub use angle::*;
-pub use rotation::*;
\ No newline at end of file
+pub use rotation::*;
+pub use transform::*;
\ No newline at end of file
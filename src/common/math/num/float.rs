@@ -18,6 +18,8 @@ where
     fn cos(self) -> Self;
     fn sin_cos(self) -> (Self, Self);
     fn tan(self) -> Self;
+    fn acos(self) -> Self;
+    fn abs(self) -> Self;
     fn cast(c: f64) -> Self;
     fn clamp(self, min: Self, max: Self) -> Self;
 }
@@ -31,6 +33,8 @@ macro_rules! impl_float {
             duplicate_type_function!($type, sin);
             duplicate_type_function!($type, cos);
             duplicate_type_function!($type, tan);
+            duplicate_type_function!($type, acos);
+            duplicate_type_function!($type, abs);
             duplicate_type_function!($type, sin_cos, -> (Self, Self));
             duplicate_type_function!($type, clamp, (self, min: Self, max: Self) -> Self);
 
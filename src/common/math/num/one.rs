@@ -1,3 +1,4 @@
+#[allow(clippy::wrong_self_convention)]
 pub trait One {
     fn one() -> Self;
     fn is_one(self) -> bool;
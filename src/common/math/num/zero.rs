@@ -1,3 +1,4 @@
+#[allow(clippy::wrong_self_convention)]
 pub trait Zero {
     fn zero() -> Self;
     fn is_zero(self) -> bool;
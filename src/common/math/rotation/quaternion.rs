@@ -1,4 +1,4 @@
-use crate::math::{Vec3, Float, Number, One, Angle, Signed};
+use crate::math::{Vec3, Float, Number, One, Zero, Angle, Signed};
 
 
 /// A quaternion type
@@ -14,6 +14,12 @@ impl <T> Quat<T> {
     pub fn from_sv(s: T, v: Vec3<T>) -> Self { Self  {s, v} }
 }
 
+impl <T: Zero + One> Quat<T> {
+    pub fn identity() -> Self {
+        Self::from_sv(T::one(), Vec3::new(T::zero(), T::zero(), T::zero()))
+    }
+}
+
 impl <T: Signed> Quat<T> {
     pub fn conj(self) -> Self {
         Self::from_sv(self.s, -self.v)
@@ -40,6 +46,35 @@ impl <T: Float + Signed> Quat<T> {
     pub fn inv(self) -> Self {
         self.conj() / self.magnitude().square()
     }
+
+    pub fn dot(self, rhs: Self) -> T {
+        self.s * rhs.s + self.v.dot(rhs.v)
+    }
+}
+
+impl <T: Float + Signed + PartialOrd> Quat<T> {
+    /// Spherical interpolation between two unit quaternions along the shortest arc
+    pub fn slerp(self, rhs: Self, t: T) -> Self {
+        let mut cos = self.dot(rhs);
+        let mut rhs = rhs;
+        if cos < T::zero() {
+            cos = -cos;
+            rhs = Self::from_sv(-rhs.s, -rhs.v);
+        }
+
+        // Nearly parallel, fall back to normalized linear interpolation
+        if cos > T::cast(0.9995) {
+            let s = self.s + (rhs.s - self.s) * t;
+            let v = self.v + (rhs.v - self.v) * t;
+            return Self::from_sv(s, v).norm();
+        }
+
+        let theta = cos.acos();
+        let sin = theta.sin();
+        let a = (theta * (T::one() - t)).sin() / sin;
+        let b = (theta * t).sin() / sin;
+        Self::from_sv(self.s * a + rhs.s * b, self.v * a + rhs.v * b)
+    }
 }
 
 impl <T: Number + One> Quat<T> {
@@ -0,0 +1,175 @@
+use crate::math::{Vec3, Quat, Mat3, Mat4, Float, Signed};
+
+/// Translation, rotation and scale, applied in the order scale -> rotation -> translation
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Transform<T> {
+    pub translation: Vec3<T>,
+    pub rotation: Quat<T>,
+    pub scale: Vec3<T>,
+}
+
+impl <T: Float + Signed> Transform<T> {
+    pub fn new(translation: Vec3<T>, rotation: Quat<T>, scale: Vec3<T>) -> Self {
+        Self { translation, rotation, scale }
+    }
+
+    pub fn identity() -> Self {
+        Self::new(Vec3::fill(T::zero()), Quat::identity(), Vec3::fill(T::one()))
+    }
+
+    pub fn from_translation(translation: Vec3<T>) -> Self {
+        Self { translation, ..Self::identity() }
+    }
+
+    pub fn from_rotation(rotation: Quat<T>) -> Self {
+        Self { rotation, ..Self::identity() }
+    }
+
+    pub fn from_scale(scale: Vec3<T>) -> Self {
+        Self { scale, ..Self::identity() }
+    }
+
+    pub fn to_mat4(self) -> Mat4<T> {
+        Mat4::from_translation(self.translation) * Mat4::from_unit_quat(self.rotation) * Mat4::from_nonuniform_scale(self.scale)
+    }
+
+    /// Inverse transpose of the linear part, R * S^-1
+    pub fn normal_matrix(self) -> Mat3<T> {
+        Mat3::from_unit_quat(self.rotation) * Mat3::from_nonuniform_scale(Self::recip(self.scale))
+    }
+
+    pub fn transform_point(self, p: Vec3<T>) -> Vec3<T> {
+        self.rotation * p.hadamard(self.scale) + self.translation
+    }
+
+    pub fn transform_vector(self, v: Vec3<T>) -> Vec3<T> {
+        self.rotation * v.hadamard(self.scale)
+    }
+
+    /// Applies `child` first, then `self`.
+    /// Exact unless a non-uniform scale is combined with a rotation, which would need shear.
+    pub fn compose(self, child: Self) -> Self {
+        Self {
+            translation: self.transform_point(child.translation),
+            rotation: self.rotation * child.rotation,
+            scale: self.scale.hadamard(child.scale),
+        }
+    }
+
+    /// Undoes the transform. Only exact for uniform scale: undoing a non-uniform scale after a rotation needs shear,
+    /// which a `Transform` can't hold, so use `to_mat4().inverse()` for those.
+    pub fn inverse(self) -> Self {
+        let rotation = self.rotation.conj();
+        let scale = Self::recip(self.scale);
+        Self {
+            translation: (rotation * -self.translation).hadamard(scale),
+            rotation,
+            scale,
+        }
+    }
+
+    fn recip(v: Vec3<T>) -> Vec3<T> {
+        Vec3::new(T::one() / v.x, T::one() / v.y, T::one() / v.z)
+    }
+}
+
+impl <T: Float + Signed + PartialOrd> Transform<T> {
+    /// Linear interpolation of translation and scale, spherical of rotation
+    pub fn lerp(self, rhs: Self, t: T) -> Self {
+        Self {
+            translation: self.translation + (rhs.translation - self.translation) * t,
+            rotation: self.rotation.slerp(rhs.rotation, t),
+            scale: self.scale + (rhs.scale - self.scale) * t,
+        }
+    }
+}
+
+impl <T: Float + Signed> Default for Transform<T> {
+    fn default() -> Self { Self::identity() }
+}
+
+impl <T: Float + Signed> std::ops::Mul<Transform<T>> for Transform<T> {
+    type Output = Self;
+    fn mul(self, rhs: Transform<T>) -> Self::Output { self.compose(rhs) }
+}
+
+impl <T: Float + Signed> From<Transform<T>> for Mat4<T> {
+    fn from(t: Transform<T>) -> Self { t.to_mat4() }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Angle;
+
+    fn assert_mat4(actual: Mat4<f32>, expected: Mat4<f32>) {
+        let (actual, expected): ([[f32; 4]; 4], [[f32; 4]; 4]) = (actual.into(), expected.into());
+        for (a, e) in actual.iter().flatten().zip(expected.iter().flatten()) {
+            assert!((a - e).abs() < 1e-5, "{actual:?} != {expected:?}");
+        }
+    }
+
+    fn assert_mat3(actual: Mat3<f32>, expected: Mat3<f32>) {
+        let (actual, expected): ([[f32; 3]; 3], [[f32; 3]; 3]) = (actual.into(), expected.into());
+        for (a, e) in actual.iter().flatten().zip(expected.iter().flatten()) {
+            assert!((a - e).abs() < 1e-5, "{actual:?} != {expected:?}");
+        }
+    }
+
+    fn assert_vec3(actual: Vec3<f32>, expected: Vec3<f32>) {
+        assert!((actual - expected).magnitude() < 1e-5, "{actual:?} != {expected:?}");
+    }
+
+    fn rotation() -> Quat<f32> {
+        Quat::from_axis_angle(Vec3::new(1., 2., -2.) / 3., Angle::from_deg(70.))
+    }
+
+    #[test]
+    fn inverse_of_uniform_scale() {
+        let t = Transform::new(Vec3::new(1., -2., 3.), rotation(), Vec3::fill(2.5));
+        assert_mat4((t * t.inverse()).to_mat4(), Mat4::identity());
+        assert_mat4((t.inverse() * t).to_mat4(), Mat4::identity());
+    }
+
+    #[test]
+    fn compose_matches_matrices() {
+        let parent = Transform::new(Vec3::new(4., 0., -1.), rotation(), Vec3::fill(0.5));
+        let child = Transform::new(Vec3::new(0., 3., 2.), rotation().conj(), Vec3::new(1., 2., 3.));
+        assert_mat4((parent * child).to_mat4(), parent.to_mat4() * child.to_mat4());
+    }
+
+    #[test]
+    fn normal_matrix_of_non_uniform_scale() {
+        let t = Transform::new(Vec3::new(1., 2., 3.), rotation(), Vec3::new(1., 2., 4.));
+        let linear = t.to_mat4().truncate();
+        let inverse_transpose = linear.inverse().unwrap().transpose();
+        assert_mat3(t.normal_matrix(), inverse_transpose);
+        assert_mat3(linear.normal_matrix(), inverse_transpose);
+        assert_mat3(linear * linear.inverse().unwrap(), Mat3::identity());
+
+        // Normals stay perpendicular to the transformed surface
+        let (tangent, normal) = (Vec3::new(1., 1., 0.), Vec3::new(1., -1., 1.));
+        assert!(t.transform_vector(tangent).dot(t.normal_matrix() * normal).abs() < 1e-5);
+    }
+
+    #[test]
+    fn quaternion_matrix_rotates_like_the_quaternion() {
+        let v = Vec3::new(0.3, -1., 2.);
+        assert_vec3(Mat3::from_unit_quat(rotation()) * v, rotation() * v);
+    }
+
+    #[test]
+    fn lerp_endpoints_and_midpoint() {
+        let a = Transform::new(Vec3::new(0., 0., 0.), Quat::identity(), Vec3::fill(1.));
+        let quarter = Quat::from_axis_angle(Vec3::unit_z(), Angle::from_deg(90.));
+        let b = Transform::new(Vec3::new(2., 4., -6.), quarter, Vec3::fill(3.));
+
+        assert_mat4(a.lerp(b, 0.).to_mat4(), a.to_mat4());
+        assert_mat4(a.lerp(b, 1.).to_mat4(), b.to_mat4());
+
+        let eighth = Quat::from_axis_angle(Vec3::unit_z(), Angle::from_deg(45.));
+        let mid = Transform::new(Vec3::new(1., 2., -3.), eighth, Vec3::fill(2.));
+        assert_mat4(a.lerp(b, 0.5).to_mat4(), mid.to_mat4());
+    }
+}
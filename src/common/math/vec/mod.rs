@@ -22,4 +22,4 @@ macro_rules! impl_vec_zero(
         }
     }
 );
-pub(self) use impl_vec_zero;
\ No newline at end of file
+use impl_vec_zero;
\ No newline at end of file
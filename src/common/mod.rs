@@ -1,2 +1,3 @@
 #[allow(unused)]
-pub mod math;
\ No newline at end of file
+pub mod math;
+pub mod slot_map;
\ No newline at end of file
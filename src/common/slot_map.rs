@@ -0,0 +1,138 @@
+use std::marker::PhantomData;
+
+/// Stable reference to a value stored in a [`SlotMap`].
+/// Stays invalid after its value is removed, even if the slot gets reused.
+pub struct Handle<T> {
+    index: u32,
+    generation: u32,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl <T> Handle<T> {
+    fn new(index: u32, generation: u32) -> Self {
+        Self { index, generation, _marker: PhantomData }
+    }
+
+    pub fn index(self) -> usize { self.index as usize }
+}
+
+impl <T> Clone for Handle<T> {
+    fn clone(&self) -> Self { *self }
+}
+impl <T> Copy for Handle<T> {}
+
+impl <T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+impl <T> Eq for Handle<T> {}
+
+impl <T> std::hash::Hash for Handle<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl <T> std::fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Handle({}v{})", self.index, self.generation)
+    }
+}
+
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+/// Vector-backed storage handing out generational handles; removed slots are reused
+pub struct SlotMap<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    len: usize,
+}
+
+impl <T> SlotMap<T> {
+    pub fn new() -> Self {
+        Self { slots: Vec::new(), free: Vec::new(), len: 0 }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self { slots: Vec::with_capacity(capacity), free: Vec::new(), len: 0 }
+    }
+
+    pub fn len(&self) -> usize { self.len }
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    pub fn insert(&mut self, value: T) -> Handle<T> {
+        self.len += 1;
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.value = Some(value);
+                Handle::new(index, slot.generation)
+            }
+            None => {
+                self.slots.push(Slot { generation: 0, value: Some(value) });
+                Handle::new(self.slots.len() as u32 - 1, 0)
+            }
+        }
+    }
+
+    pub fn remove(&mut self, handle: Handle<T>) -> Option<T> {
+        let slot = self.slots.get_mut(handle.index())?;
+        if slot.generation != handle.generation { return None }
+        let value = slot.value.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(handle.index);
+        self.len -= 1;
+        Some(value)
+    }
+
+    pub fn contains(&self, handle: Handle<T>) -> bool {
+        self.get(handle).is_some()
+    }
+
+    pub fn get(&self, handle: Handle<T>) -> Option<&T> {
+        self.slots.get(handle.index())
+            .filter(|s| s.generation == handle.generation)
+            .and_then(|s| s.value.as_ref())
+    }
+
+    pub fn get_mut(&mut self, handle: Handle<T>) -> Option<&mut T> {
+        self.slots.get_mut(handle.index())
+            .filter(|s| s.generation == handle.generation)
+            .and_then(|s| s.value.as_mut())
+    }
+
+    pub fn clear(&mut self) {
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if slot.value.take().is_some() {
+                slot.generation = slot.generation.wrapping_add(1);
+                self.free.push(index as u32);
+            }
+        }
+        self.len = 0;
+    }
+
+    /// Iterates over the stored values in slot order
+    pub fn iter(&self) -> impl Iterator<Item = (Handle<T>, &T)> {
+        self.slots.iter().enumerate().filter_map(|(i, s)| s.value.as_ref().map(|v| (Handle::new(i as u32, s.generation), v)))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Handle<T>, &mut T)> {
+        self.slots.iter_mut().enumerate().filter_map(|(i, s)| {
+            let generation = s.generation;
+            s.value.as_mut().map(|v| (Handle::new(i as u32, generation), v))
+        })
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.slots.iter().filter_map(|s| s.value.as_ref())
+    }
+}
+
+impl <T> Default for SlotMap<T> {
+    fn default() -> Self { Self::new() }
+}
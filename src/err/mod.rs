@@ -6,20 +6,27 @@ pub(crate) use macros::{
     impl_error_conversions,
 };
 
-use crate::client::renderer::err::RendererError;
+use crate::client::renderer::{err::RendererError, resources::model::{err::ModelError, cache::CacheError}};
 use log::SetLoggerError;
 
 pub enum Error {
     Renderer(RendererError),
     Logger(SetLoggerError),
+    Surface(wgpu::SurfaceError),
+    Model(ModelError),
 }
 
 impl_error!(Error,
     Renderer(e) => "In renderer: {}", e;
-    Logger(e) => "While setting logger: {}", e
+    Logger(e) => "While setting logger: {}", e;
+    Surface(e) => "With render surface: {}", e;
+    Model(e) => "With model: {}", e
 );
 
 impl_error_conversions!(Error,
     RendererError => Renderer,
-    SetLoggerError => Logger
+    SetLoggerError => Logger,
+    wgpu::SurfaceError => Surface,
+    ModelError => Model,
+    CacheError => Model
 );
\ No newline at end of file
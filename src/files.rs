@@ -14,14 +14,21 @@ pub fn read_file(path: &Path) -> Result<(String, usize), FileError> {
     }
 }
 
+pub fn read_binary(path: &Path) -> Result<Vec<u8>, FileError> {
+    std::fs::read(path).map_err(|e| TextureReadIO(e, path.to_owned()))
+}
+
+/// Decodes a PNG to 8 bit channels: palettes and transparency chunks are expanded and 16 bit channels stripped.
+/// The output may still be gray, gray-alpha or RGB, see `OutputInfo::color_type`.
 pub fn read_texture<T: AsRef<std::path::Path>>(path: T) -> Result<(Vec<u8>, png::OutputInfo), FileError> {
     let path = path.as_ref();
-    let decoder = png::Decoder::new(
+    let mut decoder = png::Decoder::new(
         match std::fs::File::open(path) {
             Ok(v) => v,
             Err(e) => return Err(TextureReadIO(e, path.to_path_buf()))
         }
     );
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
     let mut reader = match decoder.read_info() {
         Ok(v) => v,
         Err(e) => return Err(TextureReadDecoding(e, path.to_path_buf()))
@@ -35,19 +42,39 @@ pub fn read_texture<T: AsRef<std::path::Path>>(path: T) -> Result<(Vec<u8>, png:
     Ok((img_data, info))
 }
 
+/// Writes tightly packed 8-bit RGBA data as a PNG file
+pub fn write_png<T: AsRef<std::path::Path>>(path: T, width: u32, height: u32, data: &[u8]) -> Result<(), FileError> {
+    let path = path.as_ref();
+    let file = match std::fs::File::create(path) {
+        Ok(v) => v,
+        Err(e) => return Err(TextureWriteIO(e, path.to_path_buf()))
+    };
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    encoder.write_header()
+        .and_then(|mut writer| writer.write_image_data(data))
+        .map_err(|e| TextureWriteEncoding(e, path.to_path_buf()))
+}
+
 // ERROR HANDLING
 
 use crate::err::macros::*;
 
-use png::DecodingError;
+use png::{DecodingError, EncodingError};
 pub enum FileError {
     FileReadIO(std::io::Error, PathBuf),
     TextureReadIO(std::io::Error, PathBuf),
     TextureReadDecoding(DecodingError, PathBuf),
+    TextureWriteIO(std::io::Error, PathBuf),
+    TextureWriteEncoding(EncodingError, PathBuf),
 }
 
 impl_error!(FileError,
     FileReadIO(e, p) => "Couldn't read text file at path '{}': {}", p.display(), e;
     TextureReadIO(e, p) => "Couldn't read texture at path '{}': {}", p.display(), e;
-    TextureReadDecoding(e, p) => "Couldn't decode texture at path '{}': {}", p.display(), e
+    TextureReadDecoding(e, p) => "Couldn't decode texture at path '{}': {}", p.display(), e;
+    TextureWriteIO(e, p) => "Couldn't write texture at path '{}': {}", p.display(), e;
+    TextureWriteEncoding(e, p) => "Couldn't encode texture at path '{}': {}", p.display(), e
 );
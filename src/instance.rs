@@ -1,30 +1,43 @@
-use crate::math::{Vec3, Mat4, Quat, Mat3};
+use crate::math::{Mat4, Mat3, Transform};
 
+/// Placement of a single model instance
+#[derive(Clone, Copy, Debug, Default)]
 pub struct Instance {
-    pub position: Vec3<f32>,
-    pub rotation: Quat<f32>,
+    pub transform: Transform<f32>,
 }
 
 impl Instance {
+    pub fn new(transform: Transform<f32>) -> Self {
+        Self { transform }
+    }
+
     pub fn to_raw(&self) -> InstanceRaw {
-        let rotation = Mat4::from_unit_quat(self.rotation);
-        let model =
-            crate::math::Mat4::from_translation(self.position) * rotation;
-        InstanceRaw {
-            model: model.into(),
-            normal: Mat3::from_unit_quat(self.rotation).into(),
-        }
+        InstanceRaw::new(self.transform.to_mat4(), self.transform.normal_matrix())
     }
 }
 
+impl From<Transform<f32>> for Instance {
+    fn from(transform: Transform<f32>) -> Self { Self::new(transform) }
+}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-#[allow(dead_code)]
 pub struct InstanceRaw {
     model: [[f32; 4]; 4],
     normal: [[f32; 3]; 3],
 }
 
+impl InstanceRaw {
+    pub fn new(model: Mat4<f32>, normal: Mat3<f32>) -> Self {
+        Self { model: model.into(), normal: normal.into() }
+    }
+
+    /// Derives the normal matrix from the model matrix
+    pub fn from_matrix(model: Mat4<f32>) -> Self {
+        Self::new(model, model.truncate().normal_matrix())
+    }
+}
+
 impl crate::client::renderer::resources::model::Vertex for InstanceRaw {
     fn desc() -> wgpu::VertexBufferLayout<'static> {
         use std::mem;
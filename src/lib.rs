@@ -1,8 +1,8 @@
 mod logger;
 mod err;
-mod instance;
+pub mod instance;
 mod files;
-mod client;
+pub mod client;
 
 pub mod common;
 
@@ -28,7 +28,7 @@ pub async fn run() -> Result<(), Error> {
 
     event_loop.run(move |event, _, control_flow| {
         match event {
-            Event::RedrawRequested(window_id) if window_id == client.window_id() => {
+            Event::RedrawRequested(window_id) if Some(window_id) == client.window_id() => {
                 client.update();
                 match client.render() {
                     Ok(_) => {}
@@ -40,39 +40,74 @@ pub async fn run() -> Result<(), Error> {
 
             Event::DeviceEvent { event, .. } => client.device_input(&event),
 
-            Event::MainEventsCleared => {
-                client.window.request_redraw();
+            Event::MainEventsCleared => if let Some(window) = &client.window {
+                window.request_redraw();
             }
 
             Event::WindowEvent {
                 ref event,
                 window_id,
-            } if window_id == client.window_id() => if !client.window_input(event) {
-                match event {
-
-                    WindowEvent::Resized(physical_size) => {
-                        client.resize(*physical_size);
-                    }
-                    WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                        client.resize(**new_inner_size);
-                    }
-
-                    WindowEvent::CloseRequested
-                    | WindowEvent::KeyboardInput {
-                        input:
-                            KeyboardInput {
-                                state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Escape),
-                                ..
-                            },
-                        ..
-                    } => *control_flow = ControlFlow::Exit,
-
-
-                    _ => {}
+            } if Some(window_id) == client.window_id() && !client.window_input(event) => match event {
+
+                WindowEvent::Resized(physical_size) => {
+                    client.resize(*physical_size);
+                }
+                WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                    client.resize(**new_inner_size);
                 }
+
+                WindowEvent::CloseRequested
+                | WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::Escape),
+                            ..
+                        },
+                    ..
+                } => *control_flow = ControlFlow::Exit,
+
+
+                _ => {}
             }
             _ => {}
         }
     });
-}
\ No newline at end of file
+}
+
+/// Renders `frames` frames without opening a window and writes the last one to `output` as a PNG.
+/// Meant for CI and batch tools; a software adapter is used if no GPU is available.
+pub async fn run_headless<P: AsRef<std::path::Path>>(width: u32, height: u32, frames: usize, output: P) -> Result<(), Error> {
+    unsafe { logger::Logger::init(log::LevelFilter::Warn)? };
+
+    let mut client = client::Client::new_headless((width, height).into()).await?;
+
+    for _ in 0..frames.max(1) {
+        client.update();
+        client.render()?;
+    }
+
+    client.renderer.capture_frame(output)?;
+
+    Ok(())
+}
+
+/// Writes the binary mesh cache next to each Wavefront OBJ file, so loading it later skips parsing
+pub fn bake_models<S: AsRef<str>>(files: &[S]) -> Result<(), Error> {
+    unsafe { logger::Logger::init(log::LevelFilter::Info)? };
+
+    use client::renderer::resources::model::{bake_obj, cache::cache_path};
+    for file in files {
+        let file = file.as_ref();
+        let data = bake_obj(file)?;
+        let output = cache_path(std::path::Path::new(file));
+        data.write(&output)?;
+
+        let vertices: usize = data.meshes.iter().map(|m| m.vertices.len()).sum();
+        let triangles: usize = data.meshes.iter().map(|m| m.indices.len()).sum();
+        log::info!("{} -> {}: {} meshes, {} vertices, {} triangles, {} materials",
+            file, output.display(), data.meshes.len(), vertices, triangles, data.materials.len());
+    }
+
+    Ok(())
+}
@@ -10,7 +10,7 @@ impl Logger {
     pub unsafe fn init(max_level: LevelFilter) -> Result<(), SetLoggerError> {
         LOGGER.max_level = max_level;
         log::set_max_level(max_level);
-        log::set_logger(&LOGGER)
+        log::set_logger(&*std::ptr::addr_of!(LOGGER))
     }
 }
 